@@ -0,0 +1,127 @@
+//! Measures the time memoria adds to allocations, compared to the system allocator.
+//!
+//! ```text
+//! cargo bench --bench overhead
+//! ```
+//!
+//! The allocators are called directly instead of being installed as the global allocator, such
+//! that both can be compared within one process.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::Barrier;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use memoria::{Alloc, ShardedTable, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, Clone, Copy)]
+#[repr(u32)]
+enum BenchUseCase {
+    #[default]
+    None,
+    Guarded,
+}
+
+impl UseCase for BenchUseCase {}
+
+static MEMORIA: Alloc<BenchUseCase> = Alloc::new();
+static SHARDED: Alloc<BenchUseCase, StatsRecorder<BenchUseCase>, System, ShardedTable> =
+    Alloc::builder().pointer_table().build();
+static UNTRACKED_DEFAULT: Alloc<BenchUseCase> = Alloc::builder().untracked_default().build();
+
+const THREADS: usize = 4;
+
+const SIZES: [(&str, usize); 2] = [("small", 64), ("large", 64 * 1024)];
+
+fn alloc_dealloc(alloc: &impl GlobalAlloc, layout: Layout) {
+    unsafe {
+        let ptr = black_box(alloc.alloc(layout));
+        alloc.dealloc(ptr, layout);
+    }
+}
+
+/// Run `iters` allocations on each of `threads` threads, and return the time until all are done.
+fn run<A: GlobalAlloc + Sync>(
+    alloc: &A,
+    use_case: Option<BenchUseCase>,
+    layout: Layout,
+    threads: usize,
+    iters: u64,
+) -> Duration {
+    let barrier = Barrier::new(threads);
+    let work = || {
+        // the current usecase is shared by all allocators
+        let _guard = use_case.and_then(|use_case| MEMORIA.with_usecase(use_case));
+        barrier.wait();
+        let start = Instant::now();
+        for _ in 0..iters {
+            alloc_dealloc(alloc, layout);
+        }
+        start.elapsed()
+    };
+    if threads == 1 {
+        return work();
+    }
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads).map(|_| scope.spawn(work)).collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .max()
+            .unwrap()
+    })
+}
+
+fn bench_allocator<A: GlobalAlloc + Sync>(
+    c: &mut Criterion,
+    name: &str,
+    alloc: &A,
+    use_case: Option<BenchUseCase>,
+) {
+    for threads in [1, THREADS] {
+        let mut group = c.benchmark_group(format!("{threads} thread(s)"));
+        group.throughput(Throughput::Elements(threads as u64));
+        for (size_name, size) in SIZES {
+            let layout = Layout::from_size_align(size, 8).unwrap();
+            group.bench_function(BenchmarkId::new(name, size_name), |b| {
+                b.iter_custom(|iters| run(alloc, use_case, layout, threads, iters))
+            });
+        }
+        group.finish();
+    }
+}
+
+fn overhead(c: &mut Criterion) {
+    bench_allocator(c, "system", &System, None);
+    bench_allocator(c, "memoria without guard", &MEMORIA, None);
+    bench_allocator(
+        c,
+        "memoria with guard",
+        &MEMORIA,
+        Some(BenchUseCase::Guarded),
+    );
+    bench_allocator(
+        c,
+        "memoria with guard, sharded table",
+        &SHARDED,
+        Some(BenchUseCase::Guarded),
+    );
+    bench_allocator(
+        c,
+        "memoria without guard, untracked default",
+        &UNTRACKED_DEFAULT,
+        None,
+    );
+}
+
+fn guards(c: &mut Criterion) {
+    c.bench_function("enter and exit guard", |b| {
+        b.iter(|| drop(black_box(MEMORIA.with_usecase(BenchUseCase::Guarded))))
+    });
+}
+
+criterion_group!(benches, overhead, guards);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+use std::env;
+
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(memoria_single_threaded)");
+    // Set by `RUSTFLAGS="--cfg loom"` to model-check `src/sync.rs`, see `tests/loom.rs`.
+    println!("cargo::rustc-check-cfg=cfg(loom)");
+
+    // wasm without the atomics target feature can not spawn threads. Other targets can opt in
+    // with `RUSTFLAGS="--cfg memoria_single_threaded"`, which is not a feature because it is not
+    // additive: only the first thread that allocates is recorded.
+    let wasm = env::var("CARGO_CFG_TARGET_FAMILY").is_ok_and(|family| family == "wasm");
+    let atomics = env::var("CARGO_CFG_TARGET_FEATURE")
+        .is_ok_and(|features| features.split(',').any(|feature| feature == "atomics"));
+    // Without `std`, there are no thread-locals to replace, see `CurrentUsecaseStore`.
+    let std = env::var_os("CARGO_FEATURE_STD").is_some();
+    if std && wasm && !atomics {
+        println!("cargo::rustc-cfg=memoria_single_threaded");
+    }
+}
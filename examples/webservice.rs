@@ -0,0 +1,194 @@
+//! A small HTTP service with per-route, per-stage and per-tenant memory attribution.
+//!
+//! Run it with `cargo run --example webservice` and then try:
+//!
+//! ```text
+//! curl localhost:6771/users
+//! curl -H 'x-tenant: 7' localhost:6771/report
+//! curl localhost:6771/metrics
+//! ```
+//!
+//! Requests are attributed to the tenant in their `x-tenant` header, or tenant 0 without one.
+//!
+//! With `--selftest`, the service sends a few requests to itself, prints its metrics and exits.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, Clone, Copy)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    // stages every request goes through
+    ParseRequest,
+    WriteResponse,
+    // routes
+    RouteUsers,
+    RouteReport,
+    RouteMetrics,
+}
+
+impl MyUseCase {
+    const ALL: [MyUseCase; 6] = [
+        MyUseCase::None,
+        MyUseCase::ParseRequest,
+        MyUseCase::WriteResponse,
+        MyUseCase::RouteUsers,
+        MyUseCase::RouteReport,
+        MyUseCase::RouteMetrics,
+    ];
+}
+
+impl memoria::UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: memoria::Alloc<MyUseCase> = memoria::Alloc::new();
+
+struct Request {
+    method: String,
+    path: String,
+    tenant: memoria::Tag,
+}
+
+fn parse_request(stream: &mut BufReader<TcpStream>) -> Option<Request> {
+    let _guard = ALLOCATOR.with_usecase(MyUseCase::ParseRequest);
+    let mut request_line = String::new();
+    stream.read_line(&mut request_line).ok()?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_owned();
+    let path = parts.next()?.to_owned();
+
+    let mut tenant = 0;
+    let mut header = String::new();
+    while stream.read_line(&mut header).ok()? > 2 {
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("x-tenant") {
+                tenant = value.trim().parse().unwrap_or(0);
+            }
+        }
+        header.clear();
+    }
+
+    Some(Request {
+        method,
+        path,
+        tenant,
+    })
+}
+
+fn users(tenant: memoria::Tag) -> String {
+    let _guard = ALLOCATOR.with_usecase_tagged(MyUseCase::RouteUsers, tenant);
+    let users: Vec<String> = (0..100).map(|i| format!("\"user{i}\"")).collect();
+    format!("[{}]\n", users.join(","))
+}
+
+fn report(tenant: memoria::Tag) -> String {
+    let _guard = ALLOCATOR.with_usecase_tagged(MyUseCase::RouteReport, tenant);
+    // pretend to aggregate a large dataset
+    let data: Vec<u64> = (0..100_000).collect();
+    format!("sum: {}\n", data.iter().sum::<u64>())
+}
+
+fn metrics(tenant: memoria::Tag) -> String {
+    let _guard = ALLOCATOR.with_usecase_tagged(MyUseCase::RouteMetrics, tenant);
+    let (stats, tagged) = ALLOCATOR
+        .with_recorder(|recorder| {
+            let stats = MyUseCase::ALL
+                .iter()
+                .map(|&use_case| (use_case, recorder.get(use_case)))
+                .collect::<Vec<_>>();
+            let mut tagged = Vec::new();
+            recorder.flush_tagged(|use_case, tenant, stat| tagged.push((use_case, tenant, stat)));
+            Ok((stats, tagged))
+        })
+        .unwrap_or_default();
+    let mut output = Vec::new();
+    memoria::export::write_prometheus(&mut output, stats).unwrap();
+
+    // The breakdown per tenant is flushed on every scrape, so it only covers the time since the
+    // previous one.
+    writeln!(
+        output,
+        "# HELP memoria_tenant_allocated_bytes Bytes allocated per tenant since the last scrape."
+    )
+    .unwrap();
+    writeln!(output, "# TYPE memoria_tenant_allocated_bytes gauge").unwrap();
+    for (use_case, tenant, stat) in tagged {
+        writeln!(
+            output,
+            "memoria_tenant_allocated_bytes{{usecase=\"{use_case:?}\",tenant=\"{tenant}\"}} {}",
+            stat.total
+        )
+        .unwrap();
+    }
+    String::from_utf8(output).unwrap()
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, body: &str) {
+    let _guard = ALLOCATOR.with_usecase(MyUseCase::WriteResponse);
+    let response = format!(
+        "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).ok();
+}
+
+fn handle_connection(stream: TcpStream) {
+    let mut reader = BufReader::new(stream);
+    let request = match parse_request(&mut reader) {
+        Some(request) => request,
+        None => return,
+    };
+    let mut stream = reader.into_inner();
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/users") => write_response(&mut stream, "200 OK", &users(request.tenant)),
+        ("GET", "/report") => write_response(&mut stream, "200 OK", &report(request.tenant)),
+        ("GET", "/metrics") => write_response(&mut stream, "200 OK", &metrics(request.tenant)),
+        _ => write_response(&mut stream, "404 Not Found", "not found\n"),
+    }
+}
+
+fn get(addr: &str, path: &str, tenant: memoria::Tag) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "GET {path} HTTP/1.1\r\nhost: {addr}\r\nx-tenant: {tenant}\r\n\r\n"
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+fn main() {
+    let selftest = std::env::args().any(|arg| arg == "--selftest");
+    let listener = TcpListener::bind(if selftest {
+        "127.0.0.1:0"
+    } else {
+        "127.0.0.1:6771"
+    })
+    .unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    println!("listening on {addr}");
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            std::thread::spawn(|| handle_connection(stream));
+        }
+    });
+
+    if selftest {
+        for tenant in 1..=3 {
+            get(&addr, "/users", tenant);
+            get(&addr, "/report", tenant);
+        }
+        print!("{}", get(&addr, "/metrics", 0));
+    } else {
+        loop {
+            std::thread::park();
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Analyze an event log written by `memoria::EventLogRecorder`.
+//!
+//! ```text
+//! memoria-analyze [--points N] [FILE]
+//! ```
+//!
+//! Reads the log from `FILE`, or from stdin if no file or `-` is given, and prints for every
+//! usecase:
+//!
+//! * a timeline of its memory usage, sampled at `N` (default 10) evenly spaced points in time,
+//! * its peak and the window of time during which usage stayed within 10% of that peak,
+//! * a histogram of allocation sizes,
+//! * leak candidates: allocation sizes that were allocated more often than freed.
+//!
+//! Usecases are printed as their numeric value, since the log does not contain names.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::process;
+
+use memoria::{EventKind, EventReader, UseCaseRepr};
+
+const DEFAULT_POINTS: usize = 10;
+const LEAK_CANDIDATES: usize = 10;
+
+#[derive(Default)]
+struct UseCaseReport {
+    allocs: usize,
+    deallocs: usize,
+    current: i64,
+    /// Memory usage after every event.
+    timeline: Vec<(u64, i64)>,
+    /// Allocation counts per power-of-two size class, keyed by the smallest size in the class.
+    sizes: BTreeMap<usize, usize>,
+    /// Allocations minus deallocations per size.
+    outstanding: HashMap<usize, i64>,
+}
+
+impl UseCaseReport {
+    fn record(&mut self, timestamp: u64, kind: EventKind, size: usize) {
+        match kind {
+            EventKind::Alloc => {
+                self.allocs += 1;
+                self.current += size as i64;
+                *self.outstanding.entry(size).or_default() += 1;
+                let class = if size == 0 {
+                    0
+                } else {
+                    1 << (usize::BITS - 1 - size.leading_zeros())
+                };
+                *self.sizes.entry(class).or_default() += 1;
+            }
+            EventKind::Dealloc => {
+                self.deallocs += 1;
+                self.current -= size as i64;
+                *self.outstanding.entry(size).or_default() -= 1;
+            }
+        }
+        self.timeline.push((timestamp, self.current));
+    }
+
+    /// The index into the timeline at which usage was highest.
+    fn peak(&self) -> Option<usize> {
+        (0..self.timeline.len()).max_by_key(|&i| (self.timeline[i].1, std::cmp::Reverse(i)))
+    }
+
+    /// The first and last timestamp around the peak during which usage stayed within 10% of it.
+    fn peak_window(&self, peak: usize) -> (u64, u64) {
+        let threshold = self.timeline[peak].1 - self.timeline[peak].1 / 10;
+        let above = |&&(_, current): &&(u64, i64)| current >= threshold;
+        let start = self.timeline[..=peak]
+            .iter()
+            .rev()
+            .take_while(above)
+            .last()
+            .map_or(self.timeline[peak].0, |&(timestamp, _)| timestamp);
+        let end = self.timeline[peak..]
+            .iter()
+            .take_while(above)
+            .last()
+            .map_or(self.timeline[peak].0, |&(timestamp, _)| timestamp);
+        (start, end)
+    }
+
+    /// Memory usage at the last event at or before `timestamp`.
+    fn usage_at(&self, timestamp: u64) -> i64 {
+        match self.timeline.partition_point(|&(t, _)| t <= timestamp) {
+            0 => 0,
+            i => self.timeline[i - 1].1,
+        }
+    }
+}
+
+fn main() {
+    let mut points = DEFAULT_POINTS;
+    let mut path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--points" => match args.next().and_then(|value| value.parse().ok()) {
+                Some(value) if value > 0 => points = value,
+                _ => usage(),
+            },
+            "-h" | "--help" => usage(),
+            _ if path.is_none() => path = Some(arg),
+            _ => usage(),
+        }
+    }
+
+    let reader: Box<dyn Read> = match path.as_deref() {
+        None | Some("-") => Box::new(io::stdin().lock()),
+        Some(path) => match File::open(path) {
+            Ok(file) => Box::new(file),
+            Err(e) => {
+                eprintln!("memoria-analyze: {path}: {e}");
+                process::exit(1);
+            }
+        },
+    };
+
+    let stdout = io::stdout().lock();
+    if let Err(e) = run(BufReader::new(reader), points, BufWriter::new(stdout)) {
+        eprintln!("memoria-analyze: {e}");
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: memoria-analyze [--points N] [FILE]");
+    process::exit(2);
+}
+
+fn run(reader: impl Read, points: usize, mut out: impl Write) -> io::Result<()> {
+    let mut reports: BTreeMap<UseCaseRepr, UseCaseReport> = BTreeMap::new();
+    let mut first = None;
+    let mut last = 0;
+    for event in EventReader::new(reader) {
+        let event = event?;
+        first.get_or_insert(event.timestamp);
+        last = event.timestamp;
+        reports
+            .entry(event.use_case)
+            .or_default()
+            .record(event.timestamp, event.kind, event.size);
+    }
+
+    let first = match first {
+        Some(first) => first,
+        None => {
+            writeln!(out, "no events")?;
+            return out.flush();
+        }
+    };
+
+    for (use_case, report) in &reports {
+        writeln!(out, "usecase {use_case}")?;
+        writeln!(
+            out,
+            "  events: {} allocs, {} deallocs, {} bytes at end",
+            report.allocs, report.deallocs, report.current
+        )?;
+
+        if let Some(peak) = report.peak() {
+            let (start, end) = report.peak_window(peak);
+            let (timestamp, bytes) = report.timeline[peak];
+            writeln!(
+                out,
+                "  peak: {bytes} bytes at t={timestamp}, within 10% from t={start} to t={end}"
+            )?;
+        }
+
+        writeln!(out, "  timeline:")?;
+        for point in 0..points {
+            let timestamp = if points == 1 {
+                last
+            } else {
+                first + ((last - first) as u128 * point as u128 / (points - 1) as u128) as u64
+            };
+            writeln!(
+                out,
+                "    t={timestamp:<12} {} bytes",
+                report.usage_at(timestamp)
+            )?;
+        }
+
+        writeln!(out, "  sizes:")?;
+        for (class, count) in &report.sizes {
+            writeln!(out, "    >= {class:<10} {count}")?;
+        }
+
+        let mut leaks: Vec<(usize, i64)> = report
+            .outstanding
+            .iter()
+            .filter(|(_, &count)| count > 0)
+            .map(|(&size, &count)| (size, count))
+            .collect();
+        if !leaks.is_empty() {
+            leaks.sort_by_key(|&(size, count)| std::cmp::Reverse((size as i64 * count, size)));
+            writeln!(out, "  leak candidates:")?;
+            for (size, count) in leaks.into_iter().take(LEAK_CANDIDATES) {
+                writeln!(out, "    {count} x {size} bytes never freed")?;
+            }
+        }
+    }
+
+    out.flush()
+}
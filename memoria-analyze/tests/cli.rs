@@ -0,0 +1,47 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn analyze(log: &[u8], args: &[&str]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_memoria-analyze"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(log).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn report() {
+    // kind, timestamp delta, usecase, size
+    let log = [
+        0, 0, 1, 100, //
+        0, 10, 1, 50, //
+        1, 10, 1, 100, //
+    ];
+    let output = analyze(&log, &["--points", "3"]);
+    assert_eq!(
+        output,
+        "usecase 1
+  events: 2 allocs, 1 deallocs, 50 bytes at end
+  peak: 150 bytes at t=10, within 10% from t=10 to t=10
+  timeline:
+    t=0            100 bytes
+    t=10           150 bytes
+    t=20           50 bytes
+  sizes:
+    >= 32         1
+    >= 64         1
+  leak candidates:
+    1 x 50 bytes never freed
+"
+    );
+}
+
+#[test]
+fn empty() {
+    assert_eq!(analyze(&[], &[]), "no events\n");
+}
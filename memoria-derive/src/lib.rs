@@ -0,0 +1,203 @@
+//! Procedural macros for [memoria](https://docs.rs/memoria). Use them through memoria's
+//! `derive` and `instrument` features rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Expr, ExprLit, Fields, ItemFn, Lit,
+    MetaNameValue, Path, Token,
+};
+
+/// Implement `memoria::UseCase` for a fieldless enum.
+///
+/// See the documentation of `memoria::UseCase` for details.
+#[proc_macro_derive(UseCase, attributes(usecase))]
+pub fn derive_use_case(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_use_case(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_use_case(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input,
+                "UseCase can only be derived for enums",
+            ))
+        }
+    };
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "UseCase can not be derived for generic enums",
+        ));
+    }
+
+    let mut variants = Vec::new();
+    let mut default = None;
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "UseCase can only be derived for enums without fields",
+            ));
+        }
+        for attr in &variant.attrs {
+            if attr.path().is_ident("usecase") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("default") {
+                        if default.is_some() {
+                            return Err(meta.error("only one variant can be the default"));
+                        }
+                        default = Some(&variant.ident);
+                        Ok(())
+                    } else {
+                        Err(meta.error("unknown usecase attribute"))
+                    }
+                })?;
+            }
+        }
+        variants.push(&variant.ident);
+    }
+
+    let default = match default.or_else(|| variants.first().copied()) {
+        Some(default) => default,
+        None => {
+            return Err(syn::Error::new_spanned(
+                &input,
+                "UseCase can not be derived for empty enums",
+            ))
+        }
+    };
+    let names = variants.iter().map(|variant| variant.to_string());
+
+    Ok(quote! {
+        impl ::core::default::Default for #name {
+            fn default() -> Self {
+                #name::#default
+            }
+        }
+
+        impl ::core::convert::TryFrom<::memoria::UseCaseBytes> for #name {
+            type Error = ::memoria::UseCaseBytes;
+
+            fn try_from(value: ::memoria::UseCaseBytes) -> ::core::result::Result<Self, Self::Error> {
+                #(
+                    if value == #name::#variants as ::memoria::UseCaseBytes {
+                        return ::core::result::Result::Ok(#name::#variants);
+                    }
+                )*
+                ::core::result::Result::Err(value)
+            }
+        }
+
+        impl ::core::convert::From<#name> for ::memoria::UseCaseBytes {
+            fn from(use_case: #name) -> Self {
+                use_case as ::memoria::UseCaseBytes
+            }
+        }
+
+        impl #name {
+            /// The name of this variant.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    #(#name::#variants => #names,)*
+                }
+            }
+
+            /// All variants, in declaration order.
+            pub fn all_variants() -> &'static [Self] {
+                &[#(#name::#variants),*]
+            }
+        }
+
+        impl ::memoria::UseCase for #name {
+            fn name(&self) -> &'static str {
+                #name::name(self)
+            }
+        }
+    })
+}
+
+/// Run a whole function under a usecase.
+///
+/// ```ignore
+/// #[memoria::instrument(usecase = MyUseCase::Parse)]
+/// fn parse(input: &str) -> Vec<String> {
+///     input.lines().map(str::to_owned).collect()
+/// }
+/// ```
+///
+/// is equivalent to creating a guard with `ALLOCATOR.with_usecase(MyUseCase::Parse)` at the top
+/// of the function. The allocator is the static named `ALLOCATOR` in scope, a different one can
+/// be given as `allocator = path::to::STATIC`. Both arguments may also be given as string
+/// literals, like `usecase = "MyUseCase::Parse"`.
+///
+/// For `async fn`s, the body is wrapped in `memoria::Attributed`, such that the usecase is active
+/// whenever the future is polled rather than while it is created.
+///
+/// Requires the `instrument` feature of memoria.
+#[proc_macro_attribute]
+pub fn instrument(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args =
+        parse_macro_input!(args with Punctuated::<MetaNameValue, Token![,]>::parse_terminated);
+    let item = parse_macro_input!(item as ItemFn);
+    expand_instrument(args, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_instrument(
+    args: Punctuated<MetaNameValue, Token![,]>,
+    mut item: ItemFn,
+) -> syn::Result<TokenStream2> {
+    let mut use_case = None;
+    let mut allocator: Path = parse_quote!(ALLOCATOR);
+    for arg in args {
+        // Values may be given as string literals, like `usecase = "MyUseCase::Parse"`.
+        let value = match arg.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(lit), ..
+            }) => lit.parse()?,
+            value => value,
+        };
+        if arg.path.is_ident("usecase") {
+            use_case = Some(value);
+        } else if arg.path.is_ident("allocator") {
+            allocator = match value {
+                Expr::Path(path) => path.path,
+                value => return Err(syn::Error::new_spanned(value, "expected a path")),
+            };
+        } else {
+            return Err(syn::Error::new_spanned(
+                arg.path,
+                "unknown argument, expected `usecase` or `allocator`",
+            ));
+        }
+    }
+    let use_case: Expr = use_case.ok_or_else(|| {
+        syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "missing argument `usecase = ...`",
+        )
+    })?;
+
+    let block = &item.block;
+    item.block = if item.sig.asyncness.is_some() {
+        parse_quote!({
+            ::memoria::Attributed::new(async move #block, &#allocator, #use_case).await
+        })
+    } else {
+        parse_quote!({
+            let __memoria_guard = #allocator.with_usecase(#use_case);
+            #block
+        })
+    };
+    Ok(quote!(#item))
+}
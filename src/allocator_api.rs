@@ -0,0 +1,125 @@
+//! Support for the unstable [Allocator] trait, for attributing individual containers to a
+//! usecase.
+//!
+//! [Alloc] itself implements [Allocator], attributing allocations to the current usecase like the
+//! global allocator does. [Alloc::usecase_allocator] instead returns a handle that attributes
+//! every allocation to a fixed usecase, regardless of which guards are active:
+//!
+//! ```ignore
+//! #![feature(allocator_api)]
+//!
+//! let cache = ALLOCATOR.usecase_allocator(MyUseCase::Cache);
+//! let mut entries = Vec::new_in(cache);
+//! // attributed to MyUseCase::Cache, even while growing outside of any guard
+//! entries.push(1);
+//! ```
+//!
+//! Requires the `allocator-api` feature and nightly.
+
+use std::alloc::{AllocError, Allocator, GlobalAlloc, Layout};
+use std::ptr::NonNull;
+
+use crate::{Alloc, PointerTable, Recorder, UseCase, UseCaseRepr};
+
+/// An [Allocator] that attributes all allocations to one usecase.
+///
+/// Returned by [Alloc::usecase_allocator].
+pub struct UsecaseAllocator<'a, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> {
+    alloc: &'a Alloc<U, R, A, P>,
+    use_case: UseCaseRepr,
+}
+
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> UsecaseAllocator<'_, U, R, A, P> {
+    /// The usecase allocations are attributed to.
+    pub fn use_case(&self) -> U {
+        U::from_repr(self.use_case).unwrap_or_default()
+    }
+}
+
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Clone
+    for UsecaseAllocator<'_, U, R, A, P>
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Copy
+    for UsecaseAllocator<'_, U, R, A, P>
+{
+}
+
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Alloc<U, R, A, P> {
+    /// Return an [Allocator] that attributes all allocations to `use_case`.
+    pub fn usecase_allocator(&self, use_case: U) -> UsecaseAllocator<'_, U, R, A, P> {
+        UsecaseAllocator {
+            alloc: self,
+            use_case: use_case.into_repr(),
+        }
+    }
+
+    fn allocate_as(
+        &self,
+        layout: Layout,
+        use_case: Option<UseCaseRepr>,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            // `GlobalAlloc` does not support zero-sized allocations, so they are neither
+            // allocated nor tracked.
+            let dangling =
+                NonNull::new(std::ptr::without_provenance_mut(layout.align())).ok_or(AllocError)?;
+            return Ok(NonNull::slice_from_raw_parts(dangling, 0));
+        }
+        if self.is_finalized() {
+            // SAFETY: the layout has a non-zero size.
+            let ptr = NonNull::new(unsafe { self.alloc.alloc(layout) }).ok_or(AllocError)?;
+            return Ok(NonNull::slice_from_raw_parts(ptr, layout.size()));
+        }
+        let charged = self
+            .charge_cap(layout.size(), use_case)
+            .map_err(|_| AllocError)?;
+        // SAFETY: the layout has a non-zero size.
+        let Some(ptr) = NonNull::new(unsafe { self.alloc_routed(layout, use_case) }) else {
+            if let Some(charged) = charged {
+                self.caps.release(charged, layout.size());
+            }
+            return Err(AllocError);
+        };
+        self.handle_on_alloc(ptr.as_ptr() as usize, layout, use_case, charged);
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    /// # Safety
+    ///
+    /// See [Allocator::deallocate].
+    unsafe fn deallocate_any(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            self.handle_on_dealloc(ptr.as_ptr() as usize, layout);
+            self.dealloc_routed(ptr.as_ptr(), layout);
+        }
+    }
+}
+
+unsafe impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Allocator
+    for Alloc<U, R, A, P>
+{
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.allocate_as(layout, None)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.deallocate_any(ptr, layout)
+    }
+}
+
+unsafe impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Allocator
+    for UsecaseAllocator<'_, U, R, A, P>
+{
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.alloc.allocate_as(layout, Some(self.use_case))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.alloc.deallocate_any(ptr, layout)
+    }
+}
@@ -0,0 +1,146 @@
+use core::alloc::{GlobalAlloc, Layout};
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "std")]
+use once_cell::sync::OnceCell;
+
+use crate::{Alloc, PointerTable, Recorder, UseCase, UseCaseRepr};
+
+/// The number of routes that can be registered with [Alloc::route].
+#[cfg(feature = "std")]
+const MAX_ROUTES: usize = 8;
+
+/// An allocator that the allocations of a usecase can be routed to, see [Alloc::route].
+///
+/// # Safety
+///
+/// [Arena::owns] must return `true` for every pointer returned by this allocator that has not
+/// been freed yet, and `false` for every pointer that was returned by any other allocator.
+#[cfg(feature = "std")]
+pub unsafe trait Arena: GlobalAlloc + Sync {
+    /// Whether `ptr` was allocated by this arena.
+    fn owns(&self, ptr: *mut u8) -> bool;
+}
+
+/// The routes of an [Alloc], see [Alloc::route].
+#[cfg(feature = "std")]
+pub(crate) struct Routes {
+    routes: [OnceCell<(UseCaseRepr, &'static dyn Arena)>; MAX_ROUTES],
+    len: AtomicUsize,
+}
+
+#[cfg(feature = "std")]
+impl Routes {
+    pub(crate) const fn new() -> Self {
+        Routes {
+            routes: [const { OnceCell::new() }; MAX_ROUTES],
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    fn iter(&self) -> impl DoubleEndedIterator<Item = &(UseCaseRepr, &'static dyn Arena)> {
+        let len = self.len.load(Ordering::Acquire).min(MAX_ROUTES);
+        self.routes[..len].iter().filter_map(OnceCell::get)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Alloc<U, R, A, P> {
+    /// Serve allocations made for `use_case` from `arena` instead of the wrapped allocator.
+    ///
+    /// This isolates the memory of a usecase, such that it can be released back to the operating
+    /// system at once, for example by an arena that reserves its own memory mapping:
+    ///
+    /// ```ignore
+    /// static CACHE_ARENA: MmapArena = MmapArena::new(1 << 30);
+    ///
+    /// ALLOCATOR.route(MyUseCase::Cache, &CACHE_ARENA);
+    /// ```
+    ///
+    /// Memory is always freed by the arena that allocated it, regardless of the usecase that is
+    /// active at the time, and even if it was not tracked, see [Arena::owns]. Reallocations stay in
+    /// the arena of the usecase that is active while reallocating. If `use_case` is routed more
+    /// than once, the arena of the last call is used for new allocations.
+    ///
+    /// # Panics
+    ///
+    /// Routes can not be removed, and at most eight can be registered per [Alloc].
+    pub fn route(&self, use_case: U, arena: &'static dyn Arena) {
+        let index = self.routes.len.fetch_add(1, Ordering::AcqRel);
+        assert!(
+            index < MAX_ROUTES,
+            "at most {MAX_ROUTES} routes are supported"
+        );
+        self.routes.routes[index]
+            .set((use_case.into_repr(), arena))
+            .ok();
+    }
+
+    /// Allocate from the arena of `use_case` if given, and of the current usecase otherwise.
+    #[inline]
+    pub(crate) unsafe fn alloc_routed(
+        &self,
+        layout: Layout,
+        use_case: Option<UseCaseRepr>,
+    ) -> *mut u8 {
+        if self.routes.len.load(Ordering::Relaxed) == 0 {
+            return self.alloc.alloc(layout);
+        }
+        self.alloc_routed_slow(layout, use_case)
+    }
+
+    #[inline(never)]
+    unsafe fn alloc_routed_slow(&self, layout: Layout, use_case: Option<UseCaseRepr>) -> *mut u8 {
+        // If the usecase can not be determined, the wrapped allocator is used.
+        let use_case = match use_case {
+            Some(use_case) => Some(use_case),
+            None => self
+                .synchronized(Some(layout.size()), |current_value| {
+                    Ok(current_value.unwrap_or_else(|| U::default().into_repr()))
+                })
+                .ok(),
+        };
+        let arena = use_case.and_then(|use_case| {
+            self.routes
+                .iter()
+                .rev()
+                .find(|(routed, _)| *routed == use_case)
+        });
+        match arena {
+            Some((_, arena)) => arena.alloc(layout),
+            None => self.alloc.alloc(layout),
+        }
+    }
+
+    /// Free `ptr` through the arena that owns it, or the wrapped allocator.
+    #[inline]
+    pub(crate) unsafe fn dealloc_routed(&self, ptr: *mut u8, layout: Layout) {
+        if self.routes.len.load(Ordering::Relaxed) == 0 {
+            return self.alloc.dealloc(ptr, layout);
+        }
+        match self.routes.iter().find(|(_, arena)| arena.owns(ptr)) {
+            Some((_, arena)) => arena.dealloc(ptr, layout),
+            None => self.alloc.dealloc(ptr, layout),
+        }
+    }
+}
+
+/// Routes are kept in cells that require `std`, so without it everything goes to the wrapped
+/// allocator.
+#[cfg(not(feature = "std"))]
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Alloc<U, R, A, P> {
+    #[inline]
+    pub(crate) unsafe fn alloc_routed(
+        &self,
+        layout: Layout,
+        _use_case: Option<UseCaseRepr>,
+    ) -> *mut u8 {
+        self.alloc.alloc(layout)
+    }
+
+    #[inline]
+    pub(crate) unsafe fn dealloc_routed(&self, ptr: *mut u8, layout: Layout) {
+        self.alloc.dealloc(ptr, layout)
+    }
+}
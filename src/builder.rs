@@ -0,0 +1,202 @@
+use std::alloc::{GlobalAlloc, System};
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+
+use crate::filter::{self, Filter};
+use crate::{
+    Alloc, Clock, CurrentUsecaseStore, DeallocAttribution, DefaultTable, PointerTable, Recorder,
+    StatsRecorder, UseCase, UseCaseRepr,
+};
+
+/// Configures an [Alloc], see [Alloc::builder].
+///
+/// All methods are `const`, such that the result can be used as a `#[global_allocator]`:
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::Alloc<MyUseCase> = memoria::Alloc::builder()
+///     .sample_rate(64)
+///     .min_size(1024)
+///     .build();
+/// ```
+pub struct AllocBuilder<
+    U: UseCase,
+    R: Recorder<U> = StatsRecorder<U>,
+    A: GlobalAlloc = System,
+    P: PointerTable = DefaultTable,
+> {
+    // `ManuallyDrop`, since replacing them would otherwise drop the previous values, which is not
+    // possible in a const fn. Neither has done any work yet, so nothing is leaked.
+    recorder: ManuallyDrop<R>,
+    alloc: ManuallyDrop<A>,
+    dealloc_attribution: DeallocAttribution,
+    abort_on_forbidden_alloc: bool,
+    untracked_default: bool,
+    age_clock: Option<&'static dyn Clock>,
+    store: Option<&'static dyn CurrentUsecaseStore>,
+    sample_rate: u32,
+    min_size: usize,
+    name_matches: Option<fn(UseCaseRepr, &str) -> bool>,
+    _phantom: PhantomData<(U, P)>,
+}
+
+impl<U: UseCase> AllocBuilder<U> {
+    pub(crate) const fn new() -> Self {
+        AllocBuilder {
+            recorder: ManuallyDrop::new(StatsRecorder::new()),
+            alloc: ManuallyDrop::new(System),
+            dealloc_attribution: DeallocAttribution::Owner,
+            abort_on_forbidden_alloc: false,
+            untracked_default: false,
+            age_clock: None,
+            store: None,
+            sample_rate: 1,
+            min_size: 0,
+            name_matches: None,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> AllocBuilder<U, R, A, P> {
+    /// Record into `recorder` instead of a [StatsRecorder].
+    pub const fn recorder<R2: Recorder<U>>(self, recorder: R2) -> AllocBuilder<U, R2, A, P> {
+        AllocBuilder {
+            recorder: ManuallyDrop::new(recorder),
+            alloc: self.alloc,
+            dealloc_attribution: self.dealloc_attribution,
+            abort_on_forbidden_alloc: self.abort_on_forbidden_alloc,
+            untracked_default: self.untracked_default,
+            age_clock: self.age_clock,
+            store: self.store,
+            sample_rate: self.sample_rate,
+            min_size: self.min_size,
+            name_matches: self.name_matches,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Wrap `alloc` instead of the system allocator.
+    pub const fn allocator<A2: GlobalAlloc>(self, alloc: A2) -> AllocBuilder<U, R, A2, P> {
+        AllocBuilder {
+            recorder: self.recorder,
+            alloc: ManuallyDrop::new(alloc),
+            dealloc_attribution: self.dealloc_attribution,
+            abort_on_forbidden_alloc: self.abort_on_forbidden_alloc,
+            untracked_default: self.untracked_default,
+            age_clock: self.age_clock,
+            store: self.store,
+            sample_rate: self.sample_rate,
+            min_size: self.min_size,
+            name_matches: self.name_matches,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Track live allocations in the table `P2` instead of [DefaultTable], see [PointerTable].
+    pub const fn pointer_table<P2: PointerTable>(self) -> AllocBuilder<U, R, A, P2> {
+        AllocBuilder {
+            recorder: self.recorder,
+            alloc: self.alloc,
+            dealloc_attribution: self.dealloc_attribution,
+            abort_on_forbidden_alloc: self.abort_on_forbidden_alloc,
+            untracked_default: self.untracked_default,
+            age_clock: self.age_clock,
+            store: self.store,
+            sample_rate: self.sample_rate,
+            min_size: self.min_size,
+            name_matches: self.name_matches,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Only record every `sample_rate`-th allocation of each thread. The default is `1`, which
+    /// records every allocation.
+    ///
+    /// This reduces the overhead of recording, at the cost of accuracy: stats then only cover
+    /// the sampled allocations, and have to be multiplied by `sample_rate` for an estimate of the
+    /// actual usage. Deallocations of memory that was not sampled are not reported as
+    /// [Error::DeallocUntrackedPointer](crate::Error::DeallocUntrackedPointer).
+    ///
+    /// [Alloc::measure] and the other measuring functions still see every allocation.
+    pub const fn sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = if sample_rate == 0 { 1 } else { sample_rate };
+        self
+    }
+
+    /// Do not record allocations smaller than `min_size` bytes. The default is `0`, which records
+    /// every allocation.
+    ///
+    /// [Alloc::measure] and the other measuring functions still see every allocation.
+    pub const fn min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// See [Alloc::with_dealloc_attribution].
+    pub const fn dealloc_attribution(mut self, dealloc_attribution: DeallocAttribution) -> Self {
+        self.dealloc_attribution = dealloc_attribution;
+        self
+    }
+
+    /// See [Alloc::with_abort_on_forbidden_alloc].
+    pub const fn abort_on_forbidden_alloc(mut self) -> Self {
+        self.abort_on_forbidden_alloc = true;
+        self
+    }
+
+    /// See [Alloc::with_untracked_default].
+    pub const fn untracked_default(mut self) -> Self {
+        self.untracked_default = true;
+        self
+    }
+
+    /// See [Alloc::with_age_clock].
+    pub const fn age_clock(mut self, clock: &'static dyn Clock) -> Self {
+        self.age_clock = Some(clock);
+        self
+    }
+
+    /// See [Alloc::with_store].
+    pub const fn store(mut self, store: &'static dyn CurrentUsecaseStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Return the configured allocator.
+    pub const fn build(self) -> Alloc<U, R, A, P> {
+        let mut alloc = Alloc::new_with_filter(
+            ManuallyDrop::into_inner(self.recorder),
+            ManuallyDrop::into_inner(self.alloc),
+            Filter::new(self.sample_rate, self.min_size, self.name_matches),
+        );
+        alloc.dealloc_attribution = self.dealloc_attribution;
+        alloc.abort_on_forbidden_alloc = self.abort_on_forbidden_alloc;
+        alloc.untracked_default = self.untracked_default;
+        alloc.age_clock = self.age_clock;
+        alloc.store = self.store;
+        alloc
+    }
+}
+
+impl<U: UseCase + fmt::Debug, R: Recorder<U>, A: GlobalAlloc, P: PointerTable>
+    AllocBuilder<U, R, A, P>
+{
+    /// Let environment variables override the configuration, such that the overhead of memoria
+    /// can be tuned without rebuilding the program. The environment is read on first use, that
+    /// is when the first allocation is made.
+    ///
+    /// - `MEMORIA_ENABLED`: set to `0`, `false`, `no` or `off` to record nothing.
+    /// - `MEMORIA_SAMPLE_RATE`: overrides [AllocBuilder::sample_rate].
+    /// - `MEMORIA_MIN_SIZE`: overrides [AllocBuilder::min_size].
+    /// - `MEMORIA_USECASES`: a comma-separated list of usecase names. If set, only allocations
+    ///   for these usecases are recorded. Names are compared to [UseCase::name], or to the
+    ///   `Debug` representation of usecases that do not override it.
+    ///
+    /// Invalid values are ignored. Usecases can still be switched while nothing is recorded.
+    pub const fn env_config(mut self) -> Self {
+        self.name_matches = Some(filter::name_matches::<U>);
+        self
+    }
+}
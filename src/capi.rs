@@ -0,0 +1,156 @@
+//! `extern "C"` functions for hosts that embed Rust code using memoria, such as C++ or Python
+//! programs.
+//!
+//! Register the global allocator once from Rust, then the host can query stats and switch
+//! usecases across the FFI boundary:
+//!
+//! ```ignore
+//! memoria::capi::install(&ALLOCATOR);
+//! ```
+//!
+//! ```c
+//! int memoria_set_usecase(uint32_t use_case);
+//! int memoria_restore_usecase(void);
+//! ptrdiff_t memoria_stats_snapshot(char *buf, size_t len);
+//! ptrdiff_t memoria_errors_snapshot(char *buf, size_t len);
+//! ```
+//!
+//! The functions are exported from any `cdylib` or `staticlib` that depends on memoria with the
+//! `capi` feature enabled.
+//!
+//! Requires the `capi` feature.
+
+use std::alloc::GlobalAlloc;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int};
+use std::fmt;
+
+use once_cell::sync::OnceCell;
+
+use crate::export::json::{render_json_errors, render_json_stats};
+use crate::{utils, Alloc, Guard, PointerTable, StatsRecorder, UseCase, UseCaseRepr};
+
+/// Type-erased access to the registered allocator.
+trait CapiAlloc: Sync {
+    fn enter(&'static self, use_case: u32) -> Option<Guard<'static>>;
+    fn stats_json(&self) -> String;
+    fn errors_json(&self) -> String;
+}
+
+impl<U, A, P> CapiAlloc for Alloc<U, StatsRecorder<U>, A, P>
+where
+    U: UseCase + fmt::Debug + Sync,
+    A: GlobalAlloc + Sync,
+    P: PointerTable,
+{
+    fn enter(&'static self, use_case: u32) -> Option<Guard<'static>> {
+        self.with_usecase(U::from_repr(UseCaseRepr::from(use_case))?)
+    }
+
+    fn stats_json(&self) -> String {
+        render_json_stats(self)
+    }
+
+    fn errors_json(&self) -> String {
+        render_json_errors(self)
+    }
+}
+
+static ALLOC: OnceCell<&'static dyn CapiAlloc> = OnceCell::new();
+
+utils::local! {
+    /// Guards created by [memoria_set_usecase], innermost last.
+    static GUARDS: RefCell<Vec<Guard<'static>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Make `alloc` the allocator that the C functions operate on.
+///
+/// Only one allocator can be registered. Calling this again does nothing.
+pub fn install<U, A, P>(alloc: &'static Alloc<U, StatsRecorder<U>, A, P>)
+where
+    U: UseCase + fmt::Debug + Sync,
+    A: GlobalAlloc + Sync,
+    P: PointerTable,
+{
+    ALLOC.set(alloc).ok();
+}
+
+/// Switch the current thread to `use_case`, until the matching call to
+/// [memoria_restore_usecase].
+///
+/// Returns `0` on success, and `-1` if no allocator was installed, `use_case` is not a valid
+/// usecase, or the usecase could not be switched.
+#[no_mangle]
+pub extern "C" fn memoria_set_usecase(use_case: u32) -> c_int {
+    let Some(guard) = ALLOC.get().and_then(|alloc| alloc.enter(use_case)) else {
+        return -1;
+    };
+    match GUARDS.try_with(|guards| guards.borrow_mut().push(guard)) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Switch the current thread back to the usecase that was active before the last call to
+/// [memoria_set_usecase].
+///
+/// Returns `0` on success, and `-1` if there is nothing to restore.
+#[no_mangle]
+pub extern "C" fn memoria_restore_usecase() -> c_int {
+    // Dropped outside of the borrow, since dropping a guard runs the recorder's hooks.
+    let guard = GUARDS
+        .try_with(|guards| guards.borrow_mut().pop())
+        .ok()
+        .flatten();
+    match guard {
+        Some(guard) => {
+            drop(guard);
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Write the current stats of all usecases as a NUL-terminated JSON object into `buf`, like the
+/// `/stats` endpoint of the `http` module does. Stats are not reset.
+///
+/// Returns the length of the JSON without the NUL terminator, or `-1` if no allocator was
+/// installed. Like `snprintf`, the output is truncated if it is longer than `len - 1`, in which
+/// case the caller should retry with a larger buffer. `buf` may be null if `len` is zero.
+///
+/// # Safety
+///
+/// `buf` must be valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn memoria_stats_snapshot(buf: *mut c_char, len: usize) -> isize {
+    match ALLOC.get() {
+        Some(alloc) => write_c_string(&alloc.stats_json(), buf, len),
+        None => -1,
+    }
+}
+
+/// Like [memoria_stats_snapshot], but write the count of every error, like the `/errors`
+/// endpoint of the `http` module does.
+///
+/// # Safety
+///
+/// `buf` must be valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn memoria_errors_snapshot(buf: *mut c_char, len: usize) -> isize {
+    match ALLOC.get() {
+        Some(alloc) => write_c_string(&alloc.errors_json(), buf, len),
+        None => -1,
+    }
+}
+
+/// # Safety
+///
+/// See [memoria_stats_snapshot].
+unsafe fn write_c_string(s: &str, buf: *mut c_char, len: usize) -> isize {
+    if len > 0 && !buf.is_null() {
+        let n = s.len().min(len - 1);
+        std::ptr::copy_nonoverlapping(s.as_ptr().cast::<c_char>(), buf, n);
+        *buf.add(n) = 0;
+    }
+    s.len() as isize
+}
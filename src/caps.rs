@@ -0,0 +1,215 @@
+use core::alloc::GlobalAlloc;
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "std")]
+use dashmap::DashMap;
+
+#[cfg(feature = "std")]
+use crate::sync::ResettableCell;
+use crate::{Alloc, Error, PointerTable, Recorder, UseCase, UseCaseRepr};
+
+/// The hard caps of an [Alloc], see [Alloc::set_cap].
+///
+/// A usecase without a cap has a limit of `usize::MAX`, which still tracks its usage. This is
+/// used for usecases with a shrinker, see [Alloc::register_shrinker].
+#[cfg(feature = "std")]
+pub(crate) struct Caps {
+    caps: ResettableCell<DashMap<UseCaseRepr, Cap>>,
+}
+
+#[cfg(feature = "std")]
+struct Cap {
+    limit: AtomicUsize,
+    used: AtomicUsize,
+}
+
+#[cfg(feature = "std")]
+impl Caps {
+    pub(crate) const fn new() -> Self {
+        Caps {
+            caps: ResettableCell::new(),
+        }
+    }
+
+    /// Charge `size` bytes to the cap of `use_case`. Returns `Ok(false)` if the usecase has no
+    /// cap, and an error if the cap would be exceeded, in which case nothing is charged.
+    fn charge(&self, use_case: UseCaseRepr, size: usize) -> Result<bool, Error> {
+        let Some(cap) = self.caps.get().and_then(|caps| caps.get(&use_case)) else {
+            return Ok(false);
+        };
+        let limit = cap.limit.load(Ordering::Relaxed);
+        cap.used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(size).filter(|&used| used <= limit)
+            })
+            .map(|_| true)
+            .map_err(|_| Error::CapExceeded)
+    }
+
+    /// Undo [Caps::charge].
+    pub(crate) fn release(&self, use_case: UseCaseRepr, size: usize) {
+        if let Some(cap) = self.caps.get().and_then(|caps| caps.get(&use_case)) {
+            cap.used.fetch_sub(size, Ordering::Relaxed);
+        }
+    }
+
+    /// Start tracking the usage of `use_case` without limiting it, unless it already is tracked.
+    ///
+    /// Inserting may allocate, so this must be called from within `Alloc::synchronized`.
+    pub(crate) fn watch(&self, use_case: UseCaseRepr) {
+        self.caps
+            .get_or_init(DashMap::new)
+            .entry(use_case)
+            .or_insert_with(|| Cap {
+                limit: AtomicUsize::new(usize::MAX),
+                used: AtomicUsize::new(0),
+            });
+    }
+
+    /// The number of bytes charged to `use_case`, or `None` if its usage is not tracked.
+    pub(crate) fn used(&self, use_case: UseCaseRepr) -> Option<usize> {
+        self.caps
+            .get()
+            .and_then(|caps| caps.get(&use_case))
+            .map(|cap| cap.used.load(Ordering::Relaxed))
+    }
+
+    /// Forget all caps, for use in the child process after `fork`.
+    pub(crate) fn reset(&self) {
+        self.caps.reset();
+    }
+}
+
+#[cfg(feature = "std")]
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Alloc<U, R, A, P> {
+    /// Refuse allocations that would bring the memory used by `use_case` above `bytes`.
+    ///
+    /// Such allocations return null instead, and are reported as [Error::CapExceeded]. Fallible
+    /// allocations, such as through `Vec::try_reserve`, can handle this gracefully, while
+    /// infallible ones call the allocation error handler, which aborts the process by default.
+    /// This allows isolating subsystems of a process from each other, like cgroups do for
+    /// processes.
+    ///
+    /// Only allocations that memoria tracks count towards the cap, which excludes allocations
+    /// skipped by [AllocBuilder::sample_rate](crate::AllocBuilder::sample_rate) and similar
+    /// settings, and allocations that were made before a cap was first set on the usecase.
+    /// Setting a cap on a usecase that already has one only changes the limit.
+    pub fn set_cap(&self, use_case: U, bytes: usize) {
+        let use_case = use_case.into_repr();
+        // Inserting may allocate, which must not check the caps while they are locked.
+        self.synchronized(None, |_| {
+            let caps = self.caps.caps.get_or_init(DashMap::new);
+            match caps.get(&use_case) {
+                Some(cap) => cap.limit.store(bytes, Ordering::Relaxed),
+                None => {
+                    caps.insert(
+                        use_case,
+                        Cap {
+                            limit: AtomicUsize::new(bytes),
+                            used: AtomicUsize::new(0),
+                        },
+                    );
+                }
+            }
+            Ok(())
+        })
+        .ok();
+    }
+
+    /// Remove the cap of `use_case`, see [Alloc::set_cap].
+    pub fn remove_cap(&self, use_case: U) {
+        // The usage stays tracked, so that setting a cap again accounts for live allocations
+        // that were charged to the previous one.
+        self.synchronized(None, |_| {
+            if let Some(cap) = self
+                .caps
+                .caps
+                .get()
+                .and_then(|caps| caps.get(&use_case.into_repr()))
+            {
+                cap.limit.store(usize::MAX, Ordering::Relaxed);
+            }
+            Ok(())
+        })
+        .ok();
+    }
+
+    /// The number of bytes that count towards the cap of `use_case`, or `None` if it has no cap.
+    pub fn cap_usage(&self, use_case: U) -> Option<usize> {
+        self.synchronized(None, |_| {
+            Ok(self
+                .caps
+                .caps
+                .get()
+                .and_then(|caps| caps.get(&use_case.into_repr()))
+                .filter(|cap| cap.limit.load(Ordering::Relaxed) != usize::MAX)
+                .map(|cap| cap.used.load(Ordering::Relaxed)))
+        })
+        .ok()
+        .flatten()
+    }
+
+    /// Charge an allocation that is about to be made to the cap of its usecase, which is
+    /// `use_case` if given and the current usecase otherwise.
+    ///
+    /// Returns the usecase that was charged, if any, or an error if the allocation must be
+    /// refused.
+    #[inline]
+    pub(crate) fn charge_cap(
+        &self,
+        size: usize,
+        use_case: Option<UseCaseRepr>,
+    ) -> Result<Option<UseCaseRepr>, Error> {
+        if self.caps.caps.get().is_none() {
+            return Ok(None);
+        }
+        self.charge_cap_slow(size, use_case)
+    }
+
+    #[inline(never)]
+    fn charge_cap_slow(
+        &self,
+        size: usize,
+        use_case: Option<UseCaseRepr>,
+    ) -> Result<Option<UseCaseRepr>, Error> {
+        self.synchronized(Some(size), |current_value| {
+            let use_case = use_case
+                .or(*current_value)
+                .unwrap_or_else(|| U::default().into_repr());
+            Ok(self.caps.charge(use_case, size)?.then_some(use_case))
+        })
+        .or_else(|error| match error {
+            Error::CapExceeded => Err(error),
+            // The usecase is unknown, so the cap can not be enforced.
+            _ => Ok(None),
+        })
+    }
+}
+
+/// Caps are keyed in a concurrent map that requires `std`, so without it nothing is ever charged.
+#[cfg(not(feature = "std"))]
+pub(crate) struct Caps;
+
+#[cfg(not(feature = "std"))]
+impl Caps {
+    pub(crate) const fn new() -> Self {
+        Caps
+    }
+
+    pub(crate) fn release(&self, _use_case: UseCaseRepr, _size: usize) {}
+
+    pub(crate) fn reset(&self) {}
+}
+
+#[cfg(not(feature = "std"))]
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Alloc<U, R, A, P> {
+    #[inline]
+    pub(crate) fn charge_cap(
+        &self,
+        _size: usize,
+        _use_case: Option<UseCaseRepr>,
+    ) -> Result<Option<UseCaseRepr>, Error> {
+        Ok(None)
+    }
+}
@@ -0,0 +1,148 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+#[cfg(feature = "std")]
+use once_cell::sync::OnceCell;
+
+/// A source of monotonic timestamps for memoria's time-based features, such as
+/// [StatsRecorder::with_peak_clock](crate::StatsRecorder::with_peak_clock) and
+/// [Alloc::with_age_clock](crate::Alloc::with_age_clock).
+///
+/// Clocks are called from within the allocator, often on every allocation, so [Clock::now] must
+/// be cheap, and must not allocate or panic. A good choice is reading a coarse timestamp from an
+/// atomic that is periodically updated by another thread. Any `Fn() -> u64` is a clock with
+/// one tick per second, and can be passed by reference:
+///
+/// ```
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// # #[derive(Default)] struct MyUseCase;
+/// # impl From<MyUseCase> for u32 { fn from(_: MyUseCase) -> u32 { 0 } }
+/// # impl From<u32> for MyUseCase { fn from(_: u32) -> MyUseCase { MyUseCase } }
+/// # impl memoria::UseCase for MyUseCase {}
+/// static SECONDS: AtomicU64 = AtomicU64::new(0);
+///
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::Alloc<MyUseCase> =
+///     memoria::Alloc::new().with_age_clock(&|| SECONDS.load(Ordering::Relaxed));
+/// ```
+///
+/// [InstantClock] reads the monotonic clock of the operating system, and [ManualClock] is
+/// advanced by hand, for tests.
+pub trait Clock: Sync {
+    /// The current time in ticks. Must never decrease.
+    fn now(&self) -> u64;
+
+    /// The number of ticks per second, used to convert ticks into durations, for example for
+    /// rates. Defaults to one.
+    fn ticks_per_second(&self) -> u64 {
+        1
+    }
+
+    /// The time elapsed between the ticks `earlier` and `later`.
+    fn elapsed(&self, earlier: u64, later: u64) -> Duration {
+        let ticks_per_second = self.ticks_per_second().max(1);
+        let ticks = later.saturating_sub(earlier);
+        Duration::from_secs(ticks / ticks_per_second)
+            + Duration::from_nanos(
+                ((ticks % ticks_per_second) as u128 * 1_000_000_000 / ticks_per_second as u128)
+                    as u64,
+            )
+    }
+}
+
+impl<F: Fn() -> u64 + Sync> Clock for F {
+    fn now(&self) -> u64 {
+        self()
+    }
+}
+
+/// The clock of features that need one, unless another one is configured.
+#[cfg(feature = "log")]
+pub(crate) static DEFAULT_CLOCK: InstantClock = InstantClock::new();
+
+/// A [Clock] that counts milliseconds since it was first read, using [Instant].
+///
+/// Reading it asks the operating system for the time, which is fast on most platforms, but slower
+/// than reading an atomic.
+#[cfg(feature = "std")]
+pub struct InstantClock {
+    start: OnceCell<Instant>,
+}
+
+#[cfg(feature = "std")]
+impl InstantClock {
+    /// Construct a new clock.
+    pub const fn new() -> Self {
+        InstantClock {
+            start: OnceCell::new(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for InstantClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for InstantClock {
+    fn now(&self) -> u64 {
+        let now = Instant::now();
+        now.saturating_duration_since(*self.start.get_or_init(|| now))
+            .as_millis() as u64
+    }
+
+    fn ticks_per_second(&self) -> u64 {
+        1000
+    }
+}
+
+/// A [Clock] that only moves when told to, for tests. Ticks are seconds.
+///
+/// ```
+/// static CLOCK: memoria::ManualClock = memoria::ManualClock::new();
+///
+/// # use memoria::Clock;
+/// CLOCK.advance(5);
+/// assert_eq!(CLOCK.now(), 5);
+/// ```
+pub struct ManualClock {
+    now: AtomicU64,
+}
+
+impl ManualClock {
+    /// Construct a new clock at tick zero.
+    pub const fn new() -> Self {
+        ManualClock {
+            now: AtomicU64::new(0),
+        }
+    }
+
+    /// Move the clock forward by `ticks`.
+    pub fn advance(&self, ticks: u64) {
+        self.now.fetch_add(ticks, Ordering::Relaxed);
+    }
+
+    /// Set the clock to `ticks`. Setting it to an earlier time than before breaks the contract of
+    /// [Clock::now].
+    pub fn set(&self, ticks: u64) {
+        self.now.store(ticks, Ordering::Relaxed);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use crate::{UseCase, UseCaseBytes};
+
+/// The name of [DynUseCase::default].
+const DEFAULT_NAME: &str = "default";
+
+struct Interner {
+    names: Vec<&'static str>,
+    ids: HashMap<&'static str, UseCaseBytes>,
+}
+
+static INTERNER: Lazy<Mutex<Interner>> = Lazy::new(|| {
+    Mutex::new(Interner {
+        names: vec![DEFAULT_NAME],
+        ids: HashMap::from([(DEFAULT_NAME, 0)]),
+    })
+});
+
+// The number of registered usecases. Kept outside of the interner, because `TryFrom` is called
+// from within the allocator and must not take a lock that `register_usecase` holds while
+// allocating.
+static REGISTERED: AtomicU32 = AtomicU32::new(1);
+
+/// A usecase registered at runtime through [register_usecase].
+///
+/// For applications where the set of usecases is not known at compile time, such as ones that
+/// load plugins. Every distinct name gets its own stats:
+///
+/// ```
+/// use memoria::{Alloc, DynUseCase};
+///
+/// #[global_allocator]
+/// static ALLOCATOR: Alloc<DynUseCase> = Alloc::new();
+///
+/// let plugin = memoria::register_usecase("plugin:foo");
+/// assert_eq!(plugin.name(), "plugin:foo");
+///
+/// let _guard = ALLOCATOR.with_usecase(plugin);
+/// ```
+///
+/// The default usecase is named `"default"`. Registered names are never freed.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct DynUseCase(UseCaseBytes);
+
+impl DynUseCase {
+    /// The name this usecase was registered with.
+    pub fn name(&self) -> &'static str {
+        lock().names[self.0 as usize]
+    }
+}
+
+impl fmt::Debug for DynUseCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DynUseCase").field(&self.name()).finish()
+    }
+}
+
+impl TryFrom<UseCaseBytes> for DynUseCase {
+    type Error = UseCaseBytes;
+
+    fn try_from(value: UseCaseBytes) -> Result<Self, Self::Error> {
+        if value < REGISTERED.load(Ordering::Acquire) {
+            Ok(DynUseCase(value))
+        } else {
+            Err(value)
+        }
+    }
+}
+
+impl From<DynUseCase> for UseCaseBytes {
+    fn from(use_case: DynUseCase) -> Self {
+        use_case.0
+    }
+}
+
+impl UseCase for DynUseCase {
+    fn name(&self) -> &'static str {
+        DynUseCase::name(self)
+    }
+}
+
+/// Get the [DynUseCase] for `name`, registering it if this is the first time it is seen.
+///
+/// Calling this again with the same name returns the same usecase.
+///
+/// # Panics
+///
+/// Panics if more than `u32::MAX` distinct names are registered.
+pub fn register_usecase(name: &str) -> DynUseCase {
+    let mut interner = lock();
+    if let Some(&id) = interner.ids.get(name) {
+        return DynUseCase(id);
+    }
+    let id = UseCaseBytes::try_from(interner.names.len()).expect("too many usecases registered");
+    let name: &'static str = Box::leak(name.into());
+    interner.names.push(name);
+    interner.ids.insert(name, id);
+    REGISTERED.store(id + 1, Ordering::Release);
+    DynUseCase(id)
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Interner> {
+    // The interner is never left in an inconsistent state, so a panic while it was locked is of no
+    // concern.
+    INTERNER.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,338 @@
+mod ring;
+
+use std::alloc::Layout;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use ring::Ring;
+pub use ring::{Event, EventKind};
+
+use crate::{
+    Callsite, Clock, Error, OverheadEstimate, Recorder, StatsRecorder, Tag, UseCase, UseCaseRepr,
+};
+
+impl EventKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            EventKind::Alloc => 0,
+            EventKind::Dealloc => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(EventKind::Alloc),
+            1 => Some(EventKind::Dealloc),
+            _ => None,
+        }
+    }
+}
+
+/// A recorder that logs every allocation and deallocation into a fixed-size buffer, from which
+/// they can be written out to a file or socket in a compact binary format. All calls are
+/// forwarded to another recorder `R`.
+///
+/// Aggregate stats can't tell you what happened in between two flushes. With an event log,
+/// offline tooling such as [EventReader] can reconstruct exact memory timelines.
+///
+/// The buffer holds up to `N` events and is lock-free: recording an event never blocks or
+/// allocates. When the buffer is full, events are dropped and counted in
+/// [EventLogRecorder::dropped]. Call [EventLogRecorder::drain] often enough, for example from a
+/// background thread, to avoid that.
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::Alloc<MyUseCase, memoria::EventLogRecorder<MyUseCase>> =
+///     memoria::Alloc::new_with(
+///         memoria::EventLogRecorder::new(memoria::StatsRecorder::new()).with_clock(&now),
+///         std::alloc::System,
+///     );
+///
+/// // periodically:
+/// ALLOCATOR.with_recorder(|recorder| Ok(recorder.drain(&mut file))).ok();
+/// ```
+///
+/// # Format
+///
+/// Each event is written as a kind byte (`0` for allocations, `1` for deallocations), followed by
+/// the timestamp delta to the previous event, the usecase and the size, each as an unsigned
+/// LEB128 varint. The first event of each [drain](EventLogRecorder::drain) has a delta relative
+/// to the last event of the previous one, so concatenating all drains yields a valid log.
+pub struct EventLogRecorder<U: UseCase, R: Recorder<U> = StatsRecorder<U>, const N: usize = 4096> {
+    inner: R,
+    clock: Option<&'static dyn Clock>,
+    ring: Ring<N>,
+    last_timestamp: AtomicU64,
+    _phantom: PhantomData<U>,
+}
+
+impl<U: UseCase, R: Recorder<U>, const N: usize> EventLogRecorder<U, R, N> {
+    /// Construct a new recorder with an empty buffer.
+    pub const fn new(inner: R) -> Self {
+        EventLogRecorder {
+            inner,
+            clock: None,
+            ring: Ring::new(),
+            last_timestamp: AtomicU64::new(0),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Timestamp every event using `clock`. Without a clock, all timestamps are zero.
+    ///
+    /// `clock` is read on every allocation, see [Clock] for the requirements.
+    pub const fn with_clock(mut self, clock: &'static dyn Clock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Access the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// The number of events that were lost because the buffer was full.
+    pub fn dropped(&self) -> usize {
+        self.ring.dropped()
+    }
+
+    fn push(&self, kind: EventKind, use_case: UseCaseRepr, size: usize) {
+        self.ring.push(Event {
+            timestamp: self.clock.map_or(0, |clock| clock.now()),
+            kind,
+            use_case,
+            size,
+        });
+    }
+
+    /// Write all buffered events to `writer`, and return how many were written.
+    ///
+    /// Concurrent calls return `Ok(0)` immediately. If writing fails, the events that were
+    /// already taken out of the buffer are lost.
+    pub fn drain(&self, mut writer: impl Write) -> io::Result<usize> {
+        let mut buffer = [0u8; 1 + 3 * 10];
+        self.ring.drain_with(|event| {
+            let last_timestamp = self.last_timestamp.swap(event.timestamp, Ordering::Relaxed);
+            buffer[0] = event.kind.to_byte();
+            let mut len = 1;
+            for value in [
+                event.timestamp.wrapping_sub(last_timestamp),
+                event.use_case,
+                event.size as u64,
+            ] {
+                len += write_varint(&mut buffer[len..], value);
+            }
+            writer.write_all(&buffer[..len])
+        })
+    }
+
+    /// Write all buffered events to `writer` in heaptrack's format, and return how many were
+    /// written.
+    ///
+    /// The same restrictions as for [EventLogRecorder::drain] apply, and a later
+    /// [drain](EventLogRecorder::drain) continues after the last event written here. Requires the
+    /// `heaptrack` feature.
+    #[cfg(feature = "heaptrack")]
+    pub fn drain_heaptrack<W: Write>(
+        &self,
+        writer: &mut crate::export::heaptrack::HeaptrackWriter<U, W>,
+    ) -> io::Result<usize>
+    where
+        U: std::fmt::Debug,
+    {
+        self.ring.drain_with(|event| {
+            self.last_timestamp
+                .store(event.timestamp, Ordering::Relaxed);
+            writer.write_event(&event)
+        })
+    }
+}
+
+fn write_varint(buffer: &mut [u8], mut value: u64) -> usize {
+    let mut len = 0;
+    while value >= 0x80 {
+        buffer[len] = value as u8 | 0x80;
+        value >>= 7;
+        len += 1;
+    }
+    buffer[len] = value as u8;
+    len + 1
+}
+
+fn read_varint(mut reader: impl Read) -> io::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        reader.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint too long",
+    ))
+}
+
+/// Reads events written by [EventLogRecorder::drain], reconstructing absolute timestamps.
+pub struct EventReader<T: Read> {
+    reader: T,
+    timestamp: u64,
+}
+
+impl<T: Read> EventReader<T> {
+    /// Read events from `reader`. Wrapping it in a [std::io::BufReader] is recommended.
+    pub fn new(reader: T) -> Self {
+        EventReader {
+            reader,
+            timestamp: 0,
+        }
+    }
+
+    fn read_event(&mut self, kind: u8) -> io::Result<Event> {
+        let kind = EventKind::from_byte(kind)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid event kind"))?;
+        let delta = read_varint(&mut self.reader)?;
+        let use_case = read_varint(&mut self.reader)?;
+        let size = read_varint(&mut self.reader)?;
+        self.timestamp = self.timestamp.wrapping_add(delta);
+        Ok(Event {
+            timestamp: self.timestamp,
+            kind,
+            use_case,
+            size: size
+                .try_into()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid size"))?,
+        })
+    }
+}
+
+impl<T: Read> Iterator for EventReader<T> {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut kind = [0u8];
+        match self.reader.read(&mut kind) {
+            Ok(0) => None,
+            Ok(_) => Some(self.read_event(kind[0])),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+unsafe impl<U: UseCase, R: Recorder<U>, const N: usize> Recorder<U> for EventLogRecorder<U, R, N> {
+    fn on_alloc(&self, use_case: U, size: usize) -> bool {
+        let use_case_bytes: UseCaseRepr = use_case.into_repr();
+        let tracked = self
+            .inner
+            .on_alloc(U::from_repr(use_case_bytes).unwrap_or_default(), size);
+        // Untracked allocations won't see a matching deallocation.
+        if tracked {
+            self.push(EventKind::Alloc, use_case_bytes, size);
+        }
+        tracked
+    }
+
+    fn on_shared_alloc(&self, use_case: U, size: usize) -> bool {
+        let use_case_bytes: UseCaseRepr = use_case.into_repr();
+        let tracked = self
+            .inner
+            .on_shared_alloc(U::from_repr(use_case_bytes).unwrap_or_default(), size);
+        if tracked {
+            self.push(EventKind::Alloc, use_case_bytes, size);
+        }
+        tracked
+    }
+
+    fn on_alloc_layout(&self, use_case: U, layout: Layout) {
+        self.inner.on_alloc_layout(use_case, layout)
+    }
+
+    fn on_dealloc(&self, use_case: U, size: usize) {
+        let use_case_bytes: UseCaseRepr = use_case.into_repr();
+        self.push(EventKind::Dealloc, use_case_bytes, size);
+        self.inner
+            .on_dealloc(U::from_repr(use_case_bytes).unwrap_or_default(), size)
+    }
+
+    fn on_shared_dealloc(&self, use_case: U, size: usize) {
+        let use_case_bytes: UseCaseRepr = use_case.into_repr();
+        self.push(EventKind::Dealloc, use_case_bytes, size);
+        self.inner
+            .on_shared_dealloc(U::from_repr(use_case_bytes).unwrap_or_default(), size)
+    }
+
+    fn on_attributed_drop(&self, use_case: U, size: usize) {
+        self.inner.on_attributed_drop(use_case, size)
+    }
+
+    fn on_transfer(&self, from: U, to: U, size: usize) {
+        self.inner.on_transfer(from, to, size)
+    }
+
+    fn on_cross_thread_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_cross_thread_dealloc(use_case, size)
+    }
+
+    fn on_external_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_alloc(use_case, size)
+    }
+
+    fn on_external_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_dealloc(use_case, size)
+    }
+
+    fn on_callsite_alloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_alloc(use_case, callsite, size)
+    }
+
+    fn on_callsite_dealloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_dealloc(use_case, callsite, size)
+    }
+
+    fn on_tagged_alloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_alloc(use_case, tag, size)
+    }
+
+    fn on_tagged_dealloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_dealloc(use_case, tag, size)
+    }
+
+    fn on_forbidden_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_forbidden_alloc(use_case, size)
+    }
+
+    fn on_usecase_enter(&self, use_case: U) {
+        self.inner.on_usecase_enter(use_case)
+    }
+
+    fn on_usecase_exit(&self, use_case: U) {
+        self.inner.on_usecase_exit(use_case)
+    }
+
+    fn on_guard_enter(&self, use_case: U) {
+        self.inner.on_guard_enter(use_case)
+    }
+
+    fn on_guard_exit(&self, use_case: U) {
+        self.inner.on_guard_exit(use_case)
+    }
+
+    fn on_flush(&self) {
+        self.inner.on_flush()
+    }
+
+    fn on_overhead_estimate(&self, estimate: OverheadEstimate) {
+        self.inner.on_overhead_estimate(estimate)
+    }
+
+    fn on_fork(&self) {
+        self.inner.on_fork()
+    }
+
+    fn on_error(&self, code: Error, size: Option<usize>) {
+        self.inner.on_error(code, size)
+    }
+}
@@ -0,0 +1,199 @@
+//! The buffer behind [EventLogRecorder](super::EventLogRecorder).
+//!
+//! This module only depends on [UseCaseRepr](crate::UseCaseRepr) from the rest of the crate, such
+//! that `tests/loom.rs` can include it and model-check it. Like in `sync.rs`, it is built on
+//! loom's atomics when it is built as part of a test with `--cfg loom`.
+
+#[cfg(not(all(loom, test)))]
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+#[cfg(all(loom, test))]
+use loom::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use crate::UseCaseRepr;
+
+/// Whether an [Event] is an allocation or a deallocation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum EventKind {
+    /// Memory was allocated.
+    Alloc,
+    /// Memory was deallocated.
+    Dealloc,
+}
+
+/// A single allocation or deallocation, as written by
+/// [EventLogRecorder::drain](super::EventLogRecorder::drain) and read by
+/// [EventReader](super::EventReader).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Event {
+    /// The value of the recorder's clock when the event happened.
+    pub timestamp: u64,
+    /// Whether memory was allocated or deallocated.
+    pub kind: EventKind,
+    /// The usecase the memory is attributed to.
+    pub use_case: UseCaseRepr,
+    /// The size of the allocation in bytes.
+    pub size: usize,
+}
+
+struct Slot {
+    /// `2 * lap` while the slot can be written to in lap `lap`, `2 * lap + 1` once it has been
+    /// written and can be read.
+    sequence: AtomicUsize,
+    timestamp: AtomicU64,
+    dealloc: AtomicBool,
+    use_case: AtomicU64,
+    size: AtomicUsize,
+}
+
+impl Slot {
+    #[cfg(not(all(loom, test)))]
+    const fn empty() -> Slot {
+        Slot {
+            sequence: AtomicUsize::new(0),
+            timestamp: AtomicU64::new(0),
+            dealloc: AtomicBool::new(false),
+            use_case: AtomicU64::new(0),
+            size: AtomicUsize::new(0),
+        }
+    }
+
+    #[cfg(all(loom, test))]
+    fn empty() -> Slot {
+        Slot {
+            sequence: AtomicUsize::new(0),
+            timestamp: AtomicU64::new(0),
+            dealloc: AtomicBool::new(false),
+            use_case: AtomicU64::new(0),
+            size: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// A lock-free buffer of up to `N` events, written to by any number of threads and read by one
+/// at a time.
+///
+/// Writing never blocks: when the buffer is full, the event is dropped and counted instead.
+pub(crate) struct Ring<const N: usize> {
+    slots: [Slot; N],
+    tail: AtomicUsize,
+    head: AtomicUsize,
+    draining: AtomicBool,
+    dropped: AtomicUsize,
+}
+
+impl<const N: usize> Ring<N> {
+    #[cfg(not(all(loom, test)))]
+    pub(crate) const fn new() -> Self {
+        Ring {
+            slots: [const { Slot::empty() }; N],
+            tail: AtomicUsize::new(0),
+            head: AtomicUsize::new(0),
+            draining: AtomicBool::new(false),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    #[cfg(all(loom, test))]
+    pub(crate) fn new() -> Self {
+        Ring {
+            slots: core::array::from_fn(|_| Slot::empty()),
+            tail: AtomicUsize::new(0),
+            head: AtomicUsize::new(0),
+            draining: AtomicBool::new(false),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of events that were lost because the buffer was full.
+    pub(crate) fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Append `event`, or drop it if the buffer is full.
+    pub(crate) fn push(&self, event: Event) {
+        let mut pos = self.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos % N];
+            let writable = 2 * (pos / N);
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            if sequence == writable {
+                match self.tail.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        slot.timestamp.store(event.timestamp, Ordering::Relaxed);
+                        slot.dealloc
+                            .store(event.kind == EventKind::Dealloc, Ordering::Relaxed);
+                        slot.use_case.store(event.use_case, Ordering::Relaxed);
+                        slot.size.store(event.size, Ordering::Relaxed);
+                        slot.sequence.store(writable + 1, Ordering::Release);
+                        return;
+                    }
+                    Err(actual) => pos = actual,
+                }
+            } else if sequence < writable {
+                // the slot still holds an event from the previous lap that was not drained yet
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                return;
+            } else {
+                pos = self.tail.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<Event> {
+        let pos = self.head.load(Ordering::Relaxed);
+        let slot = &self.slots[pos % N];
+        let readable = 2 * (pos / N) + 1;
+        if slot.sequence.load(Ordering::Acquire) != readable {
+            return None;
+        }
+        let event = Event {
+            timestamp: slot.timestamp.load(Ordering::Relaxed),
+            kind: if slot.dealloc.load(Ordering::Relaxed) {
+                EventKind::Dealloc
+            } else {
+                EventKind::Alloc
+            },
+            use_case: slot.use_case.load(Ordering::Relaxed),
+            size: slot.size.load(Ordering::Relaxed),
+        };
+        slot.sequence.store(readable + 1, Ordering::Release);
+        self.head.store(pos + 1, Ordering::Relaxed);
+        Some(event)
+    }
+
+    /// Take all buffered events out of the buffer and pass them to `f`, until it fails, and
+    /// return how many were passed.
+    ///
+    /// Concurrent calls return `Ok(0)` immediately.
+    pub(crate) fn drain_with<E>(
+        &self,
+        mut f: impl FnMut(Event) -> Result<(), E>,
+    ) -> Result<usize, E> {
+        if self
+            .draining
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return Ok(0);
+        }
+
+        let mut written = 0;
+        let mut result = Ok(());
+        while let Some(event) = self.pop() {
+            result = f(event);
+            if result.is_err() {
+                break;
+            }
+            written += 1;
+        }
+
+        self.draining.store(false, Ordering::Release);
+        result.map(|()| written)
+    }
+}
@@ -0,0 +1,606 @@
+//! Render stats in formats understood by other tools.
+
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::{Callsite, Event, EventKind, Stat, UseCase, UseCaseRepr};
+
+#[cfg(feature = "pprof")]
+pub mod pprof;
+
+#[cfg(feature = "heaptrack")]
+pub mod heaptrack;
+
+#[cfg(any(feature = "http", feature = "capi"))]
+pub(crate) mod json;
+
+mod table;
+pub(crate) use table::Bytes;
+pub use table::StatsTable;
+
+/// Name, type, help text and accessor for each metric emitted by [write_prometheus].
+type PrometheusMetric = (&'static str, &'static str, &'static str, fn(&Stat) -> isize);
+
+const PROMETHEUS_METRICS: [PrometheusMetric; 5] = [
+    (
+        "memoria_current_bytes",
+        "gauge",
+        "Memory currently allocated per usecase.",
+        |stat| stat.current,
+    ),
+    (
+        "memoria_peak_bytes",
+        "gauge",
+        "Largest amount of memory allocated at once per usecase.",
+        |stat| stat.peak,
+    ),
+    (
+        "memoria_allocated_bytes_total",
+        "counter",
+        "Memory allocated in total per usecase.",
+        |stat| stat.total,
+    ),
+    (
+        "memoria_threads",
+        "gauge",
+        "Number of threads currently inside a usecase.",
+        |stat| stat.threads,
+    ),
+    (
+        "memoria_peak_threads",
+        "gauge",
+        "Largest number of threads inside a usecase at once.",
+        |stat| stat.peak_threads,
+    ),
+];
+
+/// Write stats in the Prometheus text exposition format.
+///
+/// Each usecase becomes a `usecase` label, rendered using [UseCase::name]. Stats are
+/// usually obtained from [StatsRecorder::get](crate::StatsRecorder::get) rather than
+/// [StatsRecorder::flush](crate::StatsRecorder::flush), since Prometheus expects counters to be
+/// cumulative.
+///
+/// ```
+/// memoria::usecase! {
+///     enum MyUseCase {
+///         default Parse,
+///     }
+/// }
+///
+/// let mut output = Vec::new();
+/// let stat = memoria::Stat {
+///     current: 10,
+///     ..Default::default()
+/// };
+/// memoria::export::write_prometheus(&mut output, [(MyUseCase::Parse, stat)]).unwrap();
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.contains("memoria_current_bytes{usecase=\"Parse\"} 10\n"));
+/// ```
+pub fn write_prometheus<U: UseCase + fmt::Debug>(
+    mut writer: impl Write,
+    stats: impl IntoIterator<Item = (U, Stat)>,
+) -> io::Result<()> {
+    let stats: Vec<(U, Stat)> = stats.into_iter().collect();
+
+    for (name, kind, help, value) in PROMETHEUS_METRICS {
+        writeln!(writer, "# HELP {name} {help}")?;
+        writeln!(writer, "# TYPE {name} {kind}")?;
+        for (use_case, stat) in &stats {
+            writeln!(
+                writer,
+                "{name}{{usecase=\"{}\"}} {}",
+                PrometheusLabel(&Label(use_case)),
+                value(stat)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write stats as CSV, with a header row followed by one row per usecase.
+///
+/// The first column is `usecase`, rendered using [UseCase::name], followed by one column per
+/// field of [Stat] named after the field. Column names and order are stable, new fields are only
+/// ever appended.
+///
+/// ```
+/// memoria::usecase! {
+///     enum MyUseCase {
+///         default Parse,
+///     }
+/// }
+///
+/// let mut output = Vec::new();
+/// let stat = memoria::Stat {
+///     current: 10,
+///     ..Default::default()
+/// };
+/// memoria::export::write_csv(&mut output, [(MyUseCase::Parse, stat)]).unwrap();
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.starts_with("usecase,current,peak,"));
+/// assert!(output.lines().nth(1).unwrap().starts_with("Parse,10,0,"));
+/// ```
+pub fn write_csv<U: UseCase + fmt::Debug>(
+    mut writer: impl Write,
+    stats: impl IntoIterator<Item = (U, Stat)>,
+) -> io::Result<()> {
+    write!(writer, "usecase")?;
+    for (name, _) in stat_columns(&Stat::ZERO) {
+        write!(writer, ",{name}")?;
+    }
+    writeln!(writer)?;
+
+    for (use_case, stat) in stats {
+        write!(writer, "{}", CsvField(&Label(&use_case)))?;
+        for (_, value) in stat_columns(&stat) {
+            write!(writer, ",{value}")?;
+        }
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+/// Write stats as newline-delimited JSON, with one object per usecase.
+///
+/// Each object has a `usecase` key, rendered using [UseCase::name], followed by one key per
+/// field of [Stat], using the same names as the columns of [write_csv].
+///
+/// ```
+/// memoria::usecase! {
+///     enum MyUseCase {
+///         default Parse,
+///     }
+/// }
+///
+/// let mut output = Vec::new();
+/// let stat = memoria::Stat {
+///     current: 10,
+///     ..Default::default()
+/// };
+/// memoria::export::write_ndjson(&mut output, [(MyUseCase::Parse, stat)]).unwrap();
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.starts_with(r#"{"usecase":"Parse","current":10,"peak":0,"#));
+/// assert!(output.ends_with("}\n"));
+/// ```
+pub fn write_ndjson<U: UseCase + fmt::Debug>(
+    mut writer: impl Write,
+    stats: impl IntoIterator<Item = (U, Stat)>,
+) -> io::Result<()> {
+    for (use_case, stat) in stats {
+        write!(writer, "{{\"usecase\":{}", JsonString(&Label(&use_case)))?;
+        for (name, value) in stat_columns(&stat) {
+            write!(writer, ",\"{name}\":{value}")?;
+        }
+        writeln!(writer, "}}")?;
+    }
+    Ok(())
+}
+
+/// Write snapshots of stats as a Chrome trace, which can be opened in
+/// [Perfetto](https://ui.perfetto.dev) or `chrome://tracing`.
+///
+/// Each usecase becomes a counter track showing its current and peak memory over time. Snapshots
+/// are `(timestamp, usecase, stat)` as returned by
+/// [TimeSeriesRecorder::snapshots](crate::TimeSeriesRecorder::snapshots), and timestamps are
+/// interpreted as microseconds.
+///
+/// ```
+/// memoria::usecase! {
+///     enum MyUseCase {
+///         default Parse,
+///     }
+/// }
+///
+/// let mut output = Vec::new();
+/// let stat = memoria::Stat {
+///     current: 10,
+///     ..Default::default()
+/// };
+/// memoria::export::write_chrome_trace(&mut output, [(1000, MyUseCase::Parse, stat)]).unwrap();
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.contains(r#""name":"Parse","ph":"C","ts":1000"#));
+/// ```
+pub fn write_chrome_trace<U: UseCase + fmt::Debug>(
+    mut writer: impl Write,
+    snapshots: impl IntoIterator<Item = (u64, U, Stat)>,
+) -> io::Result<()> {
+    write!(writer, "{{\"traceEvents\":[")?;
+    for (i, (timestamp, use_case, stat)) in snapshots.into_iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+        write!(
+            writer,
+            "{{\"name\":{},\"ph\":\"C\",\"ts\":{timestamp},\"pid\":0,\"tid\":0,\
+             \"args\":{{\"current\":{},\"peak\":{}}}}}",
+            JsonString(&Label(&use_case)),
+            stat.current,
+            stat.peak
+        )?;
+    }
+    writeln!(writer, "]}}")?;
+    Ok(())
+}
+
+/// Write events from an [EventLogRecorder](crate::EventLogRecorder) as a
+/// [speedscope](https://www.speedscope.app) profile in its evented format.
+///
+/// The profile uses bytes instead of time as its weight: every run of consecutive events of the
+/// same usecase becomes one block, as wide as the bytes it allocated. The resulting "time order"
+/// view is a timeline of allocations in the order they happened, and the "left heavy" view sums
+/// them up per usecase. Deallocations are written to a second profile in the same file. Event
+/// timestamps are not used, only the order of events.
+///
+/// ```
+/// use memoria::{Event, EventKind, UseCase};
+///
+/// memoria::usecase! {
+///     enum MyUseCase {
+///         default Parse,
+///     }
+/// }
+///
+/// let event = Event {
+///     timestamp: 0,
+///     kind: EventKind::Alloc,
+///     use_case: MyUseCase::Parse.into_repr(),
+///     size: 10,
+/// };
+/// let mut output = Vec::new();
+/// memoria::export::write_speedscope::<MyUseCase>(&mut output, [event]).unwrap();
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.contains(r#""frames":[{"name":"Parse"}]"#));
+/// assert!(output.contains(r#"{"type":"C","frame":0,"at":10}"#));
+/// ```
+pub fn write_speedscope<U: UseCase + fmt::Debug>(
+    mut writer: impl Write,
+    events: impl IntoIterator<Item = Event>,
+) -> io::Result<()> {
+    let mut frames: Vec<UseCaseRepr> = Vec::new();
+    // runs of consecutive events of the same usecase as `(frame, bytes)`, for allocations and
+    // deallocations
+    let mut runs: [Vec<(usize, usize)>; 2] = [Vec::new(), Vec::new()];
+    for event in events {
+        let frame = match frames.iter().position(|&frame| frame == event.use_case) {
+            Some(frame) => frame,
+            None => {
+                frames.push(event.use_case);
+                frames.len() - 1
+            }
+        };
+        let runs = &mut runs[usize::from(event.kind == EventKind::Dealloc)];
+        match runs.last_mut() {
+            Some((last, bytes)) if *last == frame => *bytes += event.size,
+            _ => runs.push((frame, event.size)),
+        }
+    }
+
+    write!(
+        writer,
+        "{{\"$schema\":\"https://www.speedscope.app/file-format-schema.json\",\
+         \"shared\":{{\"frames\":["
+    )?;
+    for (i, &frame) in frames.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+        let use_case = U::from_repr(frame).unwrap_or_default();
+        write!(writer, "{{\"name\":{}}}", JsonString(&Label(&use_case)))?;
+    }
+    write!(writer, "]}},\"profiles\":[")?;
+    for (i, (name, runs)) in ["allocated", "freed"].into_iter().zip(&runs).enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+        let end: usize = runs.iter().map(|(_, bytes)| bytes).sum();
+        write!(
+            writer,
+            "{{\"type\":\"evented\",\"name\":\"{name}\",\"unit\":\"bytes\",\
+             \"startValue\":0,\"endValue\":{end},\"events\":["
+        )?;
+        let mut at = 0;
+        for (j, (frame, bytes)) in runs.iter().enumerate() {
+            if j > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, "{{\"type\":\"O\",\"frame\":{frame},\"at\":{at}}},")?;
+            at += bytes;
+            write!(writer, "{{\"type\":\"C\",\"frame\":{frame},\"at\":{at}}}")?;
+        }
+        write!(writer, "]}}")?;
+    }
+    writeln!(writer, "]}}")
+}
+
+/// Write snapshots of stats in the format of valgrind's massif heap profiler, which can be
+/// viewed with `ms_print` or massif-visualizer.
+///
+/// Snapshots are `(timestamp, usecase, stat)` as returned by
+/// [TimeSeriesRecorder::snapshots](crate::TimeSeriesRecorder::snapshots), where consecutive
+/// entries with the same timestamp form one snapshot. Timestamps are interpreted as
+/// milliseconds. Every snapshot has a detail tree of the current memory of each usecase. The
+/// snapshot with the most memory is marked as the peak, and its tree additionally nests the
+/// callsites recorded through [Alloc::with_usecase_at](crate::Alloc::with_usecase_at) below their
+/// usecase. Pass an empty iterator for `callsites` to only export usecases.
+///
+/// ```
+/// memoria::usecase! {
+///     enum MyUseCase {
+///         default Parse,
+///     }
+/// }
+///
+/// let mut output = Vec::new();
+/// let stat = memoria::Stat {
+///     current: 10,
+///     ..Default::default()
+/// };
+/// memoria::export::write_massif(&mut output, [(1000, MyUseCase::Parse, stat)], []).unwrap();
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.contains("time=1000\nmem_heap_B=10\n"));
+/// assert!(output.contains("heap_tree=peak\n"));
+/// assert!(output.contains("\n n0: 10 Parse\n"));
+/// ```
+pub fn write_massif<U: UseCase + fmt::Debug>(
+    mut writer: impl Write,
+    snapshots: impl IntoIterator<Item = (u64, U, Stat)>,
+    callsites: impl IntoIterator<Item = (U, Callsite, Stat)>,
+) -> io::Result<()> {
+    let mut grouped: Vec<(u64, Vec<(String, isize)>)> = Vec::new();
+    for (timestamp, use_case, stat) in snapshots {
+        let use_case = (massif_label(&Label(&use_case)), stat.current.max(0));
+        match grouped.last_mut() {
+            Some((last, use_cases)) if *last == timestamp => use_cases.push(use_case),
+            _ => grouped.push((timestamp, vec![use_case])),
+        }
+    }
+    let callsites: Vec<(String, Callsite, isize)> = callsites
+        .into_iter()
+        .map(|(use_case, callsite, stat)| {
+            (
+                massif_label(&Label(&use_case)),
+                callsite,
+                stat.current.max(0),
+            )
+        })
+        .collect();
+
+    let total = |use_cases: &[(String, isize)]| -> isize {
+        use_cases.iter().map(|(_, current)| current).sum()
+    };
+    let mut peak = 0;
+    for (i, (_, use_cases)) in grouped.iter().enumerate() {
+        if total(use_cases) > total(&grouped[peak].1) {
+            peak = i;
+        }
+    }
+
+    writeln!(writer, "desc: (none)")?;
+    writeln!(writer, "cmd: memoria")?;
+    writeln!(writer, "time_unit: ms")?;
+    for (i, (timestamp, use_cases)) in grouped.iter_mut().enumerate() {
+        let total = total(use_cases);
+        writeln!(writer, "#-----------")?;
+        writeln!(writer, "snapshot={i}")?;
+        writeln!(writer, "#-----------")?;
+        writeln!(writer, "time={timestamp}")?;
+        writeln!(writer, "mem_heap_B={total}")?;
+        writeln!(writer, "mem_heap_extra_B=0")?;
+        writeln!(writer, "mem_stacks_B=0")?;
+        writeln!(
+            writer,
+            "heap_tree={}",
+            if i == peak { "peak" } else { "detailed" }
+        )?;
+
+        use_cases.retain(|(_, current)| *current > 0);
+        use_cases.sort_by_key(|(_, current)| std::cmp::Reverse(*current));
+        writeln!(
+            writer,
+            "n{}: {total} (heap allocation functions) malloc/new/new[], --alloc-fns, etc.",
+            use_cases.len()
+        )?;
+        for (use_case, current) in use_cases.iter() {
+            let mut children: Vec<(Callsite, isize)> = callsites
+                .iter()
+                .filter(|(label, _, current)| i == peak && label == use_case && *current > 0)
+                .map(|(_, callsite, current)| (*callsite, *current))
+                .collect();
+            children.sort_by_key(|(_, current)| std::cmp::Reverse(*current));
+            writeln!(writer, " n{}: {current} {use_case}", children.len())?;
+            for (callsite, current) in children {
+                writeln!(
+                    writer,
+                    "  n0: {current} {}:{}",
+                    massif_label(&callsite.file()),
+                    callsite.line()
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Render a node label for a massif detail tree, which must not span multiple lines.
+fn massif_label(name: &impl fmt::Display) -> String {
+    name.to_string().replace(['\n', '\r'], " ")
+}
+
+/// Write stats as collapsed stacks, the input format of
+/// [inferno](https://github.com/jonhoo/inferno) and
+/// [flamegraph.pl](https://github.com/brendangregg/FlameGraph).
+///
+/// Each usecase becomes a root frame, with callsites recorded through
+/// [Alloc::with_usecase_at](crate::Alloc::with_usecase_at) nested below it. `value` selects the
+/// weight of each stack, for example `|stat| stat.total` for the bytes allocated since the last
+/// flush. Callsite stats are expected to be a breakdown of the usecase stats and are subtracted
+/// from the weight of their usecase. Stacks with a weight of zero or less are omitted.
+///
+/// ```
+/// memoria::usecase! {
+///     enum MyUseCase {
+///         default Parse,
+///     }
+/// }
+///
+/// let mut output = Vec::new();
+/// let stat = memoria::Stat {
+///     total: 10,
+///     ..Default::default()
+/// };
+/// let callsite = std::panic::Location::caller();
+/// let callsite_stat = memoria::Stat {
+///     total: 4,
+///     ..Default::default()
+/// };
+/// memoria::export::write_collapsed(
+///     &mut output,
+///     [(MyUseCase::Parse, stat)],
+///     [(MyUseCase::Parse, callsite, callsite_stat)],
+///     |stat| stat.total,
+/// )
+/// .unwrap();
+/// let expected = format!("Parse 6\nParse;{}:{} 4\n", callsite.file(), callsite.line());
+/// assert_eq!(String::from_utf8(output).unwrap(), expected);
+/// ```
+pub fn write_collapsed<U: UseCase + fmt::Debug>(
+    mut writer: impl Write,
+    stats: impl IntoIterator<Item = (U, Stat)>,
+    callsites: impl IntoIterator<Item = (U, Callsite, Stat)>,
+    value: impl Fn(&Stat) -> isize,
+) -> io::Result<()> {
+    let mut stacks: Vec<(String, isize)> = stats
+        .into_iter()
+        .map(|(use_case, stat)| (collapsed_frame(&Label(&use_case).to_string()), value(&stat)))
+        .collect();
+
+    for (use_case, callsite, stat) in callsites {
+        let use_case = collapsed_frame(&Label(&use_case).to_string());
+        let weight = value(&stat);
+        if let Some((_, use_case_weight)) = stacks.iter_mut().find(|(stack, _)| *stack == use_case)
+        {
+            *use_case_weight -= weight;
+        }
+        stacks.push((
+            format!(
+                "{use_case};{}:{}",
+                collapsed_frame(callsite.file()),
+                callsite.line()
+            ),
+            weight,
+        ));
+    }
+
+    for (stack, weight) in stacks {
+        if weight > 0 {
+            writeln!(writer, "{stack} {weight}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sanitize the name of a frame in a collapsed stack, where `;` separates frames and a newline
+/// separates stacks.
+fn collapsed_frame(name: &str) -> String {
+    name.replace([';', '\n'], ",")
+}
+
+/// Every field of a [Stat] with its column name, in a stable order shared by all exporters that
+/// emit the full stat.
+pub(crate) fn stat_columns(stat: &Stat) -> [(&'static str, i128); 19] {
+    [
+        ("current", stat.current as i128),
+        ("peak", stat.peak as i128),
+        ("peak_at", stat.peak_at as i128),
+        ("total", stat.total as i128),
+        ("count", stat.count as i128),
+        ("max_single", stat.max_single as i128),
+        ("freed_in_drop", stat.freed_in_drop as i128),
+        ("freed_cross_thread", stat.freed_cross_thread as i128),
+        ("high_align", stat.high_align as i128),
+        ("padding", stat.padding as i128),
+        ("external", stat.external as i128),
+        ("shared", stat.shared as i128),
+        ("threads", stat.threads as i128),
+        ("peak_threads", stat.peak_threads as i128),
+        ("forbidden", stat.forbidden as i128),
+        ("lifetime_peak", stat.lifetime_peak as i128),
+        ("negative_balance", stat.negative_balance as i128),
+        ("entries", stat.entries as i128),
+        ("active_time", stat.active_time as i128),
+    ]
+}
+
+/// Renders the [UseCase::name] of a usecase, or its `Debug` representation if the name is empty.
+pub(crate) struct Label<'a, U>(pub(crate) &'a U);
+
+impl<U: UseCase + fmt::Debug> fmt::Display for Label<'_, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.name() {
+            "" => write!(f, "{:?}", self.0),
+            name => f.write_str(name),
+        }
+    }
+}
+
+/// Renders a value as a quoted JSON string.
+pub(crate) struct JsonString<'a, T>(pub(crate) &'a T);
+
+impl<T: fmt::Display> fmt::Display for JsonString<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rendered = self.0.to_string();
+        f.write_str("\"")?;
+        for c in rendered.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                c => write!(f, "{c}")?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+/// Escapes a value for use as a Prometheus label value.
+struct PrometheusLabel<'a, T>(&'a T);
+
+impl<T: fmt::Display> fmt::Display for PrometheusLabel<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rendered = self.0.to_string();
+        for c in rendered.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                c => write!(f, "{c}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Quotes a value for use as a CSV field if it contains a separator, quote or newline.
+struct CsvField<'a, T>(&'a T);
+
+impl<T: fmt::Display> fmt::Display for CsvField<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rendered = self.0.to_string();
+        if !rendered.contains([',', '"', '\n', '\r']) {
+            return f.write_str(&rendered);
+        }
+        f.write_str("\"")?;
+        for c in rendered.chars() {
+            match c {
+                '"' => f.write_str("\"\"")?,
+                c => write!(f, "{c}")?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
@@ -0,0 +1,124 @@
+//! Write event logs in the interchange format of [heaptrack](https://github.com/KDE/heaptrack),
+//! for viewing memoria captures in `heaptrack_gui` or `heaptrack_print`.
+//!
+//! heaptrack records a backtrace for every allocation. memoria has no backtraces, so every
+//! usecase becomes a pseudo-frame instead, named using [UseCase::name]. The timeline, peak and
+//! leak views then show memory per usecase.
+//!
+//! ```ignore
+//! let mut writer = HeaptrackWriter::<MyUseCase, _>::new(File::create("memoria.heaptrack")?)?;
+//!
+//! // periodically:
+//! ALLOCATOR.with_recorder(|recorder| Ok(recorder.drain_heaptrack(&mut writer))).ok();
+//! ```
+//!
+//! ```text
+//! $ heaptrack_print memoria.heaptrack
+//! ```
+//!
+//! Timestamps are taken from the clock of the [EventLogRecorder](crate::EventLogRecorder), which
+//! heaptrack interprets as milliseconds.
+//!
+//! Requires the `heaptrack` feature.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::marker::PhantomData;
+
+use crate::export::Label;
+use crate::{Event, EventKind, UseCase, UseCaseRepr};
+
+/// The heaptrack version this format was modelled after, encoded as `0xMMmmpp`.
+const HEAPTRACK_VERSION: u32 = 0x010500;
+
+/// The version of the interchange format.
+const FILE_FORMAT_VERSION: u32 = 3;
+
+/// Converts [Event]s into heaptrack's interchange format.
+///
+/// Frames, traces and allocation sizes are written the first time an event refers to them, so
+/// the output of a single writer must not be split across files.
+pub struct HeaptrackWriter<U, W: Write> {
+    writer: W,
+    /// The trace index of every usecase seen so far.
+    traces: HashMap<UseCaseRepr, usize>,
+    /// The allocation info index of every size and trace seen so far.
+    infos: HashMap<(usize, usize), usize>,
+    start: Option<u64>,
+    last_timestamp: u64,
+    _phantom: PhantomData<U>,
+}
+
+impl<U: UseCase + fmt::Debug, W: Write> HeaptrackWriter<U, W> {
+    /// Write the header to `writer`.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "v {HEAPTRACK_VERSION:x} {FILE_FORMAT_VERSION:x}")?;
+        writeln!(writer, "X memoria")?;
+        Ok(HeaptrackWriter {
+            writer,
+            traces: HashMap::new(),
+            infos: HashMap::new(),
+            start: None,
+            last_timestamp: 0,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Write a single event.
+    pub fn write_event(&mut self, event: &Event) -> io::Result<()> {
+        let start = *self.start.get_or_insert(event.timestamp);
+        let timestamp = event.timestamp.saturating_sub(start);
+        if timestamp != self.last_timestamp {
+            writeln!(self.writer, "c {timestamp:x}")?;
+            self.last_timestamp = timestamp;
+        }
+
+        let info = self.info(event.use_case, event.size)?;
+        match event.kind {
+            EventKind::Alloc => writeln!(self.writer, "+ {info:x}"),
+            EventKind::Dealloc => writeln!(self.writer, "- {info:x}"),
+        }
+    }
+
+    /// Write all `events`.
+    pub fn write_events(&mut self, events: impl IntoIterator<Item = Event>) -> io::Result<()> {
+        for event in events {
+            self.write_event(&event)?;
+        }
+        Ok(())
+    }
+
+    /// Flush and return the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Return the index of the allocation info for `size` bytes in `use_case`, writing it and
+    /// the pseudo-frame of the usecase first if they are new.
+    fn info(&mut self, use_case: UseCaseRepr, size: usize) -> io::Result<usize> {
+        let trace = match self.traces.get(&use_case) {
+            Some(&trace) => trace,
+            None => {
+                // strings, instruction pointers and traces are numbered from 1, and every usecase
+                // adds one of each
+                let index = self.traces.len() + 1;
+                let name = U::from_repr(use_case).unwrap_or_default();
+                let name = Label(&name).to_string().replace(['\n', '\r'], " ");
+                writeln!(self.writer, "s {name}")?;
+                writeln!(self.writer, "i {index:x} 0 {index:x} 0 0")?;
+                writeln!(self.writer, "t {index:x} 0")?;
+                self.traces.insert(use_case, index);
+                index
+            }
+        };
+
+        let next = self.infos.len();
+        let info = *self.infos.entry((size, trace)).or_insert(next);
+        if info == next {
+            writeln!(self.writer, "a {size:x} {trace:x}")?;
+        }
+        Ok(info)
+    }
+}
@@ -0,0 +1,62 @@
+//! JSON rendering shared by the HTTP listener and the C API.
+
+use std::alloc::GlobalAlloc;
+use std::fmt::{self, Write as _};
+
+use crate::export::{stat_columns, JsonString, Label};
+use crate::{Alloc, Error, PointerTable, Stat, StatsRecorder, UseCase};
+
+/// Render the current stats of all usecases as a JSON object, without resetting them.
+pub(crate) fn render_json_stats<U: UseCase + fmt::Debug, A: GlobalAlloc, P: PointerTable>(
+    alloc: &Alloc<U, StatsRecorder<U>, A, P>,
+) -> String {
+    let stats = alloc
+        .with_recorder(|recorder| {
+            let mut stats = Vec::new();
+            recorder.peek(|use_case, stat| stats.push((use_case, stat)));
+            Ok(stats)
+        })
+        .unwrap_or_default();
+
+    let mut body = String::from("{");
+    for (i, (use_case, stat)) in stats.iter().enumerate() {
+        if i > 0 {
+            body.push(',');
+        }
+        write!(body, "{}:", JsonString(&Label(use_case))).ok();
+        write_json_stat(&mut body, stat);
+    }
+    body.push('}');
+    body
+}
+
+fn write_json_stat(body: &mut String, stat: &Stat) {
+    let fields = stat_columns(stat);
+    body.push('{');
+    for (i, (name, value)) in fields.iter().enumerate() {
+        if i > 0 {
+            body.push(',');
+        }
+        write!(body, "\"{name}\":{value}").ok();
+    }
+    body.push('}');
+}
+
+/// Render the count of every error as a JSON object.
+pub(crate) fn render_json_errors<U: UseCase, A: GlobalAlloc, P: PointerTable>(
+    alloc: &Alloc<U, StatsRecorder<U>, A, P>,
+) -> String {
+    let counts = alloc
+        .with_recorder(|recorder| Ok(Error::ALL.map(|error| (error, recorder.get_error(error)))))
+        .ok();
+
+    let mut body = String::from("{");
+    for (i, (error, count)) in counts.iter().flatten().enumerate() {
+        if i > 0 {
+            body.push(',');
+        }
+        write!(body, "{}:{count}", JsonString(&format!("{error:?}"))).ok();
+    }
+    body.push('}');
+    body
+}
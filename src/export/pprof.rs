@@ -0,0 +1,211 @@
+//! Export stats as a [pprof](https://github.com/google/pprof) heap profile.
+//!
+//! The profile contains one sample per usecase, and one sample per callsite recorded through
+//! [Alloc::with_usecase_at](crate::Alloc::with_usecase_at), with the callsite nested under its
+//! usecase. Each sample carries the number of allocations, the allocated bytes and the bytes
+//! currently in use.
+//!
+//! ```ignore
+//! let (stats, callsites) = ALLOCATOR.with_recorder(|recorder| {
+//!     let (mut stats, mut callsites) = (Vec::new(), Vec::new());
+//!     recorder.flush(|use_case, stat| stats.push((use_case, stat)), |_, _| {});
+//!     recorder.flush_callsites(|use_case, callsite, stat| {
+//!         callsites.push((use_case, callsite, stat))
+//!     });
+//!     Ok((stats, callsites))
+//! })?;
+//! memoria::export::pprof::write_profile(File::create("heap.pb.gz")?, stats, callsites)?;
+//! ```
+//!
+//! ```text
+//! $ go tool pprof -sample_index=inuse_space -top heap.pb.gz
+//! ```
+//!
+//! Requires the `pprof` feature.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::export::Label;
+use crate::{Callsite, Stat, UseCase};
+
+/// Sample types in the order in which [values] returns them.
+const SAMPLE_TYPES: [(&str, &str); 3] = [
+    ("alloc_objects", "count"),
+    ("alloc_space", "bytes"),
+    ("inuse_space", "bytes"),
+];
+
+fn values(stat: &Stat) -> [i64; 3] {
+    [stat.count as i64, stat.total as i64, stat.current as i64]
+}
+
+/// Write a gzipped pprof profile of per-usecase and per-callsite stats.
+///
+/// Callsite stats are expected to be a breakdown of the usecase stats, as returned by
+/// [StatsRecorder::flush_callsites](crate::StatsRecorder::flush_callsites). They are subtracted
+/// from the sample of their usecase, so that the usecase's total in the profile is not counted
+/// twice. Pass an empty iterator for `callsites` to only export usecases.
+pub fn write_profile<U: UseCase + fmt::Debug>(
+    writer: impl Write,
+    stats: impl IntoIterator<Item = (U, Stat)>,
+    callsites: impl IntoIterator<Item = (U, Callsite, Stat)>,
+) -> io::Result<()> {
+    let mut profile = Profile::default();
+
+    let mut use_cases: Vec<(u64, [i64; 3])> = Vec::new();
+    for (use_case, stat) in stats {
+        let location = profile.location(Label(&use_case).to_string(), None);
+        use_cases.push((location, values(&stat)));
+    }
+
+    for (use_case, callsite, stat) in callsites {
+        let use_case_location = profile.location(Label(&use_case).to_string(), None);
+        let callsite_location = profile.location(
+            format!("{}:{}", callsite.file(), callsite.line()),
+            Some(callsite),
+        );
+        let callsite_values = values(&stat);
+        if let Some((_, values)) = use_cases
+            .iter_mut()
+            .find(|(location, _)| *location == use_case_location)
+        {
+            for (value, callsite_value) in values.iter_mut().zip(callsite_values) {
+                *value -= callsite_value;
+            }
+        }
+        profile.sample(&[callsite_location, use_case_location], callsite_values);
+    }
+
+    for (location, values) in use_cases {
+        profile.sample(&[location], values);
+    }
+
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    encoder.write_all(&profile.encode())?;
+    encoder.finish()?.flush()
+}
+
+/// The subset of `profile.proto` that is needed to describe memoria's stats.
+///
+/// Every location has exactly one function, and both share the same id.
+#[derive(Default)]
+struct Profile {
+    strings: Vec<String>,
+    string_ids: HashMap<String, i64>,
+    locations: Vec<(String, Option<Callsite>)>,
+    location_ids: HashMap<String, u64>,
+    samples: Vec<(Vec<u64>, [i64; 3])>,
+}
+
+impl Profile {
+    fn string(&mut self, value: &str) -> i64 {
+        if self.strings.is_empty() {
+            // the string table must start with the empty string
+            self.strings.push(String::new());
+            self.string_ids.insert(String::new(), 0);
+        }
+        if let Some(&id) = self.string_ids.get(value) {
+            return id;
+        }
+        let id = self.strings.len() as i64;
+        self.strings.push(value.to_owned());
+        self.string_ids.insert(value.to_owned(), id);
+        id
+    }
+
+    fn location(&mut self, name: String, callsite: Option<Callsite>) -> u64 {
+        if let Some(&id) = self.location_ids.get(&name) {
+            return id;
+        }
+        self.locations.push((name.clone(), callsite));
+        let id = self.locations.len() as u64;
+        self.location_ids.insert(name, id);
+        id
+    }
+
+    /// Add a sample, with `stack` listing location ids from the innermost frame outwards.
+    fn sample(&mut self, stack: &[u64], values: [i64; 3]) {
+        self.samples.push((stack.to_vec(), values));
+    }
+
+    fn encode(mut self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        for (name, unit) in SAMPLE_TYPES {
+            let mut value_type = Vec::new();
+            varint_field(&mut value_type, 1, self.string(name) as u64);
+            varint_field(&mut value_type, 2, self.string(unit) as u64);
+            bytes_field(&mut out, 1, &value_type);
+        }
+
+        for (stack, values) in std::mem::take(&mut self.samples) {
+            let mut sample = Vec::new();
+            packed_field(&mut sample, 1, stack.iter().copied());
+            packed_field(&mut sample, 2, values.iter().map(|&value| value as u64));
+            bytes_field(&mut out, 2, &sample);
+        }
+
+        let locations = std::mem::take(&mut self.locations);
+        for (id, (_, callsite)) in (1..).zip(&locations) {
+            let mut location = Vec::new();
+            varint_field(&mut location, 1, id);
+            let mut line = Vec::new();
+            varint_field(&mut line, 1, id);
+            if let Some(callsite) = callsite {
+                varint_field(&mut line, 2, callsite.line().into());
+            }
+            bytes_field(&mut location, 4, &line);
+            bytes_field(&mut out, 4, &location);
+        }
+
+        for (id, (name, callsite)) in (1..).zip(&locations) {
+            let mut function = Vec::new();
+            varint_field(&mut function, 1, id);
+            let name = self.string(name) as u64;
+            varint_field(&mut function, 2, name);
+            varint_field(&mut function, 3, name);
+            if let Some(callsite) = callsite {
+                varint_field(&mut function, 4, self.string(callsite.file()) as u64);
+            }
+            bytes_field(&mut out, 5, &function);
+        }
+
+        for string in &self.strings {
+            bytes_field(&mut out, 6, string.as_bytes());
+        }
+
+        out
+    }
+}
+
+fn varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn varint_field(out: &mut Vec<u8>, field: u64, value: u64) {
+    varint(out, field << 3);
+    varint(out, value);
+}
+
+fn bytes_field(out: &mut Vec<u8>, field: u64, value: &[u8]) {
+    varint(out, (field << 3) | 2);
+    varint(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+
+fn packed_field(out: &mut Vec<u8>, field: u64, values: impl Iterator<Item = u64>) {
+    let mut packed = Vec::new();
+    for value in values {
+        varint(&mut packed, value);
+    }
+    bytes_field(out, field, &packed);
+}
@@ -0,0 +1,139 @@
+use std::fmt;
+
+use super::Label;
+use crate::{SortKey, Stat, UseCase};
+
+/// Header and renderer of a column of [StatsTable] between usecase and share.
+type Column = (&'static str, fn(&Stat) -> String);
+
+const COLUMNS: [Column; 4] = [
+    ("current", |stat| Bytes(stat.current).to_string()),
+    ("peak", |stat| Bytes(stat.peak).to_string()),
+    ("total", |stat| Bytes(stat.total).to_string()),
+    ("count", |stat| stat.count.to_string()),
+];
+
+/// Stats rendered as an aligned text table, for logs and terminals.
+///
+/// There is one row per usecase, sorted by a [SortKey], largest first, followed by a row with
+/// the sum of all usecases. Sizes are rendered with binary units. The last column is the share
+/// of each usecase in the sum of the column that the table is sorted by.
+///
+/// Returned by [StatsRecorder::render_table](crate::StatsRecorder::render_table), and rendered
+/// through `Display`:
+///
+/// ```
+/// memoria::usecase! {
+///     enum MyUseCase {
+///         default Parse,
+///         Render,
+///     }
+/// }
+///
+/// use memoria::{export::StatsTable, SortKey, Stat};
+///
+/// let stat = |current| Stat {
+///     current,
+///     ..Default::default()
+/// };
+/// let table = StatsTable::new(
+///     [(MyUseCase::Parse, stat(1024)), (MyUseCase::Render, stat(3072))],
+///     SortKey::Current,
+/// );
+/// assert_eq!(
+///     table.to_string(),
+///     "\
+/// usecase  current  peak  total  count       %
+/// Render   3.0 KiB   0 B    0 B      0   75.0%
+/// Parse    1.0 KiB   0 B    0 B      0   25.0%
+/// total    4.0 KiB   0 B    0 B      0  100.0%
+/// "
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct StatsTable<U> {
+    stats: Vec<(U, Stat)>,
+    by: SortKey,
+}
+
+impl<U> StatsTable<U> {
+    /// Build a table from stats, such as the result of
+    /// [StatsRecorder::flush](crate::StatsRecorder::flush), sorted by `by`.
+    pub fn new(stats: impl IntoIterator<Item = (U, Stat)>, by: SortKey) -> Self {
+        let mut stats: Vec<(U, Stat)> = stats.into_iter().collect();
+        stats.sort_by_key(|(_, stat)| std::cmp::Reverse(by.key(stat)));
+        StatsTable { stats, by }
+    }
+}
+
+impl<U: UseCase + fmt::Debug> fmt::Display for StatsTable<U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sum = Stat::ZERO;
+        for (_, stat) in &self.stats {
+            sum.current += stat.current;
+            sum.peak += stat.peak;
+            sum.total += stat.total;
+            sum.count += stat.count;
+        }
+
+        let row = |label: String, stat: &Stat| {
+            let mut cells = vec![label];
+            cells.extend(COLUMNS.iter().map(|(_, render)| render(stat)));
+            cells.push(match self.by.key(&sum) {
+                0 => "-".to_owned(),
+                whole => format!("{:.1}%", self.by.key(stat) as f64 * 100.0 / whole as f64),
+            });
+            cells
+        };
+
+        let mut rows = vec![std::iter::once("usecase")
+            .chain(COLUMNS.iter().map(|(name, _)| *name))
+            .chain(std::iter::once("%"))
+            .map(str::to_owned)
+            .collect::<Vec<_>>()];
+        rows.extend(
+            self.stats
+                .iter()
+                .map(|(use_case, stat)| row(Label(use_case).to_string(), stat)),
+        );
+        rows.push(row("total".to_owned(), &sum));
+
+        let mut widths = vec![0; rows[0].len()];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        for row in &rows {
+            for (i, (cell, width)) in row.iter().zip(&widths).enumerate() {
+                if i == 0 {
+                    write!(f, "{cell:<width$}")?;
+                } else {
+                    write!(f, "  {cell:>width$}")?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders a number of bytes with a binary unit, such as `1.5 MiB`.
+pub(crate) struct Bytes(pub(crate) isize);
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+        if self.0.unsigned_abs() < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+        let mut value = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while value.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        write!(f, "{value:.1} {}", UNITS[unit])
+    }
+}
@@ -0,0 +1,171 @@
+//! Decides which allocations are recorded, see [AllocBuilder::sample_rate],
+//! [AllocBuilder::min_size] and [AllocBuilder::env_config].
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+
+use once_cell::race::OnceBox;
+
+#[cfg(doc)]
+use crate::AllocBuilder;
+use crate::{ThreadState, UseCase, UseCaseRepr};
+
+/// The environment is not read.
+const ENV_IGNORED: u8 = 0;
+/// The environment is read on first use.
+const ENV_UNREAD: u8 = 1;
+/// A thread is reading the environment. Other threads keep using the previous settings.
+const ENV_READING: u8 = 2;
+const ENV_READ: u8 = 3;
+
+pub(crate) struct Filter {
+    enabled: AtomicBool,
+    sample_rate: AtomicU32,
+    min_size: AtomicUsize,
+    env: AtomicU8,
+    // Whether the usecase with the given representation has the given name.
+    name_matches: Option<fn(UseCaseRepr, &str) -> bool>,
+    // The names in `MEMORIA_USECASES`, if set.
+    use_cases: OnceBox<Vec<String>>,
+}
+
+impl Filter {
+    pub(crate) const fn new(
+        sample_rate: u32,
+        min_size: usize,
+        name_matches: Option<fn(UseCaseRepr, &str) -> bool>,
+    ) -> Self {
+        Filter {
+            enabled: AtomicBool::new(true),
+            sample_rate: AtomicU32::new(sample_rate),
+            min_size: AtomicUsize::new(min_size),
+            env: AtomicU8::new(if name_matches.is_some() {
+                ENV_UNREAD
+            } else {
+                ENV_IGNORED
+            }),
+            name_matches,
+            use_cases: OnceBox::new(),
+        }
+    }
+
+    /// Decide whether an allocation of `size` bytes for `use_case` is recorded.
+    ///
+    /// Must be called while memoria's bookkeeping is busy, since reading the environment
+    /// allocates.
+    pub(crate) fn should_record(
+        &self,
+        state: Option<&ThreadState>,
+        use_case: UseCaseRepr,
+        size: usize,
+    ) -> bool {
+        if self.env.load(Ordering::Acquire) == ENV_UNREAD {
+            self.read_env();
+        }
+        if !self.enabled.load(Ordering::Relaxed) || size < self.min_size.load(Ordering::Relaxed) {
+            return false;
+        }
+        if let (Some(use_cases), Some(name_matches)) = (self.use_cases.get(), self.name_matches) {
+            if !use_cases.iter().any(|name| name_matches(use_case, name)) {
+                return false;
+            }
+        }
+        let sample_rate = self.sample_rate.load(Ordering::Relaxed);
+        if sample_rate <= 1 {
+            return true;
+        }
+        state.is_some_and(|state| {
+            let n = state.sample_counter.get();
+            state.sample_counter.set(n.wrapping_add(1));
+            n % sample_rate == 0
+        })
+    }
+
+    /// Whether the deallocation of untracked memory of `size` bytes is expected, because it
+    /// might have been filtered out when it was allocated.
+    pub(crate) fn expects_untracked(&self, size: usize) -> bool {
+        !self.enabled.load(Ordering::Relaxed)
+            || size < self.min_size.load(Ordering::Relaxed)
+            || self.sample_rate.load(Ordering::Relaxed) > 1
+            || self.use_cases.get().is_some()
+    }
+
+    fn read_env(&self) {
+        if self
+            .env
+            .compare_exchange(
+                ENV_UNREAD,
+                ENV_READING,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return;
+        }
+        if let Some(enabled) = env_var("MEMORIA_ENABLED") {
+            let disabled = matches!(
+                enabled.trim().to_ascii_lowercase().as_str(),
+                "0" | "false" | "no" | "off"
+            );
+            self.enabled.store(!disabled, Ordering::Relaxed);
+        }
+        if let Some(sample_rate) =
+            env_var("MEMORIA_SAMPLE_RATE").and_then(|x| x.trim().parse().ok())
+        {
+            self.sample_rate
+                .store(u32::max(sample_rate, 1), Ordering::Relaxed);
+        }
+        if let Some(min_size) = env_var("MEMORIA_MIN_SIZE").and_then(|x| x.trim().parse().ok()) {
+            self.min_size.store(min_size, Ordering::Relaxed);
+        }
+        if let Some(use_cases) = env_var("MEMORIA_USECASES") {
+            let use_cases = use_cases
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(String::from)
+                .collect();
+            self.use_cases.set(Box::new(use_cases)).ok();
+        }
+        self.env.store(ENV_READ, Ordering::Release);
+    }
+}
+
+#[cfg(feature = "std")]
+fn env_var(name: &str) -> Option<String> {
+    std::env::var_os(name)?.into_string().ok()
+}
+
+/// Without `std`, there is no environment to read.
+#[cfg(not(feature = "std"))]
+fn env_var(_name: &str) -> Option<String> {
+    None
+}
+
+/// Whether the [UseCase::name] of `use_case`, or its `Debug` representation if the name is empty,
+/// is `name`, without allocating.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) fn name_matches<U: UseCase + fmt::Debug>(use_case: UseCaseRepr, name: &str) -> bool {
+    struct Matcher<'a> {
+        rest: &'a str,
+    }
+
+    impl fmt::Write for Matcher<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.rest = self.rest.strip_prefix(s).ok_or(fmt::Error)?;
+            Ok(())
+        }
+    }
+
+    let use_case = U::from_repr(use_case).unwrap_or_default();
+    match use_case.name() {
+        "" => {}
+        own => return own == name,
+    }
+    let mut matcher = Matcher { rest: name };
+    fmt::write(&mut matcher, format_args!("{use_case:?}")).is_ok() && matcher.rest.is_empty()
+}
@@ -0,0 +1,59 @@
+//! Reset memoria's state in child processes after `fork`.
+//!
+//! ```ignore
+//! memoria::fork::install(&ALLOCATOR)?;
+//! ```
+//!
+//! See [Alloc::after_fork] for what is reset.
+//!
+//! Requires the `fork` feature and a unix platform.
+
+use std::alloc::GlobalAlloc;
+use std::io;
+
+use once_cell::sync::OnceCell;
+
+use crate::{Alloc, PointerTable, Recorder, UseCase};
+
+/// Type-erased access to [Alloc::after_fork].
+trait AfterFork: Sync {
+    /// # Safety
+    ///
+    /// See [Alloc::after_fork].
+    unsafe fn after_fork(&self);
+}
+
+impl<U: UseCase + Sync, R: Recorder<U> + Sync, A: GlobalAlloc + Sync, P: PointerTable> AfterFork
+    for Alloc<U, R, A, P>
+{
+    unsafe fn after_fork(&self) {
+        // SAFETY: guaranteed by the caller.
+        unsafe { Alloc::after_fork(self) }
+    }
+}
+
+static ALLOC: OnceCell<&'static dyn AfterFork> = OnceCell::new();
+
+extern "C" fn child() {
+    if let Some(alloc) = ALLOC.get() {
+        // SAFETY: the child handler of `pthread_atfork` runs in the child process right after
+        // `fork`, which only has the thread that called `fork`.
+        unsafe { alloc.after_fork() };
+    }
+}
+
+/// Register a `pthread_atfork` handler that calls [Alloc::after_fork] in every child process.
+///
+/// Only one allocator can be registered. Calling this again does nothing.
+pub fn install<U: UseCase + Sync, R: Recorder<U> + Sync, A: GlobalAlloc + Sync, P: PointerTable>(
+    alloc: &'static Alloc<U, R, A, P>,
+) -> io::Result<()> {
+    if ALLOC.set(alloc).is_err() {
+        return Ok(());
+    }
+    // SAFETY: the handler only touches memoria's own state.
+    match unsafe { libc::pthread_atfork(None, None, Some(child)) } {
+        0 => Ok(()),
+        code => Err(io::Error::from_raw_os_error(code)),
+    }
+}
@@ -0,0 +1,222 @@
+use std::alloc::Layout;
+use std::marker::PhantomData;
+
+use dashmap::DashMap;
+
+use crate::sync::ResettableCell;
+
+use crate::{
+    Callsite, Error, OverheadEstimate, Recorder, StatsRecorder, Tag, UseCase, UseCaseRepr,
+};
+
+const BUCKETS: usize = usize::BITS as usize + 1;
+
+/// Allocation counts per power-of-two size class.
+///
+/// Bucket `0` counts zero-sized allocations, bucket `i` counts allocations with a size in
+/// `[2^(i-1), 2^i)`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SizeHistogram {
+    counts: [usize; BUCKETS],
+}
+
+impl Default for SizeHistogram {
+    fn default() -> Self {
+        SizeHistogram {
+            counts: [0; BUCKETS],
+        }
+    }
+}
+
+impl SizeHistogram {
+    fn bucket(size: usize) -> usize {
+        (usize::BITS - size.leading_zeros()) as usize
+    }
+
+    fn record(&mut self, size: usize) {
+        self.counts[Self::bucket(size)] += 1;
+    }
+
+    /// The number of allocations in the same size class as `size`.
+    pub fn count_for_size(&self, size: usize) -> usize {
+        self.counts[Self::bucket(size)]
+    }
+
+    /// Iterate over all non-empty size classes as `(smallest size in class, count)`, smallest
+    /// size class first.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(bucket, &count)| (if bucket == 0 { 0 } else { 1 << (bucket - 1) }, count))
+    }
+
+    /// The total number of allocations.
+    pub fn total_count(&self) -> usize {
+        self.counts.iter().sum()
+    }
+}
+
+/// A recorder that builds a histogram of allocation sizes per usecase, and otherwise forwards to
+/// another recorder `R`.
+///
+/// Distinguishing "many 64-byte allocations" from "few 1 MiB allocations" within a usecase calls
+/// for very different optimizations.
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::Alloc<MyUseCase, memoria::HistogramRecorder<MyUseCase>> =
+///     memoria::Alloc::new_with(
+///         memoria::HistogramRecorder::new(memoria::StatsRecorder::new()),
+///         std::alloc::System,
+///     );
+/// ```
+pub struct HistogramRecorder<U: UseCase, R: Recorder<U> = StatsRecorder<U>> {
+    inner: R,
+    histograms: ResettableCell<DashMap<UseCaseRepr, SizeHistogram>>,
+    _phantom: PhantomData<U>,
+}
+
+impl<U: UseCase, R: Recorder<U>> HistogramRecorder<U, R> {
+    /// Construct a new recorder.
+    pub const fn new(inner: R) -> Self {
+        HistogramRecorder {
+            inner,
+            histograms: ResettableCell::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Access the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get the histogram for a single usecase.
+    pub fn get_histogram(&self, use_case: U) -> SizeHistogram {
+        self.histograms
+            .get()
+            .and_then(|histograms| histograms.get(&use_case.into_repr()).map(|x| *x))
+            .unwrap_or_default()
+    }
+
+    fn record(&self, use_case: UseCaseRepr, size: usize) {
+        self.histograms
+            .get_or_init(DashMap::new)
+            .entry(use_case)
+            .or_default()
+            .record(size);
+    }
+
+    /// Return all histograms and reset them.
+    pub fn flush_histograms(&self, mut histogram_fn: impl FnMut(U, &SizeHistogram)) {
+        if let Some(histograms) = self.histograms.get() {
+            for kv in histograms.iter() {
+                histogram_fn(U::from_repr(*kv.key()).unwrap_or_default(), kv.value());
+            }
+            histograms.clear();
+        }
+    }
+}
+
+unsafe impl<U: UseCase, R: Recorder<U>> Recorder<U> for HistogramRecorder<U, R> {
+    fn on_alloc(&self, use_case: U, size: usize) -> bool {
+        let use_case_bytes: UseCaseRepr = use_case.into_repr();
+        self.record(use_case_bytes, size);
+        self.inner
+            .on_alloc(U::from_repr(use_case_bytes).unwrap_or_default(), size)
+    }
+
+    fn on_shared_alloc(&self, use_case: U, size: usize) -> bool {
+        let use_case_bytes: UseCaseRepr = use_case.into_repr();
+        self.record(use_case_bytes, size);
+        self.inner
+            .on_shared_alloc(U::from_repr(use_case_bytes).unwrap_or_default(), size)
+    }
+
+    fn on_alloc_layout(&self, use_case: U, layout: Layout) {
+        self.inner.on_alloc_layout(use_case, layout)
+    }
+
+    fn on_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_dealloc(use_case, size)
+    }
+
+    fn on_shared_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_shared_dealloc(use_case, size)
+    }
+
+    fn on_attributed_drop(&self, use_case: U, size: usize) {
+        self.inner.on_attributed_drop(use_case, size)
+    }
+
+    fn on_transfer(&self, from: U, to: U, size: usize) {
+        self.inner.on_transfer(from, to, size)
+    }
+
+    fn on_cross_thread_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_cross_thread_dealloc(use_case, size)
+    }
+
+    fn on_external_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_alloc(use_case, size)
+    }
+
+    fn on_external_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_dealloc(use_case, size)
+    }
+
+    fn on_callsite_alloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_alloc(use_case, callsite, size)
+    }
+
+    fn on_callsite_dealloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_dealloc(use_case, callsite, size)
+    }
+
+    fn on_tagged_alloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_alloc(use_case, tag, size)
+    }
+
+    fn on_tagged_dealloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_dealloc(use_case, tag, size)
+    }
+
+    fn on_forbidden_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_forbidden_alloc(use_case, size)
+    }
+
+    fn on_usecase_enter(&self, use_case: U) {
+        self.inner.on_usecase_enter(use_case)
+    }
+
+    fn on_usecase_exit(&self, use_case: U) {
+        self.inner.on_usecase_exit(use_case)
+    }
+
+    fn on_guard_enter(&self, use_case: U) {
+        self.inner.on_guard_enter(use_case)
+    }
+
+    fn on_guard_exit(&self, use_case: U) {
+        self.inner.on_guard_exit(use_case)
+    }
+
+    fn on_flush(&self) {
+        self.inner.on_flush()
+    }
+
+    fn on_overhead_estimate(&self, estimate: OverheadEstimate) {
+        self.inner.on_overhead_estimate(estimate)
+    }
+
+    fn on_fork(&self) {
+        self.histograms.reset();
+        self.inner.on_fork()
+    }
+
+    fn on_error(&self, code: Error, size: Option<usize>) {
+        self.inner.on_error(code, size)
+    }
+}
@@ -0,0 +1,96 @@
+//! A minimal embedded HTTP listener for inspecting stats of a running process.
+//!
+//! ```ignore
+//! memoria::http::serve(&ALLOCATOR, "127.0.0.1:6770")?;
+//! ```
+//!
+//! ```text
+//! $ curl http://127.0.0.1:6770/stats
+//! {"Parse":{"current":1234,"peak":2048,...}}
+//! $ curl http://127.0.0.1:6770/errors
+//! {"CurrentUsecaseBadBytes":0,...}
+//! ```
+//!
+//! Usecases are rendered using [UseCase::name](crate::UseCase::name) and errors using their
+//! `Debug` implementation. Stats are read without
+//! resetting them, so this can be used alongside [reporter](crate::reporter) or any other code
+//! that flushes the recorder.
+//!
+//! The listener handles one connection at a time and is meant for debugging, not for exposure to
+//! untrusted networks.
+//!
+//! Requires the `http` feature.
+
+use std::alloc::GlobalAlloc;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+
+use crate::export::json::{render_json_errors, render_json_stats};
+use crate::{Alloc, PointerTable, StatsRecorder, UseCase};
+
+/// Bind to `addr` and serve the stats of `alloc` from a background thread.
+///
+/// Returns the address that was bound, which is useful when binding to port `0`. The thread runs
+/// until the process exits.
+pub fn serve<U, A, P>(
+    alloc: &'static Alloc<U, StatsRecorder<U>, A, P>,
+    addr: impl ToSocketAddrs,
+) -> io::Result<SocketAddr>
+where
+    U: UseCase + fmt::Debug + Send + Sync,
+    A: GlobalAlloc + Sync,
+    P: PointerTable,
+{
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    thread::Builder::new()
+        .name("memoria-http".to_owned())
+        .spawn(move || {
+            // errors of a single connection are the client's problem
+            for stream in listener.incoming().flatten() {
+                handle(alloc, stream).ok();
+            }
+        })?;
+    Ok(local_addr)
+}
+
+fn handle<U: UseCase + fmt::Debug, A: GlobalAlloc, P: PointerTable>(
+    alloc: &Alloc<U, StatsRecorder<U>, A, P>,
+    stream: TcpStream,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the headers so that the client does not see a reset connection.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next(), parts.next());
+    let (status, body) = match (method, path) {
+        (Some("GET"), Some("/stats")) => ("200 OK", render_json_stats(alloc)),
+        (Some("GET"), Some("/errors")) => ("200 OK", render_json_errors(alloc)),
+        (Some("GET"), _) => ("404 Not Found", "{\"error\":\"not found\"}".to_owned()),
+        _ => (
+            "405 Method Not Allowed",
+            "{\"error\":\"method not allowed\"}".to_owned(),
+        ),
+    };
+
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {body}",
+        body.len()
+    )?;
+    stream.flush()
+}
@@ -0,0 +1,128 @@
+use std::alloc::GlobalAlloc;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::{Alloc, PointerTable, Recorder, UseCase, UseCaseRepr};
+
+/// Extension trait for attributing the work done by lazy iterators to a usecase.
+///
+/// Iterator adapters such as `map` and `filter` do not do anything until they are polled, so a
+/// guard that is alive while the adapter chain is *built* does not cover the allocations done
+/// while it is *consumed*:
+///
+/// ```ignore
+/// let iter = {
+///     let _guard = ALLOCATOR.with_usecase(MyUseCase::Parse);
+///     lines.iter().map(|line| line.to_owned())
+/// };
+/// // allocations happen here, outside of the guard
+/// let parsed: Vec<String> = iter.collect();
+/// ```
+///
+/// [IteratorExt::attributed] instead switches to the usecase for the duration of every call to
+/// `next()`:
+///
+/// ```ignore
+/// use memoria::IteratorExt;
+///
+/// let parsed: Vec<String> = lines
+///     .iter()
+///     .map(|line| line.to_owned())
+///     .attributed(&ALLOCATOR, MyUseCase::Parse)
+///     .collect();
+/// ```
+///
+/// Only the work done inside the wrapped iterator is attributed. In the example above, growing
+/// the resulting `Vec` is still attributed to whatever usecase is active around `collect()`.
+pub trait IteratorExt: Iterator + Sized {
+    /// Wrap this iterator such that each call to `next()` runs under the given usecase.
+    fn attributed<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable>(
+        self,
+        alloc: &Alloc<U, R, A, P>,
+        use_case: U,
+    ) -> Attributed<'_, Self, U, R, A, P> {
+        Attributed {
+            inner: self,
+            alloc,
+            use_case: use_case.into_repr(),
+        }
+    }
+}
+
+impl<I: Iterator> IteratorExt for I {}
+
+/// An iterator, stream or future that switches to a usecase whenever it is polled.
+///
+/// Returned by [IteratorExt::attributed] and [Attributed::new].
+pub struct Attributed<'a, I, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> {
+    inner: I,
+    alloc: &'a Alloc<U, R, A, P>,
+    use_case: UseCaseRepr,
+}
+
+impl<'a, I, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable>
+    Attributed<'a, I, U, R, A, P>
+{
+    /// Wrap an iterator, stream or future such that each poll runs under the given usecase.
+    ///
+    /// For iterators, [IteratorExt::attributed] is usually more convenient.
+    pub fn new(inner: I, alloc: &'a Alloc<U, R, A, P>, use_case: U) -> Self {
+        Attributed {
+            inner,
+            alloc,
+            use_case: use_case.into_repr(),
+        }
+    }
+
+    /// Unwrap the inner iterator, stream or future.
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<I: Iterator, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Iterator
+    for Attributed<'_, I, U, R, A, P>
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let _guard = self.alloc.with_usecase_bytes(self.use_case);
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<S: futures_core::Stream, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable>
+    futures_core::Stream for Attributed<'_, S, U, R, A, P>
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // SAFETY: `inner` is structurally pinned, and is never moved out of a pinned `Attributed`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let _guard = this.alloc.with_usecase_bytes(this.use_case);
+        unsafe { Pin::new_unchecked(&mut this.inner) }.poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<F: Future, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Future
+    for Attributed<'_, F, U, R, A, P>
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `inner` is structurally pinned, and is never moved out of a pinned `Attributed`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let _guard = this.alloc.with_usecase_bytes(this.use_case);
+        unsafe { Pin::new_unchecked(&mut this.inner) }.poll(cx)
+    }
+}
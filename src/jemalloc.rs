@@ -0,0 +1,44 @@
+//! Read jemalloc's own stats next to the stats memoria keeps per usecase.
+//!
+//! When memoria wraps [tikv-jemallocator](https://docs.rs/tikv-jemallocator), jemalloc knows how
+//! much memory it keeps around beyond what the application asked for, such as partially used
+//! pages and its own metadata:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOCATOR: memoria::Alloc<MyUseCase, memoria::StatsRecorder<MyUseCase>, Jemalloc> =
+//!     memoria::Alloc::new_with(memoria::StatsRecorder::new(), Jemalloc);
+//!
+//! let jemalloc = memoria::jemalloc::JemallocStats::read()?;
+//! eprintln!("{} bytes active, {} tracked", jemalloc.active, ALLOCATOR.tracked_bytes());
+//! ```
+//!
+//! [reporter](crate::reporter) includes this in every [Report](crate::reporter::Report).
+//!
+//! Requires the `jemalloc` feature. The stats are only meaningful if jemalloc is the allocator
+//! memoria wraps, or otherwise serves allocations in the process.
+
+pub use tikv_jemalloc_ctl::Error;
+
+/// Allocator-internal stats of jemalloc, in bytes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct JemallocStats {
+    /// Bytes in pages that contain live allocations, see `stats.active` in jemalloc's manual.
+    pub active: usize,
+    /// Bytes in physically resident pages mapped by jemalloc, see `stats.resident`.
+    pub resident: usize,
+    /// Bytes used by jemalloc for its own bookkeeping, see `stats.metadata`.
+    pub metadata: usize,
+}
+
+impl JemallocStats {
+    /// Advance jemalloc's stats epoch, such that the stats are up to date, and read them.
+    pub fn read() -> Result<Self, Error> {
+        tikv_jemalloc_ctl::epoch::advance()?;
+        Ok(JemallocStats {
+            active: tikv_jemalloc_ctl::stats::active::read()?,
+            resident: tikv_jemalloc_ctl::stats::resident::read()?,
+            metadata: tikv_jemalloc_ctl::stats::metadata::read()?,
+        })
+    }
+}
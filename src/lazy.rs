@@ -0,0 +1,244 @@
+use std::alloc::{GlobalAlloc, Layout};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use once_cell::sync::OnceCell;
+
+use crate::{Alloc, Callsite, Error, OverheadEstimate, PointerTable, Recorder, Tag, UseCase};
+
+/// A recorder that is constructed at runtime, for recorders that cannot be built in a `const fn`,
+/// such as an event log that writes to a file.
+///
+/// Until [LazyRecorder::init] is called, nothing is recorded: allocations are not tracked, and
+/// their sizes are only summed up in [LazyRecorder::early_bytes]. Deallocations of such memory
+/// after initialization are not reported as [Error::DeallocUntrackedPointer].
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::Alloc<MyUseCase, memoria::LazyRecorder<MyUseCase, MyRecorder>> =
+///     memoria::Alloc::new_with(memoria::LazyRecorder::new(), std::alloc::System);
+///
+/// fn main() {
+///     ALLOCATOR.init_recorder(|| MyRecorder::open("allocations.log")).ok();
+/// }
+/// ```
+pub struct LazyRecorder<U: UseCase, R: Recorder<U>> {
+    inner: OnceCell<R>,
+    early_bytes: AtomicUsize,
+    early_live: AtomicUsize,
+    _phantom: PhantomData<U>,
+}
+
+impl<U: UseCase, R: Recorder<U>> LazyRecorder<U, R> {
+    /// Construct a new, uninitialized recorder.
+    pub const fn new() -> Self {
+        LazyRecorder {
+            inner: OnceCell::new(),
+            early_bytes: AtomicUsize::new(0),
+            early_live: AtomicUsize::new(0),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Initialize the recorder with the result of `f`, see also [Alloc::init_recorder].
+    ///
+    /// Returns the newly created recorder as error if the recorder was already initialized.
+    /// Allocations made by `f` itself are not recorded.
+    pub fn init(&self, f: impl FnOnce() -> R) -> Result<(), R> {
+        if self.inner.get().is_some() {
+            return Err(f());
+        }
+        self.inner.set(f())
+    }
+
+    /// Access the wrapped recorder, if it was initialized.
+    pub fn inner(&self) -> Option<&R> {
+        self.inner.get()
+    }
+
+    /// The total number of bytes allocated before initialization.
+    pub fn early_bytes(&self) -> usize {
+        self.early_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Count an allocation made before initialization, which is not tracked.
+    fn record_early(&self, size: usize) -> bool {
+        self.early_bytes.fetch_add(size, Ordering::Relaxed);
+        self.early_live.fetch_add(size, Ordering::Relaxed);
+        false
+    }
+}
+
+impl<U: UseCase, R: Recorder<U>> Default for LazyRecorder<U, R> {
+    fn default() -> Self {
+        LazyRecorder::new()
+    }
+}
+
+unsafe impl<U: UseCase, R: Recorder<U>> Recorder<U> for LazyRecorder<U, R> {
+    fn on_alloc(&self, use_case: U, size: usize) -> bool {
+        match self.inner.get() {
+            Some(inner) => inner.on_alloc(use_case, size),
+            None => self.record_early(size),
+        }
+    }
+
+    fn on_shared_alloc(&self, use_case: U, size: usize) -> bool {
+        match self.inner.get() {
+            Some(inner) => inner.on_shared_alloc(use_case, size),
+            None => self.record_early(size),
+        }
+    }
+
+    fn on_alloc_layout(&self, use_case: U, layout: Layout) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_alloc_layout(use_case, layout)
+        }
+    }
+
+    fn on_dealloc(&self, use_case: U, size: usize) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_dealloc(use_case, size)
+        }
+    }
+
+    fn on_shared_dealloc(&self, use_case: U, size: usize) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_shared_dealloc(use_case, size)
+        }
+    }
+
+    fn on_attributed_drop(&self, use_case: U, size: usize) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_attributed_drop(use_case, size)
+        }
+    }
+
+    fn on_transfer(&self, from: U, to: U, size: usize) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_transfer(from, to, size)
+        }
+    }
+
+    fn on_cross_thread_dealloc(&self, use_case: U, size: usize) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_cross_thread_dealloc(use_case, size)
+        }
+    }
+
+    fn on_external_alloc(&self, use_case: U, size: usize) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_external_alloc(use_case, size)
+        }
+    }
+
+    fn on_external_dealloc(&self, use_case: U, size: usize) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_external_dealloc(use_case, size)
+        }
+    }
+
+    fn on_callsite_alloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_callsite_alloc(use_case, callsite, size)
+        }
+    }
+
+    fn on_callsite_dealloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_callsite_dealloc(use_case, callsite, size)
+        }
+    }
+
+    fn on_tagged_alloc(&self, use_case: U, tag: Tag, size: usize) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_tagged_alloc(use_case, tag, size)
+        }
+    }
+
+    fn on_tagged_dealloc(&self, use_case: U, tag: Tag, size: usize) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_tagged_dealloc(use_case, tag, size)
+        }
+    }
+
+    fn on_forbidden_alloc(&self, use_case: U, size: usize) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_forbidden_alloc(use_case, size)
+        }
+    }
+
+    fn on_usecase_enter(&self, use_case: U) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_usecase_enter(use_case)
+        }
+    }
+
+    fn on_usecase_exit(&self, use_case: U) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_usecase_exit(use_case)
+        }
+    }
+
+    fn on_guard_enter(&self, use_case: U) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_guard_enter(use_case)
+        }
+    }
+
+    fn on_guard_exit(&self, use_case: U) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_guard_exit(use_case)
+        }
+    }
+
+    fn on_flush(&self) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_flush()
+        }
+    }
+
+    fn on_overhead_estimate(&self, estimate: OverheadEstimate) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_overhead_estimate(estimate)
+        }
+    }
+
+    fn on_fork(&self) {
+        if let Some(inner) = self.inner.get() {
+            inner.on_fork()
+        }
+    }
+
+    fn on_error(&self, code: Error, size: Option<usize>) {
+        // Memory allocated before initialization is expected to be untracked. Pointers are not
+        // remembered, so any untracked deallocation is accounted against the early allocations
+        // until they are used up.
+        if let (Error::DeallocUntrackedPointer, Some(size)) = (code, size) {
+            let swallowed = self
+                .early_live
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |live| {
+                    live.checked_sub(size)
+                })
+                .is_ok();
+            if swallowed {
+                return;
+            }
+        }
+        if let Some(inner) = self.inner.get() {
+            inner.on_error(code, size)
+        }
+    }
+}
+
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable>
+    Alloc<U, LazyRecorder<U, R>, A, P>
+{
+    /// Initialize a [LazyRecorder] with the result of `f`. Allocations before this call are not
+    /// recorded, see [LazyRecorder].
+    ///
+    /// Returns the newly created recorder as error if the recorder was already initialized.
+    pub fn init_recorder(&self, f: impl FnOnce() -> R) -> Result<(), R> {
+        self.recorder.init(f)
+    }
+}
@@ -0,0 +1,126 @@
+use alloc::collections::{BTreeMap, BinaryHeap};
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+use crate::{pointers, IntPointer, PointerTable, UseCase, UseCaseRepr};
+
+/// Live allocations that memoria is still tracking, grouped by usecase.
+///
+/// Returned by [Alloc::leak_report](crate::Alloc::leak_report).
+#[derive(Debug)]
+pub struct LeakReport<U: UseCase> {
+    /// Live bytes and allocation counts per usecase, in the order of their `UseCaseRepr`.
+    pub use_cases: Vec<(U, LiveStat)>,
+    /// The largest live allocations, biggest first.
+    pub largest: Vec<LiveAllocation<U>>,
+}
+
+/// Live memory of a single usecase.
+#[derive(Default, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LiveStat {
+    /// The amount of memory allocated and not yet freed.
+    pub bytes: usize,
+    /// The number of allocations not yet freed.
+    pub count: usize,
+}
+
+/// A single live allocation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LiveAllocation<U> {
+    /// The usecase that made the allocation.
+    pub use_case: U,
+    /// The address of the allocation.
+    pub ptr: usize,
+    /// The size of the allocation in bytes.
+    pub size: usize,
+    /// How long ago the allocation was made, according to the clock passed to
+    /// [Alloc::with_age_clock](crate::Alloc::with_age_clock). Zero if no clock was configured.
+    pub age: u64,
+    /// How many generations the allocation has survived, see
+    /// [Alloc::next_generation](crate::Alloc::next_generation).
+    pub generations: u64,
+}
+
+pub(crate) fn for_each_live<U: UseCase, P: PointerTable>(
+    now: u64,
+    generation: u64,
+    mut f: impl FnMut(LiveAllocation<U>),
+) {
+    pointers::for_each::<P>(|ptr, tracked| {
+        f(LiveAllocation {
+            use_case: U::from_repr(tracked.use_case).unwrap_or_default(),
+            ptr,
+            size: tracked.size,
+            age: now.saturating_sub(tracked.allocated_at),
+            generations: generation.saturating_sub(tracked.generation),
+        })
+    });
+}
+
+pub(crate) fn build_survivors<U: UseCase, P: PointerTable>(
+    generation: u64,
+    min_generations: u64,
+) -> Vec<(U, LiveStat)> {
+    let mut use_cases = BTreeMap::<UseCaseRepr, LiveStat>::new();
+
+    pointers::for_each::<P>(|_, tracked| {
+        if generation.saturating_sub(tracked.generation) >= min_generations {
+            let stat = use_cases.entry(tracked.use_case).or_default();
+            stat.bytes += tracked.size;
+            stat.count += 1;
+        }
+    });
+
+    use_cases
+        .into_iter()
+        .map(|(use_case, stat)| (U::from_repr(use_case).unwrap_or_default(), stat))
+        .collect()
+}
+
+pub(crate) fn build_report<U: UseCase, P: PointerTable>(
+    largest: usize,
+    now: u64,
+    generation: u64,
+) -> LeakReport<U> {
+    let mut use_cases = BTreeMap::<UseCaseRepr, LiveStat>::new();
+    let mut heap = BinaryHeap::<Reverse<(usize, IntPointer, UseCaseRepr, u64, u64)>>::new();
+
+    pointers::for_each::<P>(|ptr, tracked| {
+        let stat = use_cases.entry(tracked.use_case).or_default();
+        stat.bytes += tracked.size;
+        stat.count += 1;
+
+        if largest > 0 {
+            heap.push(Reverse((
+                tracked.size,
+                ptr,
+                tracked.use_case,
+                tracked.allocated_at,
+                tracked.generation,
+            )));
+            if heap.len() > largest {
+                heap.pop();
+            }
+        }
+    });
+
+    LeakReport {
+        use_cases: use_cases
+            .into_iter()
+            .map(|(use_case, stat)| (U::from_repr(use_case).unwrap_or_default(), stat))
+            .collect(),
+        largest: heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(
+                |Reverse((size, ptr, use_case, allocated_at, allocated_in))| LiveAllocation {
+                    use_case: U::from_repr(use_case).unwrap_or_default(),
+                    ptr,
+                    size,
+                    age: now.saturating_sub(allocated_at),
+                    generations: generation.saturating_sub(allocated_in),
+                },
+            )
+            .collect(),
+    }
+}
@@ -1,56 +1,442 @@
 #![warn(missing_docs)]
 #![doc = include_str!("../README.md")]
-use std::alloc::{GlobalAlloc, Layout, System};
-use std::cell::RefCell;
-use std::marker::PhantomData;
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "alloc-error-hook", feature(alloc_error_hook))]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 
-use dashmap::DashMap;
-use once_cell::sync::OnceCell;
+#[cfg(not(feature = "alloc"))]
+compile_error!("memoria requires the `alloc` feature, or `std` which implies it");
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::alloc::{GlobalAlloc, Layout};
+use core::marker::PhantomData;
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::alloc::System;
+#[cfg(feature = "std")]
+use std::io::Write;
+
+mod macros;
 
 mod types;
-pub use types::{Error, Recorder, UseCase, UseCaseBytes};
+pub use types::{Callsite, Error, Recorder, Tag, ThreadIndex, UseCase, UseCaseBytes, UseCaseRepr};
+
+#[cfg(feature = "derive")]
+pub use memoria_derive::UseCase;
+
+#[cfg(feature = "instrument")]
+pub use memoria_derive::instrument;
 
 mod recorder;
-pub use recorder::{Stat, StatsRecorder};
+#[cfg(feature = "std")]
+pub use recorder::StatsRecorder;
+pub use recorder::{FlushMode, NoopRecorder, Rate, SortKey, Stat, HIGH_ALIGNMENT};
 
 mod utils;
 
+mod sync;
+
+mod store;
+#[cfg(feature = "std")]
+pub use store::ThreadLocalStore;
+pub use store::{CurrentUsecaseStore, ThreadState};
+
+mod pointers;
+pub use pointers::{DefaultTable, PointerTable, ShardedTable};
+
+mod measure;
+pub use measure::MeasuredGuard;
+
+#[cfg(feature = "std")]
+mod session;
+#[cfg(feature = "std")]
+pub use session::{AttributionSession, SessionGuard};
+
+mod overhead;
+pub use overhead::OverheadEstimate;
+
+mod clock;
+#[cfg(feature = "std")]
+pub use clock::InstantClock;
+pub use clock::{Clock, ManualClock};
+
+mod caps;
+
+mod arena;
+#[cfg(feature = "std")]
+pub use arena::Arena;
+
+#[cfg(feature = "std")]
+mod builder;
+
+mod filter;
+#[cfg(feature = "std")]
+pub use builder::AllocBuilder;
+
+#[cfg(feature = "std")]
+mod dynamic;
+#[cfg(feature = "std")]
+pub use dynamic::{register_usecase, DynUseCase};
+
+mod leak;
+
+#[cfg(feature = "std")]
+pub mod export;
+
+#[cfg(feature = "std")]
+pub mod reporter;
+
+#[cfg(feature = "std")]
+pub mod shrink;
+
+mod split;
+use split::SplitId;
+
+#[cfg(feature = "std")]
+mod worker;
+
+#[cfg(feature = "std")]
+mod panic;
+#[cfg(feature = "std")]
+pub use panic::install_panic_hook;
+
+#[cfg(feature = "std")]
+mod histogram;
+#[cfg(feature = "std")]
+pub use histogram::{HistogramRecorder, SizeHistogram};
+
+#[cfg(feature = "std")]
+mod lazy;
+#[cfg(feature = "std")]
+pub use lazy::LazyRecorder;
+
+#[cfg(feature = "std")]
+mod tracked;
+#[cfg(feature = "std")]
+pub use tracked::{Tracked, TrackedBox, TrackedHashMap, TrackedMut, TrackedVec};
+
+#[cfg(feature = "std")]
+mod timeseries;
+#[cfg(feature = "std")]
+pub use timeseries::TimeSeriesRecorder;
+
+#[cfg(feature = "std")]
+mod eventlog;
+#[cfg(feature = "std")]
+pub use eventlog::{Event, EventKind, EventLogRecorder, EventReader};
+
+#[cfg(feature = "sketch")]
+mod sketch;
+pub use leak::{LeakReport, LiveAllocation, LiveStat};
+#[cfg(feature = "sketch")]
+pub use sketch::{QuantileSketch, SketchRecorder};
+
+#[cfg(all(unix, feature = "shm"))]
+mod shm;
+#[cfg(all(unix, feature = "shm"))]
+pub use shm::ShmStatsRecorder;
+
+#[cfg(feature = "iter")]
+mod iter;
+#[cfg(feature = "iter")]
+pub use iter::{Attributed, IteratorExt};
+
+#[cfg(feature = "alloc-error-hook")]
+pub mod oom;
+
+#[cfg(feature = "allocator-api")]
+pub mod allocator_api;
+
+#[cfg(feature = "serde")]
+pub mod serde;
+
+#[cfg(feature = "http")]
+pub mod http;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(feature = "log")]
+mod logging;
+#[cfg(feature = "log")]
+pub use logging::LogRecorder;
+
+#[cfg(feature = "selftest")]
+mod selftest;
+#[cfg(feature = "selftest")]
+pub use selftest::overhead_selftest;
+
+#[cfg(all(unix, feature = "signal"))]
+pub mod signal;
+
+#[cfg(all(unix, feature = "region"))]
+pub mod region;
+
+#[cfg(all(unix, feature = "fork"))]
+pub mod fork;
+
+#[cfg(feature = "rss")]
+pub mod rss;
+
+#[cfg(feature = "jemalloc")]
+pub mod jemalloc;
+
+#[cfg(feature = "backtrace")]
+mod stack;
+#[cfg(feature = "backtrace")]
+pub use stack::{BacktraceRecorder, StackStat, StackTrace, BACKTRACE_DEPTH};
+
 type IntPointer = usize;
 
-static TRACKED_POINTERS: OnceCell<DashMap<IntPointer, UseCaseBytes>> = OnceCell::new();
+/// What memoria remembers about a live allocation.
+#[derive(Clone, Copy, Default)]
+struct TrackedPointer {
+    use_case: UseCaseRepr,
+    size: usize,
+    callsite: Option<Callsite>,
+    tag: Option<Tag>,
+    // The time of the allocation according to `Alloc::with_age_clock`, zero without a clock.
+    allocated_at: u64,
+    // The value of `Alloc::generation` at the time of the allocation.
+    generation: u64,
+    // Whether the allocation was charged to the cap of its usecase, see `Alloc::set_cap`.
+    capped: bool,
+    // The thread that made the allocation, zero if unknown.
+    thread: ThreadIndex,
+    // The shares across which the allocation is split, see `Alloc::with_usecases`.
+    split: SplitId,
+}
+
+/// Set while a thread whose [ThreadState] is unavailable, for example because it is exiting, is
+/// using memoria's bookkeeping. See `Alloc::synchronized`.
+#[cfg(not(memoria_single_threaded))]
+static FALLBACK_BUSY: sync::TryLock = sync::TryLock::new();
+
+/// The lock shared by all threads whose [ThreadState] is unavailable, such that allocations made
+/// during thread exit, for example by destructors of other thread-locals, are still recorded.
+/// They are attributed to the default usecase.
+#[cfg(not(memoria_single_threaded))]
+#[inline]
+fn fallback_lock() -> Option<&'static sync::TryLock> {
+    Some(&FALLBACK_BUSY)
+}
 
-thread_local! {
-    static CURRENT_USECASE: RefCell<Option<UseCaseBytes>> = RefCell::new(None);
+/// Programs with a single thread only record the allocations of the thread that owns the
+/// thread-locals.
+#[cfg(memoria_single_threaded)]
+#[inline]
+fn fallback_lock() -> Option<&'static sync::TryLock> {
+    None
+}
+
+/// Return the [ThreadIndex] of the current thread, as kept by [ThreadLocalStore].
+#[cfg(feature = "std")]
+pub fn current_thread_index() -> ThreadIndex {
+    store::default_state().map_or(0, ThreadState::thread_index)
 }
 
 /// A drop-guard for setting and resetting the current usecase.
 ///
 /// Returned by [Alloc::with_usecase].
-pub struct Guard {
-    old_value: Option<UseCaseBytes>,
+pub struct Guard<'a> {
+    use_case: UseCaseRepr,
+    tag: Option<Tag>,
+    split: SplitId,
+    old_value: Option<UseCaseRepr>,
+    old_callsite: Option<Callsite>,
+    old_tag: Option<Tag>,
+    old_split: SplitId,
+    old_pinned: bool,
+    hooks: &'a dyn SwitchHooks,
+    state: Option<&'a ThreadState>,
     // Guard needs to be dropped in the same thread again in order to unset the usecase.
     _unsend: utils::PhantomUnsend,
     _unsync: utils::PhantomUnsync,
 }
 
-impl Drop for Guard {
+impl Guard<'_> {
+    /// Capture the usecase and tag set by this guard into a token that can be moved to another
+    /// thread and activated there with [Alloc::with_token].
+    ///
+    /// The guard itself stays bound to the current thread.
+    pub fn transferable(&self) -> UsecaseToken {
+        UsecaseToken {
+            use_case: Some(self.use_case),
+            tag: self.tag,
+            split: self.split,
+        }
+    }
+}
+
+impl Drop for Guard<'_> {
     fn drop(&mut self) {
-        CURRENT_USECASE
-            .try_with(|current_value| {
-                *current_value.borrow_mut() = self.old_value.take();
-            })
-            .ok();
+        let Some(state) = self.state else {
+            return;
+        };
+        let mut current_value = state.use_case.borrow_mut();
+        // Called while the usecase is borrowed, such that allocations made by the hooks are not
+        // recorded.
+        if let Some(exited) = *current_value {
+            self.hooks.on_guard_exit(exited);
+        }
+        let exited = core::mem::replace(&mut *current_value, self.old_value.take());
+        self.hooks.on_switch(exited, *current_value);
+        drop(current_value);
+        state.callsite.set(self.old_callsite);
+        state.tag.set(self.old_tag);
+        state.split.set(self.old_split);
+        state.pinned.set(self.old_pinned);
+    }
+}
+
+/// The usecase and tag of a thread, captured such that they can be activated on another thread.
+///
+/// Returned by [Alloc::current_token] and [Guard::transferable]. Unlike [Guard], tokens are
+/// `Send`, so the common pattern of handing work to a thread pool keeps its attribution:
+///
+/// ```ignore
+/// let token = ALLOCATOR.current_token();
+/// pool.spawn(move || {
+///     let _guard = ALLOCATOR.with_token(token);
+///     resize_images(&request);
+/// });
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct UsecaseToken {
+    use_case: Option<UseCaseRepr>,
+    tag: Option<Tag>,
+    split: SplitId,
+}
+
+impl UsecaseToken {
+    /// The captured usecase, or `None` if no usecase was active.
+    pub fn use_case<U: UseCase>(&self) -> Option<U> {
+        self.use_case.and_then(U::from_repr)
     }
+
+    /// The captured tag, see [Alloc::with_usecase_tagged].
+    pub fn tag(&self) -> Option<Tag> {
+        self.tag
+    }
+}
+
+/// A guard during which allocations are reported as [Error::AllocInForbiddenScope].
+///
+/// Returned by [Alloc::forbid_alloc].
+#[must_use = "allocations are only forbidden while the guard is alive"]
+pub struct ForbidAllocGuard<'a> {
+    state: Option<&'a ThreadState>,
+    // The guard needs to be dropped in the same thread again in order to allow allocations.
+    _unsend: utils::PhantomUnsend,
+    _unsync: utils::PhantomUnsync,
+}
+
+impl Drop for ForbidAllocGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state {
+            state.forbid_alloc.set(state.forbid_alloc.get() - 1);
+        }
+    }
+}
+
+/// Type-erased access to the recorder's usecase switch hooks, for use in [Guard].
+trait SwitchHooks {
+    fn on_switch(&self, exited: Option<UseCaseRepr>, entered: Option<UseCaseRepr>);
+    fn on_guard_exit(&self, use_case: UseCaseRepr);
+}
+
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> SwitchHooks
+    for Alloc<U, R, A, P>
+{
+    fn on_switch(&self, exited: Option<UseCaseRepr>, entered: Option<UseCaseRepr>) {
+        if let Some(exited) = exited {
+            self.recorder
+                .on_usecase_exit(U::from_repr(exited).unwrap_or_default());
+        }
+        if let Some(entered) = entered {
+            self.recorder
+                .on_usecase_enter(U::from_repr(entered).unwrap_or_default());
+        }
+    }
+
+    fn on_guard_exit(&self, use_case: UseCaseRepr) {
+        self.recorder
+            .on_guard_exit(U::from_repr(use_case).unwrap_or_default());
+    }
+}
+
+/// Which usecase a deallocation is attributed to, see [Alloc::with_dealloc_attribution].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum DeallocAttribution {
+    /// The usecase that allocated the memory. This is the default, and means that the current
+    /// memory usage of a usecase is the memory it allocated and that is still alive.
+    #[default]
+    Owner,
+    /// The usecase that is active while the memory is freed. This means that the current memory
+    /// usage of a usecase is what it allocated minus what it freed, which can be negative for
+    /// usecases that tear down data allocated elsewhere.
+    Current,
 }
 
 /// A wrapper around another allocator `A` that records memory usage statistics into `R`.
-pub struct Alloc<U: UseCase, R: Recorder<U> = StatsRecorder<U>, A: GlobalAlloc = System> {
+///
+/// `P` selects the table in which live allocations are tracked, see [PointerTable]. Without the
+/// `std` feature, `R` and `A` have no defaults, and the state of each thread has to be provided
+/// through [Alloc::with_store].
+///
+/// # Layouts
+///
+/// Every [Layout] that the wrapped allocator accepts is supported, including alignments far
+/// beyond a page. Unusual layouts are handled as follows:
+///
+/// - An allocation for which the wrapped allocator returns null is neither recorded nor tracked,
+///   and its charge against a cap is released.
+/// - Zero-sized layouts are not allowed by [GlobalAlloc]. Such calls are still passed on to the
+///   wrapped allocator, but are not recorded, and are reported as [Error::ZeroSizedAlloc].
+/// - Freeing memory with a different size than it was allocated with is reported as
+///   [Error::DeallocLayoutMismatch]. Stats are updated with the size recorded at allocation time.
+pub struct Alloc<
+    U: UseCase,
+    #[cfg(feature = "std")] R: Recorder<U> = StatsRecorder<U>,
+    #[cfg(not(feature = "std"))] R: Recorder<U>,
+    #[cfg(feature = "std")] A: GlobalAlloc = System,
+    #[cfg(not(feature = "std"))] A: GlobalAlloc,
+    P: PointerTable = DefaultTable,
+> {
     alloc: A,
     recorder: R,
+    dealloc_attribution: DeallocAttribution,
+    abort_on_forbidden_alloc: bool,
+    untracked_default: bool,
+    age_clock: Option<&'static dyn Clock>,
+    store: Option<&'static dyn CurrentUsecaseStore>,
+    filter: filter::Filter,
+    overhead: overhead::Overhead,
+    tracked_bytes: AtomicUsize,
+    generation: AtomicU64,
+    caps: caps::Caps,
+    #[cfg(feature = "std")]
+    shrinkers: shrink::Shrinkers,
+    #[cfg(feature = "std")]
+    routes: arena::Routes,
+    #[cfg(feature = "std")]
+    workers: worker::Workers,
+    finalized: AtomicBool,
     #[doc(hidden)]
-    inner: PhantomData<U>,
+    inner: PhantomData<(U, P)>,
 }
 
+/// An [Alloc] that only switches usecases, and records nothing. See [NoopRecorder].
+pub type NoopAlloc<U, #[cfg(feature = "std")] A = System, #[cfg(not(feature = "std"))] A> =
+    Alloc<U, NoopRecorder<U>, A>;
+
+#[cfg(feature = "std")]
 impl<U: UseCase> Alloc<U> {
     /// Instantiate memoria while wrapping the system allocator, and [StatsRecorder] as recorder.
     ///
@@ -64,15 +450,161 @@ impl<U: UseCase> Alloc<U> {
     pub const fn new() -> Self {
         Alloc::new_with(StatsRecorder::new(), System)
     }
+
+    /// Configure memoria step by step, starting from the configuration of [Alloc::new]:
+    ///
+    /// ```ignore
+    /// #[global_allocator]
+    /// static ALLOCATOR: memoria::Alloc<MyUseCase, MyRecorder> = memoria::Alloc::builder()
+    ///     .sample_rate(64)
+    ///     .min_size(1024)
+    ///     .recorder(MyRecorder::new())
+    ///     .build();
+    /// ```
+    pub const fn builder() -> AllocBuilder<U> {
+        AllocBuilder::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<U: UseCase> Default for Alloc<U> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<R: Recorder<U>, U: UseCase, A: GlobalAlloc> Alloc<U, R, A> {
     /// Instantiate memoria with custom memory allocator to wrap and a custom recorder.
     pub const fn new_with(recorder: R, alloc: A) -> Self {
+        Alloc::new_with_filter(recorder, alloc, filter::Filter::new(1, 0, None))
+    }
+}
+
+impl<R: Recorder<U>, U: UseCase, A: GlobalAlloc, P: PointerTable> Alloc<U, R, A, P> {
+    const fn new_with_filter(recorder: R, alloc: A, filter: filter::Filter) -> Self {
         Alloc {
             alloc,
             recorder,
-            inner: std::marker::PhantomData,
+            dealloc_attribution: DeallocAttribution::Owner,
+            abort_on_forbidden_alloc: false,
+            untracked_default: false,
+            age_clock: None,
+            store: None,
+            filter,
+            overhead: overhead::Overhead::new(),
+            tracked_bytes: AtomicUsize::new(0),
+            generation: AtomicU64::new(0),
+            caps: caps::Caps::new(),
+            #[cfg(feature = "std")]
+            shrinkers: shrink::Shrinkers::new(),
+            #[cfg(feature = "std")]
+            routes: arena::Routes::new(),
+            #[cfg(feature = "std")]
+            workers: worker::Workers::new(),
+            finalized: AtomicBool::new(false),
+            inner: PhantomData,
+        }
+    }
+
+    /// Choose which usecase deallocations are attributed to.
+    ///
+    /// Regardless of this setting, memory freed under a different usecase than the one that
+    /// allocated it is reported to [Recorder::on_transfer]. [StatsRecorder] reports those as
+    /// [StatsRecorder::flush_transfers].
+    ///
+    /// ```
+    /// # #[derive(Default)] struct MyUseCase;
+    /// # impl From<MyUseCase> for u32 { fn from(_: MyUseCase) -> u32 { 0 } }
+    /// # impl From<u32> for MyUseCase { fn from(_: u32) -> MyUseCase { MyUseCase } }
+    /// # impl memoria::UseCase for MyUseCase {}
+    /// static ALLOCATOR: memoria::Alloc<MyUseCase> =
+    ///     memoria::Alloc::new().with_dealloc_attribution(memoria::DeallocAttribution::Current);
+    /// ```
+    pub const fn with_dealloc_attribution(
+        mut self,
+        dealloc_attribution: DeallocAttribution,
+    ) -> Self {
+        self.dealloc_attribution = dealloc_attribution;
+        self
+    }
+
+    /// Abort the process when memory is allocated while a guard created with
+    /// [Alloc::forbid_alloc] is alive. Only has an effect in builds with debug assertions, release
+    /// builds only report [Error::AllocInForbiddenScope]. Without the `std` feature, this panics
+    /// instead of aborting.
+    pub const fn with_abort_on_forbidden_alloc(mut self) -> Self {
+        self.abort_on_forbidden_alloc = true;
+        self
+    }
+
+    /// Do not track allocations made while no usecase is active.
+    ///
+    /// Allocations of the default usecase are still passed to [Recorder::on_alloc], but are not
+    /// entered into the table of live allocations, which is the bulk of memoria's overhead. This
+    /// makes code outside of any guard almost as fast as without memoria. In turn, deallocations
+    /// of pointers that memoria does not know are attributed to the default usecase instead of
+    /// being reported as [Error::DeallocUntrackedPointer], which includes memory that memoria
+    /// skipped for other reasons, such as [AllocBuilder::sample_rate].
+    ///
+    /// Allocations of the default usecase do not show up in [Alloc::leak_report], are not passed
+    /// to [UseCase::on_alloc] and are not counted in [Alloc::tracked_bytes].
+    ///
+    /// ```ignore
+    /// #[global_allocator]
+    /// static ALLOCATOR: memoria::Alloc<MyUseCase> = memoria::Alloc::new().with_untracked_default();
+    /// ```
+    pub const fn with_untracked_default(mut self) -> Self {
+        self.untracked_default = true;
+        self
+    }
+
+    /// Record the time of every tracked allocation, such that [Alloc::inspect_live] and
+    /// [Alloc::leak_report] can report the [age](LiveAllocation::age) of live allocations.
+    ///
+    /// `clock` is read for every tracked allocation, so it should be cheap, see [Clock].
+    pub const fn with_age_clock(mut self, clock: &'static dyn Clock) -> Self {
+        self.age_clock = Some(clock);
+        self
+    }
+
+    /// Keep the current usecase and the rest of the state of each thread in `store` instead of
+    /// in a thread-local, see [CurrentUsecaseStore].
+    ///
+    /// Without the `std` feature, there are no thread-locals, so this is how the platform tells
+    /// memoria which thread is running. Without a store, all allocations are then attributed to
+    /// the default usecase.
+    pub const fn with_store(mut self, store: &'static dyn CurrentUsecaseStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// The state of the current thread, see [Alloc::with_store].
+    #[inline]
+    fn thread_state(&self) -> Option<&ThreadState> {
+        match self.store {
+            Some(store) => store.state(),
+            None => store::default_state(),
+        }
+    }
+
+    /// Forbid memory allocations on the current thread for as long as the guard is alive.
+    ///
+    /// This is meant for verifying that code such as real-time audio callbacks never touches the
+    /// heap. Allocations made regardless are still recorded as usual, but additionally reported
+    /// as [Error::AllocInForbiddenScope] and to [Recorder::on_forbidden_alloc]. [StatsRecorder]
+    /// counts them per usecase in [Stat::forbidden]. See also
+    /// [Alloc::with_abort_on_forbidden_alloc].
+    ///
+    /// Deallocations are allowed. Guards can be nested.
+    pub fn forbid_alloc(&self) -> ForbidAllocGuard<'_> {
+        let state = self.thread_state();
+        if let Some(state) = state {
+            state.forbid_alloc.set(state.forbid_alloc.get() + 1);
+        }
+        ForbidAllocGuard {
+            state,
+            _unsend: PhantomData,
+            _unsync: PhantomData,
         }
     }
 
@@ -82,75 +614,841 @@ impl<R: Recorder<U>, U: UseCase, A: GlobalAlloc> Alloc<U, R, A> {
     ///
     /// This function can fail to return a guard in case you are trying to switch usecases from
     /// within the allocator itself.
-    pub fn with_usecase(&self, use_case: U) -> Option<Guard> {
+    pub fn with_usecase(&self, use_case: U) -> Option<Guard<'_>> {
+        self.with_usecase_bytes(use_case.into_repr())
+    }
+
+    /// Like [Alloc::with_usecase], but the returned guard additionally measures all allocations
+    /// and deallocations made by the current thread while it is alive, see
+    /// [MeasuredGuard::finish]. This is useful for micro-benchmarking single operations:
+    ///
+    /// ```ignore
+    /// let guard = ALLOCATOR.with_usecase_measured(MyUseCase::Parse);
+    /// let document = parse(input);
+    /// let stat = guard.finish();
+    /// println!("parsing allocated {} bytes in {} allocations", stat.total, stat.count);
+    /// ```
+    ///
+    /// The same caveats as for [Alloc::scope_measured] apply. If the usecase can not be switched,
+    /// allocations are still measured.
+    pub fn with_usecase_measured(&self, use_case: U) -> MeasuredGuard<'_> {
+        let measurement = measure::Measurement::start(self.thread_state());
+        MeasuredGuard::new(measurement, self.with_usecase(use_case))
+    }
+
+    /// Like [Alloc::with_usecase], but additionally remember where the guard was created.
+    ///
+    /// Allocations made while the guard is alive are reported to the recorder together with that
+    /// source location, see [Recorder::on_callsite_alloc]. [StatsRecorder] uses this to break
+    /// down stats per (usecase, file:line), see [StatsRecorder::flush_callsites]. This is a cheap
+    /// alternative to capturing full backtraces.
+    #[track_caller]
+    pub fn with_usecase_at(&self, use_case: U) -> Option<Guard<'_>> {
+        self.with_usecase_inner(
+            use_case.into_repr(),
+            Some(Location::caller()),
+            None,
+            false,
+            0,
+        )
+    }
+
+    /// Like [Alloc::with_usecase], but additionally attribute allocations to `tag`, such as a
+    /// tenant id or an endpoint.
+    ///
+    /// Allocations made while the guard is alive are reported to the recorder together with the
+    /// tag, see [Recorder::on_tagged_alloc]. [StatsRecorder] uses this to break down stats per
+    /// (usecase, tag), see [StatsRecorder::flush_tagged].
+    ///
+    /// Unlike the usecase, the tag stays active in nested guards created without a tag, so that a
+    /// request can be tagged once while passing through multiple usecases.
+    pub fn with_usecase_tagged(&self, use_case: U, tag: Tag) -> Option<Guard<'_>> {
+        self.with_usecase_inner(use_case.into_repr(), None, Some(tag), false, 0)
+    }
+
+    pub(crate) fn with_usecase_bytes(&self, use_case: UseCaseRepr) -> Option<Guard<'_>> {
+        self.with_usecase_inner(use_case, None, None, false, 0)
+    }
+
+    /// Attribute all allocations to `use_case` until the guard is dropped, including those made
+    /// while other guards are active deeper in the call stack.
+    ///
+    /// Switching the usecase while the guard is alive, be it through [Alloc::with_usecase] or any
+    /// other function that switches usecases, has no effect. This isolates the memory used by a
+    /// call into a library from the memory of the caller, even if the library calls back into code
+    /// that switches usecases itself:
+    ///
+    /// ```ignore
+    /// let value: Config = {
+    ///     let _guard = ALLOCATOR.attribute_callee(MyUseCase::SerdeJson);
+    ///     serde_json::from_str(&input)?
+    /// };
+    /// ```
+    ///
+    /// Nested calls to this function are ignored as well, so the outermost one wins.
+    pub fn attribute_callee(&self, use_case: U) -> Option<Guard<'_>> {
+        self.with_usecase_inner(use_case.into_repr(), None, None, true, 0)
+    }
+
+    fn with_usecase_inner(
+        &self,
+        use_case: UseCaseRepr,
+        callsite: Option<Callsite>,
+        tag: Option<Tag>,
+        pin: bool,
+        split: SplitId,
+    ) -> Option<Guard<'_>> {
         self.synchronized(None, |current_value| {
+            let state = self.thread_state();
+            let old_pinned =
+                state.is_some_and(|state| state.pinned.replace(state.pinned.get() || pin));
+            // While pinned, guards keep the usecase, callsite, tag and split as they are.
+            let (use_case, callsite, tag, split) = if old_pinned {
+                (
+                    current_value.unwrap_or_else(|| U::default().into_repr()),
+                    state.and_then(|state| state.callsite.get()),
+                    None,
+                    state.map_or(0, |state| state.split.get()),
+                )
+            } else {
+                (use_case, callsite, tag, split)
+            };
             let rv = Guard {
+                use_case,
+                tag: tag.or_else(|| state.and_then(|state| state.tag.get())),
+                split,
                 old_value: current_value.take(),
+                old_callsite: state.and_then(|state| state.callsite.replace(callsite)),
+                old_tag: state.and_then(|state| match tag {
+                    Some(tag) => state.tag.replace(Some(tag)),
+                    None => state.tag.get(),
+                }),
+                old_split: state.map_or(0, |state| state.split.replace(split)),
+                old_pinned,
+                hooks: self,
+                state,
                 _unsend: PhantomData,
                 _unsync: PhantomData,
             };
-            *current_value = Some(use_case.into());
+            *current_value = Some(use_case);
+            self.on_switch(rv.old_value, *current_value);
+            self.recorder
+                .on_guard_enter(U::from_repr(use_case).unwrap_or_default());
             Ok(rv)
         })
         .ok()
     }
 
+    /// Record memory that was allocated without going through the global allocator, such as
+    /// memory-mapped files, GPU buffers or memory owned by C libraries.
+    ///
+    /// The memory is attributed to `use_case` until it is passed to
+    /// [Alloc::record_external_dealloc]. [StatsRecorder] includes it in [Stat::current] and
+    /// additionally reports it as [Stat::external].
+    pub fn record_external_alloc(&self, use_case: U, size: usize) {
+        self.synchronized(Some(size), |_| {
+            self.recorder.on_external_alloc(use_case, size);
+            self.tracked_bytes.fetch_add(size, Ordering::Relaxed);
+            Ok(())
+        })
+        .ok();
+    }
+
+    /// Record that memory previously passed to [Alloc::record_external_alloc] was freed.
+    pub fn record_external_dealloc(&self, use_case: U, size: usize) {
+        self.synchronized(Some(size), |_| {
+            self.recorder.on_external_dealloc(use_case, size);
+            self.tracked_bytes.fetch_sub(size, Ordering::Relaxed);
+            Ok(())
+        })
+        .ok();
+    }
+
+    /// Switch to the usecase and tag captured in `token`, such as on a worker thread that
+    /// continues work started on another thread.
+    ///
+    /// If no usecase was active when the token was captured, the default usecase is activated.
+    pub fn with_token(&self, token: UsecaseToken) -> Option<Guard<'_>> {
+        let use_case = token.use_case.unwrap_or_else(|| U::default().into_repr());
+        self.with_usecase_inner(use_case, None, token.tag, false, token.split)
+    }
+
+    /// Capture the usecase and tag that are active on the current thread, such that they can be
+    /// activated on another thread with [Alloc::with_token].
+    pub fn current_token(&self) -> UsecaseToken {
+        self.synchronized(None, |current_value| {
+            let state = self.thread_state();
+            Ok(UsecaseToken {
+                use_case: *current_value,
+                tag: state.and_then(|state| state.tag.get()),
+                split: state.map_or(0, |state| state.split.get()),
+            })
+        })
+        .unwrap_or_default()
+    }
+
+    /// Return the usecase that is active on the current thread, if any.
+    ///
+    /// This also returns `None` if the usecase could not be determined, for example when called
+    /// from within the allocator itself.
+    pub fn current_usecase(&self) -> Option<U> {
+        self.synchronized(None, |current_value| Ok(*current_value))
+            .ok()
+            .flatten()
+            .and_then(U::from_repr)
+    }
+
+    /// Run `f` with the given usecase active, and return its result.
+    ///
+    /// This is a shorthand for creating a guard with [Alloc::with_usecase] that is dropped right
+    /// after `f` returns.
+    pub fn scope<T>(&self, use_case: U, f: impl FnOnce() -> T) -> T {
+        let _guard = self.with_usecase(use_case);
+        f()
+    }
+
+    /// Like [Alloc::scope], but additionally return the stats of all allocations and
+    /// deallocations made by `f`.
+    ///
+    /// Only the current thread is measured, so unlike the stats of the usecase, the result is not
+    /// affected by other threads that use the same usecase at the same time. Memory freed by `f`
+    /// is counted even if it was allocated before. Nested usecases and scopes within `f` are
+    /// included.
+    pub fn scope_measured<T>(&self, use_case: U, f: impl FnOnce() -> T) -> (T, Stat) {
+        let guard = self.with_usecase_measured(use_case);
+        let rv = f();
+        (rv, guard.finish())
+    }
+
+    /// Start an [AttributionSession] for `use_case`, to measure a unit of work that is handled by
+    /// multiple threads.
+    #[cfg(feature = "std")]
+    pub fn session(&self, use_case: U) -> AttributionSession<'_, U, R, A, P> {
+        AttributionSession::new(self, use_case)
+    }
+
+    /// Run `f` and return its result, together with the stats of all allocations and
+    /// deallocations it made. The current usecase is not changed.
+    ///
+    /// This is handy for quick experiments, without defining a usecase first:
+    ///
+    /// ```ignore
+    /// let (config, stat) = ALLOCATOR.measure(|| serde_json::from_str::<Config>(input));
+    /// println!("deserializing allocated {} bytes", stat.total);
+    /// ```
+    ///
+    /// The same caveats as for [Alloc::scope_measured] apply.
+    pub fn measure<T>(&self, f: impl FnOnce() -> T) -> (T, Stat) {
+        let mut measurement = measure::Measurement::start(self.thread_state());
+        let rv = f();
+        (rv, measurement.stop())
+    }
+
+    /// Run `f` and return its result, additionally sending all allocations and deallocations the
+    /// current thread makes in the meantime to `recorder`.
+    ///
+    /// The allocator's own recorder keeps receiving all events as usual. This allows expensive
+    /// recorders to be used for a narrow scope only, for example to keep a full event log while
+    /// handling one specific request:
+    ///
+    /// ```ignore
+    /// let log = memoria::EventLogRecorder::new(memoria::StatsRecorder::new());
+    /// let response = ALLOCATOR.with_recorder_override(&log, || handle(request));
+    /// ```
+    ///
+    /// `recorder` receives [Recorder::on_alloc], [Recorder::on_alloc_layout] and
+    /// [Recorder::on_dealloc], the latter only for allocations that are tracked by the allocator's
+    /// own recorder. Its return value from [Recorder::on_alloc] is ignored. Allocations made by
+    /// `recorder` itself are not tracked. When calls are nested, only the innermost recorder
+    /// receives events.
+    pub fn with_recorder_override<T, O: Recorder<U>>(
+        &self,
+        recorder: &O,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        let recorder: &dyn Recorder<U> = recorder;
+        let recorder_ptr = &recorder as *const &dyn Recorder<U> as *const ();
+        // Removed again even if `f` panics, as the pointer would dangle afterwards.
+        let _override = self.thread_state().map(|state| {
+            utils::Restore::replace(
+                &state.recorder_override,
+                Some((self.address(), recorder_ptr)),
+            )
+        });
+        f()
+    }
+
+    fn address(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    fn with_override(&self, f: impl FnOnce(&dyn Recorder<U>)) {
+        let recorder_override = self
+            .thread_state()
+            .and_then(|state| state.recorder_override.get());
+        if let Some((alloc, recorder_ptr)) = recorder_override {
+            if alloc == self.address() {
+                // SAFETY: the pointer was installed by `with_recorder_override` on this allocator,
+                // which outlives the recorder and removes it again before returning or unwinding.
+                f(unsafe { *(recorder_ptr as *const &dyn Recorder<U>) })
+            }
+        }
+    }
+
+    /// Drop a value while the given usecase is active.
+    ///
+    /// Allocations made by `Drop` implementations are attributed to `use_case`. Memory freed by
+    /// the drop is still subtracted from the usecases that originally allocated it, but is
+    /// additionally reported to [Recorder::on_attributed_drop], such that teardown costs can be
+    /// inspected per usecase. [StatsRecorder] reports them as [Stat::freed_in_drop].
+    pub fn drop_attributed<T>(&self, use_case: U, value: T) {
+        let use_case = use_case.into_repr();
+        let _guard = self.with_usecase_bytes(use_case);
+        // Restored even if a `Drop` implementation panics, like the usecase is by the guard.
+        let _drop = self
+            .thread_state()
+            .map(|state| utils::Restore::replace(&state.drop, Some(use_case)));
+        drop(value);
+    }
+
     /// Call the given function with the given usecase.
     ///
     /// If synchronized is called from within itself (possibly indirectly through the global
     /// allocator), the function is not called in order to prevent deadlocks in other code. This
     /// can happen quite often when trying to allocate while recording an allocation.
+    ///
+    /// This runs on every allocation, so everything but the uncontended case is outlined.
+    #[inline]
     fn synchronized<R2>(
         &self,
         size: Option<usize>,
-        f: impl FnOnce(&mut Option<UseCaseBytes>) -> Result<R2, Error>,
+        f: impl FnOnce(&mut Option<UseCaseRepr>) -> Result<R2, Error>,
     ) -> Result<R2, Error> {
-        CURRENT_USECASE
-            .try_with(|value| {
-                if let Ok(mut value) = value.try_borrow_mut() {
-                    f(&mut value)
-                } else {
-                    Err(Error::CurrentUsecaseContentionRefCell)
-                }
-            })
-            .map_err(|_| Error::CurrentUsecaseContentionThreadLocal)
-            .and_then(|x| x)
-            .map_err(|e| {
-                self.recorder.on_error(e, size);
-                e
+        let local = self.thread_state().map(|state| &state.use_case);
+        let rv = sync::exclusive(local, fallback_lock(), f).unwrap_or_else(|busy| {
+            Err(match busy {
+                sync::Busy::Local => Error::CurrentUsecaseContentionRefCell,
+                sync::Busy::Fallback => Error::CurrentUsecaseContentionThreadLocal,
             })
+        });
+        if let Err(error) = &rv {
+            self.report_error(*error, size);
+        }
+        rv
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn report_error(&self, error: Error, size: Option<usize>) {
+        self.recorder.on_error(error, size);
+    }
+
+    /// Record an allocation, attributing it to `use_case` if given, or to the current usecase
+    /// otherwise.
+    ///
+    /// `charged` is the usecase returned by [Alloc::charge_cap] for this allocation. The charge
+    /// is undone if the allocation is not tracked.
+    ///
+    /// `ptr` must not be null.
+    #[inline]
+    fn handle_on_alloc(
+        &self,
+        ptr: usize,
+        layout: Layout,
+        use_case: Option<UseCaseRepr>,
+        charged: Option<UseCaseRepr>,
+    ) {
+        let tracked = self.synchronized(Some(layout.size()), |current_value| {
+            let state = self.thread_state();
+            measure::record_alloc(state, layout);
+            let use_case_bytes = use_case.or(*current_value);
+            // An explicit usecase, as used for reallocations, is never split.
+            let split = match use_case {
+                Some(_) => 0,
+                None => state.map_or(0, |state| state.split.get()),
+            };
+            let use_case = use_case_bytes.and_then(U::from_repr).unwrap_or_default();
+            if state.is_some_and(|state| state.forbid_alloc.get() > 0) {
+                self.handle_forbidden_alloc(use_case_bytes, layout.size());
+            }
+            if self.untracked_default && use_case_bytes.is_none() {
+                if self.recorder.on_alloc(use_case, layout.size()) {
+                    self.recorder.on_alloc_layout(U::default(), layout);
+                }
+                return Ok(None);
+            }
+            let filter_use_case = use_case_bytes.unwrap_or_else(|| U::default().into_repr());
+            if !self
+                .filter
+                .should_record(state, filter_use_case, layout.size())
+            {
+                return Ok(None);
+            }
+            if split::report_alloc(&self.recorder, split, filter_use_case, layout.size()) {
+                let use_case_bytes = filter_use_case;
+                self.recorder
+                    .on_alloc_layout(U::from_repr(use_case_bytes).unwrap_or_default(), layout);
+                self.with_override(|recorder| {
+                    split::report_alloc(recorder, split, use_case_bytes, layout.size());
+                    recorder
+                        .on_alloc_layout(U::from_repr(use_case_bytes).unwrap_or_default(), layout);
+                });
+                let callsite = state.and_then(|state| state.callsite.get());
+                if let Some(callsite) = callsite {
+                    self.recorder.on_callsite_alloc(
+                        U::from_repr(use_case_bytes).unwrap_or_default(),
+                        callsite,
+                        layout.size(),
+                    );
+                }
+                let tag = state.and_then(|state| state.tag.get());
+                if let Some(tag) = tag {
+                    self.recorder.on_tagged_alloc(
+                        U::from_repr(use_case_bytes).unwrap_or_default(),
+                        tag,
+                        layout.size(),
+                    );
+                }
+                let old_value = pointers::insert::<P>(
+                    ptr,
+                    TrackedPointer {
+                        use_case: use_case_bytes,
+                        size: layout.size(),
+                        callsite,
+                        tag,
+                        allocated_at: self.now(),
+                        generation: self.generation.load(Ordering::Relaxed),
+                        capped: charged == Some(use_case_bytes),
+                        thread: state.map_or(0, ThreadState::thread_index),
+                        split,
+                    },
+                );
+                if old_value.is_some() {
+                    return Err(Error::PointerTrackedTwice);
+                }
+                return Ok(Some((use_case_bytes, split)));
+            }
+            Ok(None)
+        });
+
+        if let Some(charged) = charged {
+            if !matches!(tracked, Ok(Some((use_case, _))) if use_case == charged) {
+                self.caps.release(charged, layout.size());
+            }
+        }
+
+        match tracked {
+            Ok(Some((use_case_bytes, split))) => {
+                self.tracked_bytes
+                    .fetch_add(layout.size(), Ordering::Relaxed);
+                split::for_each_share(split, use_case_bytes, layout.size(), |use_case, share| {
+                    U::from_repr(use_case).unwrap_or_default().on_alloc(share)
+                })
+            }
+            Err(Error::CurrentUsecaseContentionRefCell) => {
+                self.overhead.record_alloc(layout.size())
+            }
+            _ => {}
+        }
+    }
+
+    /// Pass a zero-sized allocation, which [GlobalAlloc] does not allow, on to the wrapped
+    /// allocator without recording it.
+    #[cold]
+    unsafe fn alloc_zero_sized(&self, layout: Layout) -> *mut u8 {
+        self.report_error(Error::ZeroSizedAlloc, Some(0));
+        self.alloc_routed(layout, None)
+    }
+
+    /// Free memory returned by [Alloc::alloc_zero_sized].
+    #[cold]
+    unsafe fn dealloc_zero_sized(&self, ptr: *mut u8, layout: Layout) {
+        self.report_error(Error::ZeroSizedAlloc, Some(0));
+        self.dealloc_routed(ptr, layout);
+    }
+
+    #[cold]
+    fn handle_forbidden_alloc(&self, use_case: Option<UseCaseRepr>, size: usize) {
+        if self.abort_on_forbidden_alloc && cfg!(debug_assertions) {
+            // Writing to stderr does not allocate.
+            #[cfg(feature = "std")]
+            {
+                std::io::stderr()
+                    .write_all(b"memoria: memory allocated while allocations are forbidden\n")
+                    .ok();
+                std::process::abort();
+            }
+            #[cfg(not(feature = "std"))]
+            panic!("memoria: memory allocated while allocations are forbidden");
+        }
+        self.recorder
+            .on_error(Error::AllocInForbiddenScope, Some(size));
+        self.recorder
+            .on_forbidden_alloc(use_case.and_then(U::from_repr).unwrap_or_default(), size);
     }
 
-    fn handle_on_alloc(&self, ptr: usize, layout: Layout) {
-        self.synchronized(Some(layout.size()), |use_case_bytes| {
-            let use_case = use_case_bytes
-                .and_then(|x| U::try_from(x).ok())
-                .unwrap_or_default();
-            if self.recorder.on_alloc(use_case, layout.size()) {
-                TRACKED_POINTERS
-                    .get_or_init(Default::default)
-                    .insert(ptr, use_case_bytes.unwrap_or_else(|| U::default().into()));
+    #[inline]
+    fn handle_on_dealloc(&self, ptr: usize, layout: Layout) {
+        let tracked = self.synchronized(Some(layout.size()), |current_value| {
+            let state = self.thread_state();
+            measure::record_dealloc(state, layout.size());
+            if let Some(drop_use_case) = state.and_then(|state| state.drop.get()) {
+                self.recorder.on_attributed_drop(
+                    U::from_repr(drop_use_case).unwrap_or_default(),
+                    layout.size(),
+                );
             }
+            match pointers::remove::<P>(ptr) {
+                Some(tracked) => {
+                    if tracked.size != layout.size() {
+                        self.report_error(Error::DeallocLayoutMismatch, Some(layout.size()));
+                    }
+                    let size = tracked.size;
+                    let current = current_value.unwrap_or_else(|| U::default().into_repr());
+                    let (attributed, split) = match self.dealloc_attribution {
+                        DeallocAttribution::Owner => (tracked.use_case, tracked.split),
+                        DeallocAttribution::Current => {
+                            (current, state.map_or(0, |state| state.split.get()))
+                        }
+                    };
+                    split::report_dealloc(&self.recorder, split, attributed, size);
+                    self.with_override(|recorder| {
+                        split::report_dealloc(recorder, split, attributed, size)
+                    });
+                    if current != tracked.use_case {
+                        self.recorder.on_transfer(
+                            U::from_repr(tracked.use_case).unwrap_or_default(),
+                            U::from_repr(current).unwrap_or_default(),
+                            size,
+                        );
+                    }
+                    if let Some(callsite) = tracked.callsite {
+                        self.recorder.on_callsite_dealloc(
+                            U::from_repr(tracked.use_case).unwrap_or_default(),
+                            callsite,
+                            size,
+                        );
+                    }
+                    if let Some(tag) = tracked.tag {
+                        self.recorder.on_tagged_dealloc(
+                            U::from_repr(tracked.use_case).unwrap_or_default(),
+                            tag,
+                            size,
+                        );
+                    }
+                    let thread = state.map_or(0, ThreadState::thread_index);
+                    if tracked.thread != thread && tracked.thread != 0 && thread != 0 {
+                        self.recorder.on_cross_thread_dealloc(
+                            U::from_repr(tracked.use_case).unwrap_or_default(),
+                            size,
+                        );
+                    }
+                    if tracked.capped {
+                        self.caps.release(tracked.use_case, size);
+                    }
+                    Ok(Some((tracked.use_case, tracked.split, size)))
+                }
+                // Memory allocated after finalizing is not tracked.
+                None if self.is_finalized() => Ok(None),
+                None if self.untracked_default => {
+                    self.recorder.on_dealloc(U::default(), layout.size());
+                    Ok(None)
+                }
+                // Memory that was not sampled is expected to be untracked.
+                None if self.filter.expects_untracked(layout.size()) => Ok(None),
+                None => Err(Error::DeallocUntrackedPointer),
+            }
+        });
+
+        match tracked {
+            Ok(Some((use_case_bytes, split, size))) => {
+                self.tracked_bytes.fetch_sub(size, Ordering::Relaxed);
+                split::for_each_share(split, use_case_bytes, size, |use_case, share| {
+                    U::from_repr(use_case).unwrap_or_default().on_dealloc(share)
+                })
+            }
+            Err(Error::CurrentUsecaseContentionRefCell) => {
+                self.overhead.record_dealloc(layout.size())
+            }
+            _ => {}
+        }
+    }
+
+    /// Walk all live tracked allocations and group them by usecase.
+    ///
+    /// If `largest` is non-zero, the report also contains up to `largest` individual allocations,
+    /// biggest first. Only allocations for which the recorder returned `true` from
+    /// [Recorder::on_alloc] are tracked.
+    ///
+    /// This is expensive and blocks allocations on other threads while it runs. It is meant to be
+    /// called at process shutdown or on demand.
+    pub fn leak_report(&self, largest: usize) -> Result<LeakReport<U>, Error> {
+        // Run under synchronized such that allocations made while building the report are not
+        // tracked, which would deadlock on the pointer map.
+        self.synchronized(None, |_| {
+            Ok(leak::build_report::<U, P>(
+                largest,
+                self.now(),
+                self.generation(),
+            ))
+        })
+    }
+
+    /// Call `f` for every live tracked allocation, for example to implement custom leak
+    /// heuristics:
+    ///
+    /// ```ignore
+    /// // with a clock that counts seconds
+    /// let mut old = HashMap::new();
+    /// ALLOCATOR.inspect_live(|allocation| {
+    ///     if allocation.age > 600 {
+    ///         *old.entry(allocation.use_case).or_insert(0) += allocation.size;
+    ///     }
+    /// })?;
+    /// ```
+    ///
+    /// The same caveats as for [Alloc::leak_report] apply. Allocations made by `f` are not
+    /// tracked.
+    pub fn inspect_live(&self, mut f: impl FnMut(LiveAllocation<U>)) -> Result<(), Error> {
+        self.synchronized(None, |_| {
+            leak::for_each_live::<U, P>(self.now(), self.generation(), &mut f);
             Ok(())
         })
-        .ok();
     }
 
-    fn handle_on_dealloc(&self, ptr: usize, layout: Layout) {
-        self.synchronized(Some(layout.size()), |_| {
-            if let Some(pointers_map) = TRACKED_POINTERS.get() {
-                if let Some((_, use_case_bytes)) = pointers_map.remove(&ptr) {
-                    self.recorder.on_dealloc(
-                        U::try_from(use_case_bytes).unwrap_or_default(),
-                        layout.size(),
-                    );
+    /// Return the live bytes and allocation counts per usecase, counting only allocations that
+    /// have survived at least `min_generations` generations, see [Alloc::next_generation].
+    ///
+    /// Memory that survives many flushes is the most actionable leak signal:
+    ///
+    /// ```ignore
+    /// for (use_case, stat) in ALLOCATOR.survivors(10)? {
+    ///     println!("{use_case:?}: {} bytes older than 10 flushes", stat.bytes);
+    /// }
+    /// ```
+    ///
+    /// The same caveats as for [Alloc::leak_report] apply.
+    pub fn survivors(&self, min_generations: u64) -> Result<Vec<(U, LiveStat)>, Error> {
+        self.synchronized(None, |_| {
+            Ok(leak::build_survivors::<U, P>(
+                self.generation(),
+                min_generations,
+            ))
+        })
+    }
+
+    /// Attribute all live tracked allocations of `use_case` to the default usecase, and return how
+    /// many bytes were moved.
+    ///
+    /// Freeing that memory later is then no longer subtracted from `use_case`. Together with
+    /// [StatsRecorder::reset], this lets a usecase that is reused for consecutive jobs start
+    /// over after each job, while leftovers of previous jobs are still accounted for:
+    ///
+    /// ```ignore
+    /// ALLOCATOR.reattribute_live(MyUseCase::Batch)?;
+    /// ALLOCATOR.with_recorder(|recorder| {
+    ///     recorder.reset(MyUseCase::Batch);
+    ///     Ok(())
+    /// })?;
+    /// ```
+    ///
+    /// Each moved allocation is reported to the recorder as freed by `use_case` and allocated by
+    /// the default usecase. Its breakdown by callsite and tag is dropped, and it no longer counts
+    /// against the cap of `use_case`. Allocations that `use_case` owns as the first usecase of
+    /// [Alloc::with_usecases] are moved entirely, and freed by all of their usecases. The same
+    /// caveats as for [Alloc::leak_report] apply.
+    pub fn reattribute_live(&self, use_case: U) -> Result<usize, Error> {
+        let from = use_case.into_repr();
+        let to = U::default().into_repr();
+        if from == to {
+            return Ok(0);
+        }
+        let (moved, unsplit, splits) = self.synchronized(None, |_| {
+            let (mut moved, mut unsplit, mut splits) = (0, 0, Vec::new());
+            pointers::for_each_mut::<P>(|tracked| {
+                if tracked.use_case != from {
+                    return;
+                }
+                split::report_dealloc(&self.recorder, tracked.split, from, tracked.size);
+                self.recorder.on_alloc(U::default(), tracked.size);
+                if tracked.capped {
+                    self.caps.release(from, tracked.size);
+                }
+                tracked.use_case = to;
+                tracked.callsite = None;
+                tracked.tag = None;
+                tracked.capped = false;
+                match core::mem::take(&mut tracked.split) {
+                    0 => unsplit += tracked.size,
+                    split => splits.push((split, tracked.size)),
                 }
+                moved += tracked.size;
+            });
+            Ok((moved, unsplit, splits))
+        })?;
+        if unsplit > 0 {
+            U::from_repr(from).unwrap_or_default().on_dealloc(unsplit);
+        }
+        for (split, size) in splits {
+            split::for_each_share(split, from, size, |use_case, share| {
+                U::from_repr(use_case).unwrap_or_default().on_dealloc(share)
+            });
+        }
+        if moved > 0 {
+            U::default().on_alloc(moved);
+        }
+        Ok(moved)
+    }
+
+    /// Return the current generation. It starts at zero, and is incremented by
+    /// [Alloc::next_generation].
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// Start a new generation of allocations. [Alloc::flush] does this automatically.
+    ///
+    /// Live allocations report how many generations they have survived in
+    /// [LiveAllocation::generations]. Applications that flush their recorder through
+    /// [Alloc::with_recorder] should call this at the same time, such that generations line up
+    /// with reporting intervals.
+    pub fn next_generation(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn now(&self) -> u64 {
+        self.age_clock.map_or(0, |clock| clock.now())
+    }
+
+    /// Discard all state inherited from the parent process. Call this in the child process right
+    /// after `fork`, before spawning any threads.
+    ///
+    /// Other threads of the parent might have been holding locks inside memoria while it forked,
+    /// which would deadlock the child. This forgets about all live allocations, caps set with
+    /// [Alloc::set_cap] and the usage counted for them and for shrinkers, and the background
+    /// threads of the parent that [Alloc::finalize] would stop, and resets the recorder through
+    /// [Recorder::on_fork]. The memory of the discarded state is leaked.
+    ///
+    /// With the `fork` feature, [fork::install](crate::fork::install) calls this automatically.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may use memoria while this runs, which is the case in the child process
+    /// right after `fork`. The locks held by the parent's threads are released without being
+    /// taken.
+    pub unsafe fn after_fork(&self) {
+        self.synchronized(None, |_| {
+            // SAFETY: guaranteed by the caller.
+            unsafe {
+                pointers::reset::<P>();
+                #[cfg(feature = "std")]
+                self.workers.reset();
             }
+            self.caps.reset();
+            self.tracked_bytes.store(0, Ordering::Relaxed);
+            self.recorder.on_fork();
             Ok(())
         })
         .ok();
     }
 
+    /// Return the number of bytes in live tracked allocations, plus the memory currently
+    /// recorded through [Alloc::record_external_alloc].
+    ///
+    /// Unlike the stats of a recorder, this is never reset. Only allocations for which the
+    /// recorder returned `true` from [Recorder::on_alloc] are counted.
+    pub fn tracked_bytes(&self) -> usize {
+        self.tracked_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Tell the recorder that the application wants a report, see [Recorder::on_flush], and start
+    /// a new generation, see [Alloc::next_generation].
+    ///
+    /// This function can fail if there is too much contention on the allocator, or if it is called
+    /// from within itself.
+    pub fn flush(&self) -> Result<(), Error> {
+        self.synchronized(None, |_| {
+            self.recorder.on_flush();
+            self.next_generation();
+            Ok(())
+        })
+    }
+
+    /// Shut memoria down at the end of the program's life, for example before returning from
+    /// `main`, instead of relying on thread-local destructors that race at exit.
+    ///
+    /// In order, this:
+    ///
+    /// 1. stops tracking: from now on, new allocations are passed straight to the wrapped
+    ///    allocator, bypassing caps, routes and the recorder. Freeing memory that was allocated
+    ///    before is still recorded.
+    /// 2. stops all threads started with [reporter::spawn] and [shrink::spawn] for this
+    ///    allocator, and waits for them to exit. Reporters flush one last time to their sink.
+    /// 3. tells the recorder to flush, see [Recorder::on_flush], and calls `sink` with it, such
+    ///    that the remaining stats can be read.
+    /// 4. returns a [leak report](Alloc::leak_report) of everything that is still alive.
+    ///
+    /// Finalizing can not be undone. Calling it again only repeats the last two steps.
+    ///
+    /// ```ignore
+    /// let leaks = ALLOCATOR.finalize(10, |recorder| {
+    ///     recorder.flush(|use_case, stat| eprintln!("{use_case:?}: {stat}"), |_, _| {});
+    /// })?;
+    /// ```
+    pub fn finalize(&self, largest: usize, sink: impl FnOnce(&R)) -> Result<LeakReport<U>, Error> {
+        self.finalized.store(true, Ordering::Relaxed);
+        #[cfg(feature = "std")]
+        self.workers.stop_all();
+        self.synchronized(None, |_| {
+            self.recorder.on_flush();
+            sink(&self.recorder);
+            Ok(())
+        })?;
+        self.leak_report(largest)
+    }
+
+    /// Whether [Alloc::finalize] was called.
+    #[inline]
+    pub fn is_finalized(&self) -> bool {
+        self.finalized.load(Ordering::Relaxed)
+    }
+
+    /// Return stats about the memory used by memoria itself, such as the table of live
+    /// allocations and the recorder's internal state.
+    ///
+    /// This counts everything allocated or freed while memoria's bookkeeping is busy, which are
+    /// the allocations reported as [Error::CurrentUsecaseContentionRefCell]. That includes
+    /// allocations made by recorders and by closures passed to [Alloc::with_recorder]. Memory
+    /// allocated there but freed elsewhere, such as the map returned by
+    /// [StatsRecorder::flush_to_map], is never subtracted again.
+    ///
+    /// Only `current`, `peak`, `total`, `count` and `max_single` are set.
+    pub fn overhead(&self) -> Stat {
+        self.overhead.get()
+    }
+
+    /// Return the wrapped allocator.
+    ///
+    /// This allows using functionality specific to the wrapped allocator, for example when
+    /// wrapping jemalloc:
+    ///
+    /// ```ignore
+    /// #[global_allocator]
+    /// static ALLOCATOR: memoria::Alloc<MyUseCase, memoria::StatsRecorder<MyUseCase>, Jemalloc> =
+    ///     memoria::Alloc::new_with(memoria::StatsRecorder::new(), Jemalloc);
+    ///
+    /// let jemalloc: &Jemalloc = ALLOCATOR.allocator();
+    /// tikv_jemalloc_ctl::epoch::advance()?;
+    /// ```
+    ///
+    /// With the `jemalloc` feature, `memoria::jemalloc` reads jemalloc's own stats, and
+    /// [reporter] includes them in every report.
+    ///
+    /// Allocations made directly through the returned allocator bypass memoria entirely.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
     /// Try to grab the current recorder such that statistics can be read and reset. Call the
     /// closure with the recorder if successful.
     ///
@@ -161,15 +1459,37 @@ impl<R: Recorder<U>, U: UseCase, A: GlobalAlloc> Alloc<U, R, A> {
     }
 }
 
-unsafe impl<R: Recorder<U>, U: UseCase, A: GlobalAlloc> GlobalAlloc for Alloc<U, R, A> {
+unsafe impl<R: Recorder<U>, U: UseCase, A: GlobalAlloc, P: PointerTable> GlobalAlloc
+    for Alloc<U, R, A, P>
+{
+    #[inline]
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let ptr = self.alloc.alloc(layout);
-        self.handle_on_alloc(ptr as usize, layout);
+        if self.is_finalized() {
+            return self.alloc.alloc(layout);
+        }
+        if layout.size() == 0 {
+            return self.alloc_zero_sized(layout);
+        }
+        let Ok(charged) = self.charge_cap(layout.size(), None) else {
+            return core::ptr::null_mut();
+        };
+        let ptr = self.alloc_routed(layout, None);
+        if ptr.is_null() {
+            if let Some(charged) = charged {
+                self.caps.release(charged, layout.size());
+            }
+            return ptr;
+        }
+        self.handle_on_alloc(ptr as usize, layout, None, charged);
         ptr
     }
 
+    #[inline]
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if layout.size() == 0 {
+            return self.dealloc_zero_sized(ptr, layout);
+        }
         self.handle_on_dealloc(ptr as usize, layout);
-        self.alloc.dealloc(ptr, layout);
+        self.dealloc_routed(ptr, layout);
     }
 }
@@ -0,0 +1,255 @@
+use std::alloc::Layout;
+use std::fmt;
+use std::io::{Cursor, Write};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::clock::DEFAULT_CLOCK;
+use crate::export::{Bytes, Label};
+use crate::{Callsite, Clock, Error, OverheadEstimate, Recorder, StatsRecorder, Tag, UseCase};
+
+/// The value of `LogRecorder::last_logged` before the stats were logged for the first time.
+const NEVER: u64 = u64::MAX;
+
+/// The size of the buffer each log line is rendered into. Longer lines are truncated.
+const LINE_LEN: usize = 256;
+
+/// A recorder that writes the stats of a [StatsRecorder] to the `log` crate on every
+/// [Alloc::flush](crate::Alloc::flush), for applications without metrics infrastructure.
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::Alloc<MyUseCase, memoria::LogRecorder<MyUseCase>> =
+///     memoria::Alloc::new_with(
+///         memoria::LogRecorder::new(memoria::StatsRecorder::new())
+///             .with_min_interval(Duration::from_secs(300))
+///             .with_warn_above(1 << 30),
+///         std::alloc::System,
+///     );
+/// ```
+///
+/// Each usecase gets an `info` line with target `memoria`, such as
+/// `Parse: current 1.5 MiB, peak 2.0 MiB, total 8.3 MiB`. Allocations refused by
+/// [Alloc::set_cap](crate::Alloc::set_cap) and usecases above the threshold set with
+/// [LogRecorder::with_warn_above] are logged as warnings.
+///
+/// Flushes more frequent than the minimum interval, a minute by default, are ignored. Lines are
+/// rendered into buffers on the stack, so only the logger itself might allocate.
+pub struct LogRecorder<U: UseCase> {
+    inner: StatsRecorder<U>,
+    min_interval: Duration,
+    warn_above: Option<usize>,
+    clock: &'static dyn Clock,
+    // When the stats were last logged, according to `clock`.
+    last_logged: AtomicU64,
+    // The number of `Error::CapExceeded` when the stats were last logged.
+    caps_exceeded: AtomicUsize,
+}
+
+impl<U: UseCase> LogRecorder<U> {
+    /// Construct a new recorder.
+    pub const fn new(inner: StatsRecorder<U>) -> Self {
+        LogRecorder {
+            inner,
+            min_interval: Duration::from_secs(60),
+            warn_above: None,
+            clock: &DEFAULT_CLOCK,
+            last_logged: AtomicU64::new(NEVER),
+            caps_exceeded: AtomicUsize::new(0),
+        }
+    }
+
+    /// Ignore flushes that happen less than `interval` after the stats were last logged.
+    pub const fn with_min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// Log a warning for each usecase whose current memory is above `bytes` when flushing.
+    pub const fn with_warn_above(mut self, bytes: usize) -> Self {
+        self.warn_above = Some(bytes);
+        self
+    }
+
+    /// Measure the minimum interval with `clock` instead of an [InstantClock](crate::InstantClock).
+    pub const fn with_clock(mut self, clock: &'static dyn Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Access the wrapped recorder.
+    pub fn inner(&self) -> &StatsRecorder<U> {
+        &self.inner
+    }
+
+    /// Whether enough time has passed since the stats were last logged. If so, the current time
+    /// is stored.
+    fn acquire_slot(&self) -> bool {
+        let now = self.clock.now();
+        let last = self.last_logged.load(Ordering::Relaxed);
+        if last != NEVER && self.clock.elapsed(last, now) < self.min_interval {
+            return false;
+        }
+        self.last_logged
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+}
+
+impl<U: UseCase + fmt::Debug> LogRecorder<U> {
+    fn log_stats(&self) {
+        self.inner.peek(|use_case, stat| {
+            if stat.current == 0 && stat.total == 0 {
+                return;
+            }
+            let label = Label(&use_case);
+            log_line(log::Level::Info, |line| {
+                write!(
+                    line,
+                    "{label}: current {}, peak {}, total {}",
+                    Bytes(stat.current),
+                    Bytes(stat.peak),
+                    Bytes(stat.total)
+                )
+            });
+            if let Some(limit) = self.warn_above {
+                if stat.current > 0 && stat.current as usize > limit {
+                    log_line(log::Level::Warn, |line| {
+                        write!(
+                            line,
+                            "{label}: current {} is above {}",
+                            Bytes(stat.current),
+                            Bytes(limit as isize)
+                        )
+                    });
+                }
+            }
+        });
+
+        let count = self.inner.get_error(Error::CapExceeded);
+        let previous = self.caps_exceeded.swap(count, Ordering::Relaxed);
+        // The count starts over when the errors of the inner recorder are flushed.
+        let refused = if count >= previous {
+            count - previous
+        } else {
+            count
+        };
+        if refused > 0 {
+            log_line(log::Level::Warn, |line| {
+                write!(line, "{refused} allocations were refused by caps")
+            });
+        }
+    }
+}
+
+/// Render a line with `f` into a buffer on the stack, and log it.
+fn log_line(level: log::Level, f: impl FnOnce(&mut Cursor<&mut [u8]>) -> std::io::Result<()>) {
+    let mut buf = [0u8; LINE_LEN];
+    let mut cursor = Cursor::new(&mut buf[..]);
+    // A write error means the buffer is full, in which case we log what we have.
+    f(&mut cursor).ok();
+    let len = cursor.position() as usize;
+    let line = match std::str::from_utf8(&buf[..len]) {
+        Ok(line) => line,
+        // Truncation can split a character.
+        Err(error) => std::str::from_utf8(&buf[..error.valid_up_to()]).unwrap_or_default(),
+    };
+    log::log!(target: "memoria", level, "{line}");
+}
+
+unsafe impl<U: UseCase + fmt::Debug> Recorder<U> for LogRecorder<U> {
+    fn on_alloc(&self, use_case: U, size: usize) -> bool {
+        self.inner.on_alloc(use_case, size)
+    }
+
+    fn on_alloc_layout(&self, use_case: U, layout: Layout) {
+        self.inner.on_alloc_layout(use_case, layout)
+    }
+
+    fn on_shared_alloc(&self, use_case: U, size: usize) -> bool {
+        self.inner.on_shared_alloc(use_case, size)
+    }
+
+    fn on_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_dealloc(use_case, size)
+    }
+
+    fn on_shared_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_shared_dealloc(use_case, size)
+    }
+
+    fn on_attributed_drop(&self, use_case: U, size: usize) {
+        self.inner.on_attributed_drop(use_case, size)
+    }
+
+    fn on_transfer(&self, from: U, to: U, size: usize) {
+        self.inner.on_transfer(from, to, size)
+    }
+
+    fn on_cross_thread_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_cross_thread_dealloc(use_case, size)
+    }
+
+    fn on_external_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_alloc(use_case, size)
+    }
+
+    fn on_external_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_dealloc(use_case, size)
+    }
+
+    fn on_callsite_alloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_alloc(use_case, callsite, size)
+    }
+
+    fn on_callsite_dealloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_dealloc(use_case, callsite, size)
+    }
+
+    fn on_tagged_alloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_alloc(use_case, tag, size)
+    }
+
+    fn on_tagged_dealloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_dealloc(use_case, tag, size)
+    }
+
+    fn on_forbidden_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_forbidden_alloc(use_case, size)
+    }
+
+    fn on_usecase_enter(&self, use_case: U) {
+        self.inner.on_usecase_enter(use_case)
+    }
+
+    fn on_usecase_exit(&self, use_case: U) {
+        self.inner.on_usecase_exit(use_case)
+    }
+
+    fn on_guard_enter(&self, use_case: U) {
+        self.inner.on_guard_enter(use_case)
+    }
+
+    fn on_guard_exit(&self, use_case: U) {
+        self.inner.on_guard_exit(use_case)
+    }
+
+    fn on_flush(&self) {
+        if self.acquire_slot() {
+            self.log_stats();
+        }
+        self.inner.on_flush()
+    }
+
+    fn on_overhead_estimate(&self, estimate: OverheadEstimate) {
+        self.inner.on_overhead_estimate(estimate)
+    }
+
+    fn on_fork(&self) {
+        self.inner.on_fork()
+    }
+
+    fn on_error(&self, code: Error, size: Option<usize>) {
+        self.inner.on_error(code, size)
+    }
+}
@@ -0,0 +1,176 @@
+/// Define a [UseCase](crate::UseCase) enum without depending on `num_enum`.
+///
+/// The variant marked with `default` must come first and becomes the [Default]. Variants can
+/// carry doc comments, attributes and explicit discriminants. The enum is `#[repr(u32)]` and
+/// derives `Clone`, `Copy`, `Debug`, `PartialEq`, `Eq`, `PartialOrd`, `Ord` and `Hash`.
+///
+/// Besides the [UseCase](crate::UseCase) impl, this generates `name()` returning the name of a
+/// variant, and `all_variants()` returning all variants in declaration order. `name()` is also
+/// used as [UseCase::name](crate::UseCase::name).
+///
+/// ```
+/// memoria::usecase! {
+///     /// What the application is doing.
+///     pub enum MyUseCase {
+///         default None,
+///         /// Reading the configuration file.
+///         LoadConfig,
+///         ProcessData = 10,
+///     }
+/// }
+///
+/// assert_eq!(MyUseCase::default(), MyUseCase::None);
+/// assert_eq!(u32::from(MyUseCase::ProcessData), 10);
+/// assert_eq!(MyUseCase::try_from(10), Ok(MyUseCase::ProcessData));
+/// assert_eq!(MyUseCase::LoadConfig.name(), "LoadConfig");
+/// assert_eq!(MyUseCase::all_variants().len(), 3);
+///
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::Alloc<MyUseCase> = memoria::new!();
+/// ```
+///
+/// To override methods of the [UseCase](crate::UseCase) trait, follow the enum with an `impl`
+/// block, whose contents are copied into the generated impl:
+///
+/// ```ignore
+/// memoria::usecase! {
+///     enum MyUseCase {
+///         default None,
+///         Parse,
+///     }
+///
+///     impl memoria::UseCase for MyUseCase {
+///         // ...
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! usecase {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $(#[$default_meta:meta])*
+            default $default:ident $(= $default_discriminant:expr)?
+            $(
+                ,
+                $(#[$variant_meta:meta])*
+                $variant:ident $(= $discriminant:expr)?
+            )*
+            $(,)?
+        }
+
+        $(
+            impl $($trait:ident)::+ for $impl_name:ident {
+                $($body:tt)*
+            }
+        )?
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[repr(u32)]
+        $vis enum $name {
+            $(#[$default_meta])*
+            $default $(= $default_discriminant)?,
+            $(
+                $(#[$variant_meta])*
+                $variant $(= $discriminant)?,
+            )*
+        }
+
+        impl ::core::default::Default for $name {
+            fn default() -> Self {
+                $name::$default
+            }
+        }
+
+        impl ::core::convert::TryFrom<$crate::UseCaseBytes> for $name {
+            type Error = $crate::UseCaseBytes;
+
+            fn try_from(
+                value: $crate::UseCaseBytes,
+            ) -> ::core::result::Result<Self, Self::Error> {
+                for &variant in $name::all_variants() {
+                    if value == variant as $crate::UseCaseBytes {
+                        return ::core::result::Result::Ok(variant);
+                    }
+                }
+                ::core::result::Result::Err(value)
+            }
+        }
+
+        impl ::core::convert::From<$name> for $crate::UseCaseBytes {
+            fn from(use_case: $name) -> Self {
+                use_case as $crate::UseCaseBytes
+            }
+        }
+
+        impl $name {
+            /// The name of this variant.
+            #[allow(dead_code)]
+            $vis fn name(&self) -> &'static str {
+                match self {
+                    $name::$default => ::core::stringify!($default),
+                    $($name::$variant => ::core::stringify!($variant),)*
+                }
+            }
+
+            /// All variants, in declaration order.
+            $vis fn all_variants() -> &'static [Self] {
+                &[$name::$default, $($name::$variant),*]
+            }
+        }
+
+        impl $crate::UseCase for $name {
+            fn name(&self) -> &'static str {
+                $name::name(self)
+            }
+
+            $($($body)*)?
+        }
+    };
+}
+
+/// Construct an [Alloc](crate::Alloc) in a `const` context, such as a `#[global_allocator]`
+/// static.
+///
+/// `memoria::new!()` is equivalent to [Alloc::new](crate::Alloc::new).
+///
+/// ```
+/// # memoria::usecase! { enum MyUseCase { default None } }
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::Alloc<MyUseCase> = memoria::new!();
+/// ```
+///
+/// Options are given as a list of `name: value` pairs, or just `name` for options without a
+/// value. Each option calls the method of the same name on [AllocBuilder](crate::AllocBuilder),
+/// so all of its options are supported, in any order. The type parameters of the static decide
+/// the usecase and the [pointer table](crate::PointerTable):
+///
+/// ```
+/// # memoria::usecase! { enum MyUseCase { default None } }
+/// use std::alloc::System;
+///
+/// use memoria::{Alloc, DeallocAttribution, NoopRecorder, ShardedTable};
+///
+/// #[global_allocator]
+/// static ALLOCATOR: Alloc<MyUseCase, NoopRecorder<MyUseCase>, System, ShardedTable> = memoria::new! {
+///     recorder: NoopRecorder::new(),
+///     allocator: System,
+///     sample_rate: 16,
+///     min_size: 64,
+///     dealloc_attribution: DeallocAttribution::Current,
+///     untracked_default,
+///     pointer_table,
+/// };
+/// ```
+#[macro_export]
+macro_rules! new {
+    () => {
+        $crate::Alloc::new()
+    };
+    ($($option:ident $(: $value:expr)?),+ $(,)?) => {
+        $crate::Alloc::builder()
+            $(.$option($($value)?))+
+            .build()
+    };
+}
@@ -0,0 +1,91 @@
+use core::alloc::Layout;
+
+use crate::{Guard, Stat, ThreadState};
+
+pub(crate) fn record_alloc(state: Option<&ThreadState>, layout: Layout) {
+    if let Some(measurement) = state.map(|state| &state.measurement) {
+        if let Some(mut stat) = measurement.get() {
+            stat.record(layout.size() as isize);
+            stat.record_layout(layout);
+            measurement.set(Some(stat));
+        }
+    }
+}
+
+pub(crate) fn record_dealloc(state: Option<&ThreadState>, size: usize) {
+    if let Some(measurement) = state.map(|state| &state.measurement) {
+        if let Some(mut stat) = measurement.get() {
+            stat.record(-(size as isize));
+            measurement.set(Some(stat));
+        }
+    }
+}
+
+/// Measures the allocations made by the current thread while it is alive, regardless of which
+/// usecase they are attributed to.
+///
+/// Measurements can be nested, in which case the outer one includes everything measured by the
+/// inner one.
+pub(crate) struct Measurement<'a> {
+    state: Option<&'a ThreadState>,
+    outer: Option<Stat>,
+    running: bool,
+}
+
+impl<'a> Measurement<'a> {
+    pub(crate) fn start(state: Option<&'a ThreadState>) -> Self {
+        let outer = state.and_then(|state| state.measurement.replace(Some(Stat::ZERO)));
+        Measurement {
+            state,
+            outer,
+            running: true,
+        }
+    }
+
+    /// Stop measuring and return what was measured.
+    pub(crate) fn stop(&mut self) -> Stat {
+        if !core::mem::take(&mut self.running) {
+            return Stat::ZERO;
+        }
+        let Some(state) = self.state else {
+            return Stat::ZERO;
+        };
+        let inner = state.measurement.get().unwrap_or_default();
+        state.measurement.set(self.outer.map(|mut outer| {
+            outer.merge(&inner);
+            outer
+        }));
+        inner
+    }
+}
+
+impl Drop for Measurement<'_> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// A [Guard] that additionally measures the allocations and deallocations made by the current
+/// thread while it is alive.
+///
+/// Returned by [Alloc::with_usecase_measured](crate::Alloc::with_usecase_measured).
+#[must_use = "the usecase is only active while the guard is alive"]
+pub struct MeasuredGuard<'a> {
+    // Dropped before the measurement, such that allocations made by the recorder's hooks while
+    // switching back are not measured.
+    guard: Option<Guard<'a>>,
+    measurement: Measurement<'a>,
+}
+
+impl<'a> MeasuredGuard<'a> {
+    pub(crate) fn new(measurement: Measurement<'a>, guard: Option<Guard<'a>>) -> Self {
+        MeasuredGuard { guard, measurement }
+    }
+
+    /// Switch back to the previous usecase, and return the stats of everything that was
+    /// allocated and deallocated on the current thread while the guard was alive.
+    pub fn finish(mut self) -> Stat {
+        drop(self.guard.take());
+        self.measurement.stop()
+    }
+}
@@ -0,0 +1,100 @@
+//! Attribute out-of-memory aborts to usecases.
+//!
+//! When an allocation fails, Rust calls the alloc error hook and then aborts the process. With
+//! [install_hook], memoria prints the usecase that was active when the allocation failed, together
+//! with the per-usecase stats of the last [StatsRecorder::flush](crate::StatsRecorder::flush), to
+//! stderr. Usecases are printed by their [UseCase::name], or their `Debug` representation if the
+//! name is empty:
+//!
+//! ```text
+//! memory allocation of 1073741824 bytes failed in usecase Render
+//! memoria: stats at last flush:
+//!   usecase None: current: 5400, peak: 5400, total: 5400
+//!   usecase Render: current: 8100, peak: 8100, total: 8100
+//! ```
+//!
+//! Nothing in this module allocates while the hook runs. The stats of the last flush are copied
+//! into a fixed-size static buffer, which holds at most [MAX_USECASES] usecases.
+//!
+//! This requires a nightly compiler and the `alloc-error-hook` feature.
+
+use std::alloc::Layout;
+use std::fmt;
+use std::io::{Cursor, Write};
+use std::sync::Mutex;
+
+use crate::export::Label;
+use crate::{store, Stat, UseCase, UseCaseRepr};
+
+/// The maximum number of usecases remembered from the last flush.
+pub const MAX_USECASES: usize = 64;
+
+struct LastFlush {
+    len: usize,
+    stats: [(UseCaseRepr, Stat); MAX_USECASES],
+}
+
+static LAST_FLUSH: Mutex<LastFlush> = Mutex::new(LastFlush {
+    len: 0,
+    stats: [(0, Stat::ZERO); MAX_USECASES],
+});
+
+/// Register memoria's handler with `std::alloc::set_alloc_error_hook`.
+///
+/// This replaces the default hook, whose output memoria's handler reproduces. `U` is the usecase
+/// type of the allocator, used for printing the names of usecases.
+pub fn install_hook<U: UseCase + fmt::Debug>() {
+    std::alloc::set_alloc_error_hook(hook::<U>);
+}
+
+/// Called at the start of a flush to forget about the previous one.
+pub(crate) fn clear_last_flush() {
+    if let Ok(mut last_flush) = LAST_FLUSH.try_lock() {
+        last_flush.len = 0;
+    }
+}
+
+/// Called for every stat that is being flushed. Stats beyond [MAX_USECASES] are discarded.
+pub(crate) fn remember_flushed(use_case: UseCaseRepr, stat: Stat) {
+    if let Ok(mut last_flush) = LAST_FLUSH.try_lock() {
+        let len = last_flush.len;
+        if len < MAX_USECASES {
+            last_flush.stats[len] = (use_case, stat);
+            last_flush.len += 1;
+        }
+    }
+}
+
+fn hook<U: UseCase + fmt::Debug>(layout: Layout) {
+    let mut buf = [0u8; 4096];
+    let mut cursor = Cursor::new(&mut buf[..]);
+    let current_usecase = store::default_state()
+        .and_then(|state| state.use_case.try_borrow().ok().map(|value| *value))
+        .flatten();
+
+    // Any write error means the buffer is full, in which case we print what we have.
+    write!(
+        cursor,
+        "memory allocation of {} bytes failed",
+        layout.size()
+    )
+    .ok();
+    match current_usecase {
+        Some(use_case) => {
+            let use_case = U::from_repr(use_case).unwrap_or_default();
+            writeln!(cursor, " in usecase {}", Label(&use_case)).ok()
+        }
+        None => writeln!(cursor, " outside of any usecase").ok(),
+    };
+
+    if let Ok(last_flush) = LAST_FLUSH.try_lock() {
+        writeln!(cursor, "memoria: stats at last flush:").ok();
+        for (use_case, stat) in &last_flush.stats[..last_flush.len] {
+            let use_case = U::from_repr(*use_case).unwrap_or_default();
+            writeln!(cursor, "  usecase {}: {stat}", Label(&use_case)).ok();
+        }
+    }
+
+    let len = cursor.position() as usize;
+    std::io::stderr().write_all(&buf[..len]).ok();
+}
@@ -0,0 +1,75 @@
+use core::sync::atomic::{AtomicIsize, Ordering};
+
+use crate::Stat;
+
+/// Memory allocated by memoria itself, see [Alloc::overhead](crate::Alloc::overhead).
+///
+/// This is recorded from within the allocator while memoria's bookkeeping is busy, so it can
+/// only use atomics.
+pub(crate) struct Overhead {
+    current: AtomicIsize,
+    peak: AtomicIsize,
+    total: AtomicIsize,
+    count: AtomicIsize,
+    max_single: AtomicIsize,
+}
+
+impl Overhead {
+    pub(crate) const fn new() -> Self {
+        Overhead {
+            current: AtomicIsize::new(0),
+            peak: AtomicIsize::new(0),
+            total: AtomicIsize::new(0),
+            count: AtomicIsize::new(0),
+            max_single: AtomicIsize::new(0),
+        }
+    }
+
+    #[cold]
+    pub(crate) fn record_alloc(&self, size: usize) {
+        let size = size as isize;
+        let current = self.current.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak.fetch_max(current, Ordering::Relaxed);
+        self.total.fetch_add(size, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.max_single.fetch_max(size, Ordering::Relaxed);
+    }
+
+    #[cold]
+    pub(crate) fn record_dealloc(&self, size: usize) {
+        self.current.fetch_sub(size as isize, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get(&self) -> Stat {
+        Stat {
+            current: self.current.load(Ordering::Relaxed),
+            peak: self.peak.load(Ordering::Relaxed),
+            total: self.total.load(Ordering::Relaxed),
+            count: self.count.load(Ordering::Relaxed),
+            max_single: self.max_single.load(Ordering::Relaxed),
+            ..Stat::ZERO
+        }
+    }
+}
+
+/// The time memoria adds to allocations, as measured by
+/// [overhead_selftest](crate::overhead_selftest).
+///
+/// All times are in nanoseconds. They depend on the machine, the wrapped allocator and how busy
+/// the process is, so they are only an estimate.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OverheadEstimate {
+    /// The time of an allocation and deallocation through the wrapped allocator alone.
+    pub baseline_ns: f64,
+    /// The time of an allocation and deallocation through memoria while a guard is active.
+    pub tracked_ns: f64,
+    /// The time of creating and dropping a guard.
+    pub guard_ns: f64,
+}
+
+impl OverheadEstimate {
+    /// The time memoria adds to an allocation and deallocation.
+    pub fn per_alloc_ns(&self) -> f64 {
+        (self.tracked_ns - self.baseline_ns).max(0.0)
+    }
+}
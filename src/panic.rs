@@ -0,0 +1,114 @@
+use std::alloc::GlobalAlloc;
+use std::fmt;
+use std::io::{Cursor, Write};
+
+use crate::export::{Bytes, Label};
+use crate::{Alloc, PointerTable, Stat, StatsRecorder, UseCase, UseCaseRepr};
+
+/// The number of usecases listed by the hook installed with [install_panic_hook].
+const TOP_USECASES: usize = 10;
+
+/// Install a panic hook that prints the usecases using the most memory to stderr, after the
+/// output of the previously installed hook:
+///
+/// ```text
+/// thread 'main' panicked at src/main.rs:10:5:
+/// index out of bounds: the len is 3 but the index is 3
+/// note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+/// memoria: memory at panic, by current bytes:
+///   Parse: current 1.5 MiB, peak 2.0 MiB, total 8.3 MiB
+///   Render: current 12.0 KiB, peak 1.0 MiB, total 4.1 MiB
+/// ```
+///
+/// At most ten usecases are listed. The output is rendered into a buffer on the stack, but
+/// reading the current stats with [StatsRecorder::peek] makes a few small allocations, which are
+/// not recorded. If the allocator is out of memory at that point, the process aborts instead of
+/// printing the summary. The `oom` module of the `alloc-error-hook` feature prints a summary
+/// without allocating when an allocation fails.
+///
+/// If the panic happened while memoria's bookkeeping was busy on the panicking thread, for
+/// example within [Alloc::with_recorder], the stats are not available and only a note is printed.
+pub fn install_panic_hook<U, A, P>(alloc: &'static Alloc<U, StatsRecorder<U>, A, P>)
+where
+    U: UseCase + fmt::Debug + Sync,
+    A: GlobalAlloc + Sync,
+    P: PointerTable,
+{
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        print_summary(alloc);
+    }));
+}
+
+fn print_summary<U: UseCase + fmt::Debug, A: GlobalAlloc, P: PointerTable>(
+    alloc: &Alloc<U, StatsRecorder<U>, A, P>,
+) {
+    let mut top = [(0, Stat::ZERO); TOP_USECASES];
+    let mut len = 0;
+    let mut omitted = 0;
+    let result = alloc.with_recorder(|recorder| {
+        recorder.peek(|use_case, stat| {
+            insert_sorted(
+                &mut top,
+                &mut len,
+                (use_case.into_repr(), stat),
+                &mut omitted,
+            )
+        });
+        Ok(())
+    });
+
+    let mut buf = [0u8; 4096];
+    let mut cursor = Cursor::new(&mut buf[..]);
+    // Any write error means the buffer is full, in which case we print what we have.
+    match result {
+        Ok(()) => {
+            writeln!(cursor, "memoria: memory at panic, by current bytes:").ok();
+            for (use_case, stat) in &top[..len] {
+                writeln!(
+                    cursor,
+                    "  {}: current {}, peak {}, total {}",
+                    Label(&U::from_repr(*use_case).unwrap_or_default()),
+                    Bytes(stat.current),
+                    Bytes(stat.peak),
+                    Bytes(stat.total)
+                )
+                .ok();
+            }
+            if omitted > 0 {
+                writeln!(cursor, "  and {omitted} more usecases").ok();
+            }
+        }
+        Err(error) => {
+            writeln!(cursor, "memoria: memory at panic not available: {error:?}").ok();
+        }
+    }
+
+    let len = cursor.position() as usize;
+    std::io::stderr().write_all(&buf[..len]).ok();
+}
+
+/// Insert `entry` into the first `len` elements of `top`, which are sorted by current bytes,
+/// largest first. Entries that do not fit are counted in `omitted`.
+fn insert_sorted(
+    top: &mut [(UseCaseRepr, Stat)],
+    len: &mut usize,
+    entry: (UseCaseRepr, Stat),
+    omitted: &mut usize,
+) {
+    let index = top[..*len]
+        .iter()
+        .position(|(_, stat)| stat.current < entry.1.current)
+        .unwrap_or(*len);
+    if *len == top.len() {
+        *omitted += 1;
+        if index == top.len() {
+            return;
+        }
+    } else {
+        *len += 1;
+    }
+    top[index..*len].rotate_right(1);
+    top[index] = entry;
+}
@@ -0,0 +1,203 @@
+//! The table of live tracked allocations.
+//!
+//! All functions are called while memoria's bookkeeping is busy, such that allocations made
+//! while growing the table are not tracked in it.
+
+use crate::{IntPointer, TrackedPointer};
+
+mod sharded;
+
+#[cfg(not(feature = "std"))]
+use sharded as imp;
+
+/// Selects how an [Alloc](crate::Alloc) stores its live tracked allocations, through its last
+/// type parameter. Use [AllocBuilder::pointer_table](crate::AllocBuilder::pointer_table) to
+/// construct an allocator with a table other than [DefaultTable].
+///
+/// All instances of [Alloc](crate::Alloc) using the same table share it. This trait is sealed,
+/// the available tables are [DefaultTable] and [ShardedTable].
+pub trait PointerTable: sealed::Sealed + Send + Sync + 'static {}
+
+mod sealed {
+    pub trait Sealed {
+        const SHARDED: bool;
+    }
+}
+
+/// The table used unless another one is selected, backed by a `DashMap`.
+///
+/// In builds with the `memoria_single_threaded` cfg, this is a plain `HashMap` instead. Without the
+/// `std` feature, it is the same table as [ShardedTable].
+pub enum DefaultTable {}
+
+impl sealed::Sealed for DefaultTable {
+    const SHARDED: bool = false;
+}
+
+impl PointerTable for DefaultTable {}
+
+/// A purpose-built concurrent hash table keyed by pointer.
+///
+/// Pointers are spread over a fixed number of shards, each of which is a flat open-addressing
+/// table behind its own spin lock. Compared to [DefaultTable], entries are stored inline without
+/// per-entry bookkeeping, and looking up a pointer touches a single cache line in the common
+/// case.
+///
+/// ```
+/// # memoria::usecase! { enum MyUseCase { default None } }
+/// use std::alloc::System;
+///
+/// use memoria::{Alloc, ShardedTable, StatsRecorder};
+///
+/// #[global_allocator]
+/// static ALLOCATOR: Alloc<MyUseCase, StatsRecorder<MyUseCase>, System, ShardedTable> =
+///     Alloc::builder().pointer_table().build();
+/// ```
+pub enum ShardedTable {}
+
+impl sealed::Sealed for ShardedTable {
+    const SHARDED: bool = true;
+}
+
+impl PointerTable for ShardedTable {}
+
+#[cfg(all(feature = "std", not(memoria_single_threaded)))]
+mod imp {
+    use dashmap::DashMap;
+
+    use super::{IntPointer, TrackedPointer};
+    use crate::sync::ResettableCell;
+
+    static TRACKED_POINTERS: ResettableCell<DashMap<IntPointer, TrackedPointer>> =
+        ResettableCell::new();
+
+    pub(crate) fn insert(ptr: IntPointer, tracked: TrackedPointer) -> Option<TrackedPointer> {
+        TRACKED_POINTERS
+            .get_or_init(Default::default)
+            .insert(ptr, tracked)
+    }
+
+    pub(crate) fn remove(ptr: IntPointer) -> Option<TrackedPointer> {
+        TRACKED_POINTERS
+            .get()
+            .and_then(|pointers_map| pointers_map.remove(&ptr))
+            .map(|(_, tracked)| tracked)
+    }
+
+    pub(crate) unsafe fn reset() {
+        TRACKED_POINTERS.reset();
+    }
+
+    pub(crate) fn for_each(mut f: impl FnMut(IntPointer, TrackedPointer)) {
+        if let Some(pointers_map) = TRACKED_POINTERS.get() {
+            for kv in pointers_map.iter() {
+                f(*kv.key(), *kv.value());
+            }
+        }
+    }
+
+    pub(crate) fn for_each_mut(mut f: impl FnMut(&mut TrackedPointer)) {
+        if let Some(pointers_map) = TRACKED_POINTERS.get() {
+            for mut kv in pointers_map.iter_mut() {
+                f(kv.value_mut());
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "std", memoria_single_threaded))]
+mod imp {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    use super::{IntPointer, TrackedPointer};
+    use crate::utils::Local;
+
+    static TRACKED_POINTERS: Local<RefCell<Option<HashMap<IntPointer, TrackedPointer>>>> =
+        Local::new(RefCell::new(None));
+
+    fn with_map<R>(f: impl FnOnce(&mut HashMap<IntPointer, TrackedPointer>) -> R) -> Option<R> {
+        TRACKED_POINTERS
+            .try_with(|pointers_map| {
+                let mut pointers_map = pointers_map.try_borrow_mut().ok()?;
+                Some(f(pointers_map.get_or_insert_with(HashMap::new)))
+            })
+            .ok()
+            .flatten()
+    }
+
+    pub(crate) fn insert(ptr: IntPointer, tracked: TrackedPointer) -> Option<TrackedPointer> {
+        with_map(|pointers_map| pointers_map.insert(ptr, tracked)).flatten()
+    }
+
+    pub(crate) fn remove(ptr: IntPointer) -> Option<TrackedPointer> {
+        with_map(|pointers_map| pointers_map.remove(&ptr)).flatten()
+    }
+
+    pub(crate) unsafe fn reset() {
+        with_map(|pointers_map| pointers_map.clear());
+    }
+
+    pub(crate) fn for_each(mut f: impl FnMut(IntPointer, TrackedPointer)) {
+        with_map(|pointers_map| {
+            for (&ptr, &tracked) in pointers_map.iter() {
+                f(ptr, tracked);
+            }
+        });
+    }
+
+    pub(crate) fn for_each_mut(f: impl FnMut(&mut TrackedPointer)) {
+        with_map(|pointers_map| pointers_map.values_mut().for_each(f));
+    }
+}
+
+pub(crate) fn insert<P: PointerTable>(
+    ptr: IntPointer,
+    tracked: TrackedPointer,
+) -> Option<TrackedPointer> {
+    if P::SHARDED {
+        sharded::insert(ptr, tracked)
+    } else {
+        imp::insert(ptr, tracked)
+    }
+}
+
+pub(crate) fn remove<P: PointerTable>(ptr: IntPointer) -> Option<TrackedPointer> {
+    if P::SHARDED {
+        sharded::remove(ptr)
+    } else {
+        imp::remove(ptr)
+    }
+}
+
+/// Forget all tracked pointers.
+///
+/// # Safety
+///
+/// See [Alloc::after_fork](crate::Alloc::after_fork).
+pub(crate) unsafe fn reset<P: PointerTable>() {
+    // SAFETY: guaranteed by the caller.
+    unsafe {
+        if P::SHARDED {
+            sharded::reset()
+        } else {
+            imp::reset()
+        }
+    }
+}
+
+pub(crate) fn for_each<P: PointerTable>(f: impl FnMut(IntPointer, TrackedPointer)) {
+    if P::SHARDED {
+        sharded::for_each(f)
+    } else {
+        imp::for_each(f)
+    }
+}
+
+pub(crate) fn for_each_mut<P: PointerTable>(f: impl FnMut(&mut TrackedPointer)) {
+    if P::SHARDED {
+        sharded::for_each_mut(f)
+    } else {
+        imp::for_each_mut(f)
+    }
+}
@@ -0,0 +1,55 @@
+//! The table behind [ShardedTable](crate::ShardedTable).
+//!
+//! Pointers are spread over a fixed number of shards, each of which is an open-addressing hash
+//! table with linear probing behind a spin lock. Removal shifts the following entries of the
+//! probe sequence back instead of leaving tombstones, so lookups never slow down as the table
+//! churns.
+
+mod table;
+
+use core::mem;
+
+use table::{shard_index, Table, SHARDS};
+
+use crate::sync::SpinLock;
+use crate::{IntPointer, TrackedPointer};
+
+static SHARD_TABLE: [SpinLock<Table<TrackedPointer>>; SHARDS] =
+    [const { SpinLock::new(Table::new()) }; SHARDS];
+
+fn shard(ptr: IntPointer) -> &'static SpinLock<Table<TrackedPointer>> {
+    &SHARD_TABLE[shard_index(ptr)]
+}
+
+pub(crate) fn insert(ptr: IntPointer, tracked: TrackedPointer) -> Option<TrackedPointer> {
+    shard(ptr).with(|table| table.insert(ptr, tracked))
+}
+
+pub(crate) fn remove(ptr: IntPointer) -> Option<TrackedPointer> {
+    shard(ptr).with(|table| table.remove(ptr))
+}
+
+/// Forget all pointers, releasing the lock of every shard without taking it.
+///
+/// # Safety
+///
+/// No other thread may access the table while this runs. This is meant for the child process
+/// after `fork`, where a thread of the parent might have held a lock.
+pub(crate) unsafe fn reset() {
+    for shard in &SHARD_TABLE {
+        // SAFETY: guaranteed by the caller.
+        unsafe { shard.with_forced(|table| mem::forget(mem::replace(table, Table::new()))) };
+    }
+}
+
+pub(crate) fn for_each(mut f: impl FnMut(IntPointer, TrackedPointer)) {
+    for shard in &SHARD_TABLE {
+        shard.with(|table| table.for_each(&mut f));
+    }
+}
+
+pub(crate) fn for_each_mut(mut f: impl FnMut(&mut TrackedPointer)) {
+    for shard in &SHARD_TABLE {
+        shard.with(|table| table.for_each_mut(&mut f));
+    }
+}
@@ -0,0 +1,164 @@
+//! The hash table that makes up a shard of [ShardedTable](crate::ShardedTable).
+//!
+//! This module does not depend on the rest of the crate, such that `tests/loom.rs` and
+//! `tests/sharded_model.rs` can include it and check it in isolation.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+
+/// The number of shards, a power of two.
+pub(crate) const SHARDS: usize = 64;
+
+/// The capacity of a shard when the first pointer is inserted into it, a power of two.
+pub(crate) const MIN_CAPACITY: usize = 64;
+
+/// Marks an empty slot. Allocations are never placed at the null address.
+const EMPTY: usize = 0;
+
+/// Spread the bits of a pointer, whose lowest bits are usually zero due to alignment.
+pub(crate) fn hash(ptr: usize) -> u64 {
+    (ptr as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+/// Return the shard that `ptr` belongs to.
+pub(crate) fn shard_index(ptr: usize) -> usize {
+    // the highest bits select the shard, the bits below them the slot
+    (hash(ptr) >> (64 - SHARDS.trailing_zeros())) as usize
+}
+
+#[derive(Clone, Copy)]
+struct Slot<V> {
+    ptr: usize,
+    value: V,
+}
+
+/// An open-addressing hash table from non-null pointers to `V`, with linear probing.
+pub(crate) struct Table<V> {
+    /// Either empty or a power of two long.
+    slots: Vec<Slot<V>>,
+    len: usize,
+}
+
+impl<V: Copy + Default> Table<V> {
+    pub(crate) const fn new() -> Self {
+        Table {
+            slots: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// The slot at which the probe sequence of `ptr` starts.
+    pub(crate) fn home(&self, ptr: usize) -> usize {
+        (hash(ptr) << SHARDS.trailing_zeros() >> 32) as usize & (self.slots.len() - 1)
+    }
+
+    fn find(&self, ptr: usize) -> Option<usize> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let mask = self.slots.len() - 1;
+        let mut index = self.home(ptr);
+        loop {
+            match self.slots[index].ptr {
+                EMPTY => return None,
+                found if found == ptr => return Some(index),
+                _ => index = (index + 1) & mask,
+            }
+        }
+    }
+
+    pub(crate) fn insert(&mut self, ptr: usize, value: V) -> Option<V> {
+        if let Some(index) = self.find(ptr) {
+            return Some(mem::replace(&mut self.slots[index].value, value));
+        }
+        // keep the load factor at or below 3/4
+        if (self.len + 1) * 4 > self.slots.len() * 3 {
+            self.grow();
+        }
+        self.place(Slot { ptr, value });
+        self.len += 1;
+        None
+    }
+
+    /// Put `slot` into the first free slot of its probe sequence.
+    fn place(&mut self, slot: Slot<V>) {
+        let mask = self.slots.len() - 1;
+        let mut index = self.home(slot.ptr);
+        while self.slots[index].ptr != EMPTY {
+            index = (index + 1) & mask;
+        }
+        self.slots[index] = slot;
+    }
+
+    fn grow(&mut self) {
+        let capacity = (self.slots.len() * 2).max(MIN_CAPACITY);
+        let empty = Slot {
+            ptr: EMPTY,
+            value: V::default(),
+        };
+        let old = mem::replace(&mut self.slots, vec![empty; capacity]);
+        for slot in old {
+            if slot.ptr != EMPTY {
+                self.place(slot);
+            }
+        }
+    }
+
+    pub(crate) fn remove(&mut self, ptr: usize) -> Option<V> {
+        let mut hole = self.find(ptr)?;
+        let removed = self.slots[hole].value;
+        self.len -= 1;
+
+        // Shift back every following entry of the cluster that would not be found anymore
+        // through the hole, i.e. whose home is not cyclically within `(hole, index]`.
+        let mask = self.slots.len() - 1;
+        let mut index = hole;
+        loop {
+            index = (index + 1) & mask;
+            let slot = self.slots[index];
+            if slot.ptr == EMPTY {
+                break;
+            }
+            let home = self.home(slot.ptr);
+            if (index.wrapping_sub(home) & mask) >= (index.wrapping_sub(hole) & mask) {
+                self.slots[hole] = slot;
+                hole = index;
+            }
+        }
+        self.slots[hole].ptr = EMPTY;
+        Some(removed)
+    }
+
+    pub(crate) fn for_each(&self, mut f: impl FnMut(usize, V)) {
+        for slot in &self.slots {
+            if slot.ptr != EMPTY {
+                f(slot.ptr, slot.value);
+            }
+        }
+    }
+
+    pub(crate) fn for_each_mut(&mut self, mut f: impl FnMut(&mut V)) {
+        for slot in &mut self.slots {
+            if slot.ptr != EMPTY {
+                f(&mut slot.value);
+            }
+        }
+    }
+}
+
+/// Accessors for the tests that include this module.
+#[allow(dead_code)]
+impl<V: Copy + Default> Table<V> {
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub(crate) fn get(&self, ptr: usize) -> Option<V> {
+        self.find(ptr).map(|index| self.slots[index].value)
+    }
+}
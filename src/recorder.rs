@@ -1,35 +1,175 @@
-use std::fmt;
-use std::marker::PhantomData;
-use std::ops::DerefMut;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use core::alloc::Layout;
+#[cfg(feature = "std")]
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use core::ops::DerefMut;
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+#[cfg(feature = "std")]
+use std::time::Instant;
 
-use crate::{Error, Recorder, UseCase, UseCaseBytes};
+#[cfg(feature = "std")]
+use crate::export::StatsTable;
+#[cfg(feature = "std")]
+use crate::{current_thread_index, Callsite, Clock, Error, Tag, ThreadIndex, UseCaseRepr};
+use crate::{Recorder, UseCase};
 
+#[cfg(feature = "std")]
 use dashmap::DashMap;
-use once_cell::sync::OnceCell;
+
+#[cfg(feature = "std")]
+crate::utils::local! {
+    // When the current thread entered its current usecase, according to
+    // `StatsRecorder::with_usecase_clock`.
+    static ENTERED_AT: Cell<u64> = const { Cell::new(0) };
+}
+
+#[cfg(feature = "std")]
+use crate::sync::ResettableCell;
+
+/// How often an error occurred, and how many bytes it affected.
+#[cfg(feature = "std")]
+struct ErrorCounter {
+    count: AtomicUsize,
+    bytes: AtomicUsize,
+}
+
+#[cfg(feature = "std")]
+impl ErrorCounter {
+    const fn new() -> Self {
+        ErrorCounter {
+            count: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+        }
+    }
+
+    fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+    }
+}
+
+/// The time of the last flush that computed rates.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy)]
+enum RatesFlush {
+    Instant(Instant),
+    Ticks(u64),
+}
 
 /// A simple recorder for memory statistics that can be flushed periodically.
+#[cfg(feature = "std")]
 pub struct StatsRecorder<U: UseCase> {
-    current_usecase_contention_ref_cell: AtomicUsize,
-    current_usecase_contention_thread_local: AtomicUsize,
-    current_usecase_bad_bytes: AtomicUsize,
-    // we store UseCaseBytes so UseCase does not need to require Hash
-    results: OnceCell<DashMap<UseCaseBytes, Stat>>,
+    errors: [ErrorCounter; Error::ALL.len()],
+    // we store UseCaseRepr so UseCase does not need to require Hash
+    results: ResettableCell<DashMap<UseCaseRepr, Stat>>,
+    callsites: ResettableCell<DashMap<(UseCaseRepr, Callsite), Stat>>,
+    transfers: ResettableCell<DashMap<(UseCaseRepr, UseCaseRepr), Stat>>,
+    tagged: ResettableCell<DashMap<(UseCaseRepr, Tag), Stat>>,
+    threads: ResettableCell<DashMap<(ThreadIndex, UseCaseRepr), Stat>>,
+    // The memory currently used per usecase, which unlike `Stat::current` is never reset.
+    lifetime: ResettableCell<DashMap<UseCaseRepr, isize>>,
+    // The sum of `Stat::current` reset by flushes per usecase, to tell the actual balance of a
+    // usecase apart from a negative `current` after a flush.
+    flushed_current: ResettableCell<DashMap<UseCaseRepr, isize>>,
+    // When `flush_with_rates` or `flush_with_clock_rates` was last called.
+    last_rates_flush: Mutex<Option<RatesFlush>>,
+    per_thread: bool,
+    lifetime_peak: bool,
+    flush_mode: FlushMode,
+    peak_clock: Option<&'static dyn Clock>,
+    usecase_clock: Option<&'static dyn Clock>,
     _phantom: PhantomData<U>,
 }
 
+#[cfg(feature = "std")]
 impl<U: UseCase> StatsRecorder<U> {
     /// Construct a new recorder.
     pub const fn new() -> Self {
         StatsRecorder {
-            current_usecase_contention_ref_cell: AtomicUsize::new(0),
-            current_usecase_contention_thread_local: AtomicUsize::new(0),
-            current_usecase_bad_bytes: AtomicUsize::new(0),
-            results: OnceCell::new(),
+            errors: [const { ErrorCounter::new() }; Error::ALL.len()],
+            results: ResettableCell::new(),
+            callsites: ResettableCell::new(),
+            transfers: ResettableCell::new(),
+            tagged: ResettableCell::new(),
+            threads: ResettableCell::new(),
+            lifetime: ResettableCell::new(),
+            flushed_current: ResettableCell::new(),
+            last_rates_flush: Mutex::new(None),
+            per_thread: false,
+            lifetime_peak: false,
+            flush_mode: FlushMode::Reset,
+            peak_clock: None,
+            usecase_clock: None,
             _phantom: PhantomData,
         }
     }
 
+    /// Record the time at which each usecase reached its peak, see [Stat::peak_at].
+    ///
+    /// `clock` is read every time a usecase reaches a new peak, which can be very often. It
+    /// should be cheap, see [Clock]. A good choice is reading a coarse timestamp or counter from
+    /// an atomic that is periodically updated by another thread:
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    ///
+    /// # #[derive(Default)] struct MyUseCase;
+    /// # impl From<MyUseCase> for u32 { fn from(_: MyUseCase) -> u32 { 0 } }
+    /// # impl From<u32> for MyUseCase { fn from(_: u32) -> MyUseCase { MyUseCase } }
+    /// # impl memoria::UseCase for MyUseCase {}
+    /// static SECONDS: AtomicU64 = AtomicU64::new(0);
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: memoria::Alloc<MyUseCase> = memoria::Alloc::new_with(
+    ///     memoria::StatsRecorder::new().with_peak_clock(&|| SECONDS.load(Ordering::Relaxed)),
+    ///     std::alloc::System,
+    /// );
+    /// ```
+    pub const fn with_peak_clock(mut self, clock: &'static dyn Clock) -> Self {
+        self.peak_clock = Some(clock);
+        self
+    }
+
+    /// Record how much time threads spend inside each usecase, see [Stat::active_time].
+    ///
+    /// `clock` is read every time a thread switches usecases. The same requirements as for
+    /// [StatsRecorder::with_peak_clock] apply.
+    pub const fn with_usecase_clock(mut self, clock: &'static dyn Clock) -> Self {
+        self.usecase_clock = Some(clock);
+        self
+    }
+
+    /// Additionally break down stats per thread, see [StatsRecorder::flush_threads].
+    ///
+    /// Memory is subtracted from the thread that frees it, which is not necessarily the thread
+    /// that allocated it. For memory that is passed between threads, the `current` value of a
+    /// single thread can therefore become negative.
+    pub const fn with_per_thread(mut self) -> Self {
+        self.per_thread = true;
+        self
+    }
+
+    /// Additionally record the all-time peak of each usecase, see [Stat::lifetime_peak].
+    pub const fn with_lifetime_peak(mut self) -> Self {
+        self.lifetime_peak = true;
+        self
+    }
+
+    /// Choose what [StatsRecorder::flush] and the other flush methods reset, see [FlushMode].
+    pub const fn with_flush_mode(mut self, flush_mode: FlushMode) -> Self {
+        self.flush_mode = flush_mode;
+        self
+    }
+
     /// Get statistics for a single usecase.
     ///
     /// This function is cheaper than `flush` but currently not by much. This may change in the
@@ -41,83 +181,634 @@ impl<U: UseCase> StatsRecorder<U> {
         };
 
         results
-            .get(&use_case.into())
+            .get(&use_case.into_repr())
             .map(|stat| *stat)
             .unwrap_or_default()
     }
 
-    fn get_mut(&self, use_case: U) -> impl DerefMut<Target = Stat> + '_ {
+    fn get_mut(&self, use_case: UseCaseRepr) -> impl DerefMut<Target = Stat> + '_ {
         self.results
             .get_or_init(DashMap::new)
-            .entry(use_case.into())
-            .or_insert_with(Default::default)
+            .entry(use_case)
+            .or_default()
     }
 
-    fn get_error_atomic(&self, code: Error) -> &AtomicUsize {
-        match code {
-            Error::CurrentUsecaseContentionRefCell => &self.current_usecase_contention_ref_cell,
-            Error::CurrentUsecaseContentionThreadLocal => {
-                &self.current_usecase_contention_thread_local
-            }
-            Error::CurrentUsecaseBadBytes => &self.current_usecase_bad_bytes,
+    fn record_lifetime(&self, use_case: UseCaseRepr, stat: &mut Stat, size: isize) {
+        if self.lifetime_peak {
+            let mut current = self
+                .lifetime
+                .get_or_init(DashMap::new)
+                .entry(use_case)
+                .or_default();
+            *current += size;
+            stat.lifetime_peak = stat.lifetime_peak.max(*current);
         }
     }
 
+    /// Report [Error::NegativeBalance] if freeing `size` bytes left the usecase with less than
+    /// zero bytes.
+    fn check_balance(&self, use_case: UseCaseRepr, stat: &mut Stat, size: usize) {
+        if stat.current >= 0 {
+            return;
+        }
+        let flushed = self
+            .flushed_current
+            .get()
+            .and_then(|flushed| flushed.get(&use_case).map(|x| *x))
+            .unwrap_or(0);
+        if flushed + stat.current < 0 {
+            stat.negative_balance += 1;
+            self.on_error(Error::NegativeBalance, Some(size));
+        }
+    }
+
+    fn get_callsite_mut(
+        &self,
+        use_case: U,
+        callsite: Callsite,
+    ) -> impl DerefMut<Target = Stat> + '_ {
+        self.callsites
+            .get_or_init(DashMap::new)
+            .entry((use_case.into_repr(), callsite))
+            .or_default()
+    }
+
+    fn get_tagged_mut(&self, use_case: U, tag: Tag) -> impl DerefMut<Target = Stat> + '_ {
+        self.tagged
+            .get_or_init(DashMap::new)
+            .entry((use_case.into_repr(), tag))
+            .or_default()
+    }
+
+    fn record_thread(&self, use_case: UseCaseRepr, size: isize) {
+        self.threads
+            .get_or_init(DashMap::new)
+            .entry((current_thread_index(), use_case))
+            .or_default()
+            .record(size);
+    }
+
+    fn get_error_counter(&self, code: Error) -> &ErrorCounter {
+        &self.errors[code.index()]
+    }
+
     /// Check how often an error has occurred
     pub fn get_error(&self, code: Error) -> usize {
-        self.get_error_atomic(code).load(Ordering::Relaxed)
+        self.get_error_counter(code).count.load(Ordering::Relaxed)
+    }
+
+    /// Return how often each error has occurred, in the order of [Error::ALL].
+    pub fn errors(&self) -> impl Iterator<Item = (Error, usize)> + '_ {
+        Error::ALL
+            .into_iter()
+            .map(|error| (error, self.get_error(error)))
     }
 
-    /// Return all recorded statistics and reset internal state.
+    /// Return the number of bytes affected by an error, that is the sum of the sizes of all
+    /// allocations and deallocations whose stats were dropped or skewed because of it.
+    ///
+    /// This helps to judge whether errors materially affect the stats. Errors that are not
+    /// related to a particular allocation don't add any bytes.
+    pub fn get_error_bytes(&self, code: Error) -> usize {
+        self.get_error_counter(code).bytes.load(Ordering::Relaxed)
+    }
+
+    /// Return all recorded statistics without resetting them.
+    pub fn peek(&self, mut stat_fn: impl FnMut(U, Stat)) {
+        if let Some(results) = self.results.get() {
+            for kv in results.iter() {
+                stat_fn(U::from_repr(*kv.key()).unwrap_or_default(), *kv.value());
+            }
+        }
+    }
+
+    /// Merge a snapshot of stats, such as the result of a flush of another recorder, into this
+    /// one. Each stat is combined with the existing one using [Stat::merge].
+    ///
+    /// This allows using a `StatsRecorder` that is not attached to any allocator to aggregate
+    /// consecutive time windows.
+    pub fn merge_from(&self, snapshot: impl IntoIterator<Item = (U, Stat)>) {
+        for (use_case, stat) in snapshot {
+            self.get_mut(use_case.into_repr()).merge(&stat);
+        }
+    }
+
+    /// Return the `n` usecases with the largest stats according to `by`, largest first, without
+    /// resetting anything.
+    ///
+    /// Usecases with equal stats are ordered by their representation. This should be called
+    /// through [Alloc::with_recorder](crate::Alloc::with_recorder), since it allocates.
+    pub fn top_n(&self, n: usize, by: SortKey) -> Vec<(U, Stat)> {
+        let mut stats: Vec<(UseCaseRepr, Stat)> = match self.results.get() {
+            Some(results) => results.iter().map(|kv| (*kv.key(), *kv.value())).collect(),
+            None => return Vec::new(),
+        };
+        stats.sort_by(|(a_use_case, a), (b_use_case, b)| {
+            by.key(b).cmp(&by.key(a)).then(a_use_case.cmp(b_use_case))
+        });
+        stats
+            .into_iter()
+            .take(n)
+            .map(|(use_case, stat)| (U::from_repr(use_case).unwrap_or_default(), stat))
+            .collect()
+    }
+
+    /// Return all recorded statistics as a text table sorted by `by`, without resetting anything.
+    ///
+    /// The table is rendered through `Display`, see [StatsTable]. Like [StatsRecorder::top_n],
+    /// this should be called through [Alloc::with_recorder](crate::Alloc::with_recorder):
+    ///
+    /// ```ignore
+    /// let table = ALLOCATOR.with_recorder(|recorder| Ok(recorder.render_table(SortKey::Peak)))?;
+    /// log::info!("memory usage:\n{table}");
+    /// ```
+    pub fn render_table(&self, by: SortKey) -> StatsTable<U> {
+        StatsTable::new(self.top_n(usize::MAX, by), by)
+    }
+
+    /// Return all recorded statistics and reset internal state, according to the [FlushMode]
+    /// set through [StatsRecorder::with_flush_mode].
     ///
     /// This method is somewhat expensive in that it acquires global resources mutably.
-    pub fn flush(&self, mut stat_fn: impl FnMut(U, Stat), mut error_fn: impl FnMut(Error, usize)) {
+    pub fn flush(&self, stat_fn: impl FnMut(U, Stat), mut error_fn: impl FnMut(Error, usize)) {
+        self.flush_with_error_bytes(stat_fn, |error, count, _| error_fn(error, count))
+    }
+
+    /// Like [StatsRecorder::flush], but additionally pass the number of bytes affected by each
+    /// error, see [StatsRecorder::get_error_bytes].
+    pub fn flush_with_error_bytes(
+        &self,
+        mut stat_fn: impl FnMut(U, Stat),
+        mut error_fn: impl FnMut(Error, usize, usize),
+    ) {
+        #[cfg(feature = "alloc-error-hook")]
+        crate::oom::clear_last_flush();
+
         if let Some(results) = self.results.get() {
             for kv in results.iter() {
-                stat_fn(U::try_from(*kv.key()).unwrap_or_default(), *kv.value());
+                #[cfg(feature = "alloc-error-hook")]
+                crate::oom::remember_flushed(*kv.key(), *kv.value());
+                stat_fn(U::from_repr(*kv.key()).unwrap_or_default(), *kv.value());
             }
-            results.clear();
+            results.retain(|use_case, stat| self.reset_stat(self.flush_mode, *use_case, stat));
         }
 
-        error_fn(
-            Error::CurrentUsecaseBadBytes,
-            self.get_error(Error::CurrentUsecaseBadBytes),
-        );
-        error_fn(
-            Error::CurrentUsecaseContentionRefCell,
-            self.get_error(Error::CurrentUsecaseContentionRefCell),
+        for error in Error::ALL {
+            error_fn(error, self.get_error(error), self.get_error_bytes(error));
+        }
+    }
+
+    /// Reset the stats of a single usecase like a flush in [FlushMode::Reset] would, without
+    /// touching any other usecase. Its stats per callsite, tag and thread are reset as well, and
+    /// so are transfers from or to it.
+    ///
+    /// Memory that was allocated before the reset is still subtracted from `use_case` when it is
+    /// freed, which makes `current` negative. Use
+    /// [Alloc::reattribute_live](crate::Alloc::reattribute_live) beforehand to avoid that.
+    pub fn reset(&self, use_case: U) {
+        let use_case = use_case.into_repr();
+        if let Some(results) = self.results.get() {
+            results.remove_if_mut(&use_case, |use_case, stat| {
+                !self.reset_stat(FlushMode::Reset, *use_case, stat)
+            });
+        }
+        if let Some(callsites) = self.callsites.get() {
+            callsites.retain(|(x, _), stat| *x != use_case || FlushMode::Reset.apply(stat));
+        }
+        if let Some(tagged) = self.tagged.get() {
+            tagged.retain(|(x, _), stat| *x != use_case || FlushMode::Reset.apply(stat));
+        }
+        if let Some(threads) = self.threads.get() {
+            threads.retain(|(_, x), stat| *x != use_case || FlushMode::Reset.apply(stat));
+        }
+        if let Some(transfers) = self.transfers.get() {
+            transfers.retain(|(from, to), _| *from != use_case && *to != use_case);
+        }
+    }
+
+    /// Reset `stat` according to `flush_mode`, and return whether it should be kept.
+    fn reset_stat(&self, flush_mode: FlushMode, use_case: UseCaseRepr, stat: &mut Stat) -> bool {
+        if flush_mode == FlushMode::Reset && stat.current != 0 {
+            *self
+                .flushed_current
+                .get_or_init(DashMap::new)
+                .entry(use_case)
+                .or_default() += stat.current;
+        }
+        flush_mode.apply(stat)
+    }
+
+    /// Like [StatsRecorder::flush], but additionally pass the [Rate] of each usecase since the
+    /// previous call to this function, given that it is now `now`.
+    ///
+    /// The rate is `None` on the first call. Rates are computed from `total` and `count`, which
+    /// are reset by every flush except in [FlushMode::Snapshot]. Don't mix calls to this function
+    /// with other flushes, since those reset the stats without restarting the interval.
+    pub fn flush_with_rates(
+        &self,
+        now: Instant,
+        stat_fn: impl FnMut(U, Stat, Option<Rate>),
+        error_fn: impl FnMut(Error, usize),
+    ) {
+        let elapsed = match self.replace_rates_flush(RatesFlush::Instant(now)) {
+            Some(RatesFlush::Instant(last)) => Some(now.saturating_duration_since(last)),
+            _ => None,
+        };
+        self.flush_rates(elapsed, stat_fn, error_fn);
+    }
+
+    /// Like [StatsRecorder::flush_with_rates], but read the current time from `clock`.
+    ///
+    /// The rate is also `None` if the previous call was to [StatsRecorder::flush_with_rates],
+    /// since the two measure time differently.
+    pub fn flush_with_clock_rates(
+        &self,
+        clock: &dyn Clock,
+        stat_fn: impl FnMut(U, Stat, Option<Rate>),
+        error_fn: impl FnMut(Error, usize),
+    ) {
+        let now = clock.now();
+        let elapsed = match self.replace_rates_flush(RatesFlush::Ticks(now)) {
+            Some(RatesFlush::Ticks(last)) => Some(clock.elapsed(last, now)),
+            _ => None,
+        };
+        self.flush_rates(elapsed, stat_fn, error_fn);
+    }
+
+    fn replace_rates_flush(&self, now: RatesFlush) -> Option<RatesFlush> {
+        self.last_rates_flush
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .replace(now)
+    }
+
+    fn flush_rates(
+        &self,
+        elapsed: Option<Duration>,
+        mut stat_fn: impl FnMut(U, Stat, Option<Rate>),
+        error_fn: impl FnMut(Error, usize),
+    ) {
+        self.flush(
+            |use_case, stat| stat_fn(use_case, stat, elapsed.map(|elapsed| stat.rate(elapsed))),
+            error_fn,
         );
-        error_fn(
-            Error::CurrentUsecaseContentionThreadLocal,
-            self.get_error(Error::CurrentUsecaseContentionThreadLocal),
+    }
+
+    /// Like [StatsRecorder::flush], but collect all statistics into a map, for example to
+    /// serialize them. Errors are not returned, use [StatsRecorder::get_error] for those.
+    ///
+    /// This allocates, and should be called through
+    /// [Alloc::with_recorder](crate::Alloc::with_recorder).
+    pub fn flush_to_map(&self) -> BTreeMap<U, Stat>
+    where
+        U: Ord,
+    {
+        let mut stats = BTreeMap::new();
+        self.flush(
+            |use_case, stat| {
+                stats.insert(use_case, stat);
+            },
+            |_, _| {},
         );
+        stats
+    }
+}
+
+#[cfg(feature = "std")]
+impl<U: UseCase> Default for StatsRecorder<U> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+#[cfg(feature = "std")]
+impl<U: UseCase> StatsRecorder<U> {
+    /// Return statistics per (usecase, callsite) and reset them according to the [FlushMode].
+    ///
+    /// Only allocations made under guards created by
+    /// [Alloc::with_usecase_at](crate::Alloc::with_usecase_at) are broken down by callsite, and
+    /// only those are returned here.
+    pub fn flush_callsites(&self, mut stat_fn: impl FnMut(U, Callsite, Stat)) {
+        if let Some(callsites) = self.callsites.get() {
+            for kv in callsites.iter() {
+                let (use_case, callsite) = *kv.key();
+                stat_fn(
+                    U::from_repr(use_case).unwrap_or_default(),
+                    callsite,
+                    *kv.value(),
+                );
+            }
+            callsites.retain(|_, stat| self.flush_mode.apply(stat));
+        }
+    }
+
+    /// Return statistics per (usecase, tag) and reset them according to the [FlushMode].
+    ///
+    /// Only allocations made while a tag set by
+    /// [Alloc::with_usecase_tagged](crate::Alloc::with_usecase_tagged) was active are broken down
+    /// by tag, and only those are returned here.
+    pub fn flush_tagged(&self, mut stat_fn: impl FnMut(U, Tag, Stat)) {
+        if let Some(tagged) = self.tagged.get() {
+            for kv in tagged.iter() {
+                let (use_case, tag) = *kv.key();
+                stat_fn(U::from_repr(use_case).unwrap_or_default(), tag, *kv.value());
+            }
+            tagged.retain(|_, stat| self.flush_mode.apply(stat));
+        }
+    }
+
+    /// Return statistics per (allocating usecase, freeing usecase) and reset them according to
+    /// the [FlushMode], see [Recorder::on_transfer].
+    ///
+    /// Only `total` and `count` of each [Stat] are set, counting the bytes and allocations that
+    /// were freed under a different usecase than they were allocated in.
+    pub fn flush_transfers(&self, mut stat_fn: impl FnMut(U, U, Stat)) {
+        if let Some(transfers) = self.transfers.get() {
+            for kv in transfers.iter() {
+                let (from, to) = *kv.key();
+                stat_fn(
+                    U::from_repr(from).unwrap_or_default(),
+                    U::from_repr(to).unwrap_or_default(),
+                    *kv.value(),
+                );
+            }
+            transfers.retain(|_, stat| self.flush_mode.apply(stat));
+        }
+    }
+
+    /// Return statistics per (thread, usecase) and reset them according to the [FlushMode].
+    ///
+    /// Nothing is returned unless the recorder was created with
+    /// [StatsRecorder::with_per_thread].
+    pub fn flush_threads(&self, mut stat_fn: impl FnMut(ThreadIndex, U, Stat)) {
+        if let Some(threads) = self.threads.get() {
+            for kv in threads.iter() {
+                let (thread, use_case) = *kv.key();
+                stat_fn(
+                    thread,
+                    U::from_repr(use_case).unwrap_or_default(),
+                    *kv.value(),
+                );
+            }
+            threads.retain(|_, stat| self.flush_mode.apply(stat));
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 unsafe impl<U: UseCase> Recorder<U> for StatsRecorder<U> {
     fn on_alloc(&self, use_case: U, size: usize) -> bool {
-        self.get_mut(use_case).record(size as isize);
+        let use_case = use_case.into_repr();
+        if self.per_thread {
+            self.record_thread(use_case, size as isize);
+        }
+        let mut stat = self.get_mut(use_case);
+        let old_peak = stat.peak;
+        stat.record(size as isize);
+        self.record_lifetime(use_case, &mut stat, size as isize);
+        if stat.peak > old_peak {
+            if let Some(clock) = self.peak_clock {
+                stat.peak_at = clock.now();
+            }
+        }
         true
     }
 
+    fn on_alloc_layout(&self, use_case: U, layout: Layout) {
+        self.get_mut(use_case.into_repr()).record_layout(layout);
+    }
+
     fn on_dealloc(&self, use_case: U, size: usize) {
-        self.get_mut(use_case).record(-(size as isize));
+        let use_case = use_case.into_repr();
+        if self.per_thread {
+            self.record_thread(use_case, -(size as isize));
+        }
+        let mut stat = self.get_mut(use_case);
+        stat.record(-(size as isize));
+        self.record_lifetime(use_case, &mut stat, -(size as isize));
+        self.check_balance(use_case, &mut stat, size);
+    }
+
+    fn on_shared_alloc(&self, use_case: U, size: usize) -> bool {
+        let use_case = use_case.into_repr();
+        self.on_alloc(U::from_repr(use_case).unwrap_or_default(), size);
+        self.get_mut(use_case).shared += size as isize;
+        true
+    }
+
+    fn on_shared_dealloc(&self, use_case: U, size: usize) {
+        let use_case = use_case.into_repr();
+        self.on_dealloc(U::from_repr(use_case).unwrap_or_default(), size);
+        self.get_mut(use_case).shared -= size as isize;
+    }
+
+    fn on_attributed_drop(&self, use_case: U, size: usize) {
+        self.get_mut(use_case.into_repr()).freed_in_drop += size as isize;
+    }
+
+    fn on_cross_thread_dealloc(&self, use_case: U, size: usize) {
+        self.get_mut(use_case.into_repr()).freed_cross_thread += size as isize;
+    }
+
+    fn on_transfer(&self, from: U, to: U, size: usize) {
+        let mut stat = self
+            .transfers
+            .get_or_init(DashMap::new)
+            .entry((from.into_repr(), to.into_repr()))
+            .or_default();
+        stat.total += size as isize;
+        stat.count += 1;
+    }
+
+    fn on_external_alloc(&self, use_case: U, size: usize) {
+        let use_case = use_case.into_repr();
+        let mut stat = self.get_mut(use_case);
+        stat.record(size as isize);
+        stat.external += size as isize;
+        self.record_lifetime(use_case, &mut stat, size as isize);
+    }
+
+    fn on_external_dealloc(&self, use_case: U, size: usize) {
+        let use_case = use_case.into_repr();
+        let mut stat = self.get_mut(use_case);
+        stat.record(-(size as isize));
+        stat.external -= size as isize;
+        self.record_lifetime(use_case, &mut stat, -(size as isize));
+        self.check_balance(use_case, &mut stat, size);
+    }
+
+    fn on_callsite_alloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.get_callsite_mut(use_case, callsite)
+            .record(size as isize);
+    }
+
+    fn on_callsite_dealloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.get_callsite_mut(use_case, callsite)
+            .record(-(size as isize));
+    }
+
+    fn on_tagged_alloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.get_tagged_mut(use_case, tag).record(size as isize);
+    }
+
+    fn on_tagged_dealloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.get_tagged_mut(use_case, tag).record(-(size as isize));
+    }
+
+    fn on_forbidden_alloc(&self, use_case: U, _size: usize) {
+        self.get_mut(use_case.into_repr()).forbidden += 1;
+    }
+
+    fn on_usecase_enter(&self, use_case: U) {
+        let mut stat = self.get_mut(use_case.into_repr());
+        stat.threads += 1;
+        if stat.threads > stat.peak_threads {
+            stat.peak_threads = stat.threads;
+        }
+        if let Some(clock) = self.usecase_clock {
+            ENTERED_AT.try_with(|x| x.set(clock.now())).ok();
+        }
+    }
+
+    fn on_guard_enter(&self, use_case: U) {
+        self.get_mut(use_case.into_repr()).entries += 1;
+    }
+
+    fn on_usecase_exit(&self, use_case: U) {
+        let mut stat = self.get_mut(use_case.into_repr());
+        stat.threads -= 1;
+        if let Some(clock) = self.usecase_clock {
+            if let Ok(entered_at) = ENTERED_AT.try_with(Cell::get) {
+                stat.active_time += clock.now().saturating_sub(entered_at);
+            }
+        }
+    }
+
+    fn on_fork(&self) {
+        self.results.reset();
+        self.callsites.reset();
+        self.transfers.reset();
+        self.tagged.reset();
+        self.threads.reset();
+        self.lifetime.reset();
+        self.flushed_current.reset();
+        for error in Error::ALL {
+            self.get_error_counter(error).reset();
+        }
+    }
+
+    fn on_error(&self, code: Error, size: Option<usize>) {
+        let counter = self.get_error_counter(code);
+        counter.count.fetch_add(1, Ordering::Relaxed);
+        if let Some(size) = size {
+            counter.bytes.fetch_add(size, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A recorder that records nothing.
+///
+/// Usecases can still be switched, but no allocation is tracked and no stats are kept. This is
+/// useful for turning memoria off without touching the code that switches usecases:
+///
+/// ```
+/// # #[derive(Default)] struct MyUseCase;
+/// # impl From<MyUseCase> for u32 { fn from(_: MyUseCase) -> u32 { 0 } }
+/// # impl From<u32> for MyUseCase { fn from(_: u32) -> MyUseCase { MyUseCase } }
+/// # impl memoria::UseCase for MyUseCase {}
+/// #[cfg(not(feature = "profiling"))]
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::NoopAlloc<MyUseCase> =
+///     memoria::Alloc::new_with(memoria::NoopRecorder::new(), std::alloc::System);
+///
+/// #[cfg(feature = "profiling")]
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::Alloc<MyUseCase> = memoria::Alloc::new();
+/// ```
+pub struct NoopRecorder<U: UseCase> {
+    _phantom: PhantomData<U>,
+}
+
+impl<U: UseCase> NoopRecorder<U> {
+    /// Construct a new recorder.
+    pub const fn new() -> Self {
+        NoopRecorder {
+            _phantom: PhantomData,
+        }
     }
+}
 
-    fn on_error(&self, code: Error, _size: Option<usize>) {
-        self.get_error_atomic(code).fetch_add(1, Ordering::Relaxed);
+impl<U: UseCase> Default for NoopRecorder<U> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+unsafe impl<U: UseCase> Recorder<U> for NoopRecorder<U> {}
+
 /// Basic memory stats for a given usecase.
 #[derive(Default, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Stat {
     /// The amount of memory currently used.
     pub current: isize,
-    /// The largest amount of memory ever used at a point in time.
+    /// The largest amount of memory ever used at a point in time, since the last flush. See also
+    /// `lifetime_peak`.
     pub peak: isize,
+    /// When `peak` was reached, according to the clock passed to
+    /// [StatsRecorder::with_peak_clock]. Zero if no clock was configured.
+    pub peak_at: u64,
     /// The amount of memory allocated in total, regardless of whether it was deallocated or not.
     pub total: isize,
+    /// The number of allocations, regardless of whether they were deallocated or not.
+    pub count: isize,
+    /// The size of the largest single allocation.
+    pub max_single: isize,
+    /// The amount of memory freed while dropping values through
+    /// [Alloc::drop_attributed](crate::Alloc::drop_attributed) with this usecase, regardless of
+    /// which usecase allocated it.
+    pub freed_in_drop: isize,
+    /// The amount of memory allocated by one thread and freed by another.
+    pub freed_cross_thread: isize,
+    /// The number of allocations with an alignment above [HIGH_ALIGNMENT].
+    pub high_align: isize,
+    /// An estimate of how many bytes were wasted on padding, in total.
+    ///
+    /// This is the difference between the requested size and the size rounded up to the
+    /// alignment and then to the next size class, assuming four size classes per power of two as
+    /// used by common allocators such as jemalloc. The actual waste depends on the wrapped
+    /// allocator.
+    pub padding: isize,
+    /// The part of `current` that was reported through
+    /// [Alloc::record_external_alloc](crate::Alloc::record_external_alloc), rather than allocated
+    /// through the global allocator.
+    pub external: isize,
+    /// The part of `current` that is this usecase's share of allocations split across several
+    /// usecases with [Alloc::with_usecases](crate::Alloc::with_usecases).
+    pub shared: isize,
+    /// The number of threads currently inside this usecase.
+    pub threads: isize,
+    /// The largest number of threads that were inside this usecase at the same time.
+    pub peak_threads: isize,
+    /// The number of allocations made while a guard created with
+    /// [Alloc::forbid_alloc](crate::Alloc::forbid_alloc) was alive.
+    pub forbidden: isize,
+    /// The largest amount of memory ever used at a point in time, since the recorder was
+    /// created. Unlike `peak`, this is never reset by a flush.
+    ///
+    /// Zero unless enabled through [StatsRecorder::with_lifetime_peak].
+    pub lifetime_peak: isize,
+    /// The number of deallocations that left this usecase with less than zero bytes, see
+    /// [Error::NegativeBalance].
+    pub negative_balance: isize,
+    /// The number of guards created for this usecase, for example through
+    /// [Alloc::with_usecase](crate::Alloc::with_usecase) or [Alloc::scope](crate::Alloc::scope).
+    pub entries: isize,
+    /// The time threads spent inside this usecase, summed over all threads, according to the
+    /// clock passed to [StatsRecorder::with_usecase_clock]. Time spent in nested usecases is not
+    /// included. Time is only added when a thread leaves the usecase. Zero if no clock was
+    /// configured.
+    pub active_time: u64,
 }
 
 impl fmt::Display for Stat {
@@ -130,8 +821,146 @@ impl fmt::Display for Stat {
     }
 }
 
+/// What [StatsRecorder::flush] resets, see [StatsRecorder::with_flush_mode].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum FlushMode {
+    /// Reset all stats to zero. This is the default.
+    ///
+    /// Every flush then reports what happened since the previous one. `current` is the net
+    /// amount allocated since the previous flush, which becomes negative when memory allocated
+    /// before it is freed.
+    #[default]
+    Reset,
+    /// Reset all stats except for the gauges `current`, `external`, `shared` and `threads`,
+    /// which keep reflecting the memory that is actually alive. `peak` starts over from
+    /// `current`.
+    KeepCurrent,
+    /// Do not reset anything, every flush returns a snapshot of all stats since the start.
+    Snapshot,
+}
+
+#[cfg(feature = "std")]
+impl FlushMode {
+    /// Reset `stat` after it was flushed, and return whether it should be kept.
+    fn apply(self, stat: &mut Stat) -> bool {
+        match self {
+            FlushMode::Reset => {
+                // Threads are still inside of their usecases after the flush, so that gauge is
+                // carried over.
+                *stat = Stat {
+                    threads: stat.threads,
+                    peak_threads: stat.threads,
+                    lifetime_peak: stat.lifetime_peak,
+                    ..Stat::ZERO
+                };
+                stat.threads != 0 || stat.lifetime_peak != 0
+            }
+            FlushMode::KeepCurrent => {
+                *stat = Stat {
+                    current: stat.current,
+                    peak: stat.current,
+                    external: stat.external,
+                    shared: stat.shared,
+                    threads: stat.threads,
+                    peak_threads: stat.threads,
+                    lifetime_peak: stat.lifetime_peak,
+                    ..Stat::ZERO
+                };
+                stat.current != 0
+                    || stat.external != 0
+                    || stat.shared != 0
+                    || stat.threads != 0
+                    || stat.lifetime_peak != 0
+            }
+            FlushMode::Snapshot => true,
+        }
+    }
+}
+
+/// Allocation rates of a usecase, see [Stat::rate].
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Rate {
+    /// The number of allocations per second.
+    pub allocs_per_sec: f64,
+    /// The number of bytes allocated per second.
+    pub bytes_per_sec: f64,
+}
+
+/// Which field of [Stat] to rank usecases by, see [StatsRecorder::top_n].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum SortKey {
+    /// [Stat::current]
+    #[default]
+    Current,
+    /// [Stat::peak]
+    Peak,
+    /// [Stat::total]
+    Total,
+    /// [Stat::count]
+    Count,
+}
+
+#[cfg(feature = "std")]
+impl SortKey {
+    pub(crate) fn key(self, stat: &Stat) -> isize {
+        match self {
+            SortKey::Current => stat.current,
+            SortKey::Peak => stat.peak,
+            SortKey::Total => stat.total,
+            SortKey::Count => stat.count,
+        }
+    }
+}
+
+/// Allocations with an alignment above this are counted in [Stat::high_align].
+///
+/// This is the largest alignment that `malloc` guarantees on common 64-bit platforms. Allocations
+/// with a larger alignment usually take a slower path in the allocator.
+pub const HIGH_ALIGNMENT: usize = 16;
+
+/// Round `size` up to the next size class, with four size classes per power of two.
+fn size_class(size: usize) -> usize {
+    if size <= 16 {
+        return size.next_multiple_of(8).max(8);
+    }
+    size.checked_next_power_of_two()
+        .and_then(|power| size.checked_next_multiple_of(power / 8))
+        .unwrap_or(size)
+}
+
 impl Stat {
-    fn record(&mut self, size: isize) {
+    pub(crate) const ZERO: Stat = Stat {
+        current: 0,
+        peak: 0,
+        peak_at: 0,
+        total: 0,
+        count: 0,
+        max_single: 0,
+        freed_in_drop: 0,
+        freed_cross_thread: 0,
+        high_align: 0,
+        padding: 0,
+        external: 0,
+        shared: 0,
+        threads: 0,
+        peak_threads: 0,
+        forbidden: 0,
+        lifetime_peak: 0,
+        negative_balance: 0,
+        entries: 0,
+        active_time: 0,
+    };
+
+    pub(crate) fn record_layout(&mut self, layout: Layout) {
+        if layout.align() > HIGH_ALIGNMENT {
+            self.high_align += 1;
+        }
+        let rounded = size_class(layout.pad_to_align().size());
+        self.padding += (rounded - layout.size()) as isize;
+    }
+
+    pub(crate) fn record(&mut self, size: isize) {
         self.current += size;
 
         if self.current > self.peak {
@@ -140,6 +969,141 @@ impl Stat {
 
         if size > 0 {
             self.total += size;
+            self.count += 1;
+
+            if size > self.max_single {
+                self.max_single = size;
+            }
+        }
+    }
+
+    /// Append the stats of a later period, which started when `self` was in its current state.
+    ///
+    /// This is the right way to combine consecutive time windows, such as the results of two
+    /// flushes of the same recorder, or a measurement nested in another. The peak of `later` is
+    /// relative to the start of its period, so the combined peak is the larger of `self.peak`
+    /// and `self.current + later.peak`.
+    ///
+    /// [Stat::threads] is a gauge, and is taken from `later`. To combine stats of concurrent
+    /// sources such as threads or processes, use `+` instead.
+    pub fn merge(&mut self, later: &Stat) {
+        let peak = self.current + later.peak;
+        if peak > self.peak {
+            self.peak = peak;
+            self.peak_at = later.peak_at;
+        }
+        self.current += later.current;
+        self.total += later.total;
+        self.count += later.count;
+        self.max_single = self.max_single.max(later.max_single);
+        self.freed_in_drop += later.freed_in_drop;
+        self.freed_cross_thread += later.freed_cross_thread;
+        self.high_align += later.high_align;
+        self.padding += later.padding;
+        self.external += later.external;
+        self.shared += later.shared;
+        self.threads = later.threads;
+        self.peak_threads = self.peak_threads.max(later.peak_threads);
+        self.forbidden += later.forbidden;
+        self.lifetime_peak = self.lifetime_peak.max(later.lifetime_peak);
+        self.negative_balance += later.negative_balance;
+        self.entries += later.entries;
+        self.active_time += later.active_time;
+    }
+
+    /// The rate at which memory was allocated, given that these stats cover `elapsed` time, such
+    /// as the interval between two flushes.
+    ///
+    /// Churn is often a better signal of a problem than absolute numbers of bytes. All rates are
+    /// zero if `elapsed` is zero.
+    pub fn rate(&self, elapsed: Duration) -> Rate {
+        let secs = elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return Rate::default();
+        }
+        Rate {
+            allocs_per_sec: self.count as f64 / secs,
+            bytes_per_sec: self.total as f64 / secs,
+        }
+    }
+
+    /// The average size of an allocation, or `None` if there were no allocations.
+    pub fn average_size(&self) -> Option<isize> {
+        if self.count > 0 {
+            Some(self.total / self.count)
+        } else {
+            None
         }
     }
 }
+
+/// Combine the stats of concurrent sources, such as threads or processes.
+///
+/// Counters and gauges are summed. The combined peaks are the sum of both peaks, which is an upper
+/// bound, since both sources did not necessarily reach their peak at the same time.
+/// `max_single` and `peak_at` are the larger of both. To combine consecutive time windows, use
+/// [Stat::merge] instead.
+impl Add for Stat {
+    type Output = Stat;
+
+    fn add(mut self, other: Stat) -> Stat {
+        self += other;
+        self
+    }
+}
+
+impl AddAssign for Stat {
+    fn add_assign(&mut self, other: Stat) {
+        self.current += other.current;
+        self.peak += other.peak;
+        self.peak_at = self.peak_at.max(other.peak_at);
+        self.total += other.total;
+        self.count += other.count;
+        self.max_single = self.max_single.max(other.max_single);
+        self.freed_in_drop += other.freed_in_drop;
+        self.freed_cross_thread += other.freed_cross_thread;
+        self.high_align += other.high_align;
+        self.padding += other.padding;
+        self.external += other.external;
+        self.shared += other.shared;
+        self.threads += other.threads;
+        self.peak_threads += other.peak_threads;
+        self.forbidden += other.forbidden;
+        self.lifetime_peak += other.lifetime_peak;
+        self.negative_balance += other.negative_balance;
+        self.entries += other.entries;
+        self.active_time += other.active_time;
+    }
+}
+
+/// The difference between two snapshots of the same stats, `later - earlier`.
+///
+/// Counters and gauges are subtracted. High-water marks (`peak`, `peak_at`, `max_single`,
+/// `peak_threads` and `lifetime_peak`) can't be subtracted, and are taken from `later`.
+impl Sub for Stat {
+    type Output = Stat;
+
+    fn sub(mut self, earlier: Stat) -> Stat {
+        self -= earlier;
+        self
+    }
+}
+
+impl SubAssign for Stat {
+    fn sub_assign(&mut self, earlier: Stat) {
+        self.current -= earlier.current;
+        self.total -= earlier.total;
+        self.count -= earlier.count;
+        self.freed_in_drop -= earlier.freed_in_drop;
+        self.freed_cross_thread -= earlier.freed_cross_thread;
+        self.high_align -= earlier.high_align;
+        self.padding -= earlier.padding;
+        self.external -= earlier.external;
+        self.shared -= earlier.shared;
+        self.threads -= earlier.threads;
+        self.forbidden -= earlier.forbidden;
+        self.negative_balance -= earlier.negative_balance;
+        self.entries -= earlier.entries;
+        self.active_time = self.active_time.saturating_sub(earlier.active_time);
+    }
+}
@@ -0,0 +1,117 @@
+//! Anonymous memory mappings that are attributed to a usecase.
+//!
+//! Large buffers and arena allocators often map memory directly instead of going through the
+//! global allocator, and therefore would not show up in any stats. A [Region] maps memory like
+//! `mmap` does, and reports it to the recorder through
+//! [Alloc::record_external_alloc](crate::Alloc::record_external_alloc):
+//!
+//! ```ignore
+//! let mut arena = {
+//!     let _guard = ALLOCATOR.with_usecase(MyUseCase::Arena);
+//!     memoria::region::Region::map(&ALLOCATOR, 64 << 20)?
+//! };
+//! arena[0] = 1;
+//! ```
+//!
+//! Requires the `region` feature and a unix platform.
+
+use std::alloc::GlobalAlloc;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+use crate::{Alloc, PointerTable, Recorder, UseCase, UseCaseRepr};
+
+/// An anonymous, private, read-write memory mapping, attributed to the usecase that was active
+/// when it was created.
+///
+/// The memory is zeroed, and unmapped when the region is dropped.
+pub struct Region<'a, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> {
+    ptr: NonNull<u8>,
+    len: usize,
+    alloc: &'a Alloc<U, R, A, P>,
+    use_case: UseCaseRepr,
+}
+
+impl<'a, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Region<'a, U, R, A, P> {
+    /// Map `len` bytes and attribute them to the current usecase.
+    ///
+    /// The size reported to the recorder is `len`, even though the kernel rounds mappings up to
+    /// whole pages and only backs pages with memory once they are touched.
+    pub fn map(alloc: &'a Alloc<U, R, A, P>, len: usize) -> io::Result<Self> {
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "can not map zero bytes",
+            ));
+        }
+        // SAFETY: an anonymous mapping at an address of the kernel's choosing does not alias any
+        // existing memory.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let use_case = alloc.current_usecase().unwrap_or_default().into_repr();
+        alloc.record_external_alloc(U::from_repr(use_case).unwrap_or_default(), len);
+        Ok(Region {
+            ptr: NonNull::new(ptr.cast()).expect("mmap returned null"),
+            len,
+            alloc,
+            use_case,
+        })
+    }
+
+    /// The usecase this region is attributed to.
+    pub fn use_case(&self) -> U {
+        U::from_repr(self.use_case).unwrap_or_default()
+    }
+}
+
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Deref for Region<'_, U, R, A, P> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the mapping is readable, `len` bytes long and lives as long as `self`.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> DerefMut
+    for Region<'_, U, R, A, P>
+{
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the mapping is writable, and only reachable through `self`.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Drop for Region<'_, U, R, A, P> {
+    fn drop(&mut self) {
+        // SAFETY: the mapping was created in `map` and is not referenced anymore.
+        unsafe {
+            libc::munmap(self.ptr.as_ptr().cast(), self.len);
+        }
+        self.alloc
+            .record_external_dealloc(self.use_case(), self.len);
+    }
+}
+
+// SAFETY: a region owns its memory like a `Box<[u8]>` does, and recording stats is thread-safe.
+unsafe impl<U: UseCase, R: Recorder<U> + Sync, A: GlobalAlloc + Sync, P: PointerTable> Send
+    for Region<'_, U, R, A, P>
+{
+}
+
+unsafe impl<U: UseCase, R: Recorder<U> + Sync, A: GlobalAlloc + Sync, P: PointerTable> Sync
+    for Region<'_, U, R, A, P>
+{
+}
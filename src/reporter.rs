@@ -0,0 +1,116 @@
+//! A background thread that periodically flushes stats.
+//!
+//! ```ignore
+//! let reporter = memoria::reporter::spawn(&ALLOCATOR, Duration::from_secs(10), |report| {
+//!     for (use_case, stat) in report.stats {
+//!         eprintln!("{use_case:?}: {stat}");
+//!     }
+//! });
+//!
+//! // ... at shutdown, flush one last time:
+//! reporter.stop();
+//! ```
+
+use std::alloc::GlobalAlloc;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use crate::worker::Worker;
+use crate::{Alloc, Error, PointerTable, Stat, StatsRecorder, UseCase};
+
+/// The result of a single flush.
+#[derive(Debug)]
+pub struct Report<U> {
+    /// Stats per usecase since the previous flush.
+    pub stats: Vec<(U, Stat)>,
+    /// How often each error occurred.
+    pub errors: Vec<(Error, usize)>,
+    /// The resident set size of the process at the time of the flush, or `None` if it could not
+    /// be read.
+    #[cfg(feature = "rss")]
+    pub rss: Option<crate::rss::RssReport>,
+    /// jemalloc's own stats at the time of the flush, or `None` if they could not be read.
+    #[cfg(feature = "jemalloc")]
+    pub jemalloc: Option<crate::jemalloc::JemallocStats>,
+}
+
+/// Handle to a running reporter thread, returned by [spawn].
+///
+/// The reporter stops when this handle is dropped, or when [Alloc::finalize] is called.
+#[must_use = "the reporter stops when the handle is dropped"]
+pub struct Reporter {
+    worker: Arc<Worker>,
+}
+
+impl Reporter {
+    /// Stop the reporter thread after flushing one last time, and wait for it to exit.
+    pub fn stop(self) {
+        self.worker.stop();
+    }
+}
+
+impl Drop for Reporter {
+    fn drop(&mut self) {
+        self.worker.stop();
+    }
+}
+
+/// Spawn a thread that flushes the recorder of `alloc` every `interval`, and hands the results
+/// to `sink`.
+///
+/// The flush itself runs inside of [Alloc::with_recorder], while `sink` is called outside of it.
+/// This means that `sink` can allocate, log, and even use `alloc` freely.
+///
+/// If a flush fails due to contention, it is retried at the next interval.
+pub fn spawn<U, A, P>(
+    alloc: &'static Alloc<U, StatsRecorder<U>, A, P>,
+    interval: Duration,
+    mut sink: impl FnMut(Report<U>) + Send + 'static,
+) -> Reporter
+where
+    U: UseCase + Send + Sync,
+    A: GlobalAlloc + Sync,
+    P: PointerTable,
+{
+    let worker = Worker::spawn("memoria-reporter", move |stopped| loop {
+        let stopping = !matches!(
+            stopped.recv_timeout(interval),
+            Err(mpsc::RecvTimeoutError::Timeout)
+        );
+
+        if let Ok(report) = flush(alloc) {
+            sink(report);
+        }
+
+        if stopping {
+            break;
+        }
+    });
+    alloc.workers.register(&worker);
+    Reporter { worker }
+}
+
+fn flush<U: UseCase, A: GlobalAlloc, P: PointerTable>(
+    alloc: &Alloc<U, StatsRecorder<U>, A, P>,
+) -> Result<Report<U>, Error> {
+    // Read outside of `with_recorder`, since reading the RSS allocates.
+    #[cfg(feature = "rss")]
+    let rss = crate::rss::RssReport::new(alloc).ok();
+    #[cfg(feature = "jemalloc")]
+    let jemalloc = crate::jemalloc::JemallocStats::read().ok();
+    alloc.with_recorder(|recorder| {
+        let mut report = Report {
+            stats: Vec::new(),
+            errors: Vec::new(),
+            #[cfg(feature = "rss")]
+            rss,
+            #[cfg(feature = "jemalloc")]
+            jemalloc,
+        };
+        recorder.flush(
+            |use_case, stat| report.stats.push((use_case, stat)),
+            |error, count| report.errors.push((error, count)),
+        );
+        Ok(report)
+    })
+}
@@ -0,0 +1,73 @@
+//! Compare the memory memoria knows about with the resident set size (RSS) of the process.
+//!
+//! memoria only sees memory that goes through the global allocator or is reported through
+//! [Alloc::record_external_alloc](crate::Alloc::record_external_alloc). Everything else, such as
+//! thread stacks, the binary itself, allocator fragmentation and memory mapped by C libraries,
+//! shows up as the difference between the two:
+//!
+//! ```ignore
+//! let rss = memoria::rss::RssReport::new(&ALLOCATOR)?;
+//! eprintln!("{} bytes resident, {} untracked", rss.resident, rss.untracked);
+//! ```
+//!
+//! [reporter](crate::reporter) includes this in every [Report](crate::reporter::Report).
+//!
+//! Reading the RSS is currently only supported on Linux and Android. Requires the `rss` feature.
+
+use std::alloc::GlobalAlloc;
+use std::io;
+
+use crate::{Alloc, PointerTable, Recorder, UseCase};
+
+/// The resident set size of the process next to the memory tracked by memoria.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RssReport {
+    /// The resident set size of the process, in bytes.
+    pub resident: usize,
+    /// The memory tracked by memoria, see [Alloc::tracked_bytes].
+    pub tracked: usize,
+    /// `resident - tracked`. This can be negative, since tracked memory is not necessarily
+    /// resident.
+    pub untracked: isize,
+}
+
+impl RssReport {
+    /// Read the current RSS and compare it with the memory tracked by `alloc`.
+    pub fn new<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable>(
+        alloc: &Alloc<U, R, A, P>,
+    ) -> io::Result<Self> {
+        let resident = resident_bytes()?;
+        let tracked = alloc.tracked_bytes();
+        Ok(RssReport {
+            resident,
+            tracked,
+            untracked: resident as isize - tracked as isize,
+        })
+    }
+}
+
+/// Return the resident set size of the process, in bytes.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn resident_bytes() -> io::Result<usize> {
+    let statm = std::fs::read_to_string("/proc/self/statm")?;
+    let pages: usize = statm
+        .split_whitespace()
+        .nth(1)
+        .and_then(|pages| pages.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed statm"))?;
+    // SAFETY: sysconf has no preconditions.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if page_size <= 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(pages * page_size as usize)
+}
+
+/// Return the resident set size of the process, in bytes.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn resident_bytes() -> io::Result<usize> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reading the RSS is not supported on this platform",
+    ))
+}
@@ -0,0 +1,66 @@
+use std::alloc::{GlobalAlloc, Layout};
+use std::hint::black_box;
+use std::time::Instant;
+
+use crate::{Alloc, OverheadEstimate, PointerTable, Recorder, UseCase};
+
+/// The number of operations timed per measurement, after as many for warming up.
+const ITERATIONS: u32 = 10_000;
+
+/// Estimate how much time memoria adds to allocations, and report the result to the recorder
+/// through [Recorder::on_overhead_estimate].
+///
+/// This times small allocations through the wrapped allocator alone and through `alloc` with a
+/// guard of the default usecase active, as well as creating guards. It takes a few milliseconds
+/// and is meant to be called once at startup, to get a rough idea of the cost of memoria on the
+/// machine at hand. The `overhead` benchmark in the repository measures more scenarios in more
+/// detail.
+///
+/// The allocations made while measuring are recorded like any other. As they are freed right
+/// away, they only show up in totals such as [Stat::total](crate::Stat::total).
+pub fn overhead_selftest<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable>(
+    alloc: &Alloc<U, R, A, P>,
+) -> OverheadEstimate {
+    let layout = Layout::new::<[u64; 8]>();
+    let baseline_ns = time_per_op(|| alloc_dealloc(&alloc.alloc, layout));
+    let tracked_ns = {
+        let _guard = alloc.with_usecase(U::default());
+        time_per_op(|| alloc_dealloc(alloc, layout))
+    };
+    let guard_ns = time_per_op(|| drop(black_box(alloc.with_usecase(U::default()))));
+
+    let estimate = OverheadEstimate {
+        baseline_ns,
+        tracked_ns,
+        guard_ns,
+    };
+    alloc
+        .synchronized(None, |_| {
+            alloc.recorder.on_overhead_estimate(estimate);
+            Ok(())
+        })
+        .ok();
+    estimate
+}
+
+fn alloc_dealloc(alloc: &impl GlobalAlloc, layout: Layout) {
+    // SAFETY: the layout has a non-zero size, and the pointer is freed with the same layout.
+    unsafe {
+        let ptr = black_box(alloc.alloc(layout));
+        if !ptr.is_null() {
+            alloc.dealloc(ptr, layout);
+        }
+    }
+}
+
+/// Return the average time of `op` in nanoseconds.
+fn time_per_op(mut op: impl FnMut()) -> f64 {
+    for _ in 0..ITERATIONS {
+        op();
+    }
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        op();
+    }
+    start.elapsed().as_nanos() as f64 / f64::from(ITERATIONS)
+}
@@ -0,0 +1,262 @@
+//! Attribute (de)serialization to usecases.
+//!
+//! Parsing is often the main source of allocations in a service. [deserialize_attributed] and
+//! [serialize_attributed] run a whole (de)serialization under a usecase:
+//!
+//! ```ignore
+//! let mut deserializer = serde_json::Deserializer::from_str(input);
+//! let payload: Payload =
+//!     memoria::serde::deserialize_attributed(&ALLOCATOR, MyUseCase::Parse, &mut deserializer)?;
+//! ```
+//!
+//! [FieldAttributed] goes one step further and attributes each top-level field of a map or struct
+//! to its own usecase:
+//!
+//! ```ignore
+//! let deserializer = FieldAttributed::new(&mut deserializer, &ALLOCATOR, |field| match field {
+//!     "users" => Some(MyUseCase::ParseUsers),
+//!     "config" => Some(MyUseCase::ParseConfig),
+//!     _ => None,
+//! });
+//! let payload = Payload::deserialize(deserializer)?;
+//! ```
+//!
+//! Requires the `serde` feature.
+
+use std::alloc::GlobalAlloc;
+use std::fmt;
+
+use ::serde::de::{DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Alloc, PointerTable, Recorder, UseCase};
+
+/// Deserialize a `T` while the given usecase is active.
+pub fn deserialize_attributed<'de, T, D, U, R, A, P>(
+    alloc: &Alloc<U, R, A, P>,
+    use_case: U,
+    deserializer: D,
+) -> Result<T, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+    U: UseCase,
+    R: Recorder<U>,
+    A: GlobalAlloc,
+    P: PointerTable,
+{
+    let _guard = alloc.with_usecase(use_case);
+    T::deserialize(deserializer)
+}
+
+/// Serialize `value` while the given usecase is active.
+pub fn serialize_attributed<T, S, U, R, A, P>(
+    alloc: &Alloc<U, R, A, P>,
+    use_case: U,
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: Serialize + ?Sized,
+    S: Serializer,
+    U: UseCase,
+    R: Recorder<U>,
+    A: GlobalAlloc,
+    P: PointerTable,
+{
+    let _guard = alloc.with_usecase(use_case);
+    value.serialize(serializer)
+}
+
+/// A `Deserializer` that attributes each top-level field of a map or struct to a usecase.
+///
+/// `field_use_case` is called with the name of each field, and the value of that field is
+/// deserialized under the returned usecase. If it returns `None`, the value is attributed to
+/// whatever usecase is currently active.
+///
+/// Keys of the top-level map must be strings. They are deserialized into an owned `String` before
+/// the value, which means that this only works with self-describing formats such as JSON.
+/// Anything other than a map or struct is deserialized as if the wrapper was not there.
+pub struct FieldAttributed<'a, D, F, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> {
+    inner: D,
+    alloc: &'a Alloc<U, R, A, P>,
+    field_use_case: F,
+}
+
+impl<'a, D, F, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable>
+    FieldAttributed<'a, D, F, U, R, A, P>
+where
+    F: Fn(&str) -> Option<U>,
+{
+    /// Wrap a deserializer.
+    pub fn new(inner: D, alloc: &'a Alloc<U, R, A, P>, field_use_case: F) -> Self {
+        FieldAttributed {
+            inner,
+            alloc,
+            field_use_case,
+        }
+    }
+
+    fn visitor<V>(self, visitor: V) -> (D, FieldVisitor<'a, V, F, U, R, A, P>) {
+        (
+            self.inner,
+            FieldVisitor {
+                inner: visitor,
+                alloc: self.alloc,
+                field_use_case: self.field_use_case,
+            },
+        )
+    }
+}
+
+macro_rules! forward {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, D::Error> {
+                self.inner.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, D, F, U, R, A, P> Deserializer<'de> for FieldAttributed<'_, D, F, U, R, A, P>
+where
+    D: Deserializer<'de>,
+    F: Fn(&str) -> Option<U>,
+    U: UseCase,
+    R: Recorder<U>,
+    A: GlobalAlloc,
+    P: PointerTable,
+{
+    type Error = D::Error;
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        let (inner, visitor) = self.visitor(visitor);
+        inner.deserialize_map(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        let (inner, visitor) = self.visitor(visitor);
+        inner.deserialize_struct(name, fields, visitor)
+    }
+
+    forward! {
+        deserialize_any()
+        deserialize_bool()
+        deserialize_i8()
+        deserialize_i16()
+        deserialize_i32()
+        deserialize_i64()
+        deserialize_i128()
+        deserialize_u8()
+        deserialize_u16()
+        deserialize_u32()
+        deserialize_u64()
+        deserialize_u128()
+        deserialize_f32()
+        deserialize_f64()
+        deserialize_char()
+        deserialize_str()
+        deserialize_string()
+        deserialize_bytes()
+        deserialize_byte_buf()
+        deserialize_option()
+        deserialize_unit()
+        deserialize_unit_struct(name: &'static str)
+        deserialize_newtype_struct(name: &'static str)
+        deserialize_seq()
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+        deserialize_identifier()
+        deserialize_ignored_any()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+struct FieldVisitor<'a, V, F, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> {
+    inner: V,
+    alloc: &'a Alloc<U, R, A, P>,
+    field_use_case: F,
+}
+
+impl<'de, V, F, U, R, A, P> Visitor<'de> for FieldVisitor<'_, V, F, U, R, A, P>
+where
+    V: Visitor<'de>,
+    F: Fn(&str) -> Option<U>,
+    U: UseCase,
+    R: Recorder<U>,
+    A: GlobalAlloc,
+    P: PointerTable,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.expecting(formatter)
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, map: M) -> Result<V::Value, M::Error> {
+        self.inner.visit_map(FieldMapAccess {
+            inner: map,
+            alloc: self.alloc,
+            field_use_case: self.field_use_case,
+            current_field: None,
+        })
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, seq: S) -> Result<V::Value, S::Error> {
+        self.inner.visit_seq(seq)
+    }
+}
+
+struct FieldMapAccess<'a, M, F, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> {
+    inner: M,
+    alloc: &'a Alloc<U, R, A, P>,
+    field_use_case: F,
+    current_field: Option<U>,
+}
+
+impl<'de, M, F, U, R, A, P> MapAccess<'de> for FieldMapAccess<'_, M, F, U, R, A, P>
+where
+    M: MapAccess<'de>,
+    F: Fn(&str) -> Option<U>,
+    U: UseCase,
+    R: Recorder<U>,
+    A: GlobalAlloc,
+    P: PointerTable,
+{
+    type Error = M::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, M::Error> {
+        let key = match self.inner.next_key::<String>()? {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        self.current_field = (self.field_use_case)(&key);
+        seed.deserialize(IntoDeserializer::<M::Error>::into_deserializer(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, M::Error> {
+        let _guard = self
+            .current_field
+            .take()
+            .and_then(|use_case| self.alloc.with_usecase(use_case));
+        self.inner.next_value_seed(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
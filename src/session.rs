@@ -0,0 +1,126 @@
+use std::alloc::{GlobalAlloc, System};
+use std::sync::{Arc, Mutex};
+
+use crate::measure::{MeasuredGuard, Measurement};
+use crate::{
+    Alloc, DefaultTable, PointerTable, Recorder, Stat, StatsRecorder, UseCase, UseCaseRepr,
+};
+
+/// Accounts the memory of a unit of work that is handled by multiple threads, such as a request
+/// in a multithreaded server.
+///
+/// Create a session when the request starts, clone it into every thread or task that works on
+/// the request and [enter](AttributionSession::enter) it there. While entered, the session's
+/// usecase is active and all allocations and deallocations of the thread are measured. Once the
+/// request is done, [finish](AttributionSession::finish) returns the stats aggregated over all
+/// threads:
+///
+/// ```ignore
+/// let session = ALLOCATOR.session(MyUseCase::Request);
+/// let handle = std::thread::spawn({
+///     let session = session.clone();
+///     move || session.scope(|| resize_images(&request))
+/// });
+/// session.scope(|| parse_body(&request));
+/// handle.join().unwrap();
+/// println!("request allocated {} bytes", session.finish().total);
+/// ```
+///
+/// Sessions are cheap to clone and can be entered any number of times, also on several threads
+/// at once. Entering one is about as expensive as [Alloc::with_usecase].
+///
+/// The stats of each time the session was entered are summed up like [Stat]'s `+` does, so
+/// `peak` is an upper bound of the actual peak of the session.
+pub struct AttributionSession<
+    'a,
+    U: UseCase,
+    R: Recorder<U> = StatsRecorder<U>,
+    A: GlobalAlloc = System,
+    P: PointerTable = DefaultTable,
+> {
+    alloc: &'a Alloc<U, R, A, P>,
+    use_case: UseCaseRepr,
+    stat: Arc<Mutex<Stat>>,
+}
+
+impl<'a, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable>
+    AttributionSession<'a, U, R, A, P>
+{
+    pub(crate) fn new(alloc: &'a Alloc<U, R, A, P>, use_case: U) -> Self {
+        AttributionSession {
+            alloc,
+            use_case: use_case.into_repr(),
+            stat: Arc::new(Mutex::new(Stat::ZERO)),
+        }
+    }
+
+    /// The usecase that is active while the session is entered.
+    pub fn use_case(&self) -> U {
+        U::from_repr(self.use_case).unwrap_or_default()
+    }
+
+    /// Enter the session on the current thread until the returned guard is dropped.
+    ///
+    /// If the usecase can not be switched, allocations are still measured.
+    pub fn enter(&self) -> SessionGuard<'_> {
+        let measurement = Measurement::start(self.alloc.thread_state());
+        SessionGuard {
+            guard: Some(MeasuredGuard::new(
+                measurement,
+                self.alloc.with_usecase_bytes(self.use_case),
+            )),
+            stat: &self.stat,
+        }
+    }
+
+    /// Run `f` with the session entered on the current thread.
+    pub fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        let _guard = self.enter();
+        f()
+    }
+
+    /// Return the stats aggregated over all threads so far, without finishing the session.
+    ///
+    /// Guards that are still alive are not included.
+    pub fn get(&self) -> Stat {
+        *self.stat.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Finish the session and return the stats aggregated over all threads.
+    ///
+    /// Clones of the session that are still alive keep working, but the returned stats do not
+    /// include what they record from now on.
+    pub fn finish(self) -> Stat {
+        self.get()
+    }
+}
+
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Clone
+    for AttributionSession<'_, U, R, A, P>
+{
+    fn clone(&self) -> Self {
+        AttributionSession {
+            alloc: self.alloc,
+            use_case: self.use_case,
+            stat: self.stat.clone(),
+        }
+    }
+}
+
+/// Keeps an [AttributionSession] entered on the current thread while it is alive.
+///
+/// Returned by [AttributionSession::enter].
+#[must_use = "the session is only entered while the guard is alive"]
+pub struct SessionGuard<'a> {
+    guard: Option<MeasuredGuard<'a>>,
+    stat: &'a Mutex<Stat>,
+}
+
+impl Drop for SessionGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(guard) = self.guard.take() {
+            let measured = guard.finish();
+            *self.stat.lock().unwrap_or_else(|e| e.into_inner()) += measured;
+        }
+    }
+}
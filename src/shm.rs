@@ -0,0 +1,324 @@
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicIsize, AtomicPtr, AtomicUsize, Ordering};
+
+use crate::{Recorder, Stat, UseCase, UseCaseRepr};
+
+struct ShmStat {
+    current: AtomicIsize,
+    peak: AtomicIsize,
+    total: AtomicIsize,
+    count: AtomicIsize,
+    max_single: AtomicIsize,
+    external: AtomicIsize,
+}
+
+impl ShmStat {
+    fn add(&self, size: isize) {
+        let current = self.current.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak.fetch_max(current, Ordering::Relaxed);
+        self.total.fetch_add(size, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.max_single.fetch_max(size, Ordering::Relaxed);
+    }
+
+    fn sub(&self, size: isize) {
+        self.current.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> Stat {
+        Stat {
+            current: self.current.load(Ordering::Relaxed),
+            peak: self.peak.load(Ordering::Relaxed),
+            total: self.total.load(Ordering::Relaxed),
+            count: self.count.load(Ordering::Relaxed),
+            max_single: self.max_single.load(Ordering::Relaxed),
+            external: self.external.load(Ordering::Relaxed),
+            ..Stat::ZERO
+        }
+    }
+}
+
+struct Row<const N: usize> {
+    stats: [ShmStat; N],
+    dropped: AtomicUsize,
+}
+
+/// The contents of the shared memory segment. An all-zero segment is valid.
+struct Segment<const P: usize, const N: usize> {
+    next_process: AtomicUsize,
+    rows: [Row<N>; P],
+}
+
+/// Marks a process that could not claim a row of its own.
+const NO_PROCESS: usize = usize::MAX;
+
+/// A recorder that keeps its stats in a shared memory segment, so that they can be aggregated
+/// across forked worker processes.
+///
+/// The segment holds a fixed-size table of atomic counters, with one row of `N` usecases per
+/// process and up to `P` processes. Recording an allocation only touches this table, so there is
+/// no IPC in the hot path. Any process can read the stats of all others.
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::Alloc<MyUseCase, memoria::ShmStatsRecorder<MyUseCase>> =
+///     memoria::Alloc::new_with(memoria::ShmStatsRecorder::new(), std::alloc::System);
+///
+/// // in the parent, before forking any workers:
+/// ALLOCATOR.with_recorder(|recorder| Ok(recorder.map())).unwrap()?;
+/// memoria::fork::install(&ALLOCATOR)?;
+///
+/// // later, in the parent:
+/// ALLOCATOR.with_recorder(|recorder| {
+///     recorder.aggregate(|use_case, stat| println!("{use_case:?}: {stat}"));
+///     Ok(())
+/// }).ok();
+/// ```
+///
+/// Nothing is recorded until [ShmStatsRecorder::map] is called. The segment has to be mapped
+/// before forking, so that it is shared with the child processes.
+///
+/// Each child claims a row of its own in [Recorder::on_fork], that is when
+/// [Alloc::after_fork](crate::Alloc::after_fork) is called, for example through the `fork`
+/// feature. Children that don't do that record into the row of their parent, which keeps the
+/// aggregate correct but loses the per-process breakdown. If all `P` rows are taken, further
+/// children record nothing. Events of usecases whose representation is `N` or larger are not
+/// recorded either, and counted in [ShmStatsRecorder::dropped].
+///
+/// Stats are cumulative: unlike [StatsRecorder](crate::StatsRecorder), there is no flush that
+/// resets them.
+///
+/// Requires the `shm` feature and a unix platform.
+pub struct ShmStatsRecorder<U: UseCase, const P: usize = 64, const N: usize = 64> {
+    segment: AtomicPtr<Segment<P, N>>,
+    process: AtomicUsize,
+    _phantom: PhantomData<U>,
+}
+
+impl<U: UseCase, const P: usize, const N: usize> ShmStatsRecorder<U, P, N> {
+    /// Construct a new recorder. No memory is mapped until [ShmStatsRecorder::map] is called.
+    pub const fn new() -> Self {
+        ShmStatsRecorder {
+            segment: AtomicPtr::new(ptr::null_mut()),
+            process: AtomicUsize::new(0),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Map the shared memory segment and start recording. Calling this again does nothing.
+    ///
+    /// This should be called through [Alloc::with_recorder](crate::Alloc::with_recorder), before
+    /// forking.
+    pub fn map(&self) -> io::Result<()> {
+        if !self.segment.load(Ordering::Acquire).is_null() {
+            return Ok(());
+        }
+        let len = mem::size_of::<Segment<P, N>>();
+        // SAFETY: an anonymous mapping at an address of the kernel's choosing does not alias any
+        // existing memory.
+        let segment = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if segment == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let segment = segment.cast::<Segment<P, N>>();
+        // SAFETY: the mapping is zeroed, which is a valid segment. The row of the mapping process
+        // is claimed right away.
+        unsafe { &*segment }
+            .next_process
+            .store(1, Ordering::Relaxed);
+        self.process.store(0, Ordering::Relaxed);
+        if self
+            .segment
+            .compare_exchange(
+                ptr::null_mut(),
+                segment,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            // SAFETY: the mapping was never shared.
+            unsafe { libc::munmap(segment.cast(), len) };
+        }
+        Ok(())
+    }
+
+    fn segment(&self) -> Option<&Segment<P, N>> {
+        // SAFETY: non-null pointers point to a mapped segment, which is only unmapped on drop.
+        unsafe { self.segment.load(Ordering::Acquire).as_ref() }
+    }
+
+    fn row(&self) -> Option<&Row<N>> {
+        let process = self.process.load(Ordering::Relaxed);
+        self.segment()?.rows.get(process)
+    }
+
+    fn with_stat(&self, use_case: U, f: impl FnOnce(&ShmStat)) -> bool {
+        let Some(row) = self.row() else {
+            return false;
+        };
+        let use_case = use_case.into_repr();
+        match usize::try_from(use_case)
+            .ok()
+            .and_then(|i| row.stats.get(i))
+        {
+            Some(stat) => f(stat),
+            None => {
+                row.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        true
+    }
+
+    /// The row of the current process, or `None` if it could not claim one or the segment is not
+    /// mapped yet. The process that called [ShmStatsRecorder::map] is always `0`.
+    pub fn process(&self) -> Option<usize> {
+        self.segment()?;
+        Some(self.process.load(Ordering::Relaxed)).filter(|&process| process != NO_PROCESS)
+    }
+
+    /// The number of processes that have claimed a row so far.
+    pub fn processes(&self) -> usize {
+        self.segment().map_or(0, |segment| {
+            segment.next_process.load(Ordering::Relaxed).min(P)
+        })
+    }
+
+    /// Return the stats of `use_case` in the given process.
+    pub fn get_process(&self, process: usize, use_case: U) -> Stat {
+        self.get_repr(process, use_case.into_repr())
+    }
+
+    fn get_repr(&self, process: usize, use_case: UseCaseRepr) -> Stat {
+        self.segment()
+            .and_then(|segment| segment.rows.get(process))
+            .and_then(|row| row.stats.get(usize::try_from(use_case).ok()?))
+            .map_or(Stat::ZERO, ShmStat::load)
+    }
+
+    /// Return the stats of `use_case` summed over all processes.
+    ///
+    /// `peak` and `max_single` are the sum and maximum of the per-process values respectively, so
+    /// `peak` is an upper bound of the actual peak across all processes.
+    pub fn get(&self, use_case: U) -> Stat {
+        let use_case = use_case.into_repr();
+        (0..self.processes()).fold(Stat::ZERO, |sum, process| {
+            sum + self.get_repr(process, use_case)
+        })
+    }
+
+    /// Call `f` for every process and usecase that allocated any memory.
+    pub fn for_each_process(&self, mut f: impl FnMut(usize, U, Stat)) {
+        let Some(segment) = self.segment() else {
+            return;
+        };
+        for (process, row) in segment.rows[..self.processes()].iter().enumerate() {
+            for (use_case, stat) in row.stats.iter().enumerate() {
+                let stat = stat.load();
+                if stat.count != 0 {
+                    f(
+                        process,
+                        U::from_repr(use_case as UseCaseRepr).unwrap_or_default(),
+                        stat,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Call `f` for every usecase that allocated any memory, with stats summed over all processes
+    /// like [ShmStatsRecorder::get] does.
+    pub fn aggregate(&self, mut f: impl FnMut(U, Stat)) {
+        let mut sums = [Stat::ZERO; N];
+        self.for_each_process(|_, use_case, stat| {
+            let i = use_case.into_repr() as usize;
+            sums[i] += stat;
+        });
+        for (use_case, stat) in sums.into_iter().enumerate() {
+            if stat.count != 0 {
+                f(
+                    U::from_repr(use_case as UseCaseRepr).unwrap_or_default(),
+                    stat,
+                );
+            }
+        }
+    }
+
+    /// The number of events across all processes that could not be recorded, because the usecase
+    /// did not fit into the table.
+    pub fn dropped(&self) -> usize {
+        self.segment().map_or(0, |segment| {
+            segment.rows[..self.processes()]
+                .iter()
+                .map(|row| row.dropped.load(Ordering::Relaxed))
+                .sum()
+        })
+    }
+}
+
+impl<U: UseCase, const P: usize, const N: usize> Default for ShmStatsRecorder<U, P, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<U: UseCase, const P: usize, const N: usize> Drop for ShmStatsRecorder<U, P, N> {
+    fn drop(&mut self) {
+        let segment = *self.segment.get_mut();
+        if !segment.is_null() {
+            // SAFETY: the segment is not referenced anymore.
+            unsafe { libc::munmap(segment.cast(), mem::size_of::<Segment<P, N>>()) };
+        }
+    }
+}
+
+// SAFETY: the segment only contains atomics.
+unsafe impl<U: UseCase + Send, const P: usize, const N: usize> Send for ShmStatsRecorder<U, P, N> {}
+unsafe impl<U: UseCase + Sync, const P: usize, const N: usize> Sync for ShmStatsRecorder<U, P, N> {}
+
+unsafe impl<U: UseCase, const P: usize, const N: usize> Recorder<U> for ShmStatsRecorder<U, P, N> {
+    fn on_alloc(&self, use_case: U, size: usize) -> bool {
+        self.with_stat(use_case, |stat| stat.add(size as isize))
+    }
+
+    fn on_dealloc(&self, use_case: U, size: usize) {
+        self.with_stat(use_case, |stat| stat.sub(size as isize));
+    }
+
+    fn on_external_alloc(&self, use_case: U, size: usize) {
+        self.with_stat(use_case, |stat| {
+            stat.add(size as isize);
+            stat.external.fetch_add(size as isize, Ordering::Relaxed);
+        });
+    }
+
+    fn on_external_dealloc(&self, use_case: U, size: usize) {
+        self.with_stat(use_case, |stat| {
+            stat.sub(size as isize);
+            stat.external.fetch_sub(size as isize, Ordering::Relaxed);
+        });
+    }
+
+    fn on_fork(&self) {
+        let Some(segment) = self.segment() else {
+            return;
+        };
+        let process = segment.next_process.fetch_add(1, Ordering::Relaxed);
+        self.process.store(
+            if process < P { process } else { NO_PROCESS },
+            Ordering::Relaxed,
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Callbacks that ask caches to evict memory once usage exceeds a watermark.
+//!
+//! ```ignore
+//! ALLOCATOR.register_shrinker(MyUseCase::Cache, 64 << 20, |excess| {
+//!     CACHE.evict_bytes(excess);
+//! });
+//!
+//! let shrinker = memoria::shrink::spawn(&ALLOCATOR, Duration::from_secs(1));
+//! ```
+
+use std::alloc::GlobalAlloc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use crate::sync::SpinLock;
+use crate::worker::Worker;
+use crate::{Alloc, PointerTable, Recorder, UseCase, UseCaseRepr};
+
+type Callback = Arc<dyn Fn(usize) + Send + Sync>;
+
+/// Identifies a shrinker registered with [Alloc::register_shrinker].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShrinkerId(u64);
+
+/// The shrinkers of an [Alloc], see [Alloc::register_shrinker].
+pub(crate) struct Shrinkers {
+    entries: SpinLock<Vec<Entry>>,
+    next_id: AtomicU64,
+    watermark: AtomicUsize,
+}
+
+struct Entry {
+    id: ShrinkerId,
+    use_case: UseCaseRepr,
+    watermark: usize,
+    callback: Callback,
+}
+
+impl Shrinkers {
+    pub(crate) const fn new() -> Self {
+        Shrinkers {
+            entries: SpinLock::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+            watermark: AtomicUsize::new(usize::MAX),
+        }
+    }
+}
+
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Alloc<U, R, A, P> {
+    /// Register `callback` to be invoked by [Alloc::shrink] while the memory used by `use_case`
+    /// is above `watermark` bytes, or while [Alloc::tracked_bytes] is above the global watermark
+    /// set with [Alloc::set_shrink_watermark].
+    ///
+    /// The callback receives the number of bytes it is asked to free, which is the larger of the
+    /// two excesses, and should evict what it can, for example from a cache. It is never called
+    /// from within an allocation, so it can allocate, lock and use this allocator freely. Usually
+    /// it is called from the thread started with [shrink::spawn](crate::shrink::spawn).
+    ///
+    /// The usage of `use_case` is counted like for [Alloc::set_cap], starting when the first
+    /// shrinker or cap is registered for it.
+    pub fn register_shrinker(
+        &self,
+        use_case: U,
+        watermark: usize,
+        callback: impl Fn(usize) + Send + Sync + 'static,
+    ) -> ShrinkerId {
+        let use_case = use_case.into_repr();
+        let id = ShrinkerId(self.shrinkers.next_id.fetch_add(1, Ordering::Relaxed));
+        let entry = Entry {
+            id,
+            use_case,
+            watermark,
+            callback: Arc::new(callback),
+        };
+        self.synchronized(None, |_| {
+            self.caps.watch(use_case);
+            Ok(())
+        })
+        .ok();
+        self.shrinkers.entries.with(|entries| entries.push(entry));
+        id
+    }
+
+    /// Remove a shrinker registered with [Alloc::register_shrinker]. Calls that are already in
+    /// progress are not interrupted.
+    pub fn unregister_shrinker(&self, id: ShrinkerId) {
+        let removed = self.shrinkers.entries.with(|entries| {
+            entries
+                .iter()
+                .position(|entry| entry.id == id)
+                .map(|index| entries.swap_remove(index))
+        });
+        // Dropping the callback can free memory, which must happen outside of the lock.
+        drop(removed);
+    }
+
+    /// Invoke all shrinkers once [Alloc::tracked_bytes] exceeds `bytes`, see
+    /// [Alloc::register_shrinker]. By default, there is no global watermark.
+    pub fn set_shrink_watermark(&self, bytes: usize) {
+        self.shrinkers.watermark.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Invoke every shrinker whose usecase, or the whole allocator, is above its watermark, and
+    /// return how many were invoked.
+    ///
+    /// [shrink::spawn](crate::shrink::spawn) calls this periodically, but it can also be called
+    /// directly, for example after a large request has been handled.
+    pub fn shrink(&self) -> usize {
+        let global_excess = self
+            .tracked_bytes()
+            .saturating_sub(self.shrinkers.watermark.load(Ordering::Relaxed));
+        let mut due = Vec::new();
+        self.shrinkers.entries.with(|entries| {
+            for entry in entries.iter() {
+                let used = self.caps.used(entry.use_case).unwrap_or(0);
+                let excess = used.saturating_sub(entry.watermark).max(global_excess);
+                if excess > 0 {
+                    due.push((entry.callback.clone(), excess));
+                }
+            }
+        });
+
+        let invoked = due.len();
+        for (callback, excess) in due {
+            callback(excess);
+        }
+        invoked
+    }
+}
+
+/// Handle to a running shrinker thread, returned by [spawn].
+///
+/// The thread stops when this handle is dropped, or when [Alloc::finalize] is called.
+#[must_use = "the shrinker thread stops when the handle is dropped"]
+pub struct ShrinkerThread {
+    worker: Arc<Worker>,
+}
+
+impl ShrinkerThread {
+    /// Stop the shrinker thread, and wait for it to exit.
+    pub fn stop(self) {
+        self.worker.stop();
+    }
+}
+
+impl Drop for ShrinkerThread {
+    fn drop(&mut self) {
+        self.worker.stop();
+    }
+}
+
+/// Spawn a thread that calls [Alloc::shrink] on `alloc` every `interval`.
+pub fn spawn<U, R, A, P>(alloc: &'static Alloc<U, R, A, P>, interval: Duration) -> ShrinkerThread
+where
+    U: UseCase,
+    R: Recorder<U>,
+    A: GlobalAlloc,
+    P: PointerTable,
+    Alloc<U, R, A, P>: Sync,
+{
+    let worker = Worker::spawn("memoria-shrinker", move |stopped| {
+        while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+            alloc.shrink();
+        }
+    });
+    alloc.workers.register(&worker);
+    ShrinkerThread { worker }
+}
@@ -0,0 +1,156 @@
+//! Dump stats on a signal, such as `kill -USR1 <pid>`.
+//!
+//! Signal handlers may only call async-signal-safe functions, which rules out allocating,
+//! locking, and therefore reading the recorder. Instead, stats are rendered ahead of time into a
+//! static buffer by [refresh], and the signal handler merely writes that buffer to a file
+//! descriptor:
+//!
+//! ```ignore
+//! // write to stderr on SIGUSR1
+//! memoria::signal::install(libc::SIGUSR1, 2)?;
+//!
+//! // periodically, for example in a reporter thread:
+//! memoria::signal::refresh(&ALLOCATOR);
+//! ```
+//!
+//! The dump therefore shows the stats as of the last call to [refresh]. A refresh that would
+//! overwrite a buffer that a signal handler is still dumping is skipped.
+//!
+//! Requires the `signal` feature and a unix platform.
+
+use std::alloc::GlobalAlloc;
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::io::{self, Cursor, Write};
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+
+use crate::export::Label;
+use crate::{Alloc, PointerTable, Stat, StatsRecorder, UseCase};
+
+/// The size of the pre-rendered buffer. Output beyond this is truncated.
+pub const BUFFER_SIZE: usize = 16 * 1024;
+
+struct Buffer {
+    data: UnsafeCell<[u8; BUFFER_SIZE]>,
+    len: AtomicUsize,
+    /// The number of signal handlers that are reading `data`.
+    readers: AtomicUsize,
+}
+
+// Access is coordinated through ACTIVE, RENDERING and `readers`.
+unsafe impl Sync for Buffer {}
+
+static BUFFERS: [Buffer; 2] = [
+    Buffer {
+        data: UnsafeCell::new([0; BUFFER_SIZE]),
+        len: AtomicUsize::new(0),
+        readers: AtomicUsize::new(0),
+    },
+    Buffer {
+        data: UnsafeCell::new([0; BUFFER_SIZE]),
+        len: AtomicUsize::new(0),
+        readers: AtomicUsize::new(0),
+    },
+];
+
+/// Index of the buffer that the signal handler reads from. The other one is written to by
+/// [render].
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+/// Serializes concurrent calls to [render].
+static RENDERING: AtomicBool = AtomicBool::new(false);
+static FD: AtomicI32 = AtomicI32::new(-1);
+
+/// Install a handler for `signal` that writes the pre-rendered stats to `fd`.
+///
+/// `fd` must stay open for as long as the handler is installed.
+pub fn install(signal: libc::c_int, fd: RawFd) -> io::Result<()> {
+    FD.store(fd, Ordering::Release);
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Render the current stats of `alloc` into the buffer dumped by the signal handler, without
+/// resetting them.
+pub fn refresh<U: UseCase + fmt::Debug, A: GlobalAlloc, P: PointerTable>(
+    alloc: &Alloc<U, StatsRecorder<U>, A, P>,
+) {
+    alloc
+        .with_recorder(|recorder| {
+            let mut stats = Vec::new();
+            recorder.peek(|use_case, stat| stats.push((use_case, stat)));
+            render(stats);
+            Ok(())
+        })
+        .ok();
+}
+
+fn render<U: UseCase + fmt::Debug>(stats: impl IntoIterator<Item = (U, Stat)>) {
+    if RENDERING.swap(true, Ordering::Acquire) {
+        return;
+    }
+
+    let inactive = &BUFFERS[1 - ACTIVE.load(Ordering::SeqCst)];
+    // A signal handler that started before the last flip might still be dumping this buffer.
+    // Signal handlers must not be waited for, so its contents are only replaced next time.
+    if inactive.readers.load(Ordering::SeqCst) != 0 {
+        RENDERING.store(false, Ordering::Release);
+        return;
+    }
+    // SAFETY: Signal handlers only read a buffer after registering in `readers` and then seeing
+    // it active. As this one stays inactive until the flip below, any handler that registers
+    // from now on backs off, and RENDERING ensures we are the only writer.
+    let data = unsafe { &mut *inactive.data.get() };
+    let mut cursor = Cursor::new(&mut data[..]);
+    writeln!(cursor, "memoria: stats per usecase:").ok();
+    for (use_case, stat) in stats {
+        // a write error means the buffer is full, so the output is truncated
+        if writeln!(cursor, "  {}: {stat}", Label(&use_case)).is_err() {
+            break;
+        }
+    }
+    inactive
+        .len
+        .store(cursor.position() as usize, Ordering::Release);
+    ACTIVE.fetch_xor(1, Ordering::SeqCst);
+
+    RENDERING.store(false, Ordering::Release);
+}
+
+extern "C" fn handler(_signal: libc::c_int) {
+    let fd = FD.load(Ordering::Acquire);
+    if fd < 0 {
+        return;
+    }
+    // Register as a reader of the active buffer, and retry if it was flipped in the meantime, in
+    // which case `render` might be writing to it.
+    let buffer = loop {
+        let active = ACTIVE.load(Ordering::SeqCst);
+        let buffer = &BUFFERS[active];
+        buffer.readers.fetch_add(1, Ordering::SeqCst);
+        if ACTIVE.load(Ordering::SeqCst) == active {
+            break buffer;
+        }
+        buffer.readers.fetch_sub(1, Ordering::SeqCst);
+    };
+    let len = buffer.len.load(Ordering::Acquire);
+    let data = buffer.data.get() as *const u8;
+    let mut written = 0;
+    while written < len {
+        // write(2) is async-signal-safe
+        let rv = unsafe { libc::write(fd, data.add(written) as *const _, len - written) };
+        if rv <= 0 {
+            break;
+        }
+        written += rv as usize;
+    }
+    buffer.readers.fetch_sub(1, Ordering::Release);
+}
@@ -0,0 +1,255 @@
+use std::alloc::Layout;
+use std::marker::PhantomData;
+
+use dashmap::DashMap;
+
+use crate::sync::ResettableCell;
+
+use crate::{
+    Callsite, Error, OverheadEstimate, Recorder, StatsRecorder, Tag, UseCase, UseCaseRepr,
+};
+
+// Each power of two is split into 2^SUB_BUCKET_BITS linear sub-buckets, which bounds the relative
+// error of a quantile to 1/2^SUB_BUCKET_BITS.
+const SUB_BUCKET_BITS: u32 = 4;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+const BUCKETS: usize = (usize::BITS - SUB_BUCKET_BITS + 1) as usize * SUB_BUCKETS;
+
+/// A mergeable quantile sketch of allocation sizes.
+///
+/// Sizes are counted in logarithmic buckets, each power of two being split into 16 linear
+/// sub-buckets. Quantiles are therefore accurate to within 6.25% of the true value, and the sketch
+/// has a fixed size regardless of how many values were recorded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct QuantileSketch {
+    counts: [u64; BUCKETS],
+    count: u64,
+}
+
+impl Default for QuantileSketch {
+    fn default() -> Self {
+        QuantileSketch {
+            counts: [0; BUCKETS],
+            count: 0,
+        }
+    }
+}
+
+impl QuantileSketch {
+    fn bucket(size: usize) -> usize {
+        if size < SUB_BUCKETS {
+            return size;
+        }
+        let exponent = usize::BITS - 1 - size.leading_zeros();
+        let shift = exponent - SUB_BUCKET_BITS;
+        let sub_bucket = (size >> shift) & (SUB_BUCKETS - 1);
+        (shift as usize + 1) * SUB_BUCKETS + sub_bucket
+    }
+
+    /// The smallest and largest size that fall into a bucket.
+    fn bucket_range(bucket: usize) -> (usize, usize) {
+        if bucket < SUB_BUCKETS {
+            return (bucket, bucket);
+        }
+        let shift = bucket / SUB_BUCKETS - 1;
+        let sub_bucket = bucket % SUB_BUCKETS;
+        let lower = (SUB_BUCKETS + sub_bucket) << shift;
+        (lower, lower + ((1 << shift) - 1))
+    }
+
+    /// Add a value to the sketch.
+    pub fn record(&mut self, size: usize) {
+        self.counts[Self::bucket(size)] += 1;
+        self.count += 1;
+    }
+
+    /// Add all values of another sketch to this one.
+    pub fn merge(&mut self, other: &QuantileSketch) {
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+        self.count += other.count;
+    }
+
+    /// The number of recorded values.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Estimate the value at quantile `q`, where `q` is between `0.0` and `1.0`.
+    ///
+    /// For example, `quantile(0.99)` is the p99 allocation size. Returns `None` if the sketch is
+    /// empty.
+    pub fn quantile(&self, q: f64) -> Option<usize> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64).round() as u64;
+        let mut seen = 0;
+        for (bucket, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen > rank {
+                let (lower, upper) = Self::bucket_range(bucket);
+                return Some(lower + (upper - lower) / 2);
+            }
+        }
+        None
+    }
+}
+
+/// A recorder that maintains a [QuantileSketch] of allocation sizes per usecase, and otherwise
+/// forwards to another recorder `R`.
+///
+/// Use this to monitor p50/p95/p99 allocation sizes per usecase. Memory usage is bounded at about
+/// 8 KiB per usecase, and recording an allocation does not allocate except for the first
+/// allocation of each usecase.
+///
+/// Requires the `sketch` feature.
+pub struct SketchRecorder<U: UseCase, R: Recorder<U> = StatsRecorder<U>> {
+    inner: R,
+    sketches: ResettableCell<DashMap<UseCaseRepr, Box<QuantileSketch>>>,
+    _phantom: PhantomData<U>,
+}
+
+impl<U: UseCase, R: Recorder<U>> SketchRecorder<U, R> {
+    /// Construct a new recorder.
+    pub const fn new(inner: R) -> Self {
+        SketchRecorder {
+            inner,
+            sketches: ResettableCell::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Access the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get the sketch for a single usecase.
+    pub fn get_sketch(&self, use_case: U) -> QuantileSketch {
+        self.sketches
+            .get()
+            .and_then(|sketches| sketches.get(&use_case.into_repr()).map(|x| **x))
+            .unwrap_or_default()
+    }
+
+    fn record(&self, use_case: UseCaseRepr, size: usize) {
+        self.sketches
+            .get_or_init(DashMap::new)
+            .entry(use_case)
+            .or_default()
+            .record(size);
+    }
+
+    /// Return all sketches and reset them.
+    pub fn flush_sketches(&self, mut sketch_fn: impl FnMut(U, &QuantileSketch)) {
+        if let Some(sketches) = self.sketches.get() {
+            for kv in sketches.iter() {
+                sketch_fn(U::from_repr(*kv.key()).unwrap_or_default(), kv.value());
+            }
+            sketches.clear();
+        }
+    }
+}
+
+unsafe impl<U: UseCase, R: Recorder<U>> Recorder<U> for SketchRecorder<U, R> {
+    fn on_alloc(&self, use_case: U, size: usize) -> bool {
+        let use_case_bytes: UseCaseRepr = use_case.into_repr();
+        self.record(use_case_bytes, size);
+        self.inner
+            .on_alloc(U::from_repr(use_case_bytes).unwrap_or_default(), size)
+    }
+
+    fn on_shared_alloc(&self, use_case: U, size: usize) -> bool {
+        let use_case_bytes: UseCaseRepr = use_case.into_repr();
+        self.record(use_case_bytes, size);
+        self.inner
+            .on_shared_alloc(U::from_repr(use_case_bytes).unwrap_or_default(), size)
+    }
+
+    fn on_alloc_layout(&self, use_case: U, layout: Layout) {
+        self.inner.on_alloc_layout(use_case, layout)
+    }
+
+    fn on_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_dealloc(use_case, size)
+    }
+
+    fn on_shared_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_shared_dealloc(use_case, size)
+    }
+
+    fn on_attributed_drop(&self, use_case: U, size: usize) {
+        self.inner.on_attributed_drop(use_case, size)
+    }
+
+    fn on_transfer(&self, from: U, to: U, size: usize) {
+        self.inner.on_transfer(from, to, size)
+    }
+
+    fn on_cross_thread_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_cross_thread_dealloc(use_case, size)
+    }
+
+    fn on_external_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_alloc(use_case, size)
+    }
+
+    fn on_external_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_dealloc(use_case, size)
+    }
+
+    fn on_callsite_alloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_alloc(use_case, callsite, size)
+    }
+
+    fn on_callsite_dealloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_dealloc(use_case, callsite, size)
+    }
+
+    fn on_tagged_alloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_alloc(use_case, tag, size)
+    }
+
+    fn on_tagged_dealloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_dealloc(use_case, tag, size)
+    }
+
+    fn on_forbidden_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_forbidden_alloc(use_case, size)
+    }
+
+    fn on_usecase_enter(&self, use_case: U) {
+        self.inner.on_usecase_enter(use_case)
+    }
+
+    fn on_usecase_exit(&self, use_case: U) {
+        self.inner.on_usecase_exit(use_case)
+    }
+
+    fn on_guard_enter(&self, use_case: U) {
+        self.inner.on_guard_enter(use_case)
+    }
+
+    fn on_guard_exit(&self, use_case: U) {
+        self.inner.on_guard_exit(use_case)
+    }
+
+    fn on_flush(&self) {
+        self.inner.on_flush()
+    }
+
+    fn on_overhead_estimate(&self, estimate: OverheadEstimate) {
+        self.inner.on_overhead_estimate(estimate)
+    }
+
+    fn on_fork(&self) {
+        self.sketches.reset();
+        self.inner.on_fork()
+    }
+
+    fn on_error(&self, code: Error, size: Option<usize>) {
+        self.inner.on_error(code, size)
+    }
+}
@@ -0,0 +1,145 @@
+//! Attribution of allocations to several usecases at once, see [Alloc::with_usecases].
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::alloc::GlobalAlloc;
+
+use crate::sync::SpinLock;
+use crate::{Alloc, Guard, PointerTable, Recorder, UseCase, UseCaseRepr};
+
+/// Identifies a set of shares passed to [Alloc::with_usecases], zero if allocations are not split.
+pub(crate) type SplitId = u16;
+
+/// A set of shares, and the sum of their weights.
+type Shares = (&'static [(UseCaseRepr, u32)], u64);
+
+/// All sets of shares ever passed to [Alloc::with_usecases], where set `i` has the id `i + 1`.
+/// They are never freed, since live allocations refer to them.
+static SPLITS: SpinLock<Vec<Shares>> = SpinLock::new(Vec::new());
+
+/// Return the id of `shares`, registering it if it is new, or zero if there are too many sets.
+///
+/// Must be called while memoria's bookkeeping is busy, since it allocates.
+fn intern(shares: &[(UseCaseRepr, u32)]) -> SplitId {
+    SPLITS.with(|splits| {
+        if let Some(index) = splits.iter().position(|(known, _)| *known == shares) {
+            return index as SplitId + 1;
+        }
+        if splits.len() >= SplitId::MAX as usize {
+            return 0;
+        }
+        let total = shares.iter().map(|(_, weight)| u64::from(*weight)).sum();
+        splits.push((Box::leak(shares.into()), total));
+        splits.len() as SplitId
+    })
+}
+
+/// Call `f` with every usecase that `size` bytes of `use_case` are attributed to, and its share
+/// of them.
+///
+/// Without a split, that is just `use_case` itself. Otherwise each usecase of the split gets a
+/// share according to its weight, rounded down, and the first one gets the rest. Usecases whose
+/// share rounds down to zero are skipped.
+pub(crate) fn for_each_share(
+    split: SplitId,
+    use_case: UseCaseRepr,
+    size: usize,
+    mut f: impl FnMut(UseCaseRepr, usize),
+) {
+    let Some((shares, total)) = split
+        .checked_sub(1)
+        .and_then(|index| SPLITS.with(|splits| splits.get(index as usize).copied()))
+    else {
+        return f(use_case, size);
+    };
+    let share_of = |weight: u32| (size as u128 * weight as u128 / total as u128) as usize;
+    let rest: usize = shares[1..]
+        .iter()
+        .map(|(_, weight)| share_of(*weight))
+        .sum();
+    f(shares[0].0, size - rest);
+    for &(use_case, weight) in &shares[1..] {
+        let share = share_of(weight);
+        if share > 0 {
+            f(use_case, share);
+        }
+    }
+}
+
+impl<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Alloc<U, R, A, P> {
+    /// Like [Alloc::with_usecase], but split the attribution of allocations across several
+    /// usecases according to their weights, such as a buffer that is shared by two pipelines:
+    ///
+    /// ```ignore
+    /// let buffer = {
+    ///     let _guard = ALLOCATOR.with_usecases(&[(MyUseCase::Ingest, 1), (MyUseCase::Export, 1)]);
+    ///     Vec::with_capacity(1 << 20)
+    /// };
+    /// ```
+    ///
+    /// Each usecase is reported its share of every allocation and deallocation through
+    /// [Recorder::on_shared_alloc] and [Recorder::on_shared_dealloc], rounded down, with the rest
+    /// going to the first usecase. The first usecase is the current usecase while the guard is
+    /// alive, and otherwise owns the allocations: it is charged for them against caps, and they
+    /// are reported under it in leak reports, transfers, and the breakdowns by callsite and tag.
+    ///
+    /// Usecases with a weight of zero are ignored. Every distinct set of shares is stored for the
+    /// lifetime of the process, and at most 65535 of them are supported. Further sets only
+    /// attribute allocations to their first usecase.
+    pub fn with_usecases(&self, shares: &[(U, u32)]) -> Option<Guard<'_>>
+    where
+        U: Clone,
+    {
+        // Collected and interned while memoria's bookkeeping is busy, such that the allocations
+        // are not attributed to the caller.
+        let (first, split) = self
+            .synchronized(None, |_| {
+                let shares: Vec<(UseCaseRepr, u32)> = shares
+                    .iter()
+                    .filter(|(_, weight)| *weight > 0)
+                    .map(|(use_case, weight)| (use_case.clone().into_repr(), *weight))
+                    .collect();
+                let split = if shares.len() > 1 { intern(&shares) } else { 0 };
+                Ok((shares.first().map(|(use_case, _)| *use_case), split))
+            })
+            .ok()?;
+        let first = first.unwrap_or_else(|| U::default().into_repr());
+        self.with_usecase_inner(first, None, None, false, split)
+    }
+}
+
+/// Report an allocation of `size` bytes for `use_case` to `recorder`, split into the shares of
+/// `split`, and return whether it is tracked.
+#[inline]
+pub(crate) fn report_alloc<U: UseCase, R: Recorder<U> + ?Sized>(
+    recorder: &R,
+    split: SplitId,
+    use_case: UseCaseRepr,
+    size: usize,
+) -> bool {
+    if split == 0 {
+        return recorder.on_alloc(U::from_repr(use_case).unwrap_or_default(), size);
+    }
+    let mut tracked = None;
+    for_each_share(split, use_case, size, |use_case, share| {
+        let shared = recorder.on_shared_alloc(U::from_repr(use_case).unwrap_or_default(), share);
+        tracked.get_or_insert(shared);
+    });
+    tracked.unwrap_or(false)
+}
+
+/// Report freeing `size` bytes of `use_case` to `recorder`, split into the shares of `split`.
+#[inline]
+pub(crate) fn report_dealloc<U: UseCase, R: Recorder<U> + ?Sized>(
+    recorder: &R,
+    split: SplitId,
+    use_case: UseCaseRepr,
+    size: usize,
+) {
+    if split == 0 {
+        return recorder.on_dealloc(U::from_repr(use_case).unwrap_or_default(), size);
+    }
+    for_each_share(split, use_case, size, |use_case, share| {
+        recorder.on_shared_dealloc(U::from_repr(use_case).unwrap_or_default(), share)
+    });
+}
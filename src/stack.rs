@@ -0,0 +1,257 @@
+use std::alloc::Layout;
+use std::marker::PhantomData;
+
+use dashmap::DashMap;
+
+use crate::sync::ResettableCell;
+
+use crate::{
+    Callsite, Error, OverheadEstimate, Recorder, StatsRecorder, Tag, UseCase, UseCaseRepr,
+};
+
+/// The maximum number of frames captured per backtrace. Deeper stacks are truncated.
+pub const BACKTRACE_DEPTH: usize = 32;
+
+/// A truncated, unresolved stack trace.
+///
+/// Capturing only records instruction pointers, symbols are resolved lazily through
+/// [StackTrace::resolve].
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct StackTrace {
+    frames: [usize; BACKTRACE_DEPTH],
+    len: usize,
+}
+
+impl StackTrace {
+    fn capture() -> Self {
+        let mut rv = StackTrace {
+            frames: [0; BACKTRACE_DEPTH],
+            len: 0,
+        };
+        // trace_unsynchronized does not take the crate's global lock, which might be held by a
+        // thread that is allocating. We are already serialized per-thread by `synchronized`.
+        unsafe {
+            backtrace::trace_unsynchronized(|frame| {
+                rv.frames[rv.len] = frame.ip() as usize;
+                rv.len += 1;
+                rv.len < BACKTRACE_DEPTH
+            });
+        }
+        rv
+    }
+
+    /// The instruction pointers of this stack trace, innermost frame first.
+    pub fn frames(&self) -> &[usize] {
+        &self.frames[..self.len]
+    }
+
+    /// Resolve the symbol names of all frames. Frames that cannot be resolved are rendered as
+    /// their address.
+    ///
+    /// This is slow and allocates.
+    pub fn resolve(&self) -> Vec<String> {
+        self.frames()
+            .iter()
+            .map(|&ip| {
+                let mut name = None;
+                backtrace::resolve(ip as *mut _, |symbol| {
+                    if name.is_none() {
+                        name = symbol.name().map(|name| name.to_string());
+                    }
+                });
+                name.unwrap_or_else(|| format!("{ip:#x}"))
+            })
+            .collect()
+    }
+}
+
+/// Allocation statistics for a single (usecase, stack trace) pair.
+#[derive(Default, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StackStat {
+    /// The amount of memory allocated from this stack trace in total.
+    pub total: usize,
+    /// The number of allocations made from this stack trace.
+    pub count: usize,
+}
+
+/// A recorder that captures stack traces for large allocations, and otherwise forwards to another
+/// recorder `R`.
+///
+/// For every allocation of at least `min_size` bytes, a truncated stack trace is captured and the
+/// allocated bytes are aggregated per (usecase, stack trace). This tells you which code path
+/// inside of a usecase is responsible for the big buffers.
+///
+/// Capturing a stack trace is expensive, so `min_size` should be chosen such that only a small
+/// fraction of allocations is affected.
+///
+/// Requires the `backtrace` feature.
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::Alloc<MyUseCase, memoria::BacktraceRecorder<MyUseCase>> =
+///     memoria::Alloc::new_with(
+///         memoria::BacktraceRecorder::new(memoria::StatsRecorder::new(), 1024 * 1024),
+///         std::alloc::System,
+///     );
+/// ```
+pub struct BacktraceRecorder<U: UseCase, R: Recorder<U> = StatsRecorder<U>> {
+    inner: R,
+    min_size: usize,
+    stacks: ResettableCell<DashMap<(UseCaseRepr, StackTrace), StackStat>>,
+    _phantom: PhantomData<U>,
+}
+
+impl<U: UseCase, R: Recorder<U>> BacktraceRecorder<U, R> {
+    /// Construct a new recorder that captures stack traces for allocations of at least
+    /// `min_size` bytes.
+    pub const fn new(inner: R, min_size: usize) -> Self {
+        BacktraceRecorder {
+            inner,
+            min_size,
+            stacks: ResettableCell::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Access the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    fn record(&self, use_case: UseCaseRepr, size: usize) {
+        let mut stat = self
+            .stacks
+            .get_or_init(DashMap::new)
+            .entry((use_case, StackTrace::capture()))
+            .or_default();
+        stat.total += size;
+        stat.count += 1;
+    }
+
+    /// Return all recorded stack traces and reset them.
+    ///
+    /// Like [StatsRecorder::flush], this should be called through
+    /// [Alloc::with_recorder](crate::Alloc::with_recorder).
+    pub fn flush_stacks(&self, mut stack_fn: impl FnMut(U, &StackTrace, StackStat)) {
+        if let Some(stacks) = self.stacks.get() {
+            for kv in stacks.iter() {
+                let (use_case, stack) = kv.key();
+                stack_fn(
+                    U::from_repr(*use_case).unwrap_or_default(),
+                    stack,
+                    *kv.value(),
+                );
+            }
+            stacks.clear();
+        }
+    }
+}
+
+unsafe impl<U: UseCase, R: Recorder<U>> Recorder<U> for BacktraceRecorder<U, R> {
+    fn on_alloc(&self, use_case: U, size: usize) -> bool {
+        if size >= self.min_size {
+            let use_case_bytes: UseCaseRepr = use_case.into_repr();
+            self.record(use_case_bytes, size);
+            self.inner
+                .on_alloc(U::from_repr(use_case_bytes).unwrap_or_default(), size)
+        } else {
+            self.inner.on_alloc(use_case, size)
+        }
+    }
+
+    fn on_shared_alloc(&self, use_case: U, size: usize) -> bool {
+        if size >= self.min_size {
+            let use_case_bytes: UseCaseRepr = use_case.into_repr();
+            self.record(use_case_bytes, size);
+            self.inner
+                .on_shared_alloc(U::from_repr(use_case_bytes).unwrap_or_default(), size)
+        } else {
+            self.inner.on_shared_alloc(use_case, size)
+        }
+    }
+
+    fn on_alloc_layout(&self, use_case: U, layout: Layout) {
+        self.inner.on_alloc_layout(use_case, layout)
+    }
+
+    fn on_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_dealloc(use_case, size)
+    }
+
+    fn on_shared_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_shared_dealloc(use_case, size)
+    }
+
+    fn on_attributed_drop(&self, use_case: U, size: usize) {
+        self.inner.on_attributed_drop(use_case, size)
+    }
+
+    fn on_transfer(&self, from: U, to: U, size: usize) {
+        self.inner.on_transfer(from, to, size)
+    }
+
+    fn on_cross_thread_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_cross_thread_dealloc(use_case, size)
+    }
+
+    fn on_external_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_alloc(use_case, size)
+    }
+
+    fn on_external_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_dealloc(use_case, size)
+    }
+
+    fn on_callsite_alloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_alloc(use_case, callsite, size)
+    }
+
+    fn on_callsite_dealloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_dealloc(use_case, callsite, size)
+    }
+
+    fn on_tagged_alloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_alloc(use_case, tag, size)
+    }
+
+    fn on_tagged_dealloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_dealloc(use_case, tag, size)
+    }
+
+    fn on_forbidden_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_forbidden_alloc(use_case, size)
+    }
+
+    fn on_usecase_enter(&self, use_case: U) {
+        self.inner.on_usecase_enter(use_case)
+    }
+
+    fn on_usecase_exit(&self, use_case: U) {
+        self.inner.on_usecase_exit(use_case)
+    }
+
+    fn on_guard_enter(&self, use_case: U) {
+        self.inner.on_guard_enter(use_case)
+    }
+
+    fn on_guard_exit(&self, use_case: U) {
+        self.inner.on_guard_exit(use_case)
+    }
+
+    fn on_flush(&self) {
+        self.inner.on_flush()
+    }
+
+    fn on_overhead_estimate(&self, estimate: OverheadEstimate) {
+        self.inner.on_overhead_estimate(estimate)
+    }
+
+    fn on_fork(&self) {
+        self.stacks.reset();
+        self.inner.on_fork()
+    }
+
+    fn on_error(&self, code: Error, size: Option<usize>) {
+        self.inner.on_error(code, size)
+    }
+}
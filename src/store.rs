@@ -0,0 +1,154 @@
+//! Where memoria keeps the state of each thread, such as its current usecase, see
+//! [CurrentUsecaseStore].
+
+use core::cell::{Cell, RefCell};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::split::SplitId;
+use crate::{Callsite, Stat, Tag, ThreadIndex, UseCaseRepr};
+
+/// The state memoria keeps for each thread of execution, such as the current usecase and the
+/// guards that are alive on it.
+///
+/// Handed out by a [CurrentUsecaseStore]. The fields are private, a store only needs to keep one
+/// instance per thread, starting from [ThreadState::new].
+pub struct ThreadState {
+    pub(crate) use_case: RefCell<Option<UseCaseRepr>>,
+    // The usecase passed to the innermost running `Alloc::drop_attributed`.
+    pub(crate) drop: Cell<Option<UseCaseRepr>>,
+    // The location of the innermost guard, if it was created through `Alloc::with_usecase_at`.
+    pub(crate) callsite: Cell<Option<Callsite>>,
+    // The tag set by the innermost guard created through `Alloc::with_usecase_tagged`.
+    pub(crate) tag: Cell<Option<Tag>>,
+    // The shares set by the innermost guard, if it was created through `Alloc::with_usecases`.
+    pub(crate) split: Cell<SplitId>,
+    // Set while a guard created through `Alloc::attribute_callee` is alive.
+    pub(crate) pinned: Cell<bool>,
+    // The number of guards created through `Alloc::forbid_alloc` that are alive.
+    pub(crate) forbid_alloc: Cell<usize>,
+    // The recorder passed to the innermost running `Alloc::with_recorder_override`, as the address
+    // of the allocator it was installed on and a pointer to a `&dyn Recorder<U>`.
+    pub(crate) recorder_override: Cell<Option<(usize, *const ())>>,
+    // Assigned on first use from `NEXT_THREAD_INDEX`, zero until then.
+    thread_index: Cell<ThreadIndex>,
+    // The number of allocations considered for sampling, see `AllocBuilder::sample_rate`.
+    pub(crate) sample_counter: Cell<u32>,
+    // Allocations made by this thread since the innermost running `Measurement` started.
+    pub(crate) measurement: Cell<Option<Stat>>,
+}
+
+impl ThreadState {
+    /// The state of a thread that has not used memoria yet.
+    pub const fn new() -> Self {
+        ThreadState {
+            use_case: RefCell::new(None),
+            drop: Cell::new(None),
+            callsite: Cell::new(None),
+            tag: Cell::new(None),
+            split: Cell::new(0),
+            pinned: Cell::new(false),
+            forbid_alloc: Cell::new(0),
+            recorder_override: Cell::new(None),
+            thread_index: Cell::new(0),
+            sample_counter: Cell::new(0),
+            measurement: Cell::new(None),
+        }
+    }
+
+    /// Return the [ThreadIndex] of the thread this state belongs to, assigning one on first use.
+    pub(crate) fn thread_index(&self) -> ThreadIndex {
+        if self.thread_index.get() == 0 {
+            self.thread_index
+                .set(NEXT_THREAD_INDEX.fetch_add(1, Ordering::Relaxed));
+        }
+        self.thread_index.get()
+    }
+}
+
+impl Default for ThreadState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static NEXT_THREAD_INDEX: AtomicU64 = AtomicU64::new(1);
+
+/// Provides the [ThreadState] of the thread that is currently running, see [Alloc::with_store].
+///
+/// With the `std` feature, memoria keeps it in a thread-local, see [ThreadLocalStore]. Without
+/// it, there are no thread-locals, so the platform has to provide storage per thread, CPU core or
+/// whatever else runs code concurrently:
+///
+/// ```ignore
+/// // A microcontroller with a single core.
+/// struct SingleCore(memoria::ThreadState);
+///
+/// // SAFETY: only one thread of execution exists, see below.
+/// unsafe impl Sync for SingleCore {}
+///
+/// unsafe impl memoria::CurrentUsecaseStore for SingleCore {
+///     fn state(&self) -> Option<&memoria::ThreadState> {
+///         Some(&self.0)
+///     }
+/// }
+///
+/// static STORE: SingleCore = SingleCore(memoria::ThreadState::new());
+///
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::Alloc<MyUseCase, memoria::NoopRecorder<MyUseCase>, MyHeap> =
+///     memoria::Alloc::new_with(memoria::NoopRecorder::new(), MyHeap::new()).with_store(&STORE);
+/// ```
+///
+/// If no state is available, for example on a thread that the store does not know about, `state`
+/// returns `None`. memoria then attributes allocations to the default usecase, and lets only one
+/// such thread at a time use its bookkeeping, the others being reported as
+/// [Error::CurrentUsecaseContentionThreadLocal](crate::Error::CurrentUsecaseContentionThreadLocal).
+///
+/// # Safety
+///
+/// `state` must not return the same state to two threads of execution that run in parallel, such
+/// as two threads or two CPU cores. Re-entrant use, such as from an interrupt handler that
+/// preempts the thread and returns to it before the thread continues, is fine. The returned state
+/// must stay valid for as long as the thread that asked for it runs.
+///
+/// [Alloc::with_store]: crate::Alloc::with_store
+pub unsafe trait CurrentUsecaseStore: Sync {
+    /// Return the state of the thread that is currently running, or `None` if there is none.
+    fn state(&self) -> Option<&ThreadState>;
+}
+
+/// The [CurrentUsecaseStore] used by default, which keeps the state in a thread-local.
+///
+/// With `--cfg memoria_single_threaded`, the state is a plain static owned by the first thread
+/// that allocates instead.
+#[cfg(feature = "std")]
+pub struct ThreadLocalStore;
+
+#[cfg(feature = "std")]
+crate::utils::local! {
+    static STATE: ThreadState = const { ThreadState::new() };
+}
+
+// SAFETY: every thread gets its own thread-local, which has no destructor and so lives as long
+// as the thread does. `ThreadState` is not `Sync`, so references to it stay on their thread.
+#[cfg(feature = "std")]
+unsafe impl CurrentUsecaseStore for ThreadLocalStore {
+    #[inline]
+    fn state(&self) -> Option<&ThreadState> {
+        let state = STATE.try_with(|state| state as *const ThreadState).ok()?;
+        // SAFETY: see above.
+        Some(unsafe { &*state })
+    }
+}
+
+#[cfg(feature = "std")]
+static DEFAULT_STORE: ThreadLocalStore = ThreadLocalStore;
+
+/// The state of the current thread according to the default store, if there is one.
+#[inline]
+pub(crate) fn default_state() -> Option<&'static ThreadState> {
+    #[cfg(feature = "std")]
+    return DEFAULT_STORE.state();
+    #[cfg(not(feature = "std"))]
+    return None;
+}
@@ -0,0 +1,263 @@
+//! The synchronization primitives that memoria implements itself.
+//!
+//! This module does not depend on the rest of the crate, such that `tests/loom.rs` can include it
+//! and model-check it with [loom](https://docs.rs/loom). When it is built as part of a test with
+//! `--cfg loom`, the primitives are built on loom's atomics instead of the standard library's.
+//! Loom's atomics cannot be created in a `const fn`, so the constructors are only `const`
+//! otherwise.
+
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+use core::cell::RefCell;
+use core::ptr;
+#[cfg(not(all(loom, test)))]
+use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+#[cfg(all(loom, test))]
+use loom::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+    thread::yield_now,
+};
+#[cfg(all(not(all(loom, test)), feature = "std"))]
+use std::thread::yield_now;
+
+/// Without `std`, there is no scheduler to yield to.
+#[cfg(not(feature = "std"))]
+fn yield_now() {
+    core::hint::spin_loop();
+}
+
+/// Declares a constructor that is `const` unless built for loom.
+macro_rules! constructor {
+    ($(#[$meta:meta])* $vis:vis fn $name:ident($($arg:ident: $ty:ty),*) -> Self $body:block) => {
+        #[cfg(not(all(loom, test)))]
+        $(#[$meta])*
+        $vis const fn $name($($arg: $ty),*) -> Self $body
+
+        #[cfg(all(loom, test))]
+        $(#[$meta])*
+        $vis fn $name($($arg: $ty),*) -> Self $body
+    };
+}
+
+/// `std::cell::UnsafeCell` with the closure-based interface of `loom::cell::UnsafeCell`.
+#[cfg(not(all(loom, test)))]
+struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+#[cfg(not(all(loom, test)))]
+impl<T> UnsafeCell<T> {
+    const fn new(value: T) -> Self {
+        UnsafeCell(core::cell::UnsafeCell::new(value))
+    }
+
+    fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
+
+/// A mutual exclusion lock that spins instead of parking threads, such that it does not allocate
+/// and can be used from within the allocator.
+///
+/// Acquiring the lock synchronizes with the previous release, so all writes made while holding
+/// the lock are visible to the next thread that acquires it.
+pub(crate) struct SpinLock<T> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: `value` is only accessed while holding `locked`, see `SpinLock::with`.
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+
+impl<T> SpinLock<T> {
+    constructor! {
+        pub(crate) fn new(value: T) -> Self {
+            SpinLock {
+                locked: AtomicBool::new(false),
+                value: UnsafeCell::new(value),
+            }
+        }
+    }
+
+    /// Run `f` while holding the lock.
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut spins = 0u32;
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // loom has to be told about every iteration of a spin loop
+            if spins < 64 && !cfg!(all(loom, test)) {
+                spins += 1;
+                core::hint::spin_loop();
+            } else {
+                yield_now();
+            }
+        }
+        // SAFETY: we hold the lock.
+        let result = self.value.with_mut(|value| f(unsafe { &mut *value }));
+        self.locked.store(false, Ordering::Release);
+        result
+    }
+
+    /// Run `f` without taking the lock, and release the lock afterwards.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may access the lock at the same time. This is meant for the child process
+    /// after `fork`, where a thread of the parent might have held the lock.
+    pub(crate) unsafe fn with_forced<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        // SAFETY: guaranteed by the caller.
+        let result = self.value.with_mut(|value| f(unsafe { &mut *value }));
+        self.locked.store(false, Ordering::Release);
+        result
+    }
+}
+
+/// A lock that is never waited for: acquiring it fails while another thread holds it.
+///
+/// Like [SpinLock], a successful [TryLock::try_lock] synchronizes with the previous
+/// [TryLock::unlock].
+#[cfg_attr(memoria_single_threaded, allow(dead_code))]
+pub(crate) struct TryLock {
+    locked: AtomicBool,
+}
+
+#[cfg_attr(memoria_single_threaded, allow(dead_code))]
+impl TryLock {
+    constructor! {
+        pub(crate) fn new() -> Self {
+            TryLock {
+                locked: AtomicBool::new(false),
+            }
+        }
+    }
+
+    /// Acquire the lock, or return `false` if it is held.
+    pub(crate) fn try_lock(&self) -> bool {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Release the lock acquired through [TryLock::try_lock].
+    pub(crate) fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+/// Why [exclusive] did not run its closure.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Busy {
+    /// The state of the thread is in use, because the thread is already running a closure passed
+    /// to [exclusive].
+    Local,
+    /// The fallback lock is held by another thread without state of its own, or by this thread.
+    Fallback,
+}
+
+/// Run `f` with exclusive access to `local`, the state of the current thread.
+///
+/// Threads without a state of their own share the lock `fallback`, and pass a default value to
+/// `f` while holding it. Without a fallback, they cannot run `f` at all.
+///
+/// Nothing is ever waited for: if `f` is already running on this thread, for example because it
+/// allocated from within the allocator, or if another thread holds `fallback`, `f` is not run.
+#[inline]
+pub(crate) fn exclusive<T: Default, R>(
+    local: Option<&RefCell<T>>,
+    fallback: Option<&TryLock>,
+    f: impl FnOnce(&mut T) -> R,
+) -> Result<R, Busy> {
+    match local {
+        Some(local) => match local.try_borrow_mut() {
+            Ok(mut value) => Ok(f(&mut value)),
+            Err(_) => Err(Busy::Local),
+        },
+        None => exclusive_fallback(fallback, f),
+    }
+}
+
+#[cold]
+#[inline(never)]
+fn exclusive_fallback<T: Default, R>(
+    fallback: Option<&TryLock>,
+    f: impl FnOnce(&mut T) -> R,
+) -> Result<R, Busy> {
+    match fallback {
+        Some(lock) if lock.try_lock() => {
+            let rv = f(&mut T::default());
+            lock.unlock();
+            Ok(rv)
+        }
+        _ => Err(Busy::Fallback),
+    }
+}
+
+/// Like `OnceCell`, but can be reset through a shared reference by leaking the previous value.
+///
+/// Used for state that has to be discarded in the child process after `fork`, where its locks
+/// might be held by threads that do not exist anymore. Leaking keeps references to the previous
+/// value valid.
+///
+/// If several threads initialize the cell at the same time, all of them return the same value,
+/// and writes made by `f` before it returned are visible to all of them.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) struct ResettableCell<T> {
+    ptr: AtomicPtr<T>,
+}
+
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+impl<T> ResettableCell<T> {
+    constructor! {
+        pub(crate) fn new() -> Self {
+            ResettableCell {
+                ptr: AtomicPtr::new(ptr::null_mut()),
+            }
+        }
+    }
+
+    pub(crate) fn get(&self) -> Option<&T> {
+        // SAFETY: non-null pointers come from `Box::into_raw`, and are only freed on drop.
+        unsafe { self.ptr.load(Ordering::Acquire).as_ref() }
+    }
+
+    pub(crate) fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+        let new = Box::into_raw(Box::new(f()));
+        match self
+            .ptr
+            .compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire)
+        {
+            // SAFETY: see `get`.
+            Ok(_) => unsafe { &*new },
+            Err(existing) => {
+                // SAFETY: `new` was never shared.
+                drop(unsafe { Box::from_raw(new) });
+                // SAFETY: see `get`.
+                unsafe { &*existing }
+            }
+        }
+    }
+
+    /// Forget the current value without dropping it.
+    pub(crate) fn reset(&self) {
+        self.ptr.store(ptr::null_mut(), Ordering::Release);
+    }
+}
+
+impl<T> Drop for ResettableCell<T> {
+    fn drop(&mut self) {
+        let ptr = self.ptr.load(Ordering::Acquire);
+        if !ptr.is_null() {
+            // SAFETY: see `get`.
+            drop(unsafe { Box::from_raw(ptr) });
+        }
+    }
+}
+
+// SAFETY: the cell owns its value like a `Box` does, and shares it like a `OnceCell` does.
+unsafe impl<T: Send + Sync> Sync for ResettableCell<T> {}
+unsafe impl<T: Send> Send for ResettableCell<T> {}
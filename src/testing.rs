@@ -0,0 +1,580 @@
+//! Helpers for putting allocation budgets under test.
+//!
+//! Use [TestRecorder] as the recorder of the global allocator in an integration test, then
+//! assert on the allocations made by a block of code:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOCATOR: memoria::Alloc<MyUseCase, memoria::testing::TestRecorder<MyUseCase>> =
+//!     memoria::Alloc::new_with(
+//!         memoria::testing::TestRecorder::new(memoria::StatsRecorder::new()),
+//!         std::alloc::System,
+//!     );
+//!
+//! #[test]
+//! fn parse_budget() {
+//!     memoria::assert_allocates!(ALLOCATOR, usecase = MyUseCase::Parse, max_bytes = 4096, {
+//!         parse(INPUT);
+//!     });
+//!     memoria::assert_no_alloc!(ALLOCATOR, {
+//!         lookup(&table, "key");
+//!     });
+//! }
+//! ```
+//!
+//! Only allocations made by the thread running the block are captured, so tests running in
+//! parallel do not affect each other.
+//!
+//! [exercise_allocator] stress-tests an allocator, to validate custom [Recorder] implementations.
+//!
+//! Requires the `testing` feature.
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::cell::RefCell;
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::{
+    utils, Alloc, Callsite, Error, EventKind, OverheadEstimate, PointerTable, Recorder,
+    StatsRecorder, Tag, UseCase, UseCaseRepr,
+};
+
+utils::local! {
+    /// Events recorded on this thread since the innermost running capture started.
+    static CAPTURED: RefCell<Option<Vec<(EventKind, UseCaseRepr, usize)>>> =
+        const { RefCell::new(None) };
+}
+
+/// A single allocation or deallocation captured by [capture].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct TestEvent<U> {
+    /// Whether memory was allocated or deallocated.
+    pub kind: EventKind,
+    /// The usecase the memory is attributed to.
+    pub use_case: U,
+    /// The size of the allocation in bytes.
+    pub size: usize,
+}
+
+/// The events captured by [capture], in the order in which they happened.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Captured<U> {
+    /// All events, in order.
+    pub events: Vec<TestEvent<U>>,
+}
+
+impl<U: PartialEq> Captured<U> {
+    /// The events attributed to `use_case`, in order.
+    pub fn for_usecase<'a>(&'a self, use_case: &'a U) -> impl Iterator<Item = &'a TestEvent<U>> {
+        self.events
+            .iter()
+            .filter(move |event| event.use_case == *use_case)
+    }
+
+    /// The number of bytes allocated for `use_case`, not counting deallocations.
+    pub fn allocated_bytes(&self, use_case: &U) -> usize {
+        self.allocations(use_case).map(|event| event.size).sum()
+    }
+
+    /// The number of allocations made for `use_case`.
+    pub fn allocation_count(&self, use_case: &U) -> usize {
+        self.allocations(use_case).count()
+    }
+
+    fn allocations<'a>(&'a self, use_case: &'a U) -> impl Iterator<Item = &'a TestEvent<U>> {
+        self.for_usecase(use_case)
+            .filter(|event| event.kind == EventKind::Alloc)
+    }
+}
+
+/// A recorder that captures the exact sequence of allocations and deallocations made during
+/// [capture], and otherwise forwards to another recorder `R`.
+///
+/// Events are buffered per thread, and only while a capture is running on that thread.
+pub struct TestRecorder<U: UseCase, R: Recorder<U> = StatsRecorder<U>> {
+    inner: R,
+    _phantom: PhantomData<U>,
+}
+
+impl<U: UseCase, R: Recorder<U>> TestRecorder<U, R> {
+    /// Construct a new recorder.
+    pub const fn new(inner: R) -> Self {
+        TestRecorder {
+            inner,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Access the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    fn push(&self, kind: EventKind, use_case: UseCaseRepr, size: usize) {
+        CAPTURED
+            .try_with(|captured| {
+                if let Some(events) = &mut *captured.borrow_mut() {
+                    events.push((kind, use_case, size));
+                }
+            })
+            .ok();
+    }
+}
+
+/// Run `f` and return the allocations and deallocations it made on the current thread.
+///
+/// Captures can be nested, in which case the outer one includes everything captured by the inner
+/// one.
+pub fn capture<T, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable>(
+    _alloc: &Alloc<U, TestRecorder<U, R>, A, P>,
+    f: impl FnOnce() -> T,
+) -> (T, Captured<U>) {
+    let outer = CAPTURED
+        .try_with(|captured| captured.replace(Some(Vec::new())))
+        .ok()
+        .flatten();
+    let rv = f();
+    let events = CAPTURED
+        .try_with(|captured| {
+            let inner = captured.replace(None).unwrap_or_default();
+            if let Some(mut outer) = outer {
+                outer.extend_from_slice(&inner);
+                *captured.borrow_mut() = Some(outer);
+            }
+            inner
+        })
+        .unwrap_or_default();
+    let events = events
+        .into_iter()
+        .map(|(kind, use_case, size)| TestEvent {
+            kind,
+            use_case: U::from_repr(use_case).unwrap_or_default(),
+            size,
+        })
+        .collect();
+    (rv, Captured { events })
+}
+
+/// Panic if `captured` exceeds the given budget for `use_case`. Used by [assert_allocates].
+#[doc(hidden)]
+#[track_caller]
+pub fn check_budget<U: PartialEq + fmt::Debug>(
+    captured: &Captured<U>,
+    use_case: &U,
+    max_bytes: Option<usize>,
+    max_count: Option<usize>,
+) {
+    let bytes = captured.allocated_bytes(use_case);
+    let count = captured.allocation_count(use_case);
+    if max_bytes.is_some_and(|max_bytes| bytes > max_bytes)
+        || max_count.is_some_and(|max_count| count > max_count)
+    {
+        panic!(
+            "{use_case:?} allocated {bytes} bytes in {count} allocations, \
+             exceeding the budget of {max_bytes:?} bytes and {max_count:?} allocations. \
+             captured events: {:#?}",
+            captured.events
+        );
+    }
+}
+
+/// Panic if `captured` contains any allocations. Used by [assert_no_alloc].
+#[doc(hidden)]
+#[track_caller]
+pub fn check_no_alloc<U: fmt::Debug>(captured: &Captured<U>) {
+    let allocations: Vec<_> = captured
+        .events
+        .iter()
+        .filter(|event| event.kind == EventKind::Alloc)
+        .collect();
+    if !allocations.is_empty() {
+        panic!("expected no allocations, but got: {allocations:#?}");
+    }
+}
+
+/// Run a block with a usecase active, and assert that the allocations attributed to that
+/// usecase stay within a budget. Evaluates to the value of the block.
+///
+/// The allocator must use a [TestRecorder](crate::testing::TestRecorder). Either `max_bytes`,
+/// `max_count` or both can be given:
+///
+/// ```ignore
+/// memoria::assert_allocates!(ALLOCATOR, usecase = MyUseCase::Parse, max_bytes = 4096, {
+///     parse(INPUT);
+/// });
+/// memoria::assert_allocates!(
+///     ALLOCATOR,
+///     usecase = MyUseCase::Parse,
+///     max_bytes = 4096,
+///     max_count = 2,
+///     { parse(INPUT) }
+/// );
+/// ```
+///
+/// Requires the `testing` feature.
+#[macro_export]
+macro_rules! assert_allocates {
+    (
+        $alloc:expr,
+        usecase = $use_case:expr,
+        $(max_bytes = $max_bytes:expr,)?
+        $(max_count = $max_count:expr,)?
+        $body:block
+    ) => {{
+        let alloc = &$alloc;
+        let (rv, captured) =
+            $crate::testing::capture(alloc, || alloc.scope($use_case, || $body));
+        let max_bytes: ::core::option::Option<usize> = ::core::option::Option::None;
+        $(let max_bytes = ::core::option::Option::Some($max_bytes);)?
+        let max_count: ::core::option::Option<usize> = ::core::option::Option::None;
+        $(let max_count = ::core::option::Option::Some($max_count);)?
+        $crate::testing::check_budget(&captured, &$use_case, max_bytes, max_count);
+        rv
+    }};
+}
+
+/// Run a block and assert that it does not allocate on the current thread, regardless of the
+/// usecase. Deallocations are allowed. Evaluates to the value of the block.
+///
+/// The allocator must use a [TestRecorder](crate::testing::TestRecorder).
+///
+/// ```ignore
+/// memoria::assert_no_alloc!(ALLOCATOR, {
+///     lookup(&table, "key");
+/// });
+/// ```
+///
+/// Requires the `testing` feature.
+#[macro_export]
+macro_rules! assert_no_alloc {
+    ($alloc:expr, $body:block) => {{
+        let (rv, captured) = $crate::testing::capture(&$alloc, || $body);
+        $crate::testing::check_no_alloc(&captured);
+        rv
+    }};
+}
+
+/// The number of threads spawned by [exercise_allocator].
+const EXERCISE_THREADS: usize = 4;
+/// The number of operations each thread of [exercise_allocator] performs.
+const EXERCISE_ROUNDS: usize = 2000;
+/// The number of allocations each thread of [exercise_allocator] keeps alive at most.
+const EXERCISE_SLOTS: usize = 32;
+
+/// What [exercise_allocator] did while the usecase passed to it was active.
+///
+/// Every allocation was freed again, so a recorder that tracks all allocations should have
+/// recorded exactly this many allocations and bytes for the usecase, and as many deallocations.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Exercised {
+    /// The number of allocations, counting every reallocation as one.
+    pub allocations: usize,
+    /// The number of bytes allocated, counting the new size of every reallocation.
+    pub bytes: usize,
+}
+
+impl Exercised {
+    fn merge(&mut self, other: Exercised) {
+        self.allocations += other.allocations;
+        self.bytes += other.bytes;
+    }
+}
+
+std::thread_local! {
+    // Allocates and frees memory when the thread exits, see `exercise_thread`.
+    static ALLOC_ON_EXIT: AllocOnExit = const { AllocOnExit(std::cell::Cell::new(None)) };
+}
+
+struct AllocOnExit(std::cell::Cell<Option<&'static dyn GlobalAlloc>>);
+
+impl Drop for AllocOnExit {
+    fn drop(&mut self) {
+        if let Some(alloc) = self.0.get() {
+            let layout = Layout::from_size_align(64, 8).unwrap();
+            // SAFETY: the layout has a non-zero size, and the memory is freed with it.
+            unsafe {
+                let ptr = alloc.alloc(layout);
+                assert!(!ptr.is_null(), "allocation during thread exit failed");
+                ptr.write_bytes(0xAA, layout.size());
+                alloc.dealloc(ptr, layout);
+            }
+        }
+    }
+}
+
+/// Hammer `alloc` from several threads with allocations of random sizes and alignments,
+/// reallocations and deallocations made while `use_case` is active, to validate a custom
+/// [Recorder] or allocator.
+///
+/// ```ignore
+/// #[test]
+/// fn recorder_is_consistent() {
+///     let exercised = memoria::testing::exercise_allocator(&ALLOCATOR, MyUseCase::Harness);
+///     let stat = ALLOCATOR.with_recorder(|r| Ok(r.get(MyUseCase::Harness))).unwrap();
+///     assert_eq!(stat.total as usize, exercised.bytes);
+///     assert_eq!(stat.current, 0);
+/// }
+/// ```
+///
+/// Panics if the allocator returns null or misaligned memory, if a reallocation does not
+/// preserve the contents of the memory, or if allocations of `use_case` are still tracked as
+/// live afterwards. The threads also allocate from thread-local destructors, where memoria
+/// falls back to the default usecase, and with the `allocator-api` feature, zero-sized
+/// allocations are made through the `Allocator` trait.
+///
+/// `use_case` should not be used by anything else while this runs. Requires the `testing`
+/// feature.
+pub fn exercise_allocator<U, R, A, P>(alloc: &'static Alloc<U, R, A, P>, use_case: U) -> Exercised
+where
+    U: UseCase,
+    R: Recorder<U>,
+    A: GlobalAlloc,
+    P: PointerTable,
+    Alloc<U, R, A, P>: Sync,
+{
+    let use_case = use_case.into_repr();
+    let live_before = live_bytes(alloc, use_case);
+
+    let threads: Vec<_> = (0..EXERCISE_THREADS)
+        .map(|index| std::thread::spawn(move || exercise_thread(alloc, use_case, index as u64)))
+        .collect();
+    let mut exercised = Exercised::default();
+    for thread in threads {
+        exercised.merge(thread.join().expect("exercise thread panicked"));
+    }
+
+    let live_after = live_bytes(alloc, use_case);
+    assert_eq!(
+        live_before, live_after,
+        "memory of the exercised usecase is still tracked as live"
+    );
+    exercised
+}
+
+fn live_bytes<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable>(
+    alloc: &Alloc<U, R, A, P>,
+    use_case: UseCaseRepr,
+) -> usize {
+    let mut bytes = 0;
+    alloc
+        .inspect_live(|allocation| {
+            if allocation.use_case.into_repr() == use_case {
+                bytes += allocation.size;
+            }
+        })
+        .expect("failed to inspect live allocations");
+    bytes
+}
+
+fn exercise_thread<U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable>(
+    alloc: &'static Alloc<U, R, A, P>,
+    use_case: UseCaseRepr,
+    seed: u64,
+) -> Exercised {
+    ALLOC_ON_EXIT.with(|on_exit| on_exit.0.set(Some(alloc)));
+
+    let mut exercised = Exercised::default();
+    let mut slots = [(std::ptr::null_mut::<u8>(), Layout::new::<u8>()); EXERCISE_SLOTS];
+    // xorshift64, seeded differently per thread.
+    let mut state = 0x9E37_79B9_7F4A_7C15 ^ (seed + 1);
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize
+    };
+
+    let _guard = alloc.with_usecase_bytes(use_case);
+    for _ in 0..EXERCISE_ROUNDS {
+        let index = random() % EXERCISE_SLOTS;
+        let fill = index as u8;
+        let (ptr, layout) = slots[index];
+        // SAFETY: all layouts have a non-zero size, and every pointer is freed or reallocated
+        // with the layout it was allocated with.
+        unsafe {
+            if ptr.is_null() {
+                let layout = random_layout(&mut random);
+                let ptr = alloc.alloc(layout);
+                check_block(ptr, layout, 0, fill);
+                ptr.write_bytes(fill, layout.size());
+                exercised.merge(Exercised {
+                    allocations: 1,
+                    bytes: layout.size(),
+                });
+                slots[index] = (ptr, layout);
+            } else if random() % 2 == 0 {
+                let new_size = random_layout(&mut random).size();
+                let new_ptr = alloc.realloc(ptr, layout, new_size);
+                let new_layout = Layout::from_size_align(new_size, layout.align()).unwrap();
+                check_block(new_ptr, new_layout, layout.size().min(new_size), fill);
+                new_ptr.write_bytes(fill, new_size);
+                exercised.merge(Exercised {
+                    allocations: 1,
+                    bytes: new_size,
+                });
+                slots[index] = (new_ptr, new_layout);
+            } else {
+                check_block(ptr, layout, layout.size(), fill);
+                alloc.dealloc(ptr, layout);
+                slots[index].0 = std::ptr::null_mut();
+            }
+        }
+    }
+    for (ptr, layout) in slots {
+        if !ptr.is_null() {
+            // SAFETY: see above.
+            unsafe { alloc.dealloc(ptr, layout) };
+        }
+    }
+
+    #[cfg(feature = "allocator-api")]
+    {
+        use std::alloc::Allocator;
+
+        let layout = Layout::from_size_align(0, 16).unwrap();
+        let ptr = alloc
+            .allocate(layout)
+            .expect("zero-sized allocation failed");
+        assert_eq!(ptr.len(), 0);
+        assert_eq!(ptr.cast::<u8>().as_ptr() as usize % 16, 0);
+        // SAFETY: the pointer was just allocated with this layout.
+        unsafe { alloc.deallocate(ptr.cast(), layout) };
+    }
+
+    exercised
+}
+
+/// A layout of mostly small sizes, sometimes up to 64 KiB, and alignments up to 4 KiB.
+fn random_layout(random: &mut impl FnMut() -> usize) -> Layout {
+    let size = match random() % 16 {
+        0 => 1 + random() % (64 << 10),
+        _ => 1 + random() % 256,
+    };
+    let align = 1 << (random() % 13);
+    Layout::from_size_align(size, align).unwrap()
+}
+
+/// Panic unless `ptr` is a valid block for `layout` whose first `len` bytes are `fill`.
+///
+/// # Safety
+///
+/// If not null, `ptr` must point to at least `len` initialized bytes.
+unsafe fn check_block(ptr: *mut u8, layout: Layout, len: usize, fill: u8) {
+    assert!(!ptr.is_null(), "allocation of {layout:?} failed");
+    assert_eq!(
+        ptr as usize % layout.align(),
+        0,
+        "allocation of {layout:?} is misaligned"
+    );
+    let contents = std::slice::from_raw_parts(ptr, len);
+    assert!(
+        contents.iter().all(|&byte| byte == fill),
+        "contents of {layout:?} were not preserved"
+    );
+}
+
+unsafe impl<U: UseCase, R: Recorder<U>> Recorder<U> for TestRecorder<U, R> {
+    fn on_alloc(&self, use_case: U, size: usize) -> bool {
+        let use_case_bytes: UseCaseRepr = use_case.into_repr();
+        self.push(EventKind::Alloc, use_case_bytes, size);
+        self.inner
+            .on_alloc(U::from_repr(use_case_bytes).unwrap_or_default(), size)
+    }
+
+    fn on_shared_alloc(&self, use_case: U, size: usize) -> bool {
+        let use_case_bytes: UseCaseRepr = use_case.into_repr();
+        self.push(EventKind::Alloc, use_case_bytes, size);
+        self.inner
+            .on_shared_alloc(U::from_repr(use_case_bytes).unwrap_or_default(), size)
+    }
+
+    fn on_alloc_layout(&self, use_case: U, layout: Layout) {
+        self.inner.on_alloc_layout(use_case, layout)
+    }
+
+    fn on_dealloc(&self, use_case: U, size: usize) {
+        let use_case_bytes: UseCaseRepr = use_case.into_repr();
+        self.push(EventKind::Dealloc, use_case_bytes, size);
+        self.inner
+            .on_dealloc(U::from_repr(use_case_bytes).unwrap_or_default(), size)
+    }
+
+    fn on_shared_dealloc(&self, use_case: U, size: usize) {
+        let use_case_bytes: UseCaseRepr = use_case.into_repr();
+        self.push(EventKind::Dealloc, use_case_bytes, size);
+        self.inner
+            .on_shared_dealloc(U::from_repr(use_case_bytes).unwrap_or_default(), size)
+    }
+
+    fn on_attributed_drop(&self, use_case: U, size: usize) {
+        self.inner.on_attributed_drop(use_case, size)
+    }
+
+    fn on_transfer(&self, from: U, to: U, size: usize) {
+        self.inner.on_transfer(from, to, size)
+    }
+
+    fn on_cross_thread_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_cross_thread_dealloc(use_case, size)
+    }
+
+    fn on_external_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_alloc(use_case, size)
+    }
+
+    fn on_external_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_dealloc(use_case, size)
+    }
+
+    fn on_callsite_alloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_alloc(use_case, callsite, size)
+    }
+
+    fn on_callsite_dealloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_dealloc(use_case, callsite, size)
+    }
+
+    fn on_tagged_alloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_alloc(use_case, tag, size)
+    }
+
+    fn on_tagged_dealloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_dealloc(use_case, tag, size)
+    }
+
+    fn on_forbidden_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_forbidden_alloc(use_case, size)
+    }
+
+    fn on_usecase_enter(&self, use_case: U) {
+        self.inner.on_usecase_enter(use_case)
+    }
+
+    fn on_usecase_exit(&self, use_case: U) {
+        self.inner.on_usecase_exit(use_case)
+    }
+
+    fn on_guard_enter(&self, use_case: U) {
+        self.inner.on_guard_enter(use_case)
+    }
+
+    fn on_guard_exit(&self, use_case: U) {
+        self.inner.on_guard_exit(use_case)
+    }
+
+    fn on_flush(&self) {
+        self.inner.on_flush()
+    }
+
+    fn on_overhead_estimate(&self, estimate: OverheadEstimate) {
+        self.inner.on_overhead_estimate(estimate)
+    }
+
+    fn on_fork(&self) {
+        self.inner.on_fork()
+    }
+
+    fn on_error(&self, code: Error, size: Option<usize>) {
+        self.inner.on_error(code, size)
+    }
+}
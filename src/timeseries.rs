@@ -0,0 +1,226 @@
+use std::alloc::Layout;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::{
+    Callsite, Clock, Error, OverheadEstimate, Recorder, Stat, StatsRecorder, Tag, UseCase,
+    UseCaseRepr,
+};
+
+/// The stats of all usecases at one point in time.
+struct Snapshot {
+    timestamp: u64,
+    stats: Vec<(UseCaseRepr, Stat)>,
+}
+
+/// A recorder that keeps the last `N` snapshots of per-usecase stats in a ring buffer.
+///
+/// Stats are recorded by a [StatsRecorder]. Every call to [TimeSeriesRecorder::tick] copies the
+/// current stats of all usecases into the ring buffer, evicting the oldest snapshot once there
+/// are `N` of them. Calling `tick` once a minute with `N = 60` lets you look at memory trends per
+/// usecase over the last hour without an external metrics system.
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: memoria::Alloc<MyUseCase, memoria::TimeSeriesRecorder<MyUseCase, 60>> =
+///     memoria::Alloc::new_with(memoria::TimeSeriesRecorder::new(), std::alloc::System);
+///
+/// // every minute:
+/// ALLOCATOR.with_recorder(|recorder| Ok(recorder.tick(unix_timestamp()))).ok();
+/// ```
+pub struct TimeSeriesRecorder<U: UseCase, const N: usize> {
+    inner: StatsRecorder<U>,
+    snapshots: Mutex<VecDeque<Snapshot>>,
+    clock: Option<&'static dyn Clock>,
+}
+
+impl<U: UseCase, const N: usize> TimeSeriesRecorder<U, N> {
+    /// Construct a new recorder with an empty ring buffer.
+    pub const fn new() -> Self {
+        TimeSeriesRecorder {
+            inner: StatsRecorder::new(),
+            snapshots: Mutex::new(VecDeque::new()),
+            clock: None,
+        }
+    }
+
+    /// Timestamp the snapshots taken by [TimeSeriesRecorder::tick_now] using `clock`.
+    pub const fn with_clock(mut self, clock: &'static dyn Clock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Access the [StatsRecorder] that holds the current stats.
+    pub fn inner(&self) -> &StatsRecorder<U> {
+        &self.inner
+    }
+
+    /// Take a snapshot of the current stats of all usecases.
+    ///
+    /// `timestamp` is stored alongside the snapshot and can be any monotonically increasing
+    /// value, such as seconds since the unix epoch. The current stats are not reset.
+    ///
+    /// This allocates, and should be called through
+    /// [Alloc::with_recorder](crate::Alloc::with_recorder).
+    pub fn tick(&self, timestamp: u64) {
+        let mut stats = Vec::new();
+        self.inner
+            .peek(|use_case, stat| stats.push((use_case.into_repr(), stat)));
+
+        let mut snapshots = self
+            .snapshots
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if snapshots.len() == N {
+            snapshots.pop_front();
+        }
+        if N > 0 {
+            snapshots.push_back(Snapshot { timestamp, stats });
+        }
+    }
+
+    /// Like [TimeSeriesRecorder::tick], but take the timestamp from the clock passed to
+    /// [TimeSeriesRecorder::with_clock], or zero if there is none.
+    pub fn tick_now(&self) {
+        self.tick(self.clock.map_or(0, |clock| clock.now()))
+    }
+
+    /// Iterate over all stored snapshots, oldest first, calling `stat_fn` with the timestamp of
+    /// the snapshot, and each usecase and its stats at that time.
+    pub fn snapshots(&self, mut stat_fn: impl FnMut(u64, U, Stat)) {
+        let snapshots = self
+            .snapshots
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for snapshot in snapshots.iter() {
+            for &(use_case, stat) in &snapshot.stats {
+                stat_fn(
+                    snapshot.timestamp,
+                    U::from_repr(use_case).unwrap_or_default(),
+                    stat,
+                );
+            }
+        }
+    }
+
+    /// Return the stats of a single usecase over time, oldest first.
+    ///
+    /// Snapshots in which the usecase did not appear are skipped.
+    pub fn history(&self, use_case: U) -> Vec<(u64, Stat)> {
+        let use_case: UseCaseRepr = use_case.into_repr();
+        let snapshots = self
+            .snapshots
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        snapshots
+            .iter()
+            .filter_map(|snapshot| {
+                snapshot
+                    .stats
+                    .iter()
+                    .find(|(x, _)| *x == use_case)
+                    .map(|(_, stat)| (snapshot.timestamp, *stat))
+            })
+            .collect()
+    }
+}
+
+impl<U: UseCase, const N: usize> Default for TimeSeriesRecorder<U, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl<U: UseCase, const N: usize> Recorder<U> for TimeSeriesRecorder<U, N> {
+    fn on_alloc(&self, use_case: U, size: usize) -> bool {
+        self.inner.on_alloc(use_case, size)
+    }
+
+    fn on_alloc_layout(&self, use_case: U, layout: Layout) {
+        self.inner.on_alloc_layout(use_case, layout)
+    }
+
+    fn on_shared_alloc(&self, use_case: U, size: usize) -> bool {
+        self.inner.on_shared_alloc(use_case, size)
+    }
+
+    fn on_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_dealloc(use_case, size)
+    }
+
+    fn on_shared_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_shared_dealloc(use_case, size)
+    }
+
+    fn on_attributed_drop(&self, use_case: U, size: usize) {
+        self.inner.on_attributed_drop(use_case, size)
+    }
+
+    fn on_transfer(&self, from: U, to: U, size: usize) {
+        self.inner.on_transfer(from, to, size)
+    }
+
+    fn on_cross_thread_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_cross_thread_dealloc(use_case, size)
+    }
+
+    fn on_external_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_alloc(use_case, size)
+    }
+
+    fn on_external_dealloc(&self, use_case: U, size: usize) {
+        self.inner.on_external_dealloc(use_case, size)
+    }
+
+    fn on_callsite_alloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_alloc(use_case, callsite, size)
+    }
+
+    fn on_callsite_dealloc(&self, use_case: U, callsite: Callsite, size: usize) {
+        self.inner.on_callsite_dealloc(use_case, callsite, size)
+    }
+
+    fn on_tagged_alloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_alloc(use_case, tag, size)
+    }
+
+    fn on_tagged_dealloc(&self, use_case: U, tag: Tag, size: usize) {
+        self.inner.on_tagged_dealloc(use_case, tag, size)
+    }
+
+    fn on_forbidden_alloc(&self, use_case: U, size: usize) {
+        self.inner.on_forbidden_alloc(use_case, size)
+    }
+
+    fn on_usecase_enter(&self, use_case: U) {
+        self.inner.on_usecase_enter(use_case)
+    }
+
+    fn on_usecase_exit(&self, use_case: U) {
+        self.inner.on_usecase_exit(use_case)
+    }
+
+    fn on_guard_enter(&self, use_case: U) {
+        self.inner.on_guard_enter(use_case)
+    }
+
+    fn on_guard_exit(&self, use_case: U) {
+        self.inner.on_guard_exit(use_case)
+    }
+
+    fn on_flush(&self) {
+        self.inner.on_flush()
+    }
+
+    fn on_overhead_estimate(&self, estimate: OverheadEstimate) {
+        self.inner.on_overhead_estimate(estimate)
+    }
+
+    fn on_fork(&self) {
+        self.inner.on_fork()
+    }
+
+    fn on_error(&self, code: Error, size: Option<usize>) {
+        self.inner.on_error(code, size)
+    }
+}
@@ -0,0 +1,136 @@
+use std::alloc::{GlobalAlloc, System};
+use std::collections::HashMap;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+
+use crate::{
+    Alloc, DefaultTable, Guard, PointerTable, Recorder, StatsRecorder, UseCase, UseCaseRepr,
+};
+
+/// A value, typically a container, that is tied to one usecase for its whole lifetime.
+///
+/// Long-lived data such as caches is often created in one usecase, but grows while many others
+/// are active. Scope-based guards attribute that growth to whatever usecase happens to be active.
+/// `Tracked` instead switches to its own usecase whenever the value is mutated or dropped:
+///
+/// ```ignore
+/// use memoria::{Tracked, TrackedVec};
+///
+/// let mut cache: TrackedVec<String, MyUseCase> =
+///     Tracked::new(&ALLOCATOR, MyUseCase::Cache, Vec::new);
+///
+/// // attributed to MyUseCase::Cache, regardless of the guards that are active here
+/// cache.get_mut().push("hello".to_owned());
+/// cache.with_mut(|cache| cache.push("world".to_owned()));
+/// ```
+///
+/// Reading through [Deref] does not switch usecases.
+pub struct Tracked<
+    'a,
+    T,
+    U: UseCase,
+    R: Recorder<U> = StatsRecorder<U>,
+    A: GlobalAlloc = System,
+    P: PointerTable = DefaultTable,
+> {
+    value: ManuallyDrop<T>,
+    alloc: &'a Alloc<U, R, A, P>,
+    use_case: UseCaseRepr,
+}
+
+/// A [Vec] tied to a usecase, see [Tracked].
+pub type TrackedVec<'a, T, U, R = StatsRecorder<U>, A = System, P = DefaultTable> =
+    Tracked<'a, Vec<T>, U, R, A, P>;
+
+/// A [Box] tied to a usecase, see [Tracked].
+pub type TrackedBox<'a, T, U, R = StatsRecorder<U>, A = System, P = DefaultTable> =
+    Tracked<'a, Box<T>, U, R, A, P>;
+
+/// A [HashMap] tied to a usecase, see [Tracked].
+pub type TrackedHashMap<'a, K, V, U, R = StatsRecorder<U>, A = System, P = DefaultTable> =
+    Tracked<'a, HashMap<K, V>, U, R, A, P>;
+
+impl<'a, T, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable>
+    Tracked<'a, T, U, R, A, P>
+{
+    /// Create the value by calling `f` while `use_case` is active.
+    pub fn new(alloc: &'a Alloc<U, R, A, P>, use_case: U, f: impl FnOnce() -> T) -> Self {
+        let use_case = use_case.into_repr();
+        let value = {
+            let _guard = alloc.with_usecase_bytes(use_case);
+            f()
+        };
+        Tracked {
+            value: ManuallyDrop::new(value),
+            alloc,
+            use_case,
+        }
+    }
+
+    /// The usecase this value is tied to.
+    pub fn use_case(&self) -> U {
+        U::from_repr(self.use_case).unwrap_or_default()
+    }
+
+    /// Borrow the value mutably. The usecase is active for as long as the borrow is alive.
+    pub fn get_mut(&mut self) -> TrackedMut<'_, T> {
+        TrackedMut {
+            _guard: self.alloc.with_usecase_bytes(self.use_case),
+            value: &mut self.value,
+        }
+    }
+
+    /// Call `f` with the value borrowed mutably, while the usecase is active.
+    pub fn with_mut<T2>(&mut self, f: impl FnOnce(&mut T) -> T2) -> T2 {
+        f(&mut self.get_mut())
+    }
+
+    /// Unwrap the value. From then on, it is no longer tied to the usecase.
+    pub fn into_inner(self) -> T {
+        let mut this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used again, and its `Drop` impl does not run.
+        unsafe { ManuallyDrop::take(&mut this.value) }
+    }
+}
+
+impl<T, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Deref
+    for Tracked<'_, T, U, R, A, P>
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T, U: UseCase, R: Recorder<U>, A: GlobalAlloc, P: PointerTable> Drop
+    for Tracked<'_, T, U, R, A, P>
+{
+    fn drop(&mut self) {
+        let _guard = self.alloc.with_usecase_bytes(self.use_case);
+        // SAFETY: `value` is never used again.
+        unsafe { ManuallyDrop::drop(&mut self.value) }
+    }
+}
+
+/// A mutable borrow of a [Tracked] value, during which its usecase is active.
+///
+/// Returned by [Tracked::get_mut].
+pub struct TrackedMut<'b, T> {
+    _guard: Option<Guard<'b>>,
+    value: &'b mut T,
+}
+
+impl<T> Deref for TrackedMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for TrackedMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
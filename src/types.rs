@@ -1,8 +1,30 @@
-use std::hash::Hash;
+use core::alloc::Layout;
+use core::hash::Hash;
+use core::panic::Location;
 
-/// The internal representation memoria uses to represent instances of `UseCase`.
+/// The representation every `UseCase` converts from and into, see [UseCase].
 pub type UseCaseBytes = u32;
 
+/// The representation memoria stores internally for every usecase, see [UseCase::into_repr].
+pub type UseCaseRepr = u64;
+
+/// A secondary dimension for attributing memory, such as a tenant id, set through
+/// [Alloc::with_usecase_tagged](crate::Alloc::with_usecase_tagged).
+pub type Tag = u64;
+
+/// Identifies a thread in per-thread stats, see
+/// [StatsRecorder::with_per_thread](crate::StatsRecorder::with_per_thread).
+///
+/// Threads are numbered starting at 1 in the order in which they first allocate. Use
+/// [current_thread_index](crate::current_thread_index) to find out the index of a thread, for
+/// example when spawning the threads of a pool. `0` is used for allocations made while a thread
+/// is shutting down.
+pub type ThreadIndex = u64;
+
+/// The source location of a guard created through
+/// [Alloc::with_usecase_at](crate::Alloc::with_usecase_at).
+pub type Callsite = &'static Location<'static>;
+
 /// A `UseCase` is a struct describing what the application is currently doing. Memory statistics
 /// are recorded per distinct value of `UseCase`.
 ///
@@ -27,7 +49,73 @@ pub type UseCaseBytes = u32;
 ///
 /// impl UseCase for ApplicationStage {}
 /// ```
-pub trait UseCase: Default + TryFrom<UseCaseBytes> + Into<UseCaseBytes> + 'static {}
+///
+/// With the `derive` feature, all of this can be generated instead. The derive also implements
+/// [UseCase::name] and adds an `all_variants()` method to the enum. The first variant is the
+/// default, unless another one is marked with `#[usecase(default)]`:
+///
+/// ```ignore
+/// #[derive(memoria::UseCase, Clone, Copy, Debug)]
+/// pub enum ApplicationStage {
+///     Unknown,
+///     Download,
+///     Process,
+/// }
+///
+/// assert_eq!(ApplicationStage::Download.name(), "Download");
+/// ```
+///
+/// # Wider representations
+///
+/// Internally, memoria stores usecases as [UseCaseRepr], which has room for 64 bits. By default
+/// only the 32 bits of [UseCaseBytes] are used. To pack more information into a usecase, such as
+/// a tenant id next to the stage, override [UseCase::into_repr] and [UseCase::from_repr]. The
+/// conversions from and to [UseCaseBytes] are then no longer used by memoria.
+pub trait UseCase: Default + TryFrom<UseCaseBytes> + Into<UseCaseBytes> + 'static {
+    /// Convert this usecase into the representation memoria stores internally.
+    ///
+    /// `from_repr(x.into_repr())` must return a value equal to `x`.
+    fn into_repr(self) -> UseCaseRepr {
+        UseCaseRepr::from(Into::<UseCaseBytes>::into(self))
+    }
+
+    /// Convert the internal representation back into a usecase, or return `None` if it is not
+    /// valid.
+    fn from_repr(repr: UseCaseRepr) -> Option<Self> {
+        let bytes = UseCaseBytes::try_from(repr).ok()?;
+        Self::try_from(bytes).ok()
+    }
+
+    /// A stable, human-readable label for this usecase, used by the exporters in
+    /// [export](crate::export) and the `http`, `capi` and `signal` features.
+    ///
+    /// The derive and [usecase!](crate::usecase) generate this from the variant names. The
+    /// default returns an empty string, in which case exporters fall back to the `Debug`
+    /// representation of the usecase.
+    fn name(&self) -> &'static str {
+        ""
+    }
+
+    /// Called after memory attributed to this usecase was allocated, in addition to
+    /// [Recorder::on_alloc].
+    ///
+    /// This is only called for allocations that the recorder tracks, so that every call is
+    /// eventually followed by a matching [UseCase::on_dealloc].
+    ///
+    /// Unlike recorder methods, this is called outside of memoria's internal bookkeeping.
+    /// Allocations made here are attributed and tracked like any other, and call this method
+    /// again. To avoid infinite recursion, switch to a usecase that does nothing in this method
+    /// before allocating.
+    ///
+    /// This function must not panic/unwind.
+    fn on_alloc(&self, _size: usize) {}
+
+    /// Called after memory attributed to this usecase was deallocated, in addition to
+    /// [Recorder::on_dealloc].
+    ///
+    /// The same restrictions as for [UseCase::on_alloc] apply.
+    fn on_dealloc(&self, _size: usize) {}
+}
 
 /// A recorder is a structure collecting statistics about memory usage. You might also call it a
 /// "metrics sink".
@@ -39,6 +127,33 @@ pub trait UseCase: Default + TryFrom<UseCaseBytes> + Into<UseCaseBytes> + 'stati
 /// This trait is unsafe to impl because it is called from within `GlobalAlloc`, which is also
 /// unsafe. All methods, at minimum, must not panic or unwind the stack. See the standard library
 /// documentation on custom allocators for more information.
+///
+/// # Concurrency
+///
+/// Methods are called from whichever thread allocates, so they can run on several threads at
+/// once. memoria guarantees the following, and nothing else:
+///
+/// - Calls made for one thread happen in the order of that thread's allocations and usecase
+///   switches. Calls made for different threads are not ordered with respect to each other,
+///   except as stated below.
+/// - Methods are never called recursively on the same thread. Allocations made by a method are
+///   not recorded, see [Error::CurrentUsecaseContentionRefCell]. Threads without a
+///   [ThreadState](crate::ThreadState), such as exiting ones, take turns instead, and their
+///   allocations are not recorded while another such thread is in a method, see
+///   [Error::CurrentUsecaseContentionThreadLocal].
+/// - For a tracked allocation, [Recorder::on_alloc] and [Recorder::on_alloc_layout] happen
+///   before the [Recorder::on_dealloc] of the same pointer, even if it is freed on another
+///   thread. The pointer is handed from one to the other through the
+///   [pointer table](crate::PointerTable), which synchronizes like a lock does, so everything a
+///   recorder writes in the former is visible in the latter, regardless of the memory orderings
+///   the recorder itself uses. Untracked allocations have no such ordering.
+/// - [Recorder::on_usecase_enter], [Recorder::on_usecase_exit], [Recorder::on_guard_enter] and
+///   [Recorder::on_guard_exit] are called on the thread that switches usecases.
+///
+/// In particular, a deallocation on one thread can be observed before a concurrent allocation on
+/// another thread, so stats summed up over threads can briefly be negative, and a recorder that
+/// reads its own counters with relaxed loads only sees an approximation while other threads are
+/// allocating.
 pub unsafe trait Recorder<U: UseCase> {
     /// Record an allocation of size `size` for a given usecase.
     ///
@@ -47,11 +162,167 @@ pub unsafe trait Recorder<U: UseCase> {
         false
     }
 
+    /// Inspect the layout of an allocation that was just passed to `on_alloc`.
+    ///
+    /// This is called in addition to `on_alloc`, and only if that returned `true`. It allows
+    /// recorders to look at the alignment of allocations, which `on_alloc` does not receive.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_alloc_layout(&self, _use_case: U, _layout: Layout) {}
+
     /// Record freed memory of size `size` for a given usecase.
     ///
     /// This function is allowed to allocate further data, but must not panic/unwind.
     fn on_dealloc(&self, _use_case: U, _size: usize) {}
 
+    /// Record the share `size` of `use_case` in an allocation that is split across several
+    /// usecases, see [Alloc::with_usecases](crate::Alloc::with_usecases).
+    ///
+    /// This is called instead of `on_alloc`, once per usecase. The allocation is tracked if this
+    /// returns `true` for the first usecase of the split. The default forwards to `on_alloc`.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_shared_alloc(&self, use_case: U, size: usize) -> bool {
+        self.on_alloc(use_case, size)
+    }
+
+    /// Record the share `size` of `use_case` in freed memory that was split across several
+    /// usecases.
+    ///
+    /// This is called instead of `on_dealloc`, once per usecase. The default forwards to
+    /// `on_dealloc`.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_shared_dealloc(&self, use_case: U, size: usize) {
+        self.on_dealloc(use_case, size)
+    }
+
+    /// Record memory of size `size` that was freed during
+    /// [Alloc::drop_attributed](crate::Alloc::drop_attributed) with the given usecase.
+    ///
+    /// This is called in addition to `on_dealloc`, which receives the usecase that originally
+    /// allocated the memory.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_attributed_drop(&self, _use_case: U, _size: usize) {}
+
+    /// Record memory of size `size` that was allocated under usecase `from` and freed while
+    /// usecase `to` was active.
+    ///
+    /// This is called in addition to `on_dealloc`, and only if the two usecases differ. It
+    /// reveals ownership handoffs, such as buffers that outlive the stage that allocated them.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_transfer(&self, _from: U, _to: U, _size: usize) {}
+
+    /// Record memory of size `size` that was allocated for the given usecase by one thread and
+    /// freed by another.
+    ///
+    /// This is called in addition to `on_dealloc`. Memory that crosses threads is a common cause
+    /// of allocator contention and cache misses.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_cross_thread_dealloc(&self, _use_case: U, _size: usize) {}
+
+    /// Record memory of size `size` that the application allocated without going through the
+    /// global allocator, see [Alloc::record_external_alloc](crate::Alloc::record_external_alloc).
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_external_alloc(&self, _use_case: U, _size: usize) {}
+
+    /// Record freed memory of size `size` that was previously passed to `on_external_alloc`.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_external_dealloc(&self, _use_case: U, _size: usize) {}
+
+    /// Record an allocation of size `size` made while a guard created with
+    /// [Alloc::with_usecase_at](crate::Alloc::with_usecase_at) was active.
+    ///
+    /// This is called in addition to `on_alloc`, and only if that returned `true`.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_callsite_alloc(&self, _use_case: U, _callsite: Callsite, _size: usize) {}
+
+    /// Record freed memory of size `size` that was previously passed to `on_callsite_alloc`.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_callsite_dealloc(&self, _use_case: U, _callsite: Callsite, _size: usize) {}
+
+    /// Record an allocation of size `size` made while a tag set with
+    /// [Alloc::with_usecase_tagged](crate::Alloc::with_usecase_tagged) was active.
+    ///
+    /// This is called in addition to `on_alloc`, and only if that returned `true`.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_tagged_alloc(&self, _use_case: U, _tag: Tag, _size: usize) {}
+
+    /// Record freed memory of size `size` that was previously passed to `on_tagged_alloc`.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_tagged_dealloc(&self, _use_case: U, _tag: Tag, _size: usize) {}
+
+    /// Record an allocation of size `size` made while a guard created with
+    /// [Alloc::forbid_alloc](crate::Alloc::forbid_alloc) was alive.
+    ///
+    /// This is called in addition to `on_alloc` and to `on_error` with
+    /// [Error::AllocInForbiddenScope].
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_forbidden_alloc(&self, _use_case: U, _size: usize) {}
+
+    /// Called when a thread switches to the given usecase, either because a
+    /// [Guard](crate::Guard) for it was created, or because a nested guard was dropped.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_usecase_enter(&self, _use_case: U) {}
+
+    /// Called when a thread switches away from the given usecase. Every call to
+    /// `on_usecase_enter` is eventually followed by a call to `on_usecase_exit` from the same
+    /// thread, unless the thread exits without dropping its guards.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_usecase_exit(&self, _use_case: U) {}
+
+    /// Called when a [Guard](crate::Guard) for the given usecase is created, right after
+    /// `on_usecase_enter`.
+    ///
+    /// Unlike `on_usecase_enter`, this is not called when a thread returns to the usecase
+    /// because a nested guard was dropped. This allows recorders to keep track of scopes, for
+    /// example to compute per-scope deltas.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_guard_enter(&self, _use_case: U) {}
+
+    /// Called when a [Guard](crate::Guard) for the given usecase is dropped, right before
+    /// `on_usecase_exit`. Every call to `on_guard_enter` is eventually followed by a call to
+    /// `on_guard_exit` from the same thread, unless the thread exits without dropping its guards.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_guard_exit(&self, _use_case: U) {}
+
+    /// Called by [Alloc::flush](crate::Alloc::flush) when the application wants a report.
+    ///
+    /// Recorders that buffer data internally can use this to hand it off. Like all other methods,
+    /// this is called while memoria's bookkeeping is busy, so allocations made here are not
+    /// recorded.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_flush(&self) {}
+
+    /// Called by [overhead_selftest](crate::overhead_selftest) with the measured overhead of
+    /// memoria, such that it can be reported along with the stats.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_overhead_estimate(&self, _estimate: crate::OverheadEstimate) {}
+
+    /// Called in the child process after `fork`, see [Alloc::after_fork](crate::Alloc::after_fork).
+    ///
+    /// Only the forking thread exists in the child, so locks held by any other thread at the time
+    /// of the fork are never released. Recorders should discard any state that might be locked.
+    ///
+    /// This function is allowed to allocate further data, but must not panic/unwind.
+    fn on_fork(&self) {}
+
     /// Record an error encountered by memoria that caused it to drop stats, such as a detected
     /// deadlock that caused it to drop metrics.
     ///
@@ -68,12 +339,106 @@ pub enum Error {
     /// This error happens potentially when memoria allocates internally.
     CurrentUsecaseContentionRefCell,
 
-    /// This error happens potentially when memoria allocates internally.
+    /// The current thread's thread-locals were unavailable, for example because the thread is
+    /// exiting, and another such thread was using memoria's fallback bookkeeping at the same time.
+    ///
+    /// This error also happens potentially when memoria allocates internally.
     CurrentUsecaseContentionThreadLocal,
 
-    /// A `UseCase` was converted to `UseCaseBytes`, and later failed to parse back into `UseCase`.
+    /// A `UseCase` was converted to `UseCaseRepr`, and later failed to parse back into `UseCase`.
     ///
-    /// Most likely your `TryFrom<UseCaseBytes>` and `Into<UseCaseBytes>` implementations don't
-    /// match, and are not isomorphic.
+    /// Most likely your `TryFrom<UseCaseBytes>` and `Into<UseCaseBytes>` implementations (or
+    /// [UseCase::into_repr] and [UseCase::from_repr]) don't match, and are not isomorphic.
     CurrentUsecaseBadBytes,
+
+    /// Memory was freed that memoria was not tracking.
+    ///
+    /// This is expected for memory that was allocated while another error prevented memoria from
+    /// recording the allocation, or for which [Recorder::on_alloc] returned `false`. Otherwise it
+    /// indicates a double-free, or a pointer freed through this allocator that was allocated by
+    /// another one.
+    DeallocUntrackedPointer,
+
+    /// Memory was freed with a [Layout](core::alloc::Layout) whose size differs from the one it
+    /// was allocated with.
+    ///
+    /// This violates the contract of [GlobalAlloc](core::alloc::GlobalAlloc), so the caller is
+    /// buggy. The memory is still passed on to the underlying allocator, but stats are updated
+    /// with the size that was recorded at allocation time, such that they stay consistent. The
+    /// alignment of the layout is not checked.
+    DeallocLayoutMismatch,
+
+    /// An allocation returned a pointer that memoria was already tracking as live.
+    ///
+    /// Either the underlying allocator handed out the same memory twice, or a previous
+    /// deallocation of that pointer was missed. The stats of the previous allocation are lost.
+    PointerTrackedTwice,
+
+    /// Memory was allocated while a guard created with
+    /// [Alloc::forbid_alloc](crate::Alloc::forbid_alloc) was alive.
+    ///
+    /// The allocation itself is still recorded as usual, and additionally reported to
+    /// [Recorder::on_forbidden_alloc].
+    AllocInForbiddenScope,
+
+    /// Memory was freed that brought the memory currently used by a usecase below zero.
+    ///
+    /// With the default [DeallocAttribution](crate::DeallocAttribution), memory is always
+    /// subtracted from the usecase that allocated it, so this indicates that tracking went wrong,
+    /// for example due to more calls to
+    /// [Alloc::record_external_dealloc](crate::Alloc::record_external_dealloc) than to
+    /// [Alloc::record_external_alloc](crate::Alloc::record_external_alloc) or a broken
+    /// [UseCase::from_repr]. With [DeallocAttribution::Current](crate::DeallocAttribution::Current),
+    /// negative balances are expected.
+    ///
+    /// This error is reported by [StatsRecorder](crate::StatsRecorder) itself, which counts it
+    /// per usecase in [Stat::negative_balance](crate::Stat::negative_balance).
+    NegativeBalance,
+
+    /// An allocation was refused, because it would have pushed a usecase past the cap set with
+    /// [Alloc::set_cap](crate::Alloc::set_cap).
+    ///
+    /// The allocator returned null for it, so nothing was allocated.
+    CapExceeded,
+
+    /// Memory was allocated or freed through [GlobalAlloc](core::alloc::GlobalAlloc) with a
+    /// zero-sized [Layout](core::alloc::Layout).
+    ///
+    /// This violates the contract of `GlobalAlloc`, so the caller is buggy. The call is still
+    /// passed on to the underlying allocator, but neither recorded nor tracked. Zero-sized
+    /// allocations through the unstable `Allocator` trait are fine, and never reach the
+    /// underlying allocator.
+    ZeroSizedAlloc,
+}
+
+impl Error {
+    /// All error variants, in the order of [Error::index].
+    pub const ALL: [Error; 10] = [
+        Error::AllocInForbiddenScope,
+        Error::CapExceeded,
+        Error::CurrentUsecaseBadBytes,
+        Error::CurrentUsecaseContentionRefCell,
+        Error::CurrentUsecaseContentionThreadLocal,
+        Error::DeallocLayoutMismatch,
+        Error::DeallocUntrackedPointer,
+        Error::NegativeBalance,
+        Error::PointerTrackedTwice,
+        Error::ZeroSizedAlloc,
+    ];
+
+    /// The position of this variant in [Error::ALL], for storing per-error data in arrays.
+    pub const fn index(self) -> usize {
+        match self {
+            Error::AllocInForbiddenScope => 0,
+            Error::CapExceeded => 1,
+            Error::CurrentUsecaseBadBytes => 2,
+            Error::CurrentUsecaseContentionRefCell => 3,
+            Error::CurrentUsecaseContentionThreadLocal => 4,
+            Error::DeallocLayoutMismatch => 5,
+            Error::DeallocUntrackedPointer => 6,
+            Error::NegativeBalance => 7,
+            Error::PointerTrackedTwice => 8,
+            Error::ZeroSizedAlloc => 9,
+        }
+    }
 }
@@ -1,7 +1,108 @@
-use std::cell::Cell;
-use std::marker::PhantomData;
-use std::sync::MutexGuard;
+use core::cell::Cell;
+use core::marker::PhantomData;
 
-// https://stackoverflow.com/a/71945606/1544347
-pub type PhantomUnsend = PhantomData<MutexGuard<'static, ()>>;
+// Raw pointers are not `Send`. Unlike a `MutexGuard` (https://stackoverflow.com/a/71945606/1544347)
+// they are not `Sync` either, which `PhantomUnsync` rules out anyway.
+pub type PhantomUnsend = PhantomData<*const ()>;
 pub type PhantomUnsync = PhantomData<Cell<()>>;
+
+/// Like `thread_local!`, but declares plain statics when memoria is built for programs with a
+/// single thread, see "Single-threaded programs and wasm" in the README.
+#[cfg(feature = "std")]
+macro_rules! local {
+    ($($(#[$meta:meta])* static $name:ident: $ty:ty = const { $init:expr };)*) => {
+        #[cfg(not(memoria_single_threaded))]
+        ::std::thread_local! {
+            $($(#[$meta])* static $name: $ty = const { $init };)*
+        }
+
+        $(
+            #[cfg(memoria_single_threaded)]
+            $(#[$meta])*
+            static $name: $crate::utils::Local<$ty> = $crate::utils::Local::new($init);
+        )*
+    };
+}
+
+#[cfg(feature = "std")]
+pub(crate) use local;
+
+/// A static that is only accessed from one thread, with the same interface as
+/// `std::thread::LocalKey`.
+///
+/// On targets that have threads, the first thread to access the static becomes its owner, and
+/// access from any other thread fails like access to a destroyed thread-local does.
+#[cfg(memoria_single_threaded)]
+pub(crate) struct Local<T> {
+    value: T,
+    #[cfg(not(target_family = "wasm"))]
+    owner: std::sync::atomic::AtomicUsize,
+}
+
+/// Returned by [Local::try_with] when called from a thread other than the owner.
+#[cfg(memoria_single_threaded)]
+#[derive(Debug)]
+pub(crate) struct NotOwner;
+
+// SAFETY: `try_with` only hands out references to the owning thread.
+#[cfg(memoria_single_threaded)]
+unsafe impl<T> Sync for Local<T> {}
+
+#[cfg(memoria_single_threaded)]
+impl<T> Local<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        Local {
+            value,
+            #[cfg(not(target_family = "wasm"))]
+            owner: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn try_with<R>(&'static self, f: impl FnOnce(&T) -> R) -> Result<R, NotOwner> {
+        #[cfg(not(target_family = "wasm"))]
+        {
+            use std::sync::atomic::Ordering;
+
+            std::thread_local! {
+                static MARKER: u8 = const { 0 };
+            }
+
+            // The address of a thread-local is unique among all running threads.
+            let thread = MARKER
+                .try_with(|marker| marker as *const u8 as usize)
+                .map_err(|_| NotOwner)?;
+            match self
+                .owner
+                .compare_exchange(0, thread, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => {}
+                Err(owner) if owner == thread => {}
+                Err(_) => return Err(NotOwner),
+            }
+        }
+        Ok(f(&self.value))
+    }
+}
+
+/// Puts the previous value back into a [Cell] when dropped, including while unwinding from a
+/// panic.
+pub(crate) struct Restore<'a, T: Copy> {
+    cell: &'a Cell<T>,
+    old: T,
+}
+
+impl<'a, T: Copy> Restore<'a, T> {
+    /// Set `cell` to `value` until the returned guard is dropped.
+    pub(crate) fn replace(cell: &'a Cell<T>, value: T) -> Self {
+        Restore {
+            old: cell.replace(value),
+            cell,
+        }
+    }
+}
+
+impl<T: Copy> Drop for Restore<'_, T> {
+    fn drop(&mut self) {
+        self.cell.set(self.old);
+    }
+}
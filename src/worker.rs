@@ -0,0 +1,91 @@
+//! Background threads that belong to an [Alloc](crate::Alloc), such that
+//! [Alloc::finalize](crate::Alloc::finalize) can stop them.
+
+use std::sync::{mpsc, Arc, Mutex, Weak};
+use std::thread;
+
+use crate::sync::SpinLock;
+
+/// A background thread that runs until told to stop.
+pub(crate) struct Worker {
+    stop: Mutex<Option<mpsc::Sender<()>>>,
+    thread: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+impl Worker {
+    /// Spawn a thread named `name` running `f`, which should return once the receiver it is
+    /// passed yields a message or is disconnected.
+    pub(crate) fn spawn(
+        name: &str,
+        f: impl FnOnce(mpsc::Receiver<()>) + Send + 'static,
+    ) -> Arc<Worker> {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name(name.to_owned())
+            .spawn(move || f(stopped))
+            .unwrap_or_else(|_| panic!("failed to spawn {name} thread"));
+        Arc::new(Worker {
+            stop: Mutex::new(Some(stop)),
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    /// Tell the thread to stop, and wait for it to exit, unless called from the thread itself.
+    pub(crate) fn stop(&self) {
+        if let Some(stop) = take(&self.stop) {
+            stop.send(()).ok();
+        }
+        if let Some(thread) = take(&self.thread) {
+            if thread.thread().id() != thread::current().id() {
+                thread.join().ok();
+            }
+        }
+    }
+}
+
+fn take<T>(value: &Mutex<Option<T>>) -> Option<T> {
+    value
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take()
+}
+
+/// The workers spawned for one allocator. Dropping the handle of a worker stops it, so only weak
+/// references are kept here.
+pub(crate) struct Workers {
+    entries: SpinLock<Vec<Weak<Worker>>>,
+}
+
+impl Workers {
+    pub(crate) const fn new() -> Self {
+        Workers {
+            entries: SpinLock::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn register(&self, worker: &Arc<Worker>) {
+        self.entries.with(|entries| {
+            entries.retain(|entry| entry.strong_count() > 0);
+            entries.push(Arc::downgrade(worker));
+        });
+    }
+
+    /// Forget all workers in the child process after `fork`, where they are not running.
+    ///
+    /// # Safety
+    ///
+    /// See [Alloc::after_fork](crate::Alloc::after_fork).
+    pub(crate) unsafe fn reset(&self) {
+        // SAFETY: guaranteed by the caller.
+        let entries = unsafe { self.entries.with_forced(std::mem::take) };
+        std::mem::forget(entries);
+    }
+
+    /// Stop all workers that are still running.
+    pub(crate) fn stop_all(&self) {
+        let entries = self.entries.with(std::mem::take);
+        for worker in entries.iter().filter_map(Weak::upgrade) {
+            worker.stop();
+        }
+    }
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "allocator-api")]
+#![feature(allocator_api)]
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Cache,
+    Request,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn get(use_case: MyUseCase) -> memoria::Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+#[test]
+fn usecase_allocator() {
+    let cache = ALLOCATOR.usecase_allocator(MyUseCase::Cache);
+    assert_eq!(cache.use_case(), MyUseCase::Cache);
+
+    let mut entries: Vec<u8, _> = Vec::with_capacity_in(100, cache);
+    {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Request);
+        // growing the container is attributed to it, not to the active guard
+        entries.reserve_exact(1000);
+    }
+    assert_eq!(get(MyUseCase::Cache).current, 1000);
+    assert_eq!(get(MyUseCase::Request).current, 0);
+
+    // zero-sized allocations are not tracked
+    let empty = Box::new_in((), cache);
+    drop(empty);
+
+    drop(entries);
+    assert_eq!(get(MyUseCase::Cache).current, 0);
+    assert_eq!(get(MyUseCase::Cache).total, 1100);
+}
+
+#[test]
+fn alloc_as_allocator() {
+    let boxed = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Request);
+        Box::new_in([0u8; 10], &ALLOCATOR)
+    };
+    assert_eq!(get(MyUseCase::Request).current, 10);
+    drop(boxed);
+}
@@ -0,0 +1,93 @@
+use std::alloc::{GlobalAlloc, Layout};
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Arena, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Cache,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+const ARENA_SIZE: usize = 1 << 20;
+
+/// Hands out memory from a fixed buffer and never reuses it.
+struct BumpArena {
+    buffer: UnsafeCell<[u8; ARENA_SIZE]>,
+    used: AtomicUsize,
+    live: AtomicUsize,
+}
+
+unsafe impl Sync for BumpArena {}
+
+unsafe impl GlobalAlloc for BumpArena {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let base = self.buffer.get() as usize;
+        let Ok(used) = self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                let start = (base + used).next_multiple_of(layout.align()) - base;
+                Some(start + layout.size()).filter(|&end| end <= ARENA_SIZE)
+            })
+        else {
+            return std::ptr::null_mut();
+        };
+        let start = (base + used).next_multiple_of(layout.align()) - base;
+        self.live.fetch_add(layout.size(), Ordering::Relaxed);
+        (base + start) as *mut u8
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, layout: Layout) {
+        self.live.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+unsafe impl Arena for BumpArena {
+    fn owns(&self, ptr: *mut u8) -> bool {
+        let base = self.buffer.get() as usize;
+        (base..base + ARENA_SIZE).contains(&(ptr as usize))
+    }
+}
+
+static CACHE_ARENA: BumpArena = BumpArena {
+    buffer: UnsafeCell::new([0; ARENA_SIZE]),
+    used: AtomicUsize::new(0),
+    live: AtomicUsize::new(0),
+};
+
+#[test]
+fn arena() {
+    ALLOCATOR.route(MyUseCase::Cache, &CACHE_ARENA);
+
+    let cached = ALLOCATOR.scope(MyUseCase::Cache, || vec![1u8; 1000]);
+    assert!(CACHE_ARENA.owns(cached.as_ptr() as *mut u8));
+    assert_eq!(CACHE_ARENA.live.load(Ordering::Relaxed), 1000);
+
+    let other = vec![2u8; 1000];
+    assert!(!CACHE_ARENA.owns(other.as_ptr() as *mut u8));
+
+    // Routed allocations are recorded like any other.
+    let current = |use_case| {
+        ALLOCATOR
+            .with_recorder(|recorder| Ok(recorder.get(use_case).current))
+            .unwrap()
+    };
+    assert_eq!(current(MyUseCase::Cache), 1000);
+
+    // Memory is returned to the arena that allocated it, regardless of the current usecase.
+    drop(cached);
+    assert_eq!(CACHE_ARENA.live.load(Ordering::Relaxed), 0);
+    assert_eq!(current(MyUseCase::Cache), 0);
+    drop(other);
+}
@@ -0,0 +1,57 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, Clone, Copy, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Mine,
+    Library,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+// Stands in for a dependency that calls back into code which switches usecases.
+fn library(callback: impl Fn() -> Vec<u8>) -> (Vec<u8>, Vec<u8>) {
+    (vec![0u8; 1000], callback())
+}
+
+fn total(use_case: MyUseCase) -> isize {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case).total))
+        .unwrap()
+}
+
+#[test]
+fn attribute_callee() {
+    let (mine_before, library_before) = (total(MyUseCase::Mine), total(MyUseCase::Library));
+
+    let _outer = ALLOCATOR.with_usecase(MyUseCase::Mine);
+    let data = {
+        let _guard = ALLOCATOR.attribute_callee(MyUseCase::Library);
+        library(|| {
+            let _guard = ALLOCATOR.with_usecase(MyUseCase::Mine);
+            assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::Library));
+            vec![0u8; 2000]
+        })
+    };
+    assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::Mine));
+
+    // Pinning ends with the guard.
+    let mine = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::None);
+        assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::None));
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Mine);
+        vec![0u8; 4000]
+    };
+
+    assert_eq!(total(MyUseCase::Library) - library_before, 3000);
+    assert_eq!(total(MyUseCase::Mine) - mine_before, 4000);
+    drop((data, mine));
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "backtrace")]
+use std::alloc::System;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, BacktraceRecorder, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq, Eq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Big,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, BacktraceRecorder<MyUseCase>> = Alloc::new_with(
+    BacktraceRecorder::new(StatsRecorder::new(), 1 << 20),
+    System,
+);
+
+#[inline(never)]
+fn allocate_big_buffer() -> Vec<u8> {
+    vec![0u8; 1 << 20]
+}
+
+#[test]
+fn captures_large_allocations() {
+    let buffer = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Big);
+        allocate_big_buffer()
+    };
+
+    let mut stacks = Vec::new();
+    ALLOCATOR
+        .with_recorder(|recorder| {
+            recorder.flush_stacks(|use_case, stack, stat| {
+                if use_case == MyUseCase::Big {
+                    stacks.push((*stack, stat));
+                }
+            });
+            Ok(())
+        })
+        .unwrap();
+
+    assert_eq!(stacks.len(), 1);
+    let (stack, stat) = stacks[0];
+    assert_eq!(stat.total, 1 << 20);
+    assert_eq!(stat.count, 1);
+    assert!(!stack.frames().is_empty());
+    drop(buffer);
+}
@@ -61,8 +61,14 @@ fn basic() {
             // too platform-specific for now
             records[0].1.peak = 0;
             records[0].1.total = 0;
+            records[0].1.padding = 0;
+            records[0].1.count = 0;
+            records[0].1.max_single = 0;
+            records[0].1.high_align = 0;
+            records[0].1.freed_cross_thread = 0;
             records[1].1.peak = 0;
             records[1].1.total = 0;
+            records[1].1.padding = 0;
             assert_eq!(
                 records,
                 vec![
@@ -72,6 +78,7 @@ fn basic() {
                             current: before + 5400,
                             peak: 0,
                             total: 0,
+                            ..Default::default()
                         },
                     ),
                     (
@@ -80,6 +87,11 @@ fn basic() {
                             current: 0,
                             peak: 0,
                             total: 0,
+                            count: 301,
+                            max_single: 7200,
+                            peak_threads: 1,
+                            entries: 1,
+                            ..Default::default()
                         },
                     ),
                 ]
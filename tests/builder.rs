@@ -0,0 +1,56 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Error, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Sampled,
+    Small,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::builder().sample_rate(2).min_size(64).build();
+
+fn get(use_case: MyUseCase) -> memoria::Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+fn untracked_errors() -> usize {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get_error(Error::DeallocUntrackedPointer)))
+        .unwrap()
+}
+
+#[test]
+fn sample_rate_and_min_size() {
+    let errors_before = untracked_errors();
+    let (_, measured) = ALLOCATOR.measure(|| {
+        ALLOCATOR.scope(MyUseCase::Sampled, || {
+            for _ in 0..10 {
+                drop(vec![0u8; 100]);
+            }
+        });
+        ALLOCATOR.scope(MyUseCase::Small, || {
+            for _ in 0..10 {
+                drop(vec![0u8; 10]);
+            }
+        });
+    });
+
+    let sampled = get(MyUseCase::Sampled);
+    assert_eq!(sampled.count, 5);
+    assert_eq!(sampled.total, 500);
+    assert_eq!(sampled.current, 0);
+    assert_eq!(get(MyUseCase::Small).count, 0);
+    assert_eq!(untracked_errors(), errors_before);
+    // measuring is not affected
+    assert_eq!(measured.count, 20);
+}
@@ -0,0 +1,86 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq, Eq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Parse,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn callsites() {
+    let (first, first_line) = {
+        let _guard = ALLOCATOR.with_usecase_at(MyUseCase::Parse);
+        (vec![0u8; 100], line!() - 1)
+    };
+    let (second, second_line) = {
+        let _guard = ALLOCATOR.with_usecase_at(MyUseCase::Parse);
+        (vec![0u8; 200], line!() - 1)
+    };
+    drop(first);
+
+    let mut callsites = Vec::new();
+    ALLOCATOR
+        .with_recorder(|recorder| {
+            recorder.flush_callsites(|use_case, callsite, stat| {
+                assert_eq!(use_case, MyUseCase::Parse);
+                assert_eq!(callsite.file(), file!());
+                callsites.push((callsite.line(), stat.current, stat.total));
+            });
+            Ok(())
+        })
+        .unwrap();
+    callsites.sort();
+
+    assert_eq!(
+        callsites,
+        vec![(first_line, 0, 100), (second_line, 200, 200)]
+    );
+    drop(second);
+}
+
+#[test]
+fn massif() {
+    let stat = |current| memoria::Stat {
+        current,
+        ..Default::default()
+    };
+    let callsite = std::panic::Location::caller();
+
+    let mut output = Vec::new();
+    memoria::export::write_massif(
+        &mut output,
+        [
+            (1, MyUseCase::Parse, stat(100)),
+            (2, MyUseCase::Parse, stat(300)),
+            (2, MyUseCase::None, stat(50)),
+            (3, MyUseCase::Parse, stat(0)),
+        ],
+        [(MyUseCase::Parse, callsite, stat(200))],
+    )
+    .unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    let peak = output.split("snapshot=").nth(2).unwrap();
+    assert!(peak.contains("heap_tree=peak\n"), "{output}");
+    assert!(
+        peak.contains(&format!(
+            "n2: 350 (heap allocation functions) malloc/new/new[], --alloc-fns, etc.\n \
+             n1: 300 Parse\n  n0: 200 {}:{}\n n0: 50 None\n",
+            callsite.file(),
+            callsite.line()
+        )),
+        "{output}"
+    );
+    let last = output.split("snapshot=").nth(3).unwrap();
+    assert!(last.contains("heap_tree=detailed\nn0: 0 "), "{output}");
+}
@@ -0,0 +1,86 @@
+#![cfg(feature = "capi")]
+use std::ffi::CStr;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::capi::{
+    memoria_errors_snapshot, memoria_restore_usecase, memoria_set_usecase, memoria_stats_snapshot,
+};
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Host,
+    Nested,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn snapshot(f: unsafe extern "C" fn(*mut std::ffi::c_char, usize) -> isize) -> String {
+    // SAFETY: null with a length of zero is allowed.
+    let len = unsafe { f(std::ptr::null_mut(), 0) };
+    assert!(len >= 0);
+    let mut buf = vec![0u8; len as usize + 1];
+    // SAFETY: `buf` is valid for `buf.len()` bytes.
+    assert_eq!(unsafe { f(buf.as_mut_ptr().cast(), buf.len()) }, len);
+    CStr::from_bytes_with_nul(&buf)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+#[test]
+fn capi() {
+    assert_eq!(memoria_set_usecase(MyUseCase::Host.into()), -1);
+    // SAFETY: null with a length of zero is allowed.
+    assert_eq!(
+        unsafe { memoria_stats_snapshot(std::ptr::null_mut(), 0) },
+        -1
+    );
+
+    memoria::capi::install(&ALLOCATOR);
+
+    assert_eq!(memoria_set_usecase(1000), -1);
+    assert_eq!(memoria_restore_usecase(), -1);
+
+    assert_eq!(memoria_set_usecase(MyUseCase::Host.into()), 0);
+    let host = vec![0u8; 100];
+    assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::Host));
+    assert_eq!(memoria_set_usecase(MyUseCase::Nested.into()), 0);
+    assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::Nested));
+    assert_eq!(memoria_restore_usecase(), 0);
+    assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::Host));
+    assert_eq!(memoria_restore_usecase(), 0);
+    assert_eq!(ALLOCATOR.current_usecase(), None);
+
+    // includes the guards that memoria keeps for the host
+    let current = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::Host).current))
+        .unwrap();
+    assert!(current >= 100);
+    let stats = snapshot(memoria_stats_snapshot);
+    assert!(
+        stats.contains(&format!("\"Host\":{{\"current\":{current},")),
+        "unexpected stats: {stats}"
+    );
+    drop(host);
+
+    let errors = snapshot(memoria_errors_snapshot);
+    assert!(errors.starts_with('{') && errors.ends_with('}'));
+
+    // truncated like snprintf
+    let mut buf = [0xffu8; 4];
+    // SAFETY: `buf` is valid for 4 bytes.
+    let len = unsafe { memoria_stats_snapshot(buf.as_mut_ptr().cast(), buf.len()) };
+    assert!(len > 3);
+    assert_eq!(buf[3], 0);
+    assert_eq!(&buf[..1], b"{");
+}
@@ -0,0 +1,58 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Error, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Cache,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn caps() {
+    assert_eq!(ALLOCATOR.cap_usage(MyUseCase::Cache), None);
+    ALLOCATOR.set_cap(MyUseCase::Cache, 10_000);
+    assert_eq!(ALLOCATOR.cap_usage(MyUseCase::Cache), Some(0));
+
+    let _guard = ALLOCATOR.with_usecase(MyUseCase::Cache);
+    let mut first: Vec<u8> = Vec::new();
+    first.try_reserve_exact(6000).unwrap();
+    assert_eq!(ALLOCATOR.cap_usage(MyUseCase::Cache), Some(6000));
+
+    let mut second: Vec<u8> = Vec::new();
+    assert!(second.try_reserve_exact(6000).is_err());
+    assert_eq!(ALLOCATOR.cap_usage(MyUseCase::Cache), Some(6000));
+    assert_eq!(
+        ALLOCATOR
+            .with_recorder(|recorder| Ok(recorder.get_error(Error::CapExceeded)))
+            .unwrap(),
+        1
+    );
+
+    // Freeing memory makes room again.
+    drop(first);
+    assert_eq!(ALLOCATOR.cap_usage(MyUseCase::Cache), Some(0));
+    second.try_reserve_exact(6000).unwrap();
+
+    // Other usecases are not affected.
+    let other = ALLOCATOR.scope(MyUseCase::None, || vec![0u8; 100_000]);
+
+    // Raising the cap keeps what was charged.
+    ALLOCATOR.set_cap(MyUseCase::Cache, 20_000);
+    let mut third: Vec<u8> = Vec::new();
+    third.try_reserve_exact(6000).unwrap();
+    assert_eq!(ALLOCATOR.cap_usage(MyUseCase::Cache), Some(12_000));
+
+    ALLOCATOR.remove_cap(MyUseCase::Cache);
+    assert_eq!(ALLOCATOR.cap_usage(MyUseCase::Cache), None);
+    let big = vec![0u8; 100_000];
+    drop((second, third, other, big));
+}
@@ -0,0 +1,75 @@
+use std::alloc::System;
+use std::time::Duration;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Clock, InstantClock, ManualClock, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Upload,
+}
+
+impl UseCase for MyUseCase {}
+
+static CLOCK: ManualClock = ManualClock::new();
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> =
+    Alloc::new_with(StatsRecorder::new().with_peak_clock(&CLOCK), System).with_age_clock(&CLOCK);
+
+fn flush_upload() -> (isize, Option<f64>) {
+    ALLOCATOR
+        .with_recorder(|recorder| {
+            let mut upload = None;
+            recorder.flush_with_clock_rates(
+                &CLOCK,
+                |use_case, stat, rate| {
+                    if use_case == MyUseCase::Upload {
+                        upload = Some((stat.peak_at as isize, rate.map(|rate| rate.bytes_per_sec)));
+                    }
+                },
+                |_, _| {},
+            );
+            Ok(upload.unwrap())
+        })
+        .unwrap()
+}
+
+#[test]
+fn manual_clock() {
+    CLOCK.set(10);
+    let data = ALLOCATOR.scope(MyUseCase::Upload, || vec![0u8; 1000]);
+    assert_eq!(flush_upload(), (10, None));
+
+    CLOCK.advance(4);
+    let more = ALLOCATOR.scope(MyUseCase::Upload, || vec![0u8; 2000]);
+    assert_eq!(flush_upload(), (14, Some(500.0)));
+
+    CLOCK.advance(6);
+    let mut ages = Vec::new();
+    ALLOCATOR
+        .inspect_live(|allocation| {
+            if allocation.use_case == MyUseCase::Upload {
+                ages.push(allocation.age);
+            }
+        })
+        .unwrap();
+    ages.sort();
+    assert_eq!(ages, [6, 10]);
+    drop((data, more));
+}
+
+#[test]
+fn instant_clock() {
+    let clock = InstantClock::new();
+    let start = clock.now();
+    std::thread::sleep(Duration::from_millis(20));
+    let now = clock.now();
+    assert!(clock.elapsed(start, now) >= Duration::from_millis(20));
+    assert_eq!(clock.elapsed(0, 1500), Duration::from_millis(1500));
+}
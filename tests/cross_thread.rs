@@ -0,0 +1,41 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Producer,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn freed_cross_thread() -> isize {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::Producer).freed_cross_thread))
+        .unwrap()
+}
+
+#[test]
+fn cross_thread() {
+    let local = ALLOCATOR.scope(MyUseCase::Producer, || vec![0u8; 100]);
+    drop(local);
+    assert_eq!(freed_cross_thread(), 0);
+
+    let sent = ALLOCATOR.scope(MyUseCase::Producer, || vec![0u8; 1000]);
+    std::thread::spawn(move || drop(sent)).join().unwrap();
+    assert_eq!(freed_cross_thread(), 1000);
+
+    // Memory allocated on another thread and freed here counts as well.
+    let received = std::thread::spawn(|| ALLOCATOR.scope(MyUseCase::Producer, || vec![0u8; 10]))
+        .join()
+        .unwrap();
+    drop(received);
+    assert_eq!(freed_cross_thread(), 1010);
+}
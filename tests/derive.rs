@@ -0,0 +1,41 @@
+#![cfg(feature = "derive")]
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase, UseCaseBytes};
+
+#[derive(memoria::UseCase, Clone, Copy, Debug, PartialEq, Eq)]
+enum MyUseCase {
+    Startup,
+    #[usecase(default)]
+    None,
+    Derived = 10,
+}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn conversions() {
+    assert_eq!(MyUseCase::default(), MyUseCase::None);
+    assert_eq!(UseCaseBytes::from(MyUseCase::Derived), 10);
+    assert_eq!(MyUseCase::try_from(10), Ok(MyUseCase::Derived));
+    assert_eq!(MyUseCase::try_from(3), Err(3));
+    assert_eq!(MyUseCase::Derived.name(), "Derived");
+    assert_eq!(UseCase::name(&MyUseCase::Startup), "Startup");
+    assert_eq!(
+        MyUseCase::all_variants(),
+        &[MyUseCase::Startup, MyUseCase::None, MyUseCase::Derived]
+    );
+}
+
+#[test]
+fn records() {
+    let _buffer = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Derived);
+        vec![0u8; 100]
+    };
+    let stat = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::Derived)))
+        .unwrap();
+    assert_eq!(stat.current, 100);
+}
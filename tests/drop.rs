@@ -0,0 +1,75 @@
+use std::panic::AssertUnwindSafe;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Build,
+    Teardown,
+    Cleanup,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn drop_attributed() {
+    let value = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Build);
+        vec![0u8; 1000]
+    };
+
+    ALLOCATOR.drop_attributed(MyUseCase::Teardown, value);
+
+    let (build, teardown) = ALLOCATOR
+        .with_recorder(|recorder| {
+            Ok((
+                recorder.get(MyUseCase::Build),
+                recorder.get(MyUseCase::Teardown),
+            ))
+        })
+        .unwrap();
+
+    assert_eq!(build.current, 0);
+    assert_eq!(build.freed_in_drop, 0);
+    assert_eq!(teardown.current, 0);
+    assert_eq!(teardown.freed_in_drop, 1000);
+}
+
+#[test]
+fn panicking_drop() {
+    struct Panics;
+
+    impl Drop for Panics {
+        fn drop(&mut self) {
+            panic!("teardown failed");
+        }
+    }
+
+    let freed_in_drop = || {
+        ALLOCATOR
+            .with_recorder(|recorder| Ok(recorder.get(MyUseCase::Cleanup).freed_in_drop))
+            .unwrap()
+    };
+
+    std::thread::spawn(move || {
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            ALLOCATOR.drop_attributed(MyUseCase::Cleanup, Panics)
+        }));
+        assert!(result.is_err());
+
+        let before = freed_in_drop();
+        drop(vec![0u8; 100]);
+        assert_eq!(freed_in_drop(), before);
+    })
+    .join()
+    .unwrap();
+}
@@ -0,0 +1,42 @@
+use pretty_assertions::assert_eq;
+
+use memoria::{register_usecase, Alloc, DynUseCase};
+
+#[global_allocator]
+static ALLOCATOR: Alloc<DynUseCase> = Alloc::new();
+
+fn get(use_case: DynUseCase) -> memoria::Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+#[test]
+fn register() {
+    let foo = register_usecase("plugin:foo");
+    let bar = register_usecase("plugin:bar");
+    assert_ne!(foo, bar);
+    assert_ne!(foo, DynUseCase::default());
+    assert_eq!(register_usecase("plugin:foo"), foo);
+    assert_eq!(register_usecase("default"), DynUseCase::default());
+
+    assert_eq!(foo.name(), "plugin:foo");
+    assert_eq!(bar.name(), "plugin:bar");
+    assert_eq!(format!("{foo:?}"), "DynUseCase(\"plugin:foo\")");
+
+    assert_eq!(DynUseCase::try_from(u32::from(bar)), Ok(bar));
+    assert_eq!(DynUseCase::try_from(u32::MAX), Err(u32::MAX));
+}
+
+#[test]
+fn attribution() {
+    let plugin = register_usecase("plugin:attribution");
+    let buffer = {
+        let _guard = ALLOCATOR.with_usecase(plugin);
+        vec![0u8; 100]
+    };
+    assert_eq!(get(plugin).current, 100);
+    drop(buffer);
+    assert_eq!(get(plugin).current, 0);
+    assert_eq!(get(plugin).total, 100);
+}
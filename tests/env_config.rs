@@ -0,0 +1,113 @@
+use std::process::Command;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Allowed,
+    Ignored,
+    Renamed,
+}
+
+impl UseCase for MyUseCase {
+    fn name(&self) -> &'static str {
+        match self {
+            MyUseCase::Renamed => "renamed",
+            _ => "",
+        }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::builder().env_config().build();
+
+fn get(use_case: MyUseCase) -> memoria::Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+fn allocate(use_case: MyUseCase, size: usize) {
+    ALLOCATOR.scope(use_case, || drop(vec![0u8; size]));
+}
+
+/// The environment is read on the first allocation, so the assertions run in a child process
+/// that is started with the environment set. Returns whether this is the child.
+fn in_child(test: &str, env: &[(&str, &str)]) -> bool {
+    if std::env::var_os("MEMORIA_TEST_CHILD").is_some() {
+        return true;
+    }
+    let status = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", test, "--test-threads=1"])
+        .env("MEMORIA_TEST_CHILD", "1")
+        .envs(env.iter().copied())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    false
+}
+
+#[test]
+fn usecases_and_min_size() {
+    if !in_child(
+        "usecases_and_min_size",
+        &[
+            ("MEMORIA_USECASES", " Allowed,Other "),
+            ("MEMORIA_MIN_SIZE", "64"),
+        ],
+    ) {
+        return;
+    }
+    allocate(MyUseCase::Allowed, 100);
+    allocate(MyUseCase::Allowed, 10);
+    allocate(MyUseCase::Ignored, 100);
+    assert_eq!(get(MyUseCase::Allowed).total, 100);
+    assert_eq!(get(MyUseCase::Ignored).total, 0);
+}
+
+#[test]
+fn disabled() {
+    if !in_child(
+        "disabled",
+        &[("MEMORIA_ENABLED", "false"), ("MEMORIA_SAMPLE_RATE", "1")],
+    ) {
+        return;
+    }
+    allocate(MyUseCase::Allowed, 100);
+    assert_eq!(get(MyUseCase::Allowed).total, 0);
+    assert_eq!(ALLOCATOR.tracked_bytes(), 0);
+}
+
+#[test]
+fn usecases_by_name() {
+    if !in_child(
+        "usecases_by_name",
+        &[("MEMORIA_USECASES", "renamed,Ignored")],
+    ) {
+        return;
+    }
+    allocate(MyUseCase::Renamed, 100);
+    allocate(MyUseCase::Ignored, 10);
+    allocate(MyUseCase::Allowed, 1000);
+    assert_eq!(get(MyUseCase::Renamed).total, 100);
+    assert_eq!(get(MyUseCase::Ignored).total, 10);
+    assert_eq!(get(MyUseCase::Allowed).total, 0);
+}
+
+#[test]
+fn overridden_name_replaces_debug() {
+    if !in_child(
+        "overridden_name_replaces_debug",
+        &[("MEMORIA_USECASES", "Renamed")],
+    ) {
+        return;
+    }
+    allocate(MyUseCase::Renamed, 100);
+    assert_eq!(get(MyUseCase::Renamed).total, 0);
+}
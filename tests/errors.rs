@@ -0,0 +1,104 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use memoria::{Alloc, Error, Recorder, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn error_count(code: Error) -> usize {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get_error(code)))
+        .unwrap()
+}
+
+fn error_bytes(code: Error) -> usize {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get_error_bytes(code)))
+        .unwrap()
+}
+
+#[test]
+fn dealloc_untracked_pointer() {
+    let before = error_count(Error::DeallocUntrackedPointer);
+    let bytes_before = error_bytes(Error::DeallocUntrackedPointer);
+    let layout = Layout::new::<[u8; 64]>();
+    unsafe {
+        let ptr = System.alloc(layout);
+        ALLOCATOR.dealloc(ptr, layout);
+    }
+    assert!(error_count(Error::DeallocUntrackedPointer) > before);
+    assert!(error_bytes(Error::DeallocUntrackedPointer) >= bytes_before + 64);
+}
+
+#[test]
+fn flush_with_error_bytes() {
+    let recorder = StatsRecorder::<MyUseCase>::new();
+    recorder.on_error(Error::PointerTrackedTwice, Some(100));
+    recorder.on_error(Error::PointerTrackedTwice, Some(20));
+    recorder.on_error(Error::PointerTrackedTwice, None);
+
+    let mut errors = Vec::new();
+    recorder.flush_with_error_bytes(
+        |_, _| {},
+        |error, count, bytes| errors.push((error, count, bytes)),
+    );
+    assert!(errors.contains(&(Error::PointerTrackedTwice, 3, 120)));
+    assert!(errors.contains(&(Error::DeallocUntrackedPointer, 0, 0)));
+}
+
+#[test]
+fn alloc_in_thread_local_destructor() {
+    struct AllocOnDrop;
+
+    impl Drop for AllocOnDrop {
+        fn drop(&mut self) {
+            drop(vec![0u8; 4096]);
+        }
+    }
+
+    thread_local! {
+        static ALLOC_ON_DROP: AllocOnDrop = const { AllocOnDrop };
+    }
+
+    let before = error_count(Error::CurrentUsecaseContentionThreadLocal);
+    let total_before = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::None).total))
+        .unwrap();
+    std::thread::spawn(|| ALLOC_ON_DROP.with(|_| ()))
+        .join()
+        .unwrap();
+    assert_eq!(
+        error_count(Error::CurrentUsecaseContentionThreadLocal),
+        before
+    );
+    let total = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::None).total))
+        .unwrap();
+    assert!(total >= total_before + 4096);
+}
+
+#[test]
+fn error_index() {
+    for (i, error) in Error::ALL.into_iter().enumerate() {
+        assert_eq!(error.index(), i);
+    }
+
+    let recorder = StatsRecorder::<MyUseCase>::new();
+    recorder.on_error(Error::NegativeBalance, None);
+    let errors: Vec<_> = recorder.errors().collect();
+    assert_eq!(errors.len(), Error::ALL.len());
+    for (error, count) in errors {
+        assert_eq!(count, usize::from(error == Error::NegativeBalance));
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use memoria::{Alloc, Event, EventKind, EventLogRecorder, EventReader, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Logged,
+}
+
+impl UseCase for MyUseCase {}
+
+static CLOCK: AtomicU64 = AtomicU64::new(0);
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, EventLogRecorder<MyUseCase, StatsRecorder<MyUseCase>, 1024>> =
+    Alloc::new_with(
+        EventLogRecorder::new(StatsRecorder::new())
+            .with_clock(&|| CLOCK.fetch_add(1, Ordering::Relaxed)),
+        std::alloc::System,
+    );
+
+#[test]
+fn roundtrip() {
+    {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Logged);
+        drop(vec![0u8; 1234]);
+    }
+
+    let mut log = Vec::new();
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.drain(&mut log)))
+        .unwrap()
+        .unwrap();
+
+    let events: Vec<Event> = EventReader::new(&log[..])
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let logged: Vec<&Event> = events
+        .iter()
+        .filter(|event| event.use_case == MyUseCase::Logged.into_repr())
+        .collect();
+    assert_eq!(logged.len(), 2);
+    assert_eq!((logged[0].kind, logged[0].size), (EventKind::Alloc, 1234));
+    assert_eq!((logged[1].kind, logged[1].size), (EventKind::Dealloc, 1234));
+    assert!(logged[0].timestamp < logged[1].timestamp);
+}
+
+#[test]
+fn speedscope() {
+    let event = |kind, use_case: MyUseCase, size| Event {
+        timestamp: 0,
+        kind,
+        use_case: use_case.into_repr(),
+        size,
+    };
+    let events = [
+        event(EventKind::Alloc, MyUseCase::Logged, 10),
+        event(EventKind::Alloc, MyUseCase::Logged, 20),
+        event(EventKind::Alloc, MyUseCase::None, 5),
+        event(EventKind::Dealloc, MyUseCase::Logged, 30),
+    ];
+
+    let mut output = Vec::new();
+    memoria::export::write_speedscope::<MyUseCase>(&mut output, events).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(
+        output.contains(r#""frames":[{"name":"Logged"},{"name":"None"}]"#),
+        "{output}"
+    );
+    assert!(
+        output.contains(
+            r#""name":"allocated","unit":"bytes","startValue":0,"endValue":35,"events":[{"type":"O","frame":0,"at":0},{"type":"C","frame":0,"at":30},{"type":"O","frame":1,"at":30},{"type":"C","frame":1,"at":35}]"#
+        ),
+        "{output}"
+    );
+    assert!(output.contains(r#""name":"freed""#), "{output}");
+}
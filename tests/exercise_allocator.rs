@@ -0,0 +1,32 @@
+#![cfg(feature = "testing")]
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Harness,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn exercise_allocator() {
+    let exercised = memoria::testing::exercise_allocator(&ALLOCATOR, MyUseCase::Harness);
+    assert!(exercised.allocations > 0);
+
+    let stat = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::Harness)))
+        .unwrap();
+    assert_eq!(
+        (stat.current, stat.total as usize, stat.count as usize),
+        (0, exercised.bytes, exercised.allocations)
+    );
+}
@@ -0,0 +1,41 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Gpu,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn get(use_case: MyUseCase) -> memoria::Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+#[test]
+fn external() {
+    let buffer = ALLOCATOR.scope(MyUseCase::Gpu, || vec![0u8; 100]);
+    ALLOCATOR.record_external_alloc(MyUseCase::Gpu, 1 << 20);
+
+    let stat = get(MyUseCase::Gpu);
+    assert_eq!(stat.current, 100 + (1 << 20));
+    assert_eq!(stat.external, 1 << 20);
+    assert_eq!(stat.count, 2);
+
+    ALLOCATOR.record_external_dealloc(MyUseCase::Gpu, 1 << 20);
+    let stat = get(MyUseCase::Gpu);
+    assert_eq!(stat.current, 100);
+    assert_eq!(stat.peak, 100 + (1 << 20));
+    assert_eq!(stat.external, 0);
+    drop(buffer);
+}
@@ -0,0 +1,92 @@
+use std::sync::mpsc;
+use std::time::Duration;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Error, LiveStat, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Work,
+    Late,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn finalize() {
+    let (tx, rx) = mpsc::channel();
+    let _reporter =
+        memoria::reporter::spawn(&ALLOCATOR, Duration::from_secs(3600), move |report| {
+            tx.send(report).ok();
+        });
+    let _shrinker = memoria::shrink::spawn(&ALLOCATOR, Duration::from_secs(3600));
+
+    let (kept, freed_late) = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Work);
+        drop(vec![0u8; 100]);
+        (vec![0u8; 300], vec![0u8; 50])
+    };
+
+    let mut flushed = Vec::new();
+    let leaks = ALLOCATOR
+        .finalize(0, |recorder| {
+            recorder.flush(|use_case, stat| flushed.push((use_case, stat)), |_, _| {})
+        })
+        .unwrap();
+    assert!(ALLOCATOR.is_finalized());
+
+    // The reporter flushed one last time before exiting, so nothing was left for `finalize`.
+    let report = rx.recv().unwrap();
+    assert!(report
+        .stats
+        .iter()
+        .any(|(use_case, stat)| *use_case == MyUseCase::Work && stat.total == 450));
+    assert!(rx.recv().is_err());
+    assert!(!flushed
+        .iter()
+        .any(|(use_case, stat)| *use_case == MyUseCase::Work && stat.total > 0));
+
+    let work = leaks
+        .use_cases
+        .iter()
+        .find(|(use_case, _)| *use_case == MyUseCase::Work)
+        .map(|(_, live)| *live);
+    assert_eq!(
+        work,
+        Some(LiveStat {
+            bytes: 350,
+            count: 2
+        })
+    );
+
+    // Freeing memory allocated before finalizing is still recorded, while new allocations are
+    // passed through.
+    drop(freed_late);
+    let late = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Late);
+        vec![0u8; 1000]
+    };
+    drop(late);
+    let (work, late, untracked) = ALLOCATOR
+        .with_recorder(|recorder| {
+            Ok((
+                recorder.get(MyUseCase::Work),
+                recorder.get(MyUseCase::Late),
+                recorder.get_error(Error::DeallocUntrackedPointer),
+            ))
+        })
+        .unwrap();
+    // relative to the last flush
+    assert_eq!(work.current, -50);
+    assert_eq!(late.total, 0);
+    assert_eq!(untracked, 0);
+    drop(kept);
+}
@@ -0,0 +1,41 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Small,
+    Large,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn flush_to_map() {
+    {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Small);
+        drop(vec![0u8; 10]);
+    }
+    {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Large);
+        drop(vec![0u8; 1000]);
+    }
+
+    let stats = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.flush_to_map()))
+        .unwrap();
+    assert_eq!(stats[&MyUseCase::Small].total, 10);
+    assert_eq!(stats[&MyUseCase::Large].total, 1000);
+
+    let stats = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.flush_to_map()))
+        .unwrap();
+    assert!(!stats.contains_key(&MyUseCase::Large));
+}
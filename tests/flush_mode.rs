@@ -0,0 +1,48 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{FlushMode, Recorder, Stat, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Cache,
+}
+
+impl UseCase for MyUseCase {}
+
+/// Allocate 100 and 50 bytes, free the 50 bytes, flush, then free the 100 bytes and flush again.
+fn flush_twice(flush_mode: FlushMode) -> (Stat, Stat) {
+    let recorder = StatsRecorder::new().with_flush_mode(flush_mode);
+    recorder.on_alloc(MyUseCase::Cache, 100);
+    recorder.on_alloc(MyUseCase::Cache, 50);
+    recorder.on_dealloc(MyUseCase::Cache, 50);
+    let first = recorder.flush_to_map().remove(&MyUseCase::Cache).unwrap();
+    recorder.on_dealloc(MyUseCase::Cache, 100);
+    let second = recorder.flush_to_map().remove(&MyUseCase::Cache).unwrap();
+    (first, second)
+}
+
+#[test]
+fn reset() {
+    let (first, second) = flush_twice(FlushMode::Reset);
+    assert_eq!((first.current, first.peak, first.total), (100, 150, 150));
+    assert_eq!((second.current, second.peak, second.total), (-100, 0, 0));
+}
+
+#[test]
+fn keep_current() {
+    let (first, second) = flush_twice(FlushMode::KeepCurrent);
+    assert_eq!((first.current, first.peak, first.total), (100, 150, 150));
+    assert_eq!((second.current, second.peak, second.total), (0, 100, 0));
+}
+
+#[test]
+fn snapshot() {
+    let (first, second) = flush_twice(FlushMode::Snapshot);
+    assert_eq!((first.current, first.peak, first.total), (100, 150, 150));
+    assert_eq!((second.current, second.peak, second.total), (0, 150, 150));
+    assert_eq!(second.count, 2);
+}
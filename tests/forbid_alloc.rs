@@ -0,0 +1,76 @@
+use std::alloc::{GlobalAlloc, Layout};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Error, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Audio,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn forbid_alloc() {
+    let data = vec![0u8; 10];
+    let _usecase = ALLOCATOR.with_usecase(MyUseCase::Audio);
+    {
+        let _forbid = ALLOCATOR.forbid_alloc();
+        // deallocations are allowed
+        drop(data);
+        {
+            let _nested = ALLOCATOR.forbid_alloc();
+            drop(vec![0u8; 100]);
+        }
+        drop(vec![0u8; 100]);
+    }
+    drop(vec![0u8; 100]);
+
+    let (stat, errors) = ALLOCATOR
+        .with_recorder(|recorder| {
+            Ok((
+                recorder.get(MyUseCase::Audio),
+                recorder.get_error(Error::AllocInForbiddenScope),
+            ))
+        })
+        .unwrap();
+    assert_eq!(stat.forbidden, 2);
+    assert_eq!(stat.count, 3);
+    assert_eq!(errors, 2);
+}
+
+#[cfg(all(unix, debug_assertions))]
+#[test]
+fn abort_on_forbidden_alloc() {
+    let alloc: Alloc<MyUseCase> = Alloc::new().with_abort_on_forbidden_alloc();
+    let layout = Layout::new::<u64>();
+
+    // SAFETY: the child exits without unwinding.
+    let pid = unsafe { libc::fork() };
+    assert!(pid >= 0);
+    if pid == 0 {
+        let _forbid = alloc.forbid_alloc();
+        // SAFETY: the layout has a non-zero size.
+        unsafe { alloc.alloc(layout) };
+        // SAFETY: exiting without running destructors of the parent.
+        unsafe { libc::_exit(0) };
+    }
+
+    let mut status = 0;
+    // SAFETY: `pid` is our child process.
+    assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+    assert!(libc::WIFSIGNALED(status));
+    assert_eq!(libc::WTERMSIG(status), libc::SIGABRT);
+
+    // allocating is fine outside of forbidden scopes
+    // SAFETY: the layout has a non-zero size, and the pointer is freed with the same layout.
+    unsafe { alloc.dealloc(alloc.alloc(layout), layout) };
+}
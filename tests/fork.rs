@@ -0,0 +1,66 @@
+#![cfg(unix)]
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Parent,
+    Child,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn current(use_case: MyUseCase) -> isize {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case).current))
+        .unwrap()
+}
+
+#[test]
+fn after_fork() {
+    #[cfg(feature = "fork")]
+    memoria::fork::install(&ALLOCATOR).unwrap();
+
+    let parent = ALLOCATOR.scope(MyUseCase::Parent, || vec![0u8; 100]);
+    assert_eq!(current(MyUseCase::Parent), 100);
+
+    // SAFETY: the child only touches memoria and exits without unwinding.
+    let pid = unsafe { libc::fork() };
+    assert!(pid >= 0);
+    if pid == 0 {
+        // SAFETY: the child has a single thread.
+        #[cfg(not(feature = "fork"))]
+        unsafe {
+            ALLOCATOR.after_fork()
+        };
+
+        let ok = current(MyUseCase::Parent) == 0 && ALLOCATOR.tracked_bytes() == 0 && {
+            let child = ALLOCATOR.scope(MyUseCase::Child, || vec![0u8; 50]);
+            drop(parent);
+            drop(child);
+            // The parent's allocation was forgotten, so freeing it is not attributed.
+            current(MyUseCase::Parent) == 0 && current(MyUseCase::Child) == 0
+        };
+        // SAFETY: exiting without running destructors or atexit handlers of the parent.
+        unsafe { libc::_exit(if ok { 0 } else { 1 }) };
+    }
+
+    let mut status = 0;
+    // SAFETY: `pid` is our child process.
+    assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+    assert!(libc::WIFEXITED(status));
+    assert_eq!(libc::WEXITSTATUS(status), 0);
+
+    // The parent is unaffected.
+    assert_eq!(current(MyUseCase::Parent), 100);
+    drop(parent);
+    assert_eq!(current(MyUseCase::Parent), 0);
+}
@@ -0,0 +1,66 @@
+use std::alloc::System;
+use std::sync::Mutex;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Recorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, Clone, Copy, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Outer,
+    Inner,
+}
+
+impl UseCase for MyUseCase {}
+
+static EVENTS: Mutex<Vec<(&str, MyUseCase)>> = Mutex::new(Vec::new());
+
+struct ScopeRecorder;
+
+unsafe impl Recorder<MyUseCase> for ScopeRecorder {
+    fn on_usecase_enter(&self, use_case: MyUseCase) {
+        EVENTS.lock().unwrap().push(("switch to", use_case));
+    }
+
+    fn on_usecase_exit(&self, use_case: MyUseCase) {
+        EVENTS.lock().unwrap().push(("switch from", use_case));
+    }
+
+    fn on_guard_enter(&self, use_case: MyUseCase) {
+        EVENTS.lock().unwrap().push(("guard enter", use_case));
+    }
+
+    fn on_guard_exit(&self, use_case: MyUseCase) {
+        EVENTS.lock().unwrap().push(("guard exit", use_case));
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, ScopeRecorder> = Alloc::new_with(ScopeRecorder, System);
+
+#[test]
+fn guard_hooks() {
+    ALLOCATOR.scope(MyUseCase::Outer, || {
+        ALLOCATOR.scope(MyUseCase::Inner, || {});
+    });
+
+    assert_eq!(
+        *EVENTS.lock().unwrap(),
+        [
+            ("switch to", MyUseCase::Outer),
+            ("guard enter", MyUseCase::Outer),
+            ("switch from", MyUseCase::Outer),
+            ("switch to", MyUseCase::Inner),
+            ("guard enter", MyUseCase::Inner),
+            ("guard exit", MyUseCase::Inner),
+            ("switch from", MyUseCase::Inner),
+            ("switch to", MyUseCase::Outer),
+            ("guard exit", MyUseCase::Outer),
+            ("switch from", MyUseCase::Outer),
+        ]
+    );
+}
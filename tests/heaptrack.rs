@@ -0,0 +1,80 @@
+#![cfg(feature = "heaptrack")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use pretty_assertions::assert_eq;
+
+use memoria::export::heaptrack::HeaptrackWriter;
+use memoria::{Alloc, Event, EventKind, EventLogRecorder, EventReader, StatsRecorder, UseCase};
+
+memoria::usecase! {
+    enum MyUseCase {
+        default None,
+        Traced,
+    }
+}
+
+static CLOCK: AtomicU64 = AtomicU64::new(100);
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, EventLogRecorder<MyUseCase, StatsRecorder<MyUseCase>, 1024>> =
+    Alloc::new_with(
+        EventLogRecorder::new(StatsRecorder::new())
+            .with_clock(&|| CLOCK.fetch_add(1, Ordering::Relaxed)),
+        std::alloc::System,
+    );
+
+#[test]
+fn format() {
+    let event = |timestamp, kind, size| Event {
+        timestamp,
+        kind,
+        use_case: MyUseCase::Traced.into_repr(),
+        size,
+    };
+
+    let mut writer = HeaptrackWriter::<MyUseCase, _>::new(Vec::new()).unwrap();
+    writer
+        .write_events([
+            event(10, EventKind::Alloc, 16),
+            event(10, EventKind::Alloc, 32),
+            event(12, EventKind::Dealloc, 16),
+        ])
+        .unwrap();
+    let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        output,
+        "v 10500 3\nX memoria\n\
+         s Traced\ni 1 0 1 0 0\nt 1 0\na 10 1\n+ 0\n\
+         a 20 1\n+ 1\n\
+         c 2\n- 0\n"
+    );
+}
+
+#[test]
+fn drain() {
+    CLOCK.store(1 << 40, Ordering::Relaxed);
+    {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Traced);
+        drop(vec![0u8; 1234]);
+    }
+
+    let mut writer = HeaptrackWriter::<MyUseCase, _>::new(Vec::new()).unwrap();
+    let written = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.drain_heaptrack(&mut writer)))
+        .unwrap()
+        .unwrap();
+    assert!(written >= 2);
+    let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert!(output.contains("s Traced\n"), "{output}");
+    assert!(output.contains("a 4d2 "), "{output}");
+
+    // the next binary log continues from the last event written to heaptrack
+    drop(vec![0u8; 10]);
+    let mut log = Vec::new();
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.drain(&mut log)))
+        .unwrap()
+        .unwrap();
+    let first = EventReader::new(&log[..]).next().unwrap().unwrap();
+    assert!(first.timestamp < 1 << 40, "{first:?}");
+}
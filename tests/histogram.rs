@@ -0,0 +1,50 @@
+use std::alloc::System;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, HistogramRecorder, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Small,
+    Large,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, HistogramRecorder<MyUseCase>> =
+    Alloc::new_with(HistogramRecorder::new(StatsRecorder::new()), System);
+
+#[test]
+fn histogram() {
+    let small: Vec<Box<[u8; 64]>> = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Small);
+        let mut small = Vec::with_capacity(10);
+        small.extend((0..10).map(|_| Box::new([0u8; 64])));
+        small
+    };
+    let large = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Large);
+        vec![0u8; 1 << 20]
+    };
+
+    let (small_histogram, large_histogram) = ALLOCATOR
+        .with_recorder(|recorder| {
+            Ok((
+                recorder.get_histogram(MyUseCase::Small),
+                recorder.get_histogram(MyUseCase::Large),
+            ))
+        })
+        .unwrap();
+
+    // ten boxes, plus the 80-byte buffer of the Vec
+    assert_eq!(small_histogram.count_for_size(64), 11);
+    assert_eq!(small_histogram.total_count(), 11);
+    assert_eq!(large_histogram.iter().collect::<Vec<_>>(), [(1 << 20, 1)]);
+    drop((small, large));
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "http")]
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Served,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn get(addr: std::net::SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn stats_and_errors() {
+    let addr = memoria::http::serve(&ALLOCATOR, "127.0.0.1:0").unwrap();
+
+    let buffer = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Served);
+        vec![0u8; 1234]
+    };
+
+    let response = get(addr, "/stats");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(
+        response.contains("\"Served\":{\"current\":1234,"),
+        "{response}"
+    );
+
+    let response = get(addr, "/errors");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.contains("\"PointerTrackedTwice\":0"), "{response}");
+
+    let response = get(addr, "/nope");
+    assert!(
+        response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+        "{response}"
+    );
+
+    drop(buffer);
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "instrument")]
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Sync,
+    Async,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn total(use_case: MyUseCase) -> isize {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case).total))
+        .unwrap()
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+#[memoria::instrument(usecase = MyUseCase::Sync)]
+fn allocate_sync(size: usize) -> Result<Vec<u8>, std::num::TryFromIntError> {
+    let size: u16 = size.try_into()?;
+    Ok(vec![0u8; size.into()])
+}
+
+#[memoria::instrument(usecase = "MyUseCase::Async", allocator = ALLOCATOR)]
+async fn allocate_async(size: usize) -> Result<Vec<u8>, std::num::TryFromIntError> {
+    let size: u16 = size.try_into()?;
+    Ok(vec![0u8; size.into()])
+}
+
+#[test]
+fn sync_fn() {
+    drop(allocate_sync(100).unwrap());
+    assert_eq!(total(MyUseCase::Sync), 100);
+}
+
+#[test]
+fn async_fn() {
+    // creating the future does not run anything
+    let future = allocate_async(200);
+    drop(block_on(future).unwrap());
+    assert_eq!(total(MyUseCase::Async), 200);
+}
@@ -0,0 +1,46 @@
+#![cfg(feature = "iter")]
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, IteratorExt, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, Clone, Copy)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Lazy,
+    Attributed,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn total(use_case: MyUseCase) -> isize {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case).total))
+        .unwrap()
+}
+
+#[test]
+fn guard_around_adapter_does_not_attribute() {
+    let iter = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Lazy);
+        (0..10).map(|_| vec![0u8; 100])
+    };
+    let buffers: Vec<_> = iter.collect();
+    assert_eq!(total(MyUseCase::Lazy), 0);
+    drop(buffers);
+}
+
+#[test]
+fn attributed_adapter() {
+    let buffers: Vec<_> = (0..10)
+        .map(|_| vec![0u8; 100])
+        .attributed(&ALLOCATOR, MyUseCase::Attributed)
+        .collect();
+    assert_eq!(total(MyUseCase::Attributed), 1000);
+    drop(buffers);
+}
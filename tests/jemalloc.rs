@@ -0,0 +1,64 @@
+#![cfg(feature = "jemalloc")]
+use std::alloc::{GlobalAlloc, Layout};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+use tikv_jemallocator::Jemalloc;
+
+use memoria::jemalloc::JemallocStats;
+use memoria::{Alloc, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Work,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, StatsRecorder<MyUseCase>, Jemalloc> =
+    Alloc::new_with(StatsRecorder::new(), Jemalloc);
+
+const SIZE: usize = 64 << 20;
+
+#[test]
+fn jemalloc() {
+    // allocations made directly through the wrapped allocator are seen by jemalloc only
+    let tracked = ALLOCATOR.tracked_bytes();
+    let before = JemallocStats::read().unwrap();
+    let layout = Layout::from_size_align(SIZE, 8).unwrap();
+    let ptr = unsafe { ALLOCATOR.allocator().alloc(layout) };
+    assert!(!ptr.is_null());
+    let after = JemallocStats::read().unwrap();
+    assert!(after.active >= before.active + SIZE);
+    assert!(after.metadata > 0);
+    assert_eq!(ALLOCATOR.tracked_bytes(), tracked);
+    unsafe { ALLOCATOR.allocator().dealloc(ptr, layout) };
+
+    // the reporter reads jemalloc's stats next to the stats per usecase
+    let (tx, rx) = mpsc::channel();
+    let reporter = memoria::reporter::spawn(&ALLOCATOR, Duration::from_millis(10), move |report| {
+        tx.send(report).ok();
+    });
+    let buffer = ALLOCATOR.scope(MyUseCase::Work, || vec![1u8; SIZE]);
+    loop {
+        let report = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        let work = report
+            .stats
+            .iter()
+            .any(|(use_case, stat)| *use_case == MyUseCase::Work && stat.total == SIZE as isize);
+        if work {
+            let jemalloc = report.jemalloc.unwrap();
+            assert!(jemalloc.active >= SIZE);
+            assert!(jemalloc.resident >= SIZE);
+            break;
+        }
+    }
+    reporter.stop();
+    drop(buffer);
+}
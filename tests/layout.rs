@@ -0,0 +1,51 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Aligned,
+    Awkward,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[allow(dead_code)]
+#[repr(align(64))]
+struct CacheLine([u8; 64]);
+
+#[test]
+fn high_alignment() {
+    let value = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Aligned);
+        Box::new(CacheLine([0; 64]))
+    };
+    let stat = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::Aligned)))
+        .unwrap();
+    assert_eq!(stat.high_align, 1);
+    assert_eq!(stat.padding, 0);
+    drop(value);
+}
+
+#[test]
+fn padding() {
+    let value = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Awkward);
+        vec![0u8; 33]
+    };
+    let stat = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::Awkward)))
+        .unwrap();
+    assert_eq!(stat.high_align, 0);
+    // rounded up to the 40-byte size class
+    assert_eq!(stat.padding, 7);
+    drop(value);
+}
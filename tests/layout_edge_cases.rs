@@ -0,0 +1,120 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Error, ShardedTable, Stat, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, Clone, Copy)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Aligned,
+    Failed,
+    ZeroSized,
+    Mismatched,
+}
+
+impl UseCase for MyUseCase {}
+
+// Not the global allocator, such that every allocation seen here is made by the tests.
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+static SHARDED: Alloc<MyUseCase, StatsRecorder<MyUseCase>, System, ShardedTable> =
+    Alloc::builder().pointer_table().build();
+
+fn stat(use_case: MyUseCase) -> Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+fn errors<P: memoria::PointerTable>(
+    alloc: &Alloc<MyUseCase, StatsRecorder<MyUseCase>, System, P>,
+) -> Vec<(Error, usize)> {
+    alloc
+        .with_recorder(|recorder| Ok(recorder.errors().filter(|(_, count)| *count > 0).collect()))
+        .unwrap()
+}
+
+#[test]
+fn huge_alignment() {
+    let _guard = ALLOCATOR.with_usecase(MyUseCase::Aligned);
+    let layouts = [
+        Layout::from_size_align(100, 1 << 13).unwrap(),
+        Layout::from_size_align(100, 1 << 16).unwrap(),
+        Layout::from_size_align(1 << 20, 1 << 20).unwrap(),
+    ];
+    let ptrs = layouts.map(|layout| unsafe { ALLOCATOR.alloc(layout) });
+    for (ptr, layout) in ptrs.iter().zip(layouts) {
+        assert!(!ptr.is_null());
+        assert_eq!(*ptr as usize % layout.align(), 0);
+    }
+    let stat = stat(MyUseCase::Aligned);
+    assert_eq!(stat.current, 200 + (1 << 20));
+    assert_eq!(stat.count, 3);
+    assert_eq!(stat.high_align, 3);
+
+    for (ptr, layout) in ptrs.into_iter().zip(layouts) {
+        unsafe { ALLOCATOR.dealloc(ptr, layout) };
+    }
+    assert_eq!(self::stat(MyUseCase::Aligned).current, 0);
+}
+
+#[test]
+fn failed_alloc() {
+    let layout = Layout::from_size_align(isize::MAX as usize - 4096, 8).unwrap();
+    let _guard = ALLOCATOR.with_usecase(MyUseCase::Failed);
+    for _ in 0..2 {
+        assert!(unsafe { ALLOCATOR.alloc(layout) }.is_null());
+        assert!(unsafe { SHARDED.alloc(layout) }.is_null());
+    }
+    let stat = stat(MyUseCase::Failed);
+    assert_eq!((stat.current, stat.total, stat.count), (0, 0, 0));
+    assert_eq!(errors(&SHARDED), []);
+}
+
+#[test]
+fn zero_sized() {
+    let layout = Layout::from_size_align(0, 16).unwrap();
+    let _guard = ALLOCATOR.with_usecase(MyUseCase::ZeroSized);
+    let before = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get_error(Error::ZeroSizedAlloc)))
+        .unwrap();
+    unsafe {
+        let ptr = ALLOCATOR.alloc(layout);
+        if !ptr.is_null() {
+            ALLOCATOR.dealloc(ptr, layout);
+        }
+    }
+    let after = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get_error(Error::ZeroSizedAlloc)))
+        .unwrap();
+    assert!(after > before);
+    assert_eq!(stat(MyUseCase::ZeroSized).count, 0);
+}
+
+#[test]
+fn dealloc_layout_mismatch() {
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let ptr = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Mismatched);
+        unsafe { ALLOCATOR.alloc(layout) }
+    };
+    assert_eq!(stat(MyUseCase::Mismatched).current, 64);
+
+    // `System` frees memory without looking at the size.
+    unsafe { ALLOCATOR.dealloc(ptr, Layout::from_size_align(32, 8).unwrap()) };
+    let stat = stat(MyUseCase::Mismatched);
+    assert_eq!(stat.current, 0);
+    assert_eq!(stat.peak, 64);
+    let (count, bytes) = ALLOCATOR
+        .with_recorder(|recorder| {
+            Ok((
+                recorder.get_error(Error::DeallocLayoutMismatch),
+                recorder.get_error_bytes(Error::DeallocLayoutMismatch),
+            ))
+        })
+        .unwrap();
+    assert_eq!((count, bytes), (1, 32));
+}
@@ -0,0 +1,55 @@
+use std::alloc::System;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Error, LazyRecorder, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, Clone, Copy)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Parsing,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, LazyRecorder<MyUseCase, StatsRecorder<MyUseCase>>> =
+    Alloc::new_with(LazyRecorder::new(), System);
+
+#[test]
+fn lazy_recorder() {
+    let early = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Parsing);
+        vec![0u8; 4096]
+    };
+    ALLOCATOR
+        .with_recorder(|recorder| {
+            assert!(recorder.inner().is_none());
+            assert!(recorder.early_bytes() >= 4096);
+            Ok(())
+        })
+        .unwrap();
+
+    assert!(ALLOCATOR.init_recorder(StatsRecorder::new).is_ok());
+    assert!(ALLOCATOR.init_recorder(StatsRecorder::new).is_err());
+
+    let late = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Parsing);
+        vec![0u8; 1024]
+    };
+    drop(early);
+
+    ALLOCATOR
+        .with_recorder(|recorder| {
+            let inner = recorder.inner().unwrap();
+            assert_eq!(inner.get(MyUseCase::Parsing).total, 1024);
+            assert_eq!(inner.get(MyUseCase::Parsing).current, 1024);
+            assert_eq!(inner.get_error(Error::DeallocUntrackedPointer), 0);
+            Ok(())
+        })
+        .unwrap();
+    drop(late);
+}
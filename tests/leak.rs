@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, LiveStat, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Cache,
+    Session,
+    Survivor,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new().with_age_clock(&|| TICKS.load(Ordering::Relaxed));
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+#[test]
+fn leak_report() {
+    let leaked = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Cache);
+        (vec![0u8; 100], vec![0u8; 3000], vec![0u8; 200])
+    };
+
+    let report = ALLOCATOR.leak_report(2).unwrap();
+    let cache = report
+        .use_cases
+        .iter()
+        .find(|(use_case, _)| *use_case == MyUseCase::Cache)
+        .map(|(_, stat)| *stat);
+    assert_eq!(
+        cache,
+        Some(LiveStat {
+            bytes: 3300,
+            count: 3
+        })
+    );
+
+    assert_eq!(report.largest.len(), 2);
+    assert!(report.largest[0].size >= report.largest[1].size);
+    assert!(report.largest[0].size >= 3000);
+    drop(leaked);
+}
+
+#[test]
+fn inspect_live() {
+    let old = ALLOCATOR.scope(MyUseCase::Session, || vec![0u8; 100]);
+    TICKS.fetch_add(10, Ordering::Relaxed);
+    let new = ALLOCATOR.scope(MyUseCase::Session, || vec![0u8; 30]);
+
+    let mut sessions = Vec::new();
+    let mut old_bytes = HashMap::new();
+    ALLOCATOR
+        .inspect_live(|allocation| {
+            if allocation.use_case == MyUseCase::Session {
+                sessions.push((allocation.size, allocation.age));
+            }
+            if allocation.age >= 10 {
+                *old_bytes.entry(allocation.use_case).or_insert(0) += allocation.size;
+            }
+        })
+        .unwrap();
+    sessions.sort();
+    assert_eq!(sessions, [(30, 0), (100, 10)]);
+    assert_eq!(old_bytes.get(&MyUseCase::Session), Some(&100));
+    drop((old, new));
+}
+
+#[test]
+fn survivors() {
+    let old = ALLOCATOR.scope(MyUseCase::Survivor, || vec![0u8; 100]);
+    ALLOCATOR.next_generation();
+    ALLOCATOR.flush().unwrap();
+    let new = ALLOCATOR.scope(MyUseCase::Survivor, || vec![0u8; 30]);
+    ALLOCATOR.next_generation();
+
+    let get = |min_generations| {
+        ALLOCATOR
+            .survivors(min_generations)
+            .unwrap()
+            .into_iter()
+            .find(|(use_case, _)| *use_case == MyUseCase::Survivor)
+            .map(|(_, stat)| stat)
+    };
+    assert_eq!(
+        get(1),
+        Some(LiveStat {
+            bytes: 130,
+            count: 2
+        })
+    );
+    assert_eq!(
+        get(3),
+        Some(LiveStat {
+            bytes: 100,
+            count: 1
+        })
+    );
+    assert_eq!(get(4), None);
+
+    let mut generations = Vec::new();
+    ALLOCATOR
+        .inspect_live(|allocation| {
+            if allocation.use_case == MyUseCase::Survivor {
+                generations.push((allocation.size, allocation.generations));
+            }
+        })
+        .unwrap();
+    generations.sort();
+    assert_eq!(generations, [(30, 1), (100, 3)]);
+    drop((old, new));
+}
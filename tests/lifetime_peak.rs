@@ -0,0 +1,51 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Recorder, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Cache,
+    Other,
+}
+
+impl UseCase for MyUseCase {}
+
+#[test]
+fn lifetime_peak() {
+    let recorder = StatsRecorder::new().with_lifetime_peak();
+    recorder.on_alloc(MyUseCase::Cache, 100);
+    recorder.on_alloc(MyUseCase::Cache, 50);
+    recorder.on_dealloc(MyUseCase::Cache, 50);
+
+    let first = recorder.flush_to_map().remove(&MyUseCase::Cache).unwrap();
+    assert_eq!((first.peak, first.lifetime_peak), (150, 150));
+
+    // still 100 bytes alive from before the flush, so this does not exceed the lifetime peak
+    recorder.on_alloc(MyUseCase::Cache, 40);
+    let second = recorder.get(MyUseCase::Cache);
+    assert_eq!(
+        (second.current, second.peak, second.lifetime_peak),
+        (40, 40, 150)
+    );
+
+    recorder.on_alloc(MyUseCase::Cache, 20);
+    assert_eq!(recorder.get(MyUseCase::Cache).lifetime_peak, 160);
+
+    // survives flushes without any activity in between
+    recorder.flush_to_map();
+    let third = recorder.flush_to_map().remove(&MyUseCase::Cache).unwrap();
+    assert_eq!((third.peak, third.lifetime_peak), (0, 160));
+}
+
+#[test]
+fn disabled() {
+    let recorder = StatsRecorder::new();
+    recorder.on_alloc(MyUseCase::Other, 100);
+    assert_eq!(recorder.get(MyUseCase::Other).lifetime_peak, 0);
+    recorder.flush_to_map();
+    assert!(recorder.flush_to_map().is_empty());
+}
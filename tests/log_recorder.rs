@@ -0,0 +1,90 @@
+#![cfg(feature = "log")]
+use std::alloc::System;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, LogRecorder, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Parse,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, LogRecorder<MyUseCase>> = Alloc::new_with(
+    LogRecorder::new(StatsRecorder::new())
+        .with_min_interval(Duration::from_secs(3600))
+        .with_warn_above(1 << 20),
+    System,
+);
+
+static LINES: Mutex<Vec<(log::Level, String)>> = Mutex::new(Vec::new());
+
+struct TestLogger;
+
+impl log::Log for TestLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target() == "memoria"
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            LINES
+                .lock()
+                .unwrap()
+                .push((record.level(), record.args().to_string()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn log_recorder() {
+    log::set_logger(&TestLogger).unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+
+    let data = ALLOCATOR.scope(MyUseCase::Parse, || vec![0u8; 2 << 20]);
+    ALLOCATOR.set_cap(MyUseCase::Parse, 3 << 20);
+    let mut refused: Vec<u8> = Vec::new();
+    ALLOCATOR.scope(MyUseCase::Parse, || {
+        assert!(refused.try_reserve_exact(4 << 20).is_err());
+    });
+
+    ALLOCATOR.flush().unwrap();
+    let lines = std::mem::take(&mut *LINES.lock().unwrap());
+    let parse: Vec<_> = lines
+        .iter()
+        .filter(|(_, line)| line.starts_with("Parse: "))
+        .collect();
+    assert_eq!(
+        parse,
+        [
+            &(
+                log::Level::Info,
+                "Parse: current 2.0 MiB, peak 2.0 MiB, total 2.0 MiB".to_owned()
+            ),
+            &(
+                log::Level::Warn,
+                "Parse: current 2.0 MiB is above 1.0 MiB".to_owned()
+            ),
+        ]
+    );
+    assert!(lines.contains(&(
+        log::Level::Warn,
+        "1 allocations were refused by caps".to_owned()
+    )));
+
+    // Flushing again right away is rate-limited.
+    ALLOCATOR.flush().unwrap();
+    assert_eq!(LINES.lock().unwrap().len(), 0);
+    drop(data);
+}
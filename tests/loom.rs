@@ -0,0 +1,365 @@
+//! Model-checks memoria's own synchronization primitives, and the data structures built on them,
+//! with loom.
+//!
+//! Run with:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --release --test loom
+//! ```
+#![cfg(loom)]
+
+extern crate alloc;
+
+#[allow(dead_code)]
+#[path = "../src/sync.rs"]
+mod sync;
+
+#[allow(dead_code)]
+#[path = "../src/pointers/sharded/table.rs"]
+mod table;
+
+#[path = "../src/eventlog/ring.rs"]
+mod ring;
+
+/// Needed by `ring`.
+type UseCaseRepr = u64;
+
+use std::cell::RefCell;
+
+use loom::cell::UnsafeCell;
+use loom::sync::Arc;
+use loom::thread;
+
+use ring::{Event, EventKind, Ring};
+use sync::{exclusive, Busy, ResettableCell, SpinLock, TryLock};
+use table::Table;
+
+#[test]
+fn spin_lock() {
+    loom::model(|| {
+        let lock = Arc::new(SpinLock::new(0usize));
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let lock = lock.clone();
+                thread::spawn(move || lock.with(|value| *value += 1))
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(lock.with(|value| *value), 2);
+    });
+}
+
+#[test]
+fn try_lock() {
+    struct Guarded {
+        lock: TryLock,
+        value: UnsafeCell<usize>,
+    }
+
+    // SAFETY: `value` is only accessed while holding `lock`.
+    unsafe impl Sync for Guarded {}
+
+    loom::model(|| {
+        let guarded = Arc::new(Guarded {
+            lock: TryLock::new(),
+            value: UnsafeCell::new(0),
+        });
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let guarded = guarded.clone();
+                thread::spawn(move || {
+                    if !guarded.lock.try_lock() {
+                        return 0;
+                    }
+                    // SAFETY: we hold the lock.
+                    guarded.value.with_mut(|value| unsafe { *value += 1 });
+                    guarded.lock.unlock();
+                    1
+                })
+            })
+            .collect();
+        let acquired: usize = threads.into_iter().map(|t| t.join().unwrap()).sum();
+        assert!(acquired >= 1);
+        assert!(guarded.lock.try_lock());
+        // SAFETY: we hold the lock.
+        assert_eq!(guarded.value.with(|value| unsafe { *value }), acquired);
+    });
+}
+
+#[test]
+fn resettable_cell() {
+    loom::model(|| {
+        let cell = Arc::new(ResettableCell::new());
+        let threads: Vec<_> = (0..2)
+            .map(|i| {
+                let cell = cell.clone();
+                thread::spawn(move || {
+                    let value: &Vec<usize> = cell.get_or_init(|| vec![i; 4]);
+                    assert!(value.iter().all(|&x| x == value[0]));
+                    value[0]
+                })
+            })
+            .collect();
+        let seen: Vec<usize> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        assert_eq!(seen[0], seen[1]);
+        assert_eq!(cell.get().map(|value| value[0]), Some(seen[0]));
+    });
+}
+
+/// Two threads insert into and remove from the same shard of the sharded pointer table, with
+/// pointers that share a probe sequence.
+#[test]
+fn sharded_table_shard() {
+    loom::model(|| {
+        let mut table = Table::new();
+        table.insert(8, 0u64);
+        // pointers with the same home as the first one, such that removals shift entries back
+        let colliding: Vec<usize> = (2..)
+            .map(|i| i * 8)
+            .filter(|&ptr| table.home(ptr) == table.home(8))
+            .take(3)
+            .collect();
+        table.insert(colliding[0], 1);
+        let shard = Arc::new(SpinLock::new(table));
+
+        let first = {
+            let shard = shard.clone();
+            let ptr = colliding[1];
+            thread::spawn(move || {
+                assert_eq!(shard.with(|table| table.insert(ptr, 2)), None);
+                shard.with(|table| table.remove(8))
+            })
+        };
+        let second = {
+            let shard = shard.clone();
+            let (ptr, existing) = (colliding[2], colliding[0]);
+            thread::spawn(move || {
+                assert_eq!(shard.with(|table| table.insert(ptr, 3)), None);
+                (
+                    shard.with(|table| table.remove(ptr)),
+                    shard.with(|table| table.remove(existing)),
+                )
+            })
+        };
+        assert_eq!(first.join().unwrap(), Some(0));
+        assert_eq!(second.join().unwrap(), (Some(3), Some(1)));
+
+        shard.with(|table| {
+            assert_eq!(table.len(), 1);
+            assert_eq!(table.get(colliding[1]), Some(2));
+            for ptr in [8, colliding[0], colliding[2]] {
+                assert_eq!(table.get(ptr), None);
+            }
+        });
+    });
+}
+
+/// Once a pointer is in the table, everything written before inserting it is visible to the
+/// thread that removes it. This is what orders `Recorder::on_alloc` before `Recorder::on_dealloc`.
+#[test]
+fn sharded_table_handoff() {
+    struct Shared {
+        shard: SpinLock<Table<u64>>,
+        recorded: UnsafeCell<u64>,
+    }
+
+    // SAFETY: `recorded` is written before the pointer is inserted, and only read after the
+    // pointer was removed, which is what this test checks.
+    unsafe impl Sync for Shared {}
+
+    loom::model(|| {
+        let mut table = Table::new();
+        // allocate the slots outside of the model
+        table.insert(8, 0);
+        table.remove(8);
+        let shared = Arc::new(Shared {
+            shard: SpinLock::new(table),
+            recorded: UnsafeCell::new(0),
+        });
+
+        let allocating = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                // SAFETY: see above.
+                shared.recorded.with_mut(|value| unsafe { *value = 42 });
+                shared.shard.with(|table| table.insert(16, 1));
+            })
+        };
+        let removed = shared.shard.with(|table| table.remove(16));
+        if removed.is_some() {
+            // SAFETY: see above.
+            assert_eq!(shared.recorded.with(|value| unsafe { *value }), 42);
+        }
+        allocating.join().unwrap();
+    });
+}
+
+/// Mirrors `Alloc::synchronized` around the pointer table: one thread has its own state, two
+/// threads share the fallback lock, and each allocates again while holding the shard, like a
+/// table that grows does. The nested allocations must fail instead of deadlocking, and the table
+/// must only contain the pointers that were inserted and not removed.
+#[test]
+fn synchronized_reentrancy() {
+    fn allocate(
+        state: Option<&RefCell<Option<u64>>>,
+        fallback: &TryLock,
+        shard: &SpinLock<Table<u64>>,
+        ptr: usize,
+    ) -> Result<(), Busy> {
+        exclusive(state, Some(fallback), |_| {
+            shard.with(|table| {
+                assert_eq!(table.insert(ptr, 1), None);
+                let expected = if state.is_some() {
+                    Busy::Local
+                } else {
+                    Busy::Fallback
+                };
+                assert_eq!(exclusive(state, Some(fallback), |_| ()), Err(expected));
+            })
+        })
+    }
+
+    fn deallocate(
+        state: Option<&RefCell<Option<u64>>>,
+        fallback: &TryLock,
+        shard: &SpinLock<Table<u64>>,
+        ptr: usize,
+    ) -> Result<(), Busy> {
+        exclusive(state, Some(fallback), |_| {
+            assert_eq!(shard.with(|table| table.remove(ptr)), Some(1));
+        })
+    }
+
+    loom::model(|| {
+        let mut table = Table::new();
+        table.insert(8, 0);
+        table.remove(8);
+        let shared = Arc::new((TryLock::new(), SpinLock::new(table)));
+
+        let without_state: Vec<_> = [16, 24]
+            .into_iter()
+            .map(|ptr| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    let (fallback, shard) = &*shared;
+                    // a pointer that was not inserted is not removed either
+                    allocate(None, fallback, shard, ptr).is_ok()
+                        && deallocate(None, fallback, shard, ptr).is_err()
+                })
+            })
+            .collect();
+
+        let (fallback, shard) = &*shared;
+        let state = RefCell::new(None);
+        allocate(Some(&state), fallback, shard, 32).unwrap();
+        deallocate(Some(&state), fallback, shard, 32).unwrap();
+
+        let leaked: Vec<bool> = without_state
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect();
+        shard.with(|table| {
+            for (ptr, leaked) in [16, 24].into_iter().zip(leaked) {
+                assert_eq!(table.get(ptr).is_some(), leaked);
+            }
+            assert_eq!(table.get(32), None);
+        });
+    });
+}
+
+fn event(id: u64) -> Event {
+    Event {
+        timestamp: id,
+        kind: if id.is_multiple_of(2) {
+            EventKind::Alloc
+        } else {
+            EventKind::Dealloc
+        },
+        use_case: id * 10,
+        size: id as usize * 100,
+    }
+}
+
+/// Two threads write into a buffer that is too small for all of their events while it is being
+/// drained. Every event is either read exactly once and intact, or counted as dropped.
+#[test]
+fn event_log_ring() {
+    loom::model(|| {
+        let ring = Arc::new(Ring::<2>::new());
+        let writers: Vec<_> = [vec![1, 2], vec![3]]
+            .into_iter()
+            .map(|ids| {
+                let ring = ring.clone();
+                thread::spawn(move || ids.into_iter().for_each(|id| ring.push(event(id))))
+            })
+            .collect();
+
+        let mut seen = Vec::new();
+        let mut read = |event: Event| {
+            assert_eq!(event, self::event(event.timestamp));
+            seen.push(event.timestamp);
+            Ok::<_, ()>(())
+        };
+        ring.drain_with(&mut read).unwrap();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        ring.drain_with(&mut read).unwrap();
+
+        let mut sorted = seen.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), seen.len());
+        assert_eq!(seen.len() + ring.dropped(), 3);
+        // events of one thread are read in the order they were written
+        let first = seen.iter().position(|&id| id == 1);
+        let second = seen.iter().position(|&id| id == 2);
+        if let (Some(first), Some(second)) = (first, second) {
+            assert!(first < second);
+        }
+    });
+}
+
+/// Only one of several concurrent drains reads events, and none are lost or read twice.
+#[test]
+fn event_log_concurrent_drains() {
+    loom::model(|| {
+        let ring = Arc::new(Ring::<2>::new());
+        let writer = {
+            let ring = ring.clone();
+            thread::spawn(move || {
+                ring.push(event(1));
+                ring.push(event(2));
+            })
+        };
+        let drainer = {
+            let ring = ring.clone();
+            thread::spawn(move || {
+                let mut seen = Vec::new();
+                ring.drain_with(|event| {
+                    seen.push(event.timestamp);
+                    Ok::<_, ()>(())
+                })
+                .unwrap();
+                seen
+            })
+        };
+
+        let mut seen = Vec::new();
+        let mut read = |event: Event| {
+            seen.push(event.timestamp);
+            Ok::<_, ()>(())
+        };
+        ring.drain_with(&mut read).unwrap();
+        writer.join().unwrap();
+        let mut drained = drainer.join().unwrap();
+        ring.drain_with(&mut read).unwrap();
+
+        drained.extend(seen);
+        drained.sort();
+        assert_eq!(drained, [1, 2]);
+        assert_eq!(ring.dropped(), 0);
+    });
+}
@@ -0,0 +1,73 @@
+use pretty_assertions::assert_eq;
+
+use std::alloc::System;
+
+use memoria::{Alloc, DeallocAttribution, NoopRecorder, ShardedTable, Stat, UseCase, UseCaseBytes};
+
+memoria::usecase! {
+    /// Usecases of this test.
+    enum MyUseCase {
+        default None,
+        /// Allocations made by [macro_usecase].
+        Macro,
+        Explicit = 42,
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = memoria::new!();
+
+#[test]
+fn generated_items() {
+    assert_eq!(MyUseCase::default(), MyUseCase::None);
+    assert_eq!(UseCaseBytes::from(MyUseCase::Macro), 1);
+    assert_eq!(UseCaseBytes::from(MyUseCase::Explicit), 42);
+    assert_eq!(MyUseCase::try_from(42), Ok(MyUseCase::Explicit));
+    assert_eq!(MyUseCase::try_from(2), Err(2));
+    assert_eq!(MyUseCase::Explicit.name(), "Explicit");
+    assert_eq!(
+        MyUseCase::all_variants(),
+        &[MyUseCase::None, MyUseCase::Macro, MyUseCase::Explicit]
+    );
+}
+
+#[test]
+fn macro_usecase() {
+    let _buffer = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Macro);
+        vec![0u8; 100]
+    };
+    let stat = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::Macro)))
+        .unwrap();
+    assert_eq!(stat.current, 100);
+}
+
+#[test]
+fn exported_name() {
+    assert_eq!(UseCase::name(&MyUseCase::Macro), "Macro");
+
+    let mut output = Vec::new();
+    memoria::export::write_prometheus(&mut output, [(MyUseCase::Explicit, Stat::default())])
+        .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("memoria_current_bytes{usecase=\"Explicit\"} 0\n"));
+}
+
+#[test]
+fn new_with_options() {
+    static CONFIGURED: Alloc<MyUseCase, NoopRecorder<MyUseCase>, System, ShardedTable> = memoria::new! {
+        recorder: NoopRecorder::new(),
+        allocator: System,
+        sample_rate: 4,
+        min_size: 64,
+        dealloc_attribution: DeallocAttribution::Current,
+        abort_on_forbidden_alloc,
+        untracked_default,
+        pointer_table,
+    };
+
+    let _: &System = CONFIGURED.allocator();
+    let _guard = CONFIGURED.with_usecase(MyUseCase::Macro);
+    assert_eq!(CONFIGURED.current_usecase(), Some(MyUseCase::Macro));
+}
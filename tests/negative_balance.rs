@@ -0,0 +1,37 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Error, Recorder, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Cache,
+}
+
+impl UseCase for MyUseCase {}
+
+#[test]
+fn negative_balance() {
+    let recorder = StatsRecorder::new();
+    recorder.on_alloc(MyUseCase::Cache, 100);
+    recorder.flush_to_map();
+
+    // negative after the flush, but memory allocated before it is freed
+    recorder.on_dealloc(MyUseCase::Cache, 100);
+    let stat = recorder.get(MyUseCase::Cache);
+    assert_eq!((stat.current, stat.negative_balance), (-100, 0));
+    assert_eq!(recorder.get_error(Error::NegativeBalance), 0);
+
+    // freeing memory that was never allocated
+    recorder.on_dealloc(MyUseCase::Cache, 10);
+    recorder.on_external_dealloc(MyUseCase::Cache, 10);
+    let stat = recorder.get(MyUseCase::Cache);
+    assert_eq!((stat.current, stat.negative_balance), (-120, 2));
+
+    let mut errors = Vec::new();
+    recorder.flush(|_, _| {}, |error, count| errors.push((error, count)));
+    assert!(errors.contains(&(Error::NegativeBalance, 2)));
+}
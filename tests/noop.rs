@@ -0,0 +1,31 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, NoopAlloc, NoopRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Ignored,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: NoopAlloc<MyUseCase> = Alloc::new_with(NoopRecorder::new(), std::alloc::System);
+
+#[test]
+fn noop() {
+    let buffer = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Ignored);
+        assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::Ignored));
+        vec![0u8; 100]
+    };
+
+    let report = ALLOCATOR.leak_report(10).unwrap();
+    assert_eq!(report.use_cases.len(), 0);
+    assert_eq!(report.largest.len(), 0);
+    drop(buffer);
+}
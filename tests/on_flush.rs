@@ -0,0 +1,66 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Recorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Counted,
+}
+
+impl UseCase for MyUseCase {}
+
+/// Counts allocated bytes of one usecase, and only publishes them when flushed.
+struct BatchingRecorder {
+    pending: AtomicUsize,
+    published: AtomicUsize,
+}
+
+unsafe impl Recorder<MyUseCase> for BatchingRecorder {
+    fn on_alloc(&self, use_case: MyUseCase, size: usize) -> bool {
+        if use_case == MyUseCase::Counted {
+            self.pending.fetch_add(size, Ordering::Relaxed);
+        }
+        false
+    }
+
+    fn on_flush(&self) {
+        // allocations made while flushing are not recorded
+        let _scratch = vec![0u8; 1000];
+        let pending = self.pending.swap(0, Ordering::Relaxed);
+        self.published.fetch_add(pending, Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, BatchingRecorder> = Alloc::new_with(
+    BatchingRecorder {
+        pending: AtomicUsize::new(0),
+        published: AtomicUsize::new(0),
+    },
+    std::alloc::System,
+);
+
+fn published() -> usize {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.published.load(Ordering::Relaxed)))
+        .unwrap()
+}
+
+#[test]
+fn on_flush() {
+    let buffer = ALLOCATOR.scope(MyUseCase::Counted, || vec![0u8; 100]);
+    assert_eq!(published(), 0);
+
+    ALLOCATOR.flush().unwrap();
+    assert_eq!(published(), 100);
+
+    ALLOCATOR.flush().unwrap();
+    assert_eq!(published(), 100);
+    drop(buffer);
+}
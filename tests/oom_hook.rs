@@ -0,0 +1,53 @@
+#![cfg(feature = "alloc-error-hook")]
+use std::alloc::Layout;
+use std::process::Command;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Render,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+/// The process aborts after the hook ran, so the allocation fails in a child process.
+#[test]
+fn oom_hook() {
+    if std::env::var_os("MEMORIA_TEST_CHILD").is_some() {
+        memoria::oom::install_hook::<MyUseCase>();
+        let _data = ALLOCATOR.scope(MyUseCase::Render, || vec![0u8; 8100]);
+        ALLOCATOR
+            .with_recorder(|recorder| {
+                recorder.flush(|_, _| (), |_, _| ());
+                Ok(())
+            })
+            .unwrap();
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Render);
+        std::alloc::handle_alloc_error(Layout::from_size_align(1 << 30, 8).unwrap());
+    }
+
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "oom_hook", "--test-threads=1", "--nocapture"])
+        .env("MEMORIA_TEST_CHILD", "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let failure = stderr
+        .find("memory allocation of 1073741824 bytes failed in usecase Render\n")
+        .unwrap();
+    let summary = stderr.find("memoria: stats at last flush:\n").unwrap();
+    assert!(failure < summary);
+    assert!(
+        stderr[summary..].contains("\n  usecase Render: current: 8100, peak: 8100, total: 8100\n")
+    );
+}
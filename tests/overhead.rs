@@ -0,0 +1,35 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Busy,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn overhead() {
+    // many live allocations force memoria to grow its table of tracked pointers
+    let buffers: Vec<Box<u64>> =
+        ALLOCATOR.scope(MyUseCase::Busy, || (0..10_000).map(Box::new).collect());
+
+    let overhead = ALLOCATOR.overhead();
+    assert!(overhead.current > 10_000 * 8, "{overhead:?}");
+    assert!(overhead.peak >= overhead.current);
+    assert!(overhead.total >= overhead.peak);
+    assert!(overhead.count > 0);
+
+    let stat = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::Busy)))
+        .unwrap();
+    assert_eq!(stat.current, 10_000 * 8 + 10_000 * 8);
+    drop(buffers);
+}
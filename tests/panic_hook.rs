@@ -0,0 +1,43 @@
+use std::process::Command;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Parse,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+/// The hook writes to the stderr of the process, which the test harness does not capture, so the
+/// panic happens in a child process.
+#[test]
+fn panic_hook() {
+    if std::env::var_os("MEMORIA_TEST_CHILD").is_some() {
+        memoria::install_panic_hook(&ALLOCATOR);
+        let data = ALLOCATOR.scope(MyUseCase::Parse, || vec![0u8; 3 << 20]);
+        panic!("parsing failed with {} bytes", data.len());
+    }
+
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "panic_hook", "--test-threads=1", "--nocapture"])
+        .env("MEMORIA_TEST_CHILD", "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let panic = stderr.find("parsing failed with 3145728 bytes").unwrap();
+    let summary = stderr
+        .find("memoria: memory at panic, by current bytes:\n")
+        .unwrap();
+    assert!(panic < summary);
+    assert!(stderr[summary..].contains("\n  Parse: current 3.0 MiB, peak 3.0 MiB, total "));
+}
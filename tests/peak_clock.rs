@@ -0,0 +1,46 @@
+use std::alloc::System;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Spiky,
+}
+
+impl UseCase for MyUseCase {}
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new_with(
+    StatsRecorder::new().with_peak_clock(&|| TICKS.load(Ordering::Relaxed)),
+    System,
+);
+
+fn allocate(size: usize) {
+    let _guard = ALLOCATOR.with_usecase(MyUseCase::Spiky);
+    drop(vec![0u8; size]);
+}
+
+#[test]
+fn peak_at() {
+    TICKS.store(1, Ordering::Relaxed);
+    allocate(100);
+    TICKS.store(2, Ordering::Relaxed);
+    allocate(1000);
+    TICKS.store(3, Ordering::Relaxed);
+    allocate(500);
+
+    let stat = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::Spiky)))
+        .unwrap();
+    assert_eq!(stat.peak, 1000);
+    assert_eq!(stat.peak_at, 2);
+}
@@ -0,0 +1,49 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{current_thread_index, Alloc, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq, PartialOrd, Eq, Ord)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Stage,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> =
+    Alloc::new_with(StatsRecorder::new().with_per_thread(), std::alloc::System);
+
+#[test]
+fn per_thread() {
+    let spawn = |size| {
+        std::thread::spawn(move || {
+            let buffer = ALLOCATOR.scope(MyUseCase::Stage, || vec![0u8; size]);
+            (current_thread_index(), buffer)
+        })
+    };
+    let (small_thread, small) = spawn(100).join().unwrap();
+    let (large_thread, large) = spawn(1000).join().unwrap();
+    assert_ne!(small_thread, large_thread);
+    assert_ne!(current_thread_index(), 0);
+    assert_eq!(current_thread_index(), current_thread_index());
+
+    let mut threads = Vec::new();
+    ALLOCATOR
+        .with_recorder(|recorder| {
+            recorder.flush_threads(|thread, use_case, stat| {
+                if use_case == MyUseCase::Stage {
+                    threads.push((thread, stat.current));
+                }
+            });
+            Ok(())
+        })
+        .unwrap();
+    threads.sort();
+
+    assert_eq!(threads, vec![(small_thread, 100), (large_thread, 1000)]);
+    drop((small, large));
+}
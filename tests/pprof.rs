@@ -0,0 +1,52 @@
+#![cfg(feature = "pprof")]
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Profiled,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn write_profile() {
+    {
+        let _guard = ALLOCATOR.with_usecase_at(MyUseCase::Profiled);
+        drop(vec![0u8; 1234]);
+    }
+
+    let (stats, callsites) = ALLOCATOR
+        .with_recorder(|recorder| {
+            let (mut stats, mut callsites) = (Vec::new(), Vec::new());
+            recorder.flush(|use_case, stat| stats.push((use_case, stat)), |_, _| {});
+            recorder.flush_callsites(|use_case, callsite, stat| {
+                callsites.push((use_case, callsite, stat))
+            });
+            Ok((stats, callsites))
+        })
+        .unwrap();
+    assert_eq!(callsites.len(), 1);
+
+    let mut output = Vec::new();
+    memoria::export::pprof::write_profile(&mut output, stats, callsites).unwrap();
+
+    let mut profile = Vec::new();
+    GzDecoder::new(&output[..])
+        .read_to_end(&mut profile)
+        .unwrap();
+    let profile = String::from_utf8_lossy(&profile);
+    assert!(profile.contains("inuse_space"));
+    assert!(profile.contains("Profiled"));
+    assert!(profile.contains(&format!("{}:25", file!())));
+}
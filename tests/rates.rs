@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Rate, Recorder, Stat, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Churn,
+}
+
+impl UseCase for MyUseCase {}
+
+#[test]
+fn rate() {
+    let stat = Stat {
+        total: 1000,
+        count: 10,
+        ..Stat::default()
+    };
+    assert_eq!(
+        stat.rate(Duration::from_secs(2)),
+        Rate {
+            allocs_per_sec: 5.0,
+            bytes_per_sec: 500.0
+        }
+    );
+    assert_eq!(stat.rate(Duration::ZERO), Rate::default());
+}
+
+#[test]
+fn flush_with_rates() {
+    let recorder = StatsRecorder::new();
+    let start = Instant::now();
+
+    let flush = |now| {
+        let mut rates = Vec::new();
+        recorder.flush_with_rates(
+            now,
+            |use_case, _, rate| rates.push((use_case, rate)),
+            |_, _| {},
+        );
+        rates
+    };
+
+    recorder.on_alloc(MyUseCase::Churn, 100);
+    assert_eq!(flush(start), [(MyUseCase::Churn, None)]);
+
+    for _ in 0..4 {
+        recorder.on_alloc(MyUseCase::Churn, 100);
+        recorder.on_dealloc(MyUseCase::Churn, 100);
+    }
+    assert_eq!(
+        flush(start + Duration::from_secs(4)),
+        [(
+            MyUseCase::Churn,
+            Some(Rate {
+                allocs_per_sec: 1.0,
+                bytes_per_sec: 100.0
+            })
+        )]
+    );
+}
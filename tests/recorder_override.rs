@@ -0,0 +1,81 @@
+use std::panic::AssertUnwindSafe;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Request,
+    Other,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn get(use_case: MyUseCase) -> memoria::Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+#[test]
+fn recorder_override() {
+    let outer = StatsRecorder::new();
+    let inner = StatsRecorder::new();
+    let buffer = ALLOCATOR.with_recorder_override(&outer, || {
+        let buffer = ALLOCATOR.scope(MyUseCase::Request, || vec![0u8; 100]);
+        ALLOCATOR.with_recorder_override(&inner, || {
+            ALLOCATOR.scope(MyUseCase::Request, || drop(vec![0u8; 30]));
+        });
+        ALLOCATOR.scope(MyUseCase::Request, || drop(vec![0u8; 10]));
+        buffer
+    });
+    ALLOCATOR.scope(MyUseCase::Request, || drop(vec![0u8; 1000]));
+
+    let stat = outer.get(MyUseCase::Request);
+    assert_eq!(stat.current, 100);
+    assert_eq!(stat.total, 110);
+    assert_eq!(stat.count, 2);
+    let stat = inner.get(MyUseCase::Request);
+    assert_eq!(stat.current, 0);
+    assert_eq!(stat.total, 30);
+    assert_eq!(stat.count, 1);
+    assert!(get(MyUseCase::Request).total >= 1140);
+
+    drop(buffer);
+    assert_eq!(outer.get(MyUseCase::Request).current, 100);
+}
+
+#[test]
+fn other_threads_not_overridden() {
+    let recorder = StatsRecorder::new();
+    ALLOCATOR.with_recorder_override(&recorder, || {
+        std::thread::spawn(|| ALLOCATOR.scope(MyUseCase::Other, || drop(vec![0u8; 100])))
+            .join()
+            .unwrap();
+    });
+    assert_eq!(recorder.get(MyUseCase::Other).count, 0);
+    assert!(get(MyUseCase::Other).total >= 100);
+}
+
+#[test]
+fn removed_after_panic() {
+    let recorder = StatsRecorder::new();
+    std::thread::spawn(move || {
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            ALLOCATOR.with_recorder_override(&recorder, || panic!("request failed"))
+        }));
+        assert!(result.is_err());
+        ALLOCATOR.scope(MyUseCase::Other, || drop(vec![0u8; 100]));
+        assert_eq!(recorder.get(MyUseCase::Other).count, 0);
+    })
+    .join()
+    .unwrap();
+}
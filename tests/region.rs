@@ -0,0 +1,45 @@
+#![cfg(all(unix, feature = "region"))]
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::region::Region;
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Arena,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn get(use_case: MyUseCase) -> memoria::Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+#[test]
+fn region() {
+    let mut region = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Arena);
+        Region::map(&ALLOCATOR, 1 << 20).unwrap()
+    };
+    assert_eq!(region.use_case(), MyUseCase::Arena);
+    assert_eq!(region.len(), 1 << 20);
+    assert!(region.iter().all(|&byte| byte == 0));
+    region[1234] = 1;
+    assert_eq!(region[1234], 1);
+
+    let stat = get(MyUseCase::Arena);
+    assert_eq!(stat.current, 1 << 20);
+    assert_eq!(stat.external, 1 << 20);
+
+    drop(region);
+    assert_eq!(get(MyUseCase::Arena).current, 0);
+}
@@ -0,0 +1,34 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use memoria::{Alloc, SortKey, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Large,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn render_table() {
+    let large = ALLOCATOR.scope(MyUseCase::Large, || vec![0u8; 1 << 20]);
+
+    let table = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.render_table(SortKey::Peak).to_string()))
+        .unwrap();
+    let lines: Vec<&str> = table.lines().collect();
+    assert!(lines[0].starts_with("usecase "));
+    assert!(lines[0].ends_with(" %"));
+    assert!(lines.iter().any(|line| line.starts_with("Large ")));
+    assert!(lines.last().unwrap().starts_with("total "));
+    assert!(lines.last().unwrap().ends_with(" 100.0%"));
+    // all rows are aligned
+    assert!(lines.iter().all(|line| line.len() == lines[0].len()));
+    drop(large);
+}
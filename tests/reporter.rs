@@ -0,0 +1,43 @@
+use std::sync::mpsc;
+use std::time::Duration;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq, Eq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Work,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn reports_periodically() {
+    let (tx, rx) = mpsc::channel();
+    let reporter = memoria::reporter::spawn(&ALLOCATOR, Duration::from_millis(10), move |report| {
+        tx.send(report).ok();
+    });
+
+    {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Work);
+        drop(vec![0u8; 100]);
+    }
+
+    let mut seen = false;
+    while !seen {
+        let report = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        seen = report
+            .stats
+            .iter()
+            .any(|(use_case, stat)| *use_case == MyUseCase::Work && stat.total == 100);
+    }
+
+    reporter.stop();
+}
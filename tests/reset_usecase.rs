@@ -0,0 +1,61 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Error, Stat, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Batch,
+    Other,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn stat(use_case: MyUseCase) -> Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+#[test]
+fn reset_usecase() {
+    let (first, other) = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Batch);
+        let first = vec![0u8; 1000];
+        let other = ALLOCATOR.scope(MyUseCase::Other, || vec![0u8; 70]);
+        (first, other)
+    };
+    assert_eq!(stat(MyUseCase::Batch).total, 1000);
+
+    let moved = ALLOCATOR.reattribute_live(MyUseCase::Batch).unwrap();
+    assert_eq!(moved, 1000);
+    ALLOCATOR
+        .with_recorder(|recorder| {
+            recorder.reset(MyUseCase::Batch);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(stat(MyUseCase::Batch), Stat::default());
+    assert_eq!(stat(MyUseCase::Other).total, 70);
+
+    // the second batch only sees its own allocations
+    let second = ALLOCATOR.scope(MyUseCase::Batch, || vec![0u8; 200]);
+    drop(first);
+    let batch = stat(MyUseCase::Batch);
+    assert_eq!((batch.current, batch.peak, batch.total), (200, 200, 200));
+    drop(second);
+    assert_eq!(stat(MyUseCase::Batch).current, 0);
+
+    drop(other);
+    assert_eq!(stat(MyUseCase::Other).current, 0);
+    let negative = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get_error(Error::NegativeBalance)))
+        .unwrap();
+    assert_eq!(negative, 0);
+}
@@ -0,0 +1,35 @@
+#![cfg(all(feature = "rss", target_os = "linux"))]
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::rss::RssReport;
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Resident,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn rss() {
+    let before = RssReport::new(&ALLOCATOR).unwrap();
+    // touch every page such that it becomes resident
+    let buffer = ALLOCATOR.scope(MyUseCase::Resident, || vec![1u8; 64 << 20]);
+    let after = RssReport::new(&ALLOCATOR).unwrap();
+
+    assert!(after.tracked >= before.tracked + (64 << 20));
+    assert!(after.resident >= before.resident + (32 << 20));
+    assert_eq!(
+        after.untracked,
+        after.resident as isize - after.tracked as isize
+    );
+    drop(buffer);
+}
@@ -0,0 +1,111 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Scoped,
+    Outer,
+    Inner,
+    Measured,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn get(use_case: MyUseCase) -> memoria::Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+#[test]
+fn scope() {
+    let buffer = ALLOCATOR.scope(MyUseCase::Scoped, || vec![0u8; 100]);
+    assert_eq!(get(MyUseCase::Scoped).current, 100);
+    drop(buffer);
+}
+
+#[test]
+fn scope_measured() {
+    let (buffer, outer) = ALLOCATOR.scope_measured(MyUseCase::Outer, || {
+        drop(vec![0u8; 1000]);
+        let (buffer, inner) =
+            ALLOCATOR.scope_measured(MyUseCase::Inner, || vec![0u8; 10].into_boxed_slice());
+        assert_eq!(inner.current, 10);
+        assert_eq!(inner.peak, 10);
+        assert_eq!(inner.count, 1);
+        buffer
+    });
+
+    assert_eq!(outer.current, 10);
+    assert_eq!(outer.peak, 1000);
+    assert_eq!(outer.total, 1010);
+    assert_eq!(outer.count, 2);
+    assert_eq!(outer.max_single, 1000);
+    drop(buffer);
+}
+
+#[test]
+fn current_usecase() {
+    assert_eq!(ALLOCATOR.current_usecase(), None);
+    ALLOCATOR.scope(MyUseCase::Outer, || {
+        assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::Outer));
+        ALLOCATOR.scope(MyUseCase::Inner, || {
+            assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::Inner));
+        });
+        assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::Outer));
+    });
+    assert_eq!(ALLOCATOR.current_usecase(), None);
+}
+
+#[test]
+fn with_usecase_measured() {
+    let barrier = std::sync::Arc::new(std::sync::Barrier::new(2));
+    let other = std::thread::spawn({
+        let barrier = barrier.clone();
+        move || {
+            barrier.wait();
+            ALLOCATOR.scope(MyUseCase::Measured, || drop(vec![0u8; 5000]));
+            barrier.wait();
+        }
+    });
+
+    let guard = ALLOCATOR.with_usecase_measured(MyUseCase::Measured);
+    assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::Measured));
+    let buffer = vec![0u8; 100];
+    // other threads using the same usecase are not measured
+    barrier.wait();
+    barrier.wait();
+    drop(vec![0u8; 50]);
+    let stat = guard.finish();
+    assert_eq!(ALLOCATOR.current_usecase(), None);
+    other.join().unwrap();
+
+    assert_eq!(stat.current, 100);
+    assert_eq!(stat.peak, 150);
+    assert_eq!(stat.total, 150);
+    assert_eq!(stat.count, 2);
+    assert!(get(MyUseCase::Measured).total >= 5150);
+    drop(buffer);
+}
+
+#[test]
+fn measure() {
+    let (buffer, stat) = ALLOCATOR.measure(|| {
+        assert_eq!(ALLOCATOR.current_usecase(), None);
+        drop(vec![0u8; 30]);
+        ALLOCATOR.scope(MyUseCase::Inner, || vec![0u8; 20])
+    });
+    assert_eq!(stat.current, 20);
+    assert_eq!(stat.peak, 30);
+    assert_eq!(stat.total, 50);
+    assert_eq!(stat.count, 2);
+    drop(buffer);
+}
@@ -0,0 +1,46 @@
+#![cfg(feature = "selftest")]
+use std::alloc::System;
+use std::sync::Mutex;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use memoria::{overhead_selftest, Alloc, OverheadEstimate, Recorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+}
+
+impl UseCase for MyUseCase {}
+
+struct EstimateRecorder(Mutex<Option<OverheadEstimate>>);
+
+unsafe impl Recorder<MyUseCase> for EstimateRecorder {
+    fn on_alloc(&self, _use_case: MyUseCase, _size: usize) -> bool {
+        true
+    }
+
+    fn on_overhead_estimate(&self, estimate: OverheadEstimate) {
+        *self.0.lock().unwrap() = Some(estimate);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, EstimateRecorder> =
+    Alloc::new_with(EstimateRecorder(Mutex::new(None)), System);
+
+#[test]
+fn selftest() {
+    let estimate = overhead_selftest(&ALLOCATOR);
+    assert!(estimate.baseline_ns > 0.0);
+    assert!(estimate.tracked_ns > 0.0);
+    assert!(estimate.guard_ns > 0.0);
+    assert!(estimate.per_alloc_ns() >= 0.0);
+
+    let reported = ALLOCATOR
+        .with_recorder(|recorder| Ok(*recorder.0.lock().unwrap()))
+        .unwrap();
+    assert_eq!(reported, Some(estimate));
+}
@@ -0,0 +1,72 @@
+#![cfg(feature = "serde")]
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+use serde::Deserialize;
+
+use memoria::serde::{deserialize_attributed, FieldAttributed};
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Payload,
+    Users,
+    Config,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[derive(Deserialize)]
+struct Payload {
+    users: Vec<String>,
+    config: String,
+}
+
+fn total(use_case: MyUseCase) -> isize {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case).total))
+        .unwrap()
+}
+
+const INPUT: &str = r#"{"users": ["alice", "bob"], "config": "verbose"}"#;
+
+#[test]
+fn whole_payload() {
+    let mut deserializer = serde_json::Deserializer::from_str(INPUT);
+    let payload: Payload =
+        deserialize_attributed(&ALLOCATOR, MyUseCase::Payload, &mut deserializer).unwrap();
+    assert_eq!(payload.users, ["alice", "bob"]);
+    assert!(total(MyUseCase::Payload) > 0);
+}
+
+#[test]
+fn per_field() {
+    let mut deserializer = serde_json::Deserializer::from_str(INPUT);
+    let deserializer = FieldAttributed::new(&mut deserializer, &ALLOCATOR, |field| match field {
+        "users" => Some(MyUseCase::Users),
+        "config" => Some(MyUseCase::Config),
+        _ => None,
+    });
+    let payload = Payload::deserialize(deserializer).unwrap();
+    assert_eq!(payload.config, "verbose");
+    assert!(total(MyUseCase::Users) > 0);
+    assert_eq!(total(MyUseCase::Config), 7);
+}
+
+#[test]
+fn stat_roundtrip() {
+    let stat = memoria::Stat {
+        current: 10,
+        peak: 20,
+        total: 30,
+        ..Default::default()
+    };
+    let json = serde_json::to_string(&stat).unwrap();
+    assert!(json.contains("\"peak\":20"), "{json}");
+    assert_eq!(serde_json::from_str::<memoria::Stat>(&json).unwrap(), stat);
+}
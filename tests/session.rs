@@ -0,0 +1,48 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Request,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn session_across_threads() {
+    let session = ALLOCATOR.session(MyUseCase::Request);
+    assert_eq!(session.use_case(), MyUseCase::Request);
+
+    let worker = std::thread::spawn({
+        let session = session.clone();
+        move || {
+            session.scope(|| {
+                assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::Request));
+                drop(vec![0u8; 1000]);
+            })
+        }
+    });
+    worker.join().unwrap();
+    assert_eq!(session.get().total, 1000);
+
+    let guard = session.enter();
+    let buffer = vec![0u8; 100];
+    drop(guard);
+    assert_eq!(ALLOCATOR.current_usecase(), None);
+    drop(vec![0u8; 10]);
+
+    let stat = session.finish();
+    assert_eq!(stat.current, 100);
+    assert_eq!(stat.total, 1100);
+    assert_eq!(stat.count, 2);
+    assert_eq!(stat.max_single, 1000);
+    drop(buffer);
+}
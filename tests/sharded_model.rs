@@ -0,0 +1,89 @@
+//! Checks a single shard of [memoria::ShardedTable] against a `HashMap`, with pointers chosen
+//! such that their probe sequences wrap around the end of the slot array.
+
+extern crate alloc;
+
+use std::collections::HashMap;
+
+use pretty_assertions::assert_eq;
+
+#[allow(dead_code)]
+#[path = "../src/pointers/sharded/table.rs"]
+mod table;
+
+use table::{Table, MIN_CAPACITY};
+
+/// Return `count` pointers whose home slot in a table of [MIN_CAPACITY] slots is one of the last
+/// `before` or first `after` slots, so that they form clusters across the wrap.
+fn wrapping_pointers(count: usize, before: usize, after: usize) -> Vec<usize> {
+    let mut table = Table::<u64>::new();
+    table.insert(8, 0);
+    assert_eq!(table.capacity(), MIN_CAPACITY);
+    let pointers: Vec<usize> = (2..)
+        .map(|i| i * 8)
+        .filter(|&ptr| (table.home(ptr) + before) % MIN_CAPACITY < before + after)
+        .take(count)
+        .collect();
+    table.remove(8);
+    pointers
+}
+
+fn check(table: &Table<u64>, model: &HashMap<usize, u64>, pointers: &[usize]) {
+    assert_eq!(table.len(), model.len());
+    for ptr in pointers {
+        assert_eq!(table.get(*ptr), model.get(ptr).copied(), "pointer {ptr:#x}");
+    }
+    let mut entries = HashMap::new();
+    table.for_each(|ptr, value| assert!(entries.insert(ptr, value).is_none()));
+    assert_eq!(&entries, model);
+}
+
+#[test]
+fn cluster_across_wrap() {
+    let pointers = wrapping_pointers(12, 1, 0);
+    let mut table = Table::new();
+    let mut model = HashMap::new();
+    for (value, &ptr) in pointers.iter().enumerate() {
+        table.insert(ptr, value as u64);
+        model.insert(ptr, value as u64);
+    }
+    // the entry in slot zero was displaced from the end of the array
+    let mut first = None;
+    table.for_each(|ptr, _| {
+        first.get_or_insert(ptr);
+    });
+    assert_eq!(table.home(first.unwrap()), MIN_CAPACITY - 1);
+
+    // delete from the middle of the cluster, on both sides of the wrap
+    for &ptr in &[pointers[5], pointers[1], pointers[9], pointers[6]] {
+        assert_eq!(table.remove(ptr), model.remove(&ptr));
+        check(&table, &model, &pointers);
+    }
+}
+
+/// Randomly inserts, overwrites and removes pointers, and compares every result with the model.
+#[test]
+fn random_operations() {
+    // 40 pointers stay below the load factor of a table with `MIN_CAPACITY` slots, 200 do not
+    for (count, before, after) in [(40, 4, 4), (40, 8, 0), (200, 8, 8)] {
+        let pointers = wrapping_pointers(count, before, after);
+        for seed in 0..20u64 {
+            let mut state = 0x2545_f491_4f6c_dd1d_u64 ^ seed;
+            let mut table = Table::new();
+            let mut model = HashMap::new();
+            for _ in 0..2_000 {
+                // xorshift64
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let ptr = pointers[(state >> 8) as usize % pointers.len()];
+                if state % 5 < 2 {
+                    assert_eq!(table.remove(ptr), model.remove(&ptr));
+                } else {
+                    assert_eq!(table.insert(ptr, state), model.insert(ptr, state));
+                }
+                check(&table, &model, &pointers);
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::alloc::System;
+
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Error, ShardedTable, StatsRecorder};
+
+memoria::usecase! {
+    enum MyUseCase {
+        default None,
+        Main,
+        Worker0,
+        Worker1,
+        Worker2,
+        Worker3,
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, StatsRecorder<MyUseCase>, System, ShardedTable> =
+    Alloc::builder().pointer_table().build();
+
+fn current(use_case: MyUseCase) -> isize {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case).current))
+        .unwrap()
+}
+
+fn errors() -> usize {
+    ALLOCATOR
+        .with_recorder(|recorder| {
+            Ok(recorder.get_error(Error::DeallocUntrackedPointer)
+                + recorder.get_error(Error::PointerTrackedTwice))
+        })
+        .unwrap()
+}
+
+fn live_count(use_case: MyUseCase) -> usize {
+    let mut count = 0;
+    ALLOCATOR
+        .inspect_live(|live| count += usize::from(live.use_case == use_case))
+        .unwrap();
+    count
+}
+
+#[test]
+fn tracks_pointers() {
+    let errors_before = errors();
+    let buffers: Vec<Vec<u8>> = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Main);
+        (1..=1000).map(|size| vec![0u8; size]).collect()
+    };
+    assert_eq!(live_count(MyUseCase::Main), 1001);
+    assert_eq!(current(MyUseCase::Main), 1000 * 1001 / 2 + 1000 * 24);
+
+    drop(buffers);
+    assert_eq!(live_count(MyUseCase::Main), 0);
+    assert_eq!(current(MyUseCase::Main), 0);
+    assert_eq!(errors(), errors_before);
+}
+
+/// Randomly allocates and frees from several threads at once, and checks that every pointer is
+/// found again.
+#[test]
+fn random_operations() {
+    let errors_before = errors();
+    let workers = [
+        MyUseCase::Worker0,
+        MyUseCase::Worker1,
+        MyUseCase::Worker2,
+        MyUseCase::Worker3,
+    ];
+    let handles: Vec<_> = workers
+        .into_iter()
+        .enumerate()
+        .map(|(i, use_case)| {
+            std::thread::spawn(move || {
+                let _guard = ALLOCATOR.with_usecase(use_case);
+                let mut state = 0x2545_f491_4f6c_dd1d_u64 ^ i as u64;
+                let mut live: Vec<Box<[u8]>> = Vec::new();
+                for _ in 0..20_000 {
+                    // xorshift64
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    if state.is_multiple_of(3) && !live.is_empty() {
+                        live.swap_remove(state as usize / 3 % live.len());
+                    } else {
+                        live.push(vec![0u8; 1 + state as usize % 256].into_boxed_slice());
+                    }
+                }
+                let bytes: usize = live.iter().map(|buffer| buffer.len()).sum();
+                (use_case, live.len(), bytes, live)
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        let (use_case, count, bytes, live) = handle.join().unwrap();
+        assert_eq!(live_count(use_case), count + 1);
+        assert_eq!(
+            current(use_case),
+            (bytes + live.capacity() * std::mem::size_of::<Box<[u8]>>()) as isize
+        );
+        drop(live);
+        assert_eq!(live_count(use_case), 0);
+        assert_eq!(current(use_case), 0);
+    }
+    assert_eq!(errors(), errors_before);
+}
@@ -0,0 +1,69 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Stat, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Ingest,
+    Export,
+    Other,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn stat(use_case: MyUseCase) -> Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+fn summary(use_case: MyUseCase) -> (isize, isize, isize) {
+    let stat = stat(use_case);
+    (stat.current, stat.total, stat.shared)
+}
+
+#[test]
+fn shared() {
+    let (even, weighted, nested) = {
+        let _guard = ALLOCATOR.with_usecases(&[(MyUseCase::Ingest, 1), (MyUseCase::Export, 1)]);
+        let even = Vec::<u8>::with_capacity(1001);
+        // not split, since the innermost guard names a single usecase
+        let nested = ALLOCATOR.scope(MyUseCase::Other, || Vec::<u8>::with_capacity(64));
+        let weighted = {
+            let _guard = ALLOCATOR.with_usecases(&[(MyUseCase::Export, 3), (MyUseCase::Ingest, 1)]);
+            Vec::<u8>::with_capacity(1000)
+        };
+        (even, weighted, nested)
+    };
+
+    // the first usecase gets the rest of uneven splits
+    assert_eq!(summary(MyUseCase::Ingest), (501 + 250, 751, 751));
+    assert_eq!(summary(MyUseCase::Export), (500 + 750, 1250, 1250));
+    assert_eq!(summary(MyUseCase::Other), (64, 64, 0));
+
+    drop(even);
+    assert_eq!(summary(MyUseCase::Ingest), (250, 751, 250));
+    assert_eq!(summary(MyUseCase::Export), (750, 1250, 750));
+
+    drop(weighted);
+    drop(nested);
+    assert_eq!(summary(MyUseCase::Ingest), (0, 751, 0));
+    assert_eq!(summary(MyUseCase::Export), (0, 1250, 0));
+    assert_eq!(summary(MyUseCase::Other), (0, 64, 0));
+
+    // a single usecase is not a split
+    let single = {
+        let _guard = ALLOCATOR.with_usecases(&[(MyUseCase::Other, 2), (MyUseCase::Export, 0)]);
+        Vec::<u8>::with_capacity(100)
+    };
+    assert_eq!(summary(MyUseCase::Other), (100, 164, 0));
+    assert_eq!(summary(MyUseCase::Export), (0, 1250, 0));
+    drop(single);
+}
@@ -0,0 +1,119 @@
+#![cfg(all(unix, feature = "shm"))]
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, ShmStatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Parent,
+    Worker,
+    OutOfRange,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, ShmStatsRecorder<MyUseCase, 4, 3>> =
+    Alloc::new_with(ShmStatsRecorder::new(), std::alloc::System);
+
+fn with_recorder<T>(f: impl FnOnce(&ShmStatsRecorder<MyUseCase, 4, 3>) -> T) -> T {
+    ALLOCATOR.with_recorder(|recorder| Ok(f(recorder))).unwrap()
+}
+
+fn spawn_worker(size: usize) -> libc::pid_t {
+    // SAFETY: the child only touches memoria and exits without unwinding.
+    let pid = unsafe { libc::fork() };
+    assert!(pid >= 0);
+    if pid == 0 {
+        // SAFETY: the child has a single thread.
+        unsafe { ALLOCATOR.after_fork() };
+        let process = with_recorder(|recorder| recorder.process());
+        let data = ALLOCATOR.scope(MyUseCase::Worker, || vec![0u8; size]);
+        std::mem::forget(data);
+        // SAFETY: exiting without running destructors or atexit handlers of the parent.
+        unsafe { libc::_exit(process.map_or(100, |process| process as i32)) };
+    }
+    pid
+}
+
+fn wait(pid: libc::pid_t) -> i32 {
+    let mut status = 0;
+    // SAFETY: `pid` is our child process.
+    assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+    assert!(libc::WIFEXITED(status));
+    libc::WEXITSTATUS(status)
+}
+
+#[test]
+fn shm() {
+    let before = ALLOCATOR.scope(MyUseCase::Parent, || vec![0u8; 10]);
+    assert_eq!(with_recorder(|recorder| recorder.process()), None);
+
+    with_recorder(|recorder| recorder.map()).unwrap();
+    assert_eq!(with_recorder(|recorder| recorder.process()), Some(0));
+
+    let parent = ALLOCATOR.scope(MyUseCase::Parent, || vec![0u8; 100]);
+    // not tracked, since it was allocated before mapping
+    drop(before);
+
+    // one more worker than there are rows left
+    let mut exits = Vec::new();
+    for size in [1000, 2000, 3000, 4000] {
+        exits.push(wait(spawn_worker(size)));
+    }
+    assert_eq!(exits, [1, 2, 3, 100]);
+
+    drop(ALLOCATOR.scope(MyUseCase::OutOfRange, || vec![0u8; 5]));
+
+    let (worker, parent_stat, per_process, aggregate, dropped) = with_recorder(|recorder| {
+        let mut per_process = Vec::new();
+        recorder.for_each_process(|process, use_case, stat| {
+            if use_case != MyUseCase::None {
+                per_process.push((process, use_case, stat.current))
+            }
+        });
+        let mut aggregate = Vec::new();
+        recorder.aggregate(|use_case, stat| {
+            if use_case != MyUseCase::None {
+                aggregate.push((use_case, stat.current, stat.count))
+            }
+        });
+        (
+            recorder.get(MyUseCase::Worker),
+            recorder.get_process(0, MyUseCase::Parent),
+            per_process,
+            aggregate,
+            recorder.dropped(),
+        )
+    });
+
+    assert_eq!(worker.current, 6000);
+    assert_eq!(worker.total, 6000);
+    assert_eq!(worker.count, 3);
+    assert_eq!(worker.max_single, 3000);
+    assert_eq!(parent_stat.current, 100);
+    assert_eq!(
+        per_process,
+        [
+            (0, MyUseCase::Parent, 100),
+            (1, MyUseCase::Worker, 1000),
+            (2, MyUseCase::Worker, 2000),
+            (3, MyUseCase::Worker, 3000),
+        ]
+    );
+    assert_eq!(
+        aggregate,
+        [(MyUseCase::Parent, 100, 1), (MyUseCase::Worker, 6000, 3)]
+    );
+    assert_eq!(dropped, 2);
+
+    drop(parent);
+    assert_eq!(
+        with_recorder(|recorder| recorder.get(MyUseCase::Parent)).current,
+        0
+    );
+}
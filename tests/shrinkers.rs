@@ -0,0 +1,85 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Cache,
+    Other,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+static CACHE: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+#[test]
+fn shrinkers() {
+    // Keep the cache's own buffer out of the usage of the usecase.
+    CACHE.lock().unwrap().reserve(8);
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let id = ALLOCATOR.register_shrinker(MyUseCase::Cache, 10_000, {
+        let requests = requests.clone();
+        move |excess| {
+            requests.lock().unwrap().push(excess);
+            // The callback may allocate and free.
+            let mut cache = CACHE.lock().unwrap();
+            let mut freed = 0;
+            while freed < excess {
+                let Some(entry) = cache.pop() else { break };
+                freed += entry.len();
+            }
+        }
+    });
+    let other = ALLOCATOR.register_shrinker(MyUseCase::Other, 0, |_| panic!("nothing to shrink"));
+
+    // Below the watermark.
+    ALLOCATOR.scope(MyUseCase::Cache, || {
+        CACHE.lock().unwrap().push(vec![0u8; 8000]);
+    });
+    assert_eq!(ALLOCATOR.shrink(), 0);
+    // Registering a shrinker does not set a cap.
+    assert_eq!(ALLOCATOR.cap_usage(MyUseCase::Cache), None);
+
+    ALLOCATOR.scope(MyUseCase::Cache, || {
+        CACHE.lock().unwrap().push(vec![0u8; 8000]);
+    });
+    assert_eq!(ALLOCATOR.shrink(), 1);
+    assert_eq!(*requests.lock().unwrap(), [6000]);
+    assert_eq!(CACHE.lock().unwrap().len(), 1);
+    assert_eq!(ALLOCATOR.shrink(), 0);
+
+    // Exceeding the global watermark invokes all shrinkers.
+    ALLOCATOR.unregister_shrinker(other);
+    ALLOCATOR.set_shrink_watermark(ALLOCATOR.tracked_bytes() - 1);
+    assert_eq!(ALLOCATOR.shrink(), 1);
+    assert_eq!(CACHE.lock().unwrap().len(), 0);
+    ALLOCATOR.set_shrink_watermark(usize::MAX);
+
+    // The background thread shrinks on its own.
+    ALLOCATOR.scope(MyUseCase::Cache, || {
+        let mut cache = CACHE.lock().unwrap();
+        cache.push(vec![0u8; 8000]);
+        cache.push(vec![0u8; 8000]);
+    });
+    let thread = memoria::shrink::spawn(&ALLOCATOR, Duration::from_millis(1));
+    while CACHE.lock().unwrap().len() != 1 {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    thread.stop();
+
+    ALLOCATOR.unregister_shrinker(id);
+    ALLOCATOR.scope(MyUseCase::Cache, || {
+        CACHE.lock().unwrap().push(vec![0u8; 100_000]);
+    });
+    assert_eq!(ALLOCATOR.shrink(), 0);
+}
@@ -0,0 +1,43 @@
+#![cfg(all(unix, feature = "signal"))]
+use std::io::Read;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Dumped,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn dump_on_signal() {
+    let (mut reader, writer) = UnixStream::pair().unwrap();
+    memoria::signal::install(libc::SIGUSR1, writer.as_raw_fd()).unwrap();
+
+    let buffer = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Dumped);
+        vec![0u8; 1234]
+    };
+    memoria::signal::refresh(&ALLOCATOR);
+
+    unsafe {
+        libc::raise(libc::SIGUSR1);
+    }
+    drop(writer);
+
+    let mut output = String::new();
+    reader.read_to_string(&mut output).unwrap();
+    assert!(output.contains("Dumped: current: 1234"), "{output}");
+    drop(buffer);
+}
@@ -0,0 +1,97 @@
+//! Built with `RUSTFLAGS="--cfg memoria_single_threaded"`, and run without libtest's harness such
+//! that everything happens on the main thread, which owns memoria's statics. Does nothing in
+//! other builds.
+
+#[cfg(memoria_single_threaded)]
+mod single_threaded {
+    use num_enum::{IntoPrimitive, TryFromPrimitive};
+    use pretty_assertions::assert_eq;
+
+    use memoria::{Alloc, Error, LiveStat, Stat, UseCase};
+
+    #[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+    #[repr(u32)]
+    enum MyUseCase {
+        #[default]
+        None,
+        Parse,
+        Render,
+    }
+
+    impl UseCase for MyUseCase {}
+
+    #[global_allocator]
+    static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+    fn stat(use_case: MyUseCase) -> Stat {
+        ALLOCATOR
+            .with_recorder(|recorder| Ok(recorder.get(use_case)))
+            .unwrap()
+    }
+
+    fn live(use_case: MyUseCase) -> Option<LiveStat> {
+        let report = ALLOCATOR.leak_report(0).unwrap();
+        report
+            .use_cases
+            .into_iter()
+            .find(|(live, _)| *live == use_case)
+            .map(|(_, stat)| stat)
+    }
+
+    fn contention() -> usize {
+        ALLOCATOR
+            .with_recorder(|recorder| {
+                Ok(recorder.get_error(Error::CurrentUsecaseContentionThreadLocal))
+            })
+            .unwrap()
+    }
+
+    pub fn main() {
+        // the current usecase and nested guards live in plain statics
+        let (parsed, rendered) = {
+            let _guard = ALLOCATOR.with_usecase(MyUseCase::Parse);
+            let parsed = vec![0u8; 100];
+            let rendered = ALLOCATOR.scope(MyUseCase::Render, || vec![0u8; 30]);
+            (parsed, rendered)
+        };
+        assert_eq!(ALLOCATOR.current_usecase(), None);
+        assert_eq!(
+            (
+                stat(MyUseCase::Parse).current,
+                stat(MyUseCase::Render).current
+            ),
+            (100, 30)
+        );
+
+        // live allocations are tracked in a plain map
+        assert_eq!(
+            live(MyUseCase::Parse),
+            Some(LiveStat {
+                bytes: 100,
+                count: 1
+            })
+        );
+        drop(parsed);
+        assert_eq!(live(MyUseCase::Parse), None);
+        assert_eq!(stat(MyUseCase::Parse).current, 0);
+
+        // other threads are not recorded
+        let errors = contention();
+        std::thread::spawn(|| {
+            let _guard = ALLOCATOR.with_usecase(MyUseCase::Render);
+            drop(vec![0u8; 1000]);
+        })
+        .join()
+        .unwrap();
+        assert_eq!(stat(MyUseCase::Render).total, 30);
+        assert!(contention() > errors);
+
+        drop(rendered);
+        assert_eq!(stat(MyUseCase::Render).current, 0);
+    }
+}
+
+fn main() {
+    #[cfg(memoria_single_threaded)]
+    single_threaded::main();
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "sketch")]
+use std::alloc::System;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, QuantileSketch, SketchRecorder, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Buffers,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, SketchRecorder<MyUseCase>> =
+    Alloc::new_with(SketchRecorder::new(StatsRecorder::new()), System);
+
+fn assert_close(actual: Option<usize>, expected: usize) {
+    let actual = actual.unwrap() as f64;
+    let expected = expected as f64;
+    assert!(
+        (actual - expected).abs() <= expected * 0.0625,
+        "{actual} is not close to {expected}"
+    );
+}
+
+#[test]
+fn quantiles() {
+    let mut sketch = QuantileSketch::default();
+    assert_eq!(sketch.quantile(0.5), None);
+    for size in 1..=1000 {
+        sketch.record(size);
+    }
+    assert_eq!(sketch.count(), 1000);
+    assert_close(sketch.quantile(0.5), 500);
+    assert_close(sketch.quantile(0.99), 990);
+    assert_eq!(sketch.quantile(0.0), Some(1));
+
+    let mut other = QuantileSketch::default();
+    for _ in 0..1000 {
+        other.record(1 << 20);
+    }
+    sketch.merge(&other);
+    assert_eq!(sketch.count(), 2000);
+    assert_close(sketch.quantile(0.99), 1 << 20);
+}
+
+#[test]
+fn recorder() {
+    let buffers: Vec<Vec<u8>> = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Buffers);
+        (0..100).map(|_| vec![0u8; 4096]).collect()
+    };
+    let sketch = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get_sketch(MyUseCase::Buffers)))
+        .unwrap();
+    assert_close(sketch.quantile(0.5), 4096);
+    drop(buffers);
+}
@@ -0,0 +1,81 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Stat, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Window,
+}
+
+impl UseCase for MyUseCase {}
+
+fn stat(current: isize, peak: isize, total: isize, count: isize) -> Stat {
+    Stat {
+        current,
+        peak,
+        total,
+        count,
+        max_single: total,
+        ..Stat::default()
+    }
+}
+
+#[test]
+fn merge() {
+    // 100 bytes are still alive at the end of the first window, and the second window
+    // allocates 50 more on top at its peak before freeing 30
+    let mut window = stat(100, 150, 200, 2);
+    window.merge(&stat(-30, 50, 60, 1));
+    assert_eq!(
+        window,
+        Stat {
+            max_single: 200,
+            ..stat(70, 150, 260, 3)
+        }
+    );
+
+    let mut window = stat(100, 150, 200, 2);
+    window.merge(&stat(0, 80, 80, 1));
+    assert_eq!(window.peak, 180);
+}
+
+#[test]
+fn add_sub() {
+    let a = stat(100, 150, 200, 2);
+    let b = stat(10, 20, 30, 3);
+
+    let sum = a + b;
+    assert_eq!(sum.current, 110);
+    assert_eq!(sum.peak, 170);
+    assert_eq!(sum.total, 230);
+    assert_eq!(sum.count, 5);
+    assert_eq!(sum.max_single, 200);
+
+    let mut sum2 = a;
+    sum2 += b;
+    assert_eq!(sum, sum2);
+
+    let diff = sum - a;
+    assert_eq!(diff.current, 10);
+    assert_eq!(diff.total, 30);
+    assert_eq!(diff.count, 3);
+    // high-water marks are taken from the left-hand side
+    assert_eq!(diff.peak, 170);
+}
+
+#[test]
+fn merge_from() {
+    let aggregate = StatsRecorder::<MyUseCase>::new();
+    aggregate.merge_from([(MyUseCase::Window, stat(100, 150, 200, 2))]);
+    aggregate.merge_from([(MyUseCase::Window, stat(-30, 50, 60, 1))]);
+    let merged = aggregate.get(MyUseCase::Window);
+    assert_eq!(merged.current, 70);
+    assert_eq!(merged.peak, 150);
+    assert_eq!(merged.total, 260);
+    assert_eq!(merged.count, 3);
+    assert_eq!(aggregate.get(MyUseCase::None), Stat::default());
+}
@@ -0,0 +1,90 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, CurrentUsecaseStore, Stat, ThreadState, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Parse,
+}
+
+impl UseCase for MyUseCase {}
+
+/// Hands out a single state to the thread that claimed it, like a platform with one core would,
+/// and no state to any other thread.
+struct OneThread {
+    owner: AtomicUsize,
+    state: ThreadState,
+}
+
+// SAFETY: `state` is only handed out to the owner.
+unsafe impl Sync for OneThread {}
+
+impl OneThread {
+    fn claim(&self) {
+        self.owner.store(thread_id(), Ordering::Relaxed);
+    }
+}
+
+unsafe impl CurrentUsecaseStore for OneThread {
+    fn state(&self) -> Option<&ThreadState> {
+        (self.owner.load(Ordering::Relaxed) == thread_id()).then_some(&self.state)
+    }
+}
+
+/// The address of a thread-local is unique among all running threads.
+fn thread_id() -> usize {
+    thread_local! {
+        static MARKER: u8 = const { 0 };
+    }
+    MARKER.with(|marker| marker as *const u8 as usize)
+}
+
+static STORE: OneThread = OneThread {
+    owner: AtomicUsize::new(0),
+    state: ThreadState::new(),
+};
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new().with_store(&STORE);
+
+fn stat(use_case: MyUseCase) -> Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+#[test]
+fn store() {
+    STORE.claim();
+
+    // the owner keeps its usecase in the store
+    let parsed = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Parse);
+        assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::Parse));
+        vec![0u8; 100]
+    };
+    assert_eq!(ALLOCATOR.current_usecase(), None);
+    assert_eq!(stat(MyUseCase::Parse).current, 100);
+
+    // threads without state can not switch usecases, but are still recorded
+    let (use_case, total) = std::thread::spawn(|| {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Parse);
+        let use_case = ALLOCATOR.current_usecase();
+        let total = stat(MyUseCase::None).total;
+        drop(vec![0u8; 1000]);
+        (use_case, stat(MyUseCase::None).total - total)
+    })
+    .join()
+    .unwrap();
+    assert_eq!((use_case, total), (None, 1000));
+    assert_eq!(stat(MyUseCase::Parse).total, 100);
+
+    drop(parsed);
+    assert_eq!(stat(MyUseCase::Parse).current, 0);
+}
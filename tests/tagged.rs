@@ -0,0 +1,58 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq, PartialOrd, Eq, Ord)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Request,
+    Render,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn per_tenant() {
+    let first = {
+        let _guard = ALLOCATOR.with_usecase_tagged(MyUseCase::Request, 1);
+        let buffer = vec![0u8; 100];
+        // the tag carries over into nested usecases
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Render);
+        (buffer, vec![0u8; 50])
+    };
+    let second = {
+        let _guard = ALLOCATOR.with_usecase_tagged(MyUseCase::Request, 2);
+        vec![0u8; 200]
+    };
+    // no tag outside of tagged guards
+    let untagged = ALLOCATOR.scope(MyUseCase::Request, || vec![0u8; 400]);
+    drop(first);
+
+    let mut tagged = Vec::new();
+    ALLOCATOR
+        .with_recorder(|recorder| {
+            recorder.flush_tagged(|use_case, tag, stat| {
+                tagged.push((use_case, tag, stat.current, stat.total))
+            });
+            Ok(())
+        })
+        .unwrap();
+    tagged.sort();
+
+    assert_eq!(
+        tagged,
+        vec![
+            (MyUseCase::Request, 1, 0, 100),
+            (MyUseCase::Request, 2, 200, 200),
+            (MyUseCase::Render, 1, 0, 50),
+        ]
+    );
+    drop(second);
+    drop(untagged);
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "testing")]
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::testing::{capture, TestEvent, TestRecorder};
+use memoria::{Alloc, EventKind, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq, Clone, Copy)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Parse,
+    Other,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, TestRecorder<MyUseCase>> =
+    Alloc::new_with(TestRecorder::new(StatsRecorder::new()), std::alloc::System);
+
+#[test]
+fn capture_events() {
+    let ((), captured) = capture(&ALLOCATOR, || {
+        let parse = ALLOCATOR.scope(MyUseCase::Parse, || Vec::<u8>::with_capacity(100));
+        let (_, inner) = capture(&ALLOCATOR, || {
+            drop(ALLOCATOR.scope(MyUseCase::Other, || Vec::<u8>::with_capacity(10)));
+        });
+        assert_eq!(inner.events.len(), 2);
+        drop(parse);
+    });
+
+    assert_eq!(
+        captured.events,
+        [
+            TestEvent {
+                kind: EventKind::Alloc,
+                use_case: MyUseCase::Parse,
+                size: 100
+            },
+            TestEvent {
+                kind: EventKind::Alloc,
+                use_case: MyUseCase::Other,
+                size: 10
+            },
+            TestEvent {
+                kind: EventKind::Dealloc,
+                use_case: MyUseCase::Other,
+                size: 10
+            },
+            TestEvent {
+                kind: EventKind::Dealloc,
+                use_case: MyUseCase::Parse,
+                size: 100
+            },
+        ]
+    );
+    assert_eq!(captured.allocated_bytes(&MyUseCase::Parse), 100);
+    assert_eq!(captured.allocation_count(&MyUseCase::Other), 1);
+    assert_eq!(captured.for_usecase(&MyUseCase::None).count(), 0);
+}
+
+#[test]
+fn budgets() {
+    let data = memoria::assert_allocates!(ALLOCATOR, usecase = MyUseCase::Parse, max_bytes = 64, {
+        vec![0u8; 64]
+    });
+    assert_eq!(data.len(), 64);
+
+    memoria::assert_allocates!(
+        ALLOCATOR,
+        usecase = MyUseCase::Parse,
+        max_bytes = 64,
+        max_count = 1,
+        {
+            // attributed to another usecase, so not part of the budget
+            ALLOCATOR.scope(MyUseCase::Other, || vec![0u8; 1000]);
+            vec![0u8; 10]
+        }
+    );
+
+    memoria::assert_no_alloc!(ALLOCATOR, {
+        drop(data);
+    });
+}
+
+#[test]
+#[should_panic(expected = "Parse allocated 65 bytes in 1 allocations")]
+fn over_budget() {
+    memoria::assert_allocates!(ALLOCATOR, usecase = MyUseCase::Parse, max_bytes = 64, {
+        vec![0u8; 65]
+    });
+}
+
+#[test]
+#[should_panic(expected = "expected no allocations")]
+fn unexpected_alloc() {
+    memoria::assert_no_alloc!(ALLOCATOR, { vec![0u8; 1] });
+}
@@ -0,0 +1,38 @@
+use std::sync::Barrier;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Worker,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn peak_threads() {
+    let barrier = Barrier::new(4);
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                let _guard = ALLOCATOR.with_usecase(MyUseCase::Worker);
+                barrier.wait();
+            });
+        }
+    });
+
+    let stat = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::Worker)))
+        .unwrap();
+    assert_eq!(stat.threads, 0);
+    assert_eq!(stat.peak_threads, 4);
+}
@@ -0,0 +1,47 @@
+use std::alloc::System;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, TimeSeriesRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Growing,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase, TimeSeriesRecorder<MyUseCase, 3>> =
+    Alloc::new_with(TimeSeriesRecorder::new(), System);
+
+#[test]
+fn ring_buffer() {
+    let mut buffers = Vec::new();
+    for timestamp in 1..=5 {
+        let buffer = {
+            let _guard = ALLOCATOR.with_usecase(MyUseCase::Growing);
+            vec![0u8; 100]
+        };
+        buffers.push(buffer);
+        ALLOCATOR
+            .with_recorder(|recorder| {
+                recorder.tick(timestamp);
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    let history = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.history(MyUseCase::Growing)))
+        .unwrap();
+    let current: Vec<_> = history
+        .iter()
+        .map(|(timestamp, stat)| (*timestamp, stat.current))
+        .collect();
+    assert_eq!(current, [(3, 300), (4, 400), (5, 500)]);
+}
@@ -0,0 +1,63 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, SortKey, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Small,
+    Large,
+    Spiky,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn get_top_n(n: usize, by: SortKey) -> Vec<(MyUseCase, isize, isize)> {
+    ALLOCATOR
+        .with_recorder(|recorder| {
+            Ok(recorder
+                .top_n(n, by)
+                .into_iter()
+                .filter(|(use_case, _)| *use_case != MyUseCase::None)
+                .map(|(use_case, stat)| (use_case, stat.current, stat.peak))
+                .collect())
+        })
+        .unwrap()
+}
+
+#[test]
+fn top_n() {
+    let small = ALLOCATOR.scope(MyUseCase::Small, || vec![0u8; 10]);
+    let large = ALLOCATOR.scope(MyUseCase::Large, || vec![0u8; 1000]);
+    ALLOCATOR.scope(MyUseCase::Spiky, || drop(vec![0u8; 100_000]));
+
+    assert_eq!(
+        get_top_n(4, SortKey::Current),
+        [
+            (MyUseCase::Large, 1000, 1000),
+            (MyUseCase::Small, 10, 10),
+            (MyUseCase::Spiky, 0, 100_000),
+        ]
+    );
+    assert_eq!(
+        get_top_n(2, SortKey::Peak)[0],
+        (MyUseCase::Spiky, 0, 100_000)
+    );
+    assert_eq!(
+        ALLOCATOR
+            .with_recorder(|recorder| Ok(recorder.top_n(0, SortKey::Total).len()))
+            .unwrap(),
+        0
+    );
+
+    // nothing was reset
+    assert_eq!(get_top_n(4, SortKey::Current).len(), 3);
+    drop(small);
+    drop(large);
+}
@@ -0,0 +1,61 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Tracked, TrackedHashMap, TrackedVec, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Cache,
+    Index,
+    Request,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+fn get(use_case: MyUseCase) -> memoria::Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+#[test]
+fn tracked_vec() {
+    let mut cache: TrackedVec<u8, MyUseCase> =
+        Tracked::new(&ALLOCATOR, MyUseCase::Cache, || Vec::with_capacity(100));
+    assert_eq!(cache.use_case(), MyUseCase::Cache);
+    assert_eq!(get(MyUseCase::Cache).current, 100);
+
+    {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Request);
+        cache.get_mut().reserve_exact(1000);
+        cache.with_mut(|cache| cache.push(1));
+    }
+    assert_eq!(cache.len(), 1);
+    assert_eq!(get(MyUseCase::Cache).current, 1000);
+    assert_eq!(get(MyUseCase::Request).current, 0);
+
+    drop(cache);
+    assert_eq!(get(MyUseCase::Cache).current, 0);
+
+    let plain = Tracked::new(&ALLOCATOR, MyUseCase::Cache, || vec![0u8; 10]).into_inner();
+    assert_eq!(plain.len(), 10);
+}
+
+#[test]
+fn tracked_hash_map() {
+    let mut index: TrackedHashMap<u64, u64, MyUseCase> =
+        Tracked::new(&ALLOCATOR, MyUseCase::Index, Default::default);
+    ALLOCATOR.scope(MyUseCase::Request, || {
+        index.get_mut().extend((0..100).map(|i| (i, i)));
+    });
+    assert!(get(MyUseCase::Index).current > 0);
+    assert_eq!(index[&5], 5);
+    drop(index);
+    assert_eq!(get(MyUseCase::Index).current, 0);
+}
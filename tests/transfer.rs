@@ -0,0 +1,48 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, DeallocAttribution, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Parse,
+    Respond,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> =
+    Alloc::new().with_dealloc_attribution(DeallocAttribution::Current);
+
+#[test]
+fn freed_in_other_usecase() {
+    let buffer = ALLOCATOR.scope(MyUseCase::Parse, || vec![0u8; 100]);
+    ALLOCATOR.scope(MyUseCase::Respond, || drop(buffer));
+
+    let (parse, respond, mut transfers) = ALLOCATOR
+        .with_recorder(|recorder| {
+            let mut transfers = Vec::new();
+            recorder.flush_transfers(|from, to, stat| {
+                if from == MyUseCase::Parse {
+                    transfers.push((to, stat.total, stat.count));
+                }
+            });
+            Ok((
+                recorder.get(MyUseCase::Parse),
+                recorder.get(MyUseCase::Respond),
+                transfers,
+            ))
+        })
+        .unwrap();
+
+    // with DeallocAttribution::Current, the free is subtracted from Respond
+    assert_eq!(parse.current, 100);
+    assert_eq!(respond.current, -100);
+
+    transfers.sort_by_key(|&(_, total, _)| total);
+    assert_eq!(transfers, vec![(MyUseCase::Respond, 100, 1)]);
+}
@@ -0,0 +1,60 @@
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, Error};
+
+memoria::usecase! {
+    enum MyUseCase {
+        default None,
+        Guarded,
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new().with_untracked_default();
+
+fn is_tracked(ptr: *const u8) -> bool {
+    let mut found = false;
+    ALLOCATOR
+        .inspect_live(|live| found |= live.ptr == ptr as usize)
+        .unwrap();
+    found
+}
+
+#[test]
+fn untracked_default() {
+    let errors_before = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get_error(Error::DeallocUntrackedPointer)))
+        .unwrap();
+
+    let outside = vec![0u8; 100];
+    let inside = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Guarded);
+        vec![0u8; 200]
+    };
+    assert!(!is_tracked(outside.as_ptr()));
+    assert!(is_tracked(inside.as_ptr()));
+
+    let (default, guarded) = ALLOCATOR
+        .with_recorder(|recorder| {
+            Ok((
+                recorder.get(MyUseCase::None).total,
+                recorder.get(MyUseCase::Guarded).current,
+            ))
+        })
+        .unwrap();
+    assert!(default >= 100);
+    assert_eq!(guarded, 200);
+
+    drop(outside);
+    drop(inside);
+    let (guarded, errors) = ALLOCATOR
+        .with_recorder(|recorder| {
+            Ok((
+                recorder.get(MyUseCase::Guarded).current,
+                recorder.get_error(Error::DeallocUntrackedPointer),
+            ))
+        })
+        .unwrap();
+    assert_eq!(guarded, 0);
+    assert_eq!(errors, errors_before);
+}
@@ -0,0 +1,55 @@
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+use pretty_assertions::assert_eq;
+
+use memoria::Alloc;
+
+static HOOKED_BYTES: AtomicIsize = AtomicIsize::new(0);
+static NESTED_BYTES: AtomicIsize = AtomicIsize::new(0);
+
+memoria::usecase! {
+    enum MyUseCase {
+        default None,
+        Hooked,
+        Nested,
+    }
+
+    impl memoria::UseCase for MyUseCase {
+        fn on_alloc(&self, size: usize) {
+            match self {
+                MyUseCase::Hooked => {
+                    HOOKED_BYTES.fetch_add(size as isize, Ordering::Relaxed);
+                    // hooks may allocate, as long as they don't recurse forever
+                    let _guard = ALLOCATOR.with_usecase(MyUseCase::Nested);
+                    drop(vec![0u8; 7]);
+                }
+                MyUseCase::Nested => {
+                    NESTED_BYTES.fetch_add(size as isize, Ordering::Relaxed);
+                }
+                MyUseCase::None => {}
+            }
+        }
+
+        fn on_dealloc(&self, size: usize) {
+            if let MyUseCase::Hooked = self {
+                HOOKED_BYTES.fetch_sub(size as isize, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = memoria::new!();
+
+#[test]
+fn hooks() {
+    let buffer = {
+        let _guard = ALLOCATOR.with_usecase(MyUseCase::Hooked);
+        vec![0u8; 100]
+    };
+    assert_eq!(HOOKED_BYTES.load(Ordering::Relaxed), 100);
+    assert_eq!(NESTED_BYTES.load(Ordering::Relaxed), 7);
+
+    drop(buffer);
+    assert_eq!(HOOKED_BYTES.load(Ordering::Relaxed), 0);
+}
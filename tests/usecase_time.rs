@@ -0,0 +1,58 @@
+use std::alloc::System;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, StatsRecorder, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Outer,
+    Inner,
+}
+
+impl UseCase for MyUseCase {}
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new_with(
+    StatsRecorder::new().with_usecase_clock(&|| TICKS.load(Ordering::Relaxed)),
+    System,
+);
+
+fn get(use_case: MyUseCase) -> memoria::Stat {
+    ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(use_case)))
+        .unwrap()
+}
+
+fn tick(n: u64) {
+    TICKS.fetch_add(n, Ordering::Relaxed);
+}
+
+#[test]
+fn entries_and_active_time() {
+    for _ in 0..2 {
+        ALLOCATOR.scope(MyUseCase::Outer, || {
+            tick(10);
+            ALLOCATOR.scope(MyUseCase::Inner, || {
+                drop(vec![0u8; 100]);
+                tick(5);
+            });
+            tick(1);
+        });
+    }
+
+    let outer = get(MyUseCase::Outer);
+    assert_eq!(outer.entries, 2);
+    assert_eq!(outer.active_time, 22);
+    let inner = get(MyUseCase::Inner);
+    assert_eq!(inner.entries, 2);
+    assert_eq!(inner.active_time, 10);
+    assert_eq!(inner.total / inner.entries, 100);
+}
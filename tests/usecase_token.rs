@@ -0,0 +1,49 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase};
+
+#[derive(TryFromPrimitive, IntoPrimitive, Default, Debug, Clone, Copy, PartialEq)]
+#[repr(u32)]
+enum MyUseCase {
+    #[default]
+    None,
+    Request,
+}
+
+impl UseCase for MyUseCase {}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn usecase_token() {
+    let before = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::Request).total))
+        .unwrap();
+
+    let guard = ALLOCATOR
+        .with_usecase_tagged(MyUseCase::Request, 7)
+        .unwrap();
+    let token = guard.transferable();
+    assert_eq!(ALLOCATOR.current_token(), token);
+    assert_eq!(token.use_case(), Some(MyUseCase::Request));
+    assert_eq!(token.tag(), Some(7));
+
+    let data = std::thread::spawn(move || {
+        assert_eq!(ALLOCATOR.current_usecase(), None);
+        let _guard = ALLOCATOR.with_token(token);
+        assert_eq!(ALLOCATOR.current_usecase(), Some(MyUseCase::Request));
+        vec![0u8; 1 << 20]
+    })
+    .join()
+    .unwrap();
+    drop(guard);
+
+    let after = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.get(MyUseCase::Request).total))
+        .unwrap();
+    assert!(after - before >= 1 << 20);
+    assert_eq!(ALLOCATOR.current_token().use_case::<MyUseCase>(), None);
+    drop(data);
+}
@@ -0,0 +1,63 @@
+use pretty_assertions::assert_eq;
+
+use memoria::{Alloc, UseCase, UseCaseBytes, UseCaseRepr};
+
+/// A stage and a tenant, packed into the upper and lower half of the representation.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct MyUseCase {
+    stage: u32,
+    tenant: u32,
+}
+
+impl TryFrom<UseCaseBytes> for MyUseCase {
+    type Error = UseCaseBytes;
+
+    fn try_from(stage: UseCaseBytes) -> Result<Self, Self::Error> {
+        Ok(MyUseCase { stage, tenant: 0 })
+    }
+}
+
+impl From<MyUseCase> for UseCaseBytes {
+    fn from(use_case: MyUseCase) -> Self {
+        use_case.stage
+    }
+}
+
+impl UseCase for MyUseCase {
+    fn into_repr(self) -> UseCaseRepr {
+        (UseCaseRepr::from(self.stage) << 32) | UseCaseRepr::from(self.tenant)
+    }
+
+    fn from_repr(repr: UseCaseRepr) -> Option<Self> {
+        Some(MyUseCase {
+            stage: (repr >> 32) as u32,
+            tenant: repr as u32,
+        })
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Alloc<MyUseCase> = Alloc::new();
+
+#[test]
+fn packed_usecases() {
+    let tenant_a = MyUseCase {
+        stage: 1,
+        tenant: 10,
+    };
+    let tenant_b = MyUseCase {
+        stage: 1,
+        tenant: 20,
+    };
+
+    let a = ALLOCATOR.scope(tenant_a, || vec![0u8; 100]);
+    let b = ALLOCATOR.scope(tenant_b, || vec![0u8; 1000]);
+
+    let stats = ALLOCATOR
+        .with_recorder(|recorder| Ok(recorder.flush_to_map()))
+        .unwrap();
+    assert_eq!(stats[&tenant_a].current, 100);
+    assert_eq!(stats[&tenant_b].current, 1000);
+
+    drop((a, b));
+}